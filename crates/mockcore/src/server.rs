@@ -812,6 +812,19 @@ impl Api for Server {
     Ok(self.state().new_address(true))
   }
 
+  fn get_address_info(
+    &self,
+    address: Address<NetworkUnchecked>,
+  ) -> Result<serde_json::Value, jsonrpc_core::Error> {
+    let address = address.assume_checked();
+    let state = self.state();
+    Ok(serde_json::json!({
+      "address": address.to_string(),
+      "ismine": state.receive_addresses.contains(&address)
+        || state.change_addresses.contains(&address),
+    }))
+  }
+
   fn get_descriptor_info(
     &self,
     desc: String,
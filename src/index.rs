@@ -35,15 +35,26 @@ use {
   sha2::{Digest, Sha256},
   std::{
     collections::HashMap,
-    io::{BufRead, BufReader, BufWriter, Write},
+    io::{BufWriter, Write},
     sync::Once,
   },
 };
 
 pub use self::entry::RuneEntry;
+pub(crate) use self::{
+  tap_history::{TapHistoryUnavailable, TapStateView},
+  tap_metrics::{TapMetrics, TapOpTiming, TapRouteCounters},
+  tap_retention::{TapExportResnapshotRequired, TapExportRetention},
+  tap_stream::{TapStream, TapStreamCursor, TapStreamEvent, TapStreamFilter, TapStreamPending},
+  updater::tap_amm::{TapAmmQuoteAmount, TapAmmUnquotable, TAP_AMM_ROUTE_MAX_HOPS},
+  updater::tap_sale::TapSaleAllowlistEntry,
+  updater::tap_stake::TAP_STAKE_REWARD_RATE_BLOCKS,
+  updater::tap_trade::TAP_TRADE_BOOK_PRICE_PRECISION,
+};
 pub(crate) use updater::inscription_updater::{
   tap_js_json_stringify_str, tap_js_json_stringify_value, tap_js_preprocess_json_for_serde,
-  tap_js_to_lowercase,
+  tap_js_to_lowercase, tap_token_send_item_check, TAP_DMT_REWARD_ADDRESS_PREFIX,
+  TAP_TOKEN_TRADE_EMERGENCY_ACTIVATION_HEIGHT,
 };
 
 pub(crate) mod entry;
@@ -52,6 +63,11 @@ mod fetcher;
 mod lot;
 mod reorg;
 mod rtx;
+mod tap_audit;
+mod tap_history;
+mod tap_metrics;
+mod tap_retention;
+mod tap_stream;
 mod updater;
 mod utxo_entry;
 
@@ -88,6 +104,11 @@ define_table! { TAP_EXPORT_DELTAS, &[u8], &[u8] }
 define_table! { TAP_EXPORT_METADATA, &[u8], &[u8] }
 // Non-consensus rolling TAP export state digests by block.
 define_table! { TAP_EXPORT_BLOCK_STATES, &[u8], &[u8] }
+// Non-consensus TAP_KV values by key and block, for point-in-time reads.
+define_table! { TAP_KEY_HISTORY, &[u8], &[u8] }
+// Non-consensus views derived from TAP state, such as candles and order
+// books. Never exported, digested or streamed, so local config may shape them.
+define_table! { TAP_DERIVED, &[u8], &[u8] }
 
 const TAP_EXPORT_ENABLED_FROM_HEIGHT: &[u8] = b"export_enabled_from_height";
 pub(crate) const TAP_EXPORT_COVERAGE_TIP: &[u8] = b"export_coverage_tip";
@@ -95,6 +116,12 @@ const TAP_EXPORT_ROLLING_ENABLED_FROM_HEIGHT: &[u8] = b"rolling_enabled_from_hei
 pub(crate) const TAP_EXPORT_ROLLING_STATE_TIP: &[u8] = b"rolling_state_tip";
 pub(crate) const TAP_EXPORT_ROLLING_STATE_ROW_COUNT: &[u8] = b"rolling_state_row_count";
 pub(crate) const TAP_EXPORT_ROLLING_STATE_DIGEST: &[u8] = b"rolling_state_digest";
+const TAP_EXPORT_PRUNED_BEFORE_HEIGHT: &[u8] = b"export_pruned_before_height";
+const TAP_KEY_HISTORY_ENABLED_FROM_HEIGHT: &[u8] = b"key_history_enabled_from_height";
+const TAP_SNAPSHOT_IMPORT_HEIGHT: &[u8] = b"snapshot_import_height";
+const TAP_SNAPSHOT_IMPORT_ROW_COUNT: &[u8] = b"snapshot_import_row_count";
+const TAP_SNAPSHOT_IMPORT_DIGEST: &[u8] = b"snapshot_import_digest";
+const TAP_SNAPSHOT_IMPORT_VERIFIED: &[u8] = b"snapshot_import_verified";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct TapExportSnapshotRow {
@@ -169,6 +196,10 @@ pub(crate) struct TapExportRetentionStatus {
   pub rolling_state_tip: Option<u32>,
   pub rolling_state_row_count: Option<u64>,
   pub rolling_state_digest: Option<String>,
+  #[serde(default)]
+  pub pruned_before_height: Option<u32>,
+  #[serde(default)]
+  pub consumer_cursors: BTreeMap<String, u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -178,6 +209,16 @@ pub(crate) struct TapExportStateDigest {
   pub state_digest: String,
 }
 
+/// TAP state imported from an export snapshot taken after block `height`.
+/// Blocks up to and including `height` are indexed without TAP processing.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub(crate) struct TapSnapshotImport {
+  pub height: u32,
+  pub row_count: u64,
+  pub state_digest: String,
+  pub verified: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct TapExportBlockDigest {
   pub height: u32,
@@ -385,6 +426,9 @@ pub struct Index {
   settings: Settings,
   started: DateTime<Utc>,
   first_index_height: u32,
+  tap_export_consumer_cursors: Mutex<BTreeMap<String, u32>>,
+  tap_metrics: TapMetrics,
+  tap_stream: TapStream,
   unrecoverably_reorged: AtomicBool,
 }
 
@@ -504,6 +548,8 @@ impl Index {
         tx.open_table(TAP_KV)?;
         tx.open_table(TAP_EXPORT_METADATA)?;
         tx.open_table(TAP_EXPORT_BLOCK_STATES)?;
+        tx.open_table(TAP_KEY_HISTORY)?;
+        tx.open_table(TAP_DERIVED)?;
 
         {
           let mut statistics = tx.open_table(STATISTIC_TO_COUNT)?;
@@ -597,8 +643,14 @@ impl Index {
     let index_sats;
     let index_transactions;
     let index_inscriptions;
+    let tap_stream_tip;
 
     {
+      let rtx = rtx::Rtx(database.begin_read()?);
+      tap_stream_tip = rtx
+        .block_height()?
+        .map(|height| height.n())
+        .zip(rtx.block_hash(None)?);
       let tx = database.begin_read()?;
       let statistics = tx.open_table(STATISTIC_TO_COUNT)?;
       index_addresses = Self::is_statistic_set(&statistics, Statistic::IndexAddresses)?;
@@ -621,6 +673,9 @@ impl Index {
       u32::MAX
     };
 
+    updater::inscription_updater::TapDeltaSegments::new(path.with_extension("tap-export-deltas"))
+      .migrate_block_files()?;
+
     Ok(Self {
       genesis_block_coinbase_txid: genesis_block_coinbase_transaction.compute_txid(),
       client,
@@ -638,6 +693,9 @@ impl Index {
       settings: settings.clone(),
       path,
       started: Utc::now(),
+      tap_export_consumer_cursors: Mutex::new(BTreeMap::new()),
+      tap_metrics: TapMetrics::default(),
+      tap_stream: TapStream::new(tap_stream_tip, settings.tap_stream()),
       unrecoverably_reorged: AtomicBool::new(false),
     })
   }
@@ -661,7 +719,7 @@ impl Index {
   }
 
   #[cfg(test)]
-  fn set_durability(&mut self, durability: redb::Durability) {
+  pub(crate) fn set_durability(&mut self, durability: redb::Durability) {
     self.durability = durability;
   }
 
@@ -862,6 +920,11 @@ impl Index {
           .map(|value| value.eq_ignore_ascii_case("verify"))
           .unwrap_or(false),
         tap_route_index_initialized: false,
+        tap_snapshot_height: Self::tap_snapshot_import_from_table(
+          &wtx.open_table(TAP_EXPORT_METADATA)?,
+        )?
+        .map(|import| import.height),
+        tap_stream_pending: TapStreamPending::default(),
       };
 
       match updater.update_index(wtx) {
@@ -984,11 +1047,8 @@ impl Index {
     self.path.with_extension("tap-export-deltas")
   }
 
-  fn tap_export_delta_file_path(&self, height: u32) -> PathBuf {
-    updater::inscription_updater::TapDeltaBatch::delta_file_path(
-      &self.tap_export_delta_dir(),
-      height,
-    )
+  pub(crate) fn tap_export_delta_segments(&self) -> updater::inscription_updater::TapDeltaSegments {
+    updater::inscription_updater::TapDeltaSegments::new(self.tap_export_delta_dir())
   }
 
   fn increment_statistic(wtx: &WriteTransaction, statistic: Statistic, n: u64) -> Result {
@@ -2807,7 +2867,7 @@ impl Index {
     })
   }
 
-  fn tap_export_digest_pair(hasher: &mut Sha256, key: &[u8], value: &[u8]) {
+  pub(crate) fn tap_export_digest_pair(hasher: &mut Sha256, key: &[u8], value: &[u8]) {
     hasher.update((key.len() as u64).to_be_bytes());
     hasher.update(key);
     hasher.update((value.len() as u64).to_be_bytes());
@@ -2820,11 +2880,33 @@ impl Index {
     hasher.finalize().into()
   }
 
-  fn tap_export_digest_hex(hasher: Sha256) -> String {
+  pub(crate) fn tap_export_digest_hex(hasher: Sha256) -> String {
     format!("{:x}", hasher.finalize())
   }
 
-  fn tap_export_rolling_zero_digest() -> String {
+  pub(crate) fn tap_export_row_hash(
+    height: u32,
+    sequence: u64,
+    op: &str,
+    key: &str,
+    value: Option<&str>,
+  ) -> Result<String> {
+    let payload = serde_json::to_vec(&serde_json::json!([height, sequence, op, key, value]))?;
+    Ok(hex::encode(Sha256::digest(&payload)))
+  }
+
+  pub(crate) fn tap_export_delta_digest<'a>(
+    row_hashes: impl IntoIterator<Item = &'a str>,
+  ) -> String {
+    let mut hasher = Sha256::new();
+    for row_hash in row_hashes {
+      hasher.update(row_hash.as_bytes());
+      hasher.update(b"\n");
+    }
+    Self::tap_export_digest_hex(hasher)
+  }
+
+  pub(crate) fn tap_export_rolling_zero_digest() -> String {
     "0".repeat(64)
   }
 
@@ -2914,6 +2996,15 @@ impl Index {
     Ok(table.get(key.as_bytes())?.map(|v| v.value().to_vec()))
   }
 
+  #[cfg(test)]
+  pub(crate) fn tap_test_clear(&self) -> Result<()> {
+    let tx = self.begin_write()?;
+    tx.delete_table(TAP_KV)?;
+    tx.open_table(TAP_KV)?;
+    tx.commit()?;
+    Ok(())
+  }
+
   #[cfg(test)]
   pub(crate) fn tap_test_put_raw_rows<I, K, V>(&self, rows: I) -> Result<()>
   where
@@ -2932,6 +3023,96 @@ impl Index {
     Ok(())
   }
 
+  #[cfg(test)]
+  pub(crate) fn tap_test_put_derived_rows<I, K, V>(&self, rows: I) -> Result<()>
+  where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+  {
+    let tx = self.begin_write()?;
+    {
+      let mut table = tx.open_table(TAP_DERIVED)?;
+      for (key, value) in rows {
+        table.insert(key.as_ref().as_bytes(), value.as_ref().as_bytes())?;
+      }
+    }
+    tx.commit()?;
+    Ok(())
+  }
+
+  /// Rewrite TAP_KV and the writer export deltas, block states, and coverage
+  /// metadata as if `blocks` had been indexed with rolling export enabled from
+  /// genesis, using the same delta writer as the updater.
+  #[cfg(test)]
+  pub(crate) fn tap_test_write_export(
+    &self,
+    blocks: &BTreeMap<u32, Vec<(&str, Option<&str>)>>,
+    coverage_start: u32,
+  ) -> Result<()> {
+    let delta_dir = self.tap_export_delta_dir();
+    if delta_dir.exists() {
+      fs::remove_dir_all(&delta_dir)?;
+    }
+
+    let tip = self.block_count()?.checked_sub(1);
+    let tx = self.begin_write()?;
+    {
+      tx.delete_table(TAP_KV)?;
+      tx.delete_table(TAP_EXPORT_BLOCK_STATES)?;
+      tx.delete_table(TAP_KEY_HISTORY)?;
+      let mut tap_kv = tx.open_table(TAP_KV)?;
+      let mut block_states = tx.open_table(TAP_EXPORT_BLOCK_STATES)?;
+      let mut key_history = tx.open_table(TAP_KEY_HISTORY)?;
+      let mut rolling = TapExportRollingState {
+        row_count: 0,
+        state_digest: Self::tap_export_rolling_zero_digest(),
+      };
+
+      for height in tip.map(|tip| 0..=tip).into_iter().flatten() {
+        let mut deltas = updater::inscription_updater::TapDeltaBatch::with_rolling_state(
+          delta_dir.clone(),
+          &mut block_states,
+          height,
+          rolling,
+        )?
+        .with_key_history(&mut key_history);
+        for (key, value) in blocks.get(&height).into_iter().flatten() {
+          let old = tap_kv.get(key.as_bytes())?.map(|old| old.value().to_vec());
+          match value {
+            Some(value) => {
+              let mut encoded = Vec::new();
+              ciborium::into_writer(value, &mut encoded)?;
+              deltas.put(key, old.as_deref(), &encoded)?;
+              tap_kv.insert(key.as_bytes(), encoded.as_slice())?;
+            }
+            None => {
+              deltas.del(key, old.as_deref())?;
+              tap_kv.remove(key.as_bytes())?;
+            }
+          }
+        }
+        rolling = deltas
+          .finalize_block()?
+          .ok_or_else(|| anyhow!("TAP export rolling state missing"))?;
+      }
+
+      let mut metadata = tx.open_table(TAP_EXPORT_METADATA)?;
+      Self::tap_export_metadata_put_u32(
+        &mut metadata,
+        TAP_EXPORT_ENABLED_FROM_HEIGHT,
+        coverage_start,
+      )?;
+      Self::tap_export_metadata_put_u32(&mut metadata, TAP_EXPORT_ROLLING_ENABLED_FROM_HEIGHT, 0)?;
+      Self::tap_export_metadata_put_u32(&mut metadata, TAP_KEY_HISTORY_ENABLED_FROM_HEIGHT, 0)?;
+      if let Some(tip) = tip {
+        Self::tap_export_metadata_put_u32(&mut metadata, TAP_EXPORT_COVERAGE_TIP, tip)?;
+      }
+    }
+    tx.commit()?;
+    Ok(())
+  }
+
   pub fn tap_get_length(&self, length_key: &str) -> Result<u64> {
     Ok(
       self
@@ -3184,7 +3365,12 @@ impl Index {
         .then(|| tx.open_table(TAP_KV))
         .transpose()?;
       let mut table = tx.open_table(TAP_EXPORT_METADATA)?;
+      // An imported snapshot already covers every block up to its height, even
+      // while ord is still indexing those blocks.
+      let block_count = Self::tap_snapshot_import_from_table(&table)?
+        .map_or(block_count, |import| block_count.max(import.height + 1));
       Self::ensure_tap_export_coverage_metadata(&mut table, block_count)?;
+      Self::ensure_tap_key_history_metadata(&mut table, block_count)?;
       if let Some(tap_kv) = tap_kv.as_mut() {
         let mut block_states = tx.open_table(TAP_EXPORT_BLOCK_STATES)?;
         Self::ensure_tap_export_rolling_metadata(
@@ -3199,6 +3385,236 @@ impl Index {
     Ok(())
   }
 
+  pub(crate) fn tap_snapshot_import(&self) -> Result<Option<TapSnapshotImport>> {
+    let rtx = self.begin_read()?;
+    match rtx.0.open_table(TAP_EXPORT_METADATA) {
+      Ok(table) => Self::tap_snapshot_import_from_table(&table),
+      Err(redb::TableError::TableDoesNotExist(_)) => Ok(None),
+      Err(err) => Err(err.into()),
+    }
+  }
+
+  pub(crate) fn tap_snapshot_import_from_table(
+    table: &impl ReadableTable<&'static [u8], &'static [u8]>,
+  ) -> Result<Option<TapSnapshotImport>> {
+    let Some(height) = Self::tap_export_metadata_get_u32(table, TAP_SNAPSHOT_IMPORT_HEIGHT)? else {
+      return Ok(None);
+    };
+    Ok(Some(TapSnapshotImport {
+      height,
+      row_count: Self::tap_export_metadata_get_u64(table, TAP_SNAPSHOT_IMPORT_ROW_COUNT)?
+        .unwrap_or_default(),
+      state_digest: Self::tap_export_metadata_get_string(table, TAP_SNAPSHOT_IMPORT_DIGEST)?
+        .unwrap_or_default(),
+      verified: Self::tap_export_metadata_get_string(table, TAP_SNAPSHOT_IMPORT_VERIFIED)?
+        .is_some_and(|verified| verified == "true"),
+    }))
+  }
+
+  /// Re-encode a snapshot row the way the updater would have stored it.
+  fn tap_snapshot_encode_value(row: &TapExportSnapshotRow) -> Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+    match row.source_encoding.as_str() {
+      "cbor" if row.value_kind.starts_with("json-") => ciborium::into_writer(
+        &serde_json::from_str::<serde_json::Value>(&row.value)?,
+        &mut encoded,
+      )?,
+      "cbor" => ciborium::into_writer(&row.value, &mut encoded)?,
+      "utf8" => encoded.extend_from_slice(row.value.as_bytes()),
+      encoding => bail!(
+        "unknown source encoding `{encoding}` for TAP snapshot key `{}`",
+        row.key
+      ),
+    }
+    Ok(encoded)
+  }
+
+  /// Load TAP state from an export snapshot taken after block
+  /// `digest.source_height - 1` into an empty TAP index. Rows must be in key
+  /// order, as returned by `snapshot-read`. The import is committed only if
+  /// the rows match `digest`, and is marked verified once the committed
+  /// TAP_KV has been read back and digested again. Until then, `/r/tap` is not
+  /// served.
+  pub(crate) fn import_tap_snapshot(
+    &self,
+    digest: &TapExportStateDigest,
+    rows: impl IntoIterator<Item = Result<TapExportSnapshotRow>>,
+  ) -> Result<(TapSnapshotImport, usize)> {
+    let height = digest
+      .source_height
+      .checked_sub(1)
+      .ok_or_else(|| anyhow!("TAP snapshot digest must have a source height of at least 1"))?;
+
+    let block_count = self.block_count()?;
+    ensure!(
+      block_count <= digest.source_height,
+      "cannot import TAP snapshot at height {height}: index has already indexed {block_count} blocks"
+    );
+
+    let existing = self.tap_snapshot_import()?;
+    if let Some(existing) = &existing {
+      ensure!(
+        !existing.verified,
+        "TAP snapshot at height {} has already been imported",
+        existing.height
+      );
+    }
+
+    let mut rolling = TapExportRollingState {
+      row_count: 0,
+      state_digest: Self::tap_export_rolling_zero_digest(),
+    };
+
+    let routes;
+
+    let tx = self.begin_write()?;
+    {
+      // Unverified imports are replaced, anything else must start empty.
+      if existing.is_some() {
+        tx.delete_table(TAP_KV)?;
+      }
+      tx.delete_table(TAP_KEY_HISTORY)?;
+      tx.delete_table(TAP_DERIVED)?;
+
+      let mut tap_kv = tx.open_table(TAP_KV)?;
+      ensure!(
+        tap_kv.is_empty()?,
+        "TAP snapshots can only be imported into an index without TAP state"
+      );
+
+      let mut hasher = Sha256::new();
+      let mut last_key = None::<String>;
+
+      for row in rows {
+        let row = row?;
+
+        if let Some(last_key) = &last_key {
+          ensure!(
+            row.key > *last_key,
+            "TAP snapshot rows are not in key order at `{}`",
+            row.key
+          );
+        }
+
+        let encoded = Self::tap_snapshot_encode_value(&row)?;
+        let value = Self::tap_export_value_string(&encoded);
+        ensure!(
+          value.as_deref() == Some(row.value.as_str()),
+          "TAP snapshot value for `{}` does not round-trip through {} encoding",
+          row.key,
+          row.source_encoding,
+        );
+
+        Self::tap_export_digest_pair(&mut hasher, row.key.as_bytes(), row.value.as_bytes());
+        Self::tap_export_rolling_state_apply(&mut rolling, &row.key, None, Some(&row.value))?;
+        tap_kv.insert(row.key.as_bytes(), encoded.as_slice())?;
+
+        last_key = Some(row.key);
+      }
+
+      let state_digest = Self::tap_export_digest_hex(hasher);
+      ensure!(
+        rolling.row_count == digest.row_count && state_digest == digest.state_digest,
+        "TAP snapshot does not match the published digest: expected {} rows with digest {}, snapshot has {} rows with digest {state_digest}",
+        digest.row_count,
+        digest.state_digest,
+        rolling.row_count,
+      );
+
+      let mut route_index = updater::inscription_updater::TapRouteIndex::new(0);
+      updater::Updater::rebuild_tap_route_index(&mut tap_kv, &mut route_index, height + 1)?;
+      routes = route_index.len();
+
+      // Restoring a savepoint taken before the import would bring back an
+      // empty TAP index for blocks the updater no longer replays.
+      for savepoint in tx.list_persistent_savepoints()?.collect::<Vec<u64>>() {
+        tx.delete_persistent_savepoint(savepoint)?;
+      }
+
+      let mut block_states = tx.open_table(TAP_EXPORT_BLOCK_STATES)?;
+      block_states.retain(|_, _| false)?;
+      block_states.insert(
+        format!("{height:010}").as_bytes(),
+        serde_json::to_vec(&TapExportBlockState {
+          height,
+          rolling_state_row_count: rolling.row_count,
+          rolling_state_digest: rolling.state_digest.clone(),
+        })?
+        .as_slice(),
+      )?;
+
+      let mut metadata = tx.open_table(TAP_EXPORT_METADATA)?;
+      Self::tap_export_metadata_put_u32(&mut metadata, TAP_EXPORT_ENABLED_FROM_HEIGHT, height + 1)?;
+      Self::tap_export_metadata_put_u32(&mut metadata, TAP_EXPORT_COVERAGE_TIP, height)?;
+      Self::tap_export_metadata_put_u32(
+        &mut metadata,
+        TAP_KEY_HISTORY_ENABLED_FROM_HEIGHT,
+        height + 1,
+      )?;
+      Self::tap_export_metadata_put_u32(
+        &mut metadata,
+        TAP_EXPORT_ROLLING_ENABLED_FROM_HEIGHT,
+        height + 1,
+      )?;
+      Self::tap_export_metadata_put_u32(&mut metadata, TAP_EXPORT_ROLLING_STATE_TIP, height)?;
+      Self::tap_export_metadata_put_u64(
+        &mut metadata,
+        TAP_EXPORT_ROLLING_STATE_ROW_COUNT,
+        rolling.row_count,
+      )?;
+      Self::tap_export_metadata_put_string(
+        &mut metadata,
+        TAP_EXPORT_ROLLING_STATE_DIGEST,
+        &rolling.state_digest,
+      )?;
+      Self::tap_export_metadata_put_u32(&mut metadata, TAP_SNAPSHOT_IMPORT_HEIGHT, height)?;
+      Self::tap_export_metadata_put_u64(
+        &mut metadata,
+        TAP_SNAPSHOT_IMPORT_ROW_COUNT,
+        digest.row_count,
+      )?;
+      Self::tap_export_metadata_put_string(
+        &mut metadata,
+        TAP_SNAPSHOT_IMPORT_DIGEST,
+        &digest.state_digest,
+      )?;
+      Self::tap_export_metadata_put_string(&mut metadata, TAP_SNAPSHOT_IMPORT_VERIFIED, "false")?;
+    }
+    updater::inscription_updater::TapDeltaBatch::delete_files_from_height(
+      &self.tap_export_delta_dir(),
+      0,
+    )?;
+    tx.commit()?;
+
+    let imported = self.tap_export_state_digest()?;
+    ensure!(
+      imported.row_count == digest.row_count && imported.state_digest == digest.state_digest,
+      "imported TAP state does not match the published digest: expected {} rows with digest {}, index has {} rows with digest {}; rerun the import",
+      digest.row_count,
+      digest.state_digest,
+      imported.row_count,
+      imported.state_digest,
+    );
+
+    let tx = self.begin_write()?;
+    Self::tap_export_metadata_put_string(
+      &mut tx.open_table(TAP_EXPORT_METADATA)?,
+      TAP_SNAPSHOT_IMPORT_VERIFIED,
+      "true",
+    )?;
+    tx.commit()?;
+
+    Ok((
+      TapSnapshotImport {
+        height,
+        row_count: digest.row_count,
+        state_digest: digest.state_digest.clone(),
+        verified: true,
+      },
+      routes,
+    ))
+  }
+
   pub fn tap_list_strings(
     &self,
     length_key: &str,
@@ -3379,6 +3795,8 @@ impl Index {
       rolling_state_tip,
       rolling_state_row_count,
       rolling_state_digest,
+      pruned_before_height,
+      mut consumer_cursors,
     ) = match rtx.0.open_table(TAP_EXPORT_METADATA) {
       Ok(table) => (
         Self::tap_export_metadata_get_u32(&table, TAP_EXPORT_ENABLED_FROM_HEIGHT)?,
@@ -3387,10 +3805,15 @@ impl Index {
         Self::tap_export_metadata_get_u32(&table, TAP_EXPORT_ROLLING_STATE_TIP)?,
         Self::tap_export_metadata_get_u64(&table, TAP_EXPORT_ROLLING_STATE_ROW_COUNT)?,
         Self::tap_export_metadata_get_string(&table, TAP_EXPORT_ROLLING_STATE_DIGEST)?,
+        Self::tap_export_metadata_get_u32(&table, TAP_EXPORT_PRUNED_BEFORE_HEIGHT)?,
+        Self::tap_export_consumer_cursors_from_table(&table)?,
       ),
-      Err(redb::TableError::TableDoesNotExist(_)) => (None, None, None, None, None, None),
+      Err(redb::TableError::TableDoesNotExist(_)) => {
+        (None, None, None, None, None, None, None, BTreeMap::new())
+      }
       Err(err) => return Err(err.into()),
     };
+    consumer_cursors.extend(self.tap_export_consumer_cursors.lock().unwrap().clone());
     let mut earliest_retained_block = None;
     let mut latest_retained_block = None;
     let mut latest_nonempty_block = None;
@@ -3402,24 +3825,16 @@ impl Index {
     let mut delta_bytes = 0u64;
     let retained_tip = export_coverage_tip.unwrap_or(watermark).min(watermark);
 
-    if let Ok(entries) = std::fs::read_dir(self.tap_export_delta_dir()) {
-      for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-        let Some(height) = updater::inscription_updater::TapDeltaBatch::delta_file_height(&path)
-        else {
-          continue;
-        };
-        if height > retained_tip {
-          continue;
-        }
-        let len = entry.metadata()?.len();
-        delta_bytes = delta_bytes.saturating_add(len);
-        earliest_retained_block = Some(earliest_retained_block.unwrap_or(height).min(height));
-        latest_retained_block = Some(latest_retained_block.unwrap_or(height).max(height));
-        if len > 0 {
-          latest_nonempty_block = Some(latest_nonempty_block.unwrap_or(height).max(height));
-        }
+    for entry in self
+      .tap_export_delta_segments()
+      .block_entries(export_enabled_from_height.unwrap_or(0), retained_tip)?
+    {
+      let height = entry.height;
+      delta_bytes = delta_bytes.saturating_add(entry.len);
+      earliest_retained_block = Some(earliest_retained_block.unwrap_or(height).min(height));
+      latest_retained_block = Some(latest_retained_block.unwrap_or(height).max(height));
+      if entry.len > 0 {
+        latest_nonempty_block = Some(latest_nonempty_block.unwrap_or(height).max(height));
       }
     }
 
@@ -3440,86 +3855,41 @@ impl Index {
       rolling_state_tip,
       rolling_state_row_count,
       rolling_state_digest,
+      pruned_before_height,
+      consumer_cursors,
     })
   }
 
   fn tap_export_sidecar_last_delta_sequence_for_block(&self, height: u32) -> Result<Option<u64>> {
-    let path = self.tap_export_delta_file_path(height);
-    if !path.exists() {
-      return Ok(None);
-    }
-
-    let file = std::fs::File::open(path)?;
-    let reader = BufReader::new(file);
-    let mut latest_sequence = None;
-
-    for line in reader.lines() {
-      let line = line?;
-      if line.trim().is_empty() {
-        continue;
-      }
-      let row: TapExportDeltaRecord = serde_json::from_str(&line)?;
-      ensure!(
-        row.height == height,
-        "tap export delta file for block {height} contains row for block {}",
-        row.height
-      );
-      latest_sequence = Some(row.sequence);
-    }
-
-    Ok(latest_sequence)
+    Ok(
+      self
+        .tap_export_sidecar_delta_rows_for_block(height)?
+        .last()
+        .map(|row| row.sequence),
+    )
   }
 
-  fn tap_export_nonempty_delta_heights(&self, from_block: u32, to_block: u32) -> Result<Vec<u32>> {
-    let mut heights = Vec::new();
-    let Ok(entries) = std::fs::read_dir(self.tap_export_delta_dir()) else {
-      return Ok(heights);
-    };
-
-    for entry in entries {
-      let entry = entry?;
-      let path = entry.path();
-      let Some(height) = updater::inscription_updater::TapDeltaBatch::delta_file_height(&path)
-      else {
-        continue;
-      };
-      if height >= from_block && height <= to_block && entry.metadata()?.len() > 0 {
-        heights.push(height);
-      }
-    }
-
-    heights.sort_unstable();
-    Ok(heights)
+  pub(crate) fn tap_export_nonempty_delta_heights(
+    &self,
+    from_block: u32,
+    to_block: u32,
+  ) -> Result<Vec<u32>> {
+    Ok(
+      self
+        .tap_export_delta_segments()
+        .block_entries(from_block, to_block)?
+        .into_iter()
+        .filter(|entry| entry.len > 0)
+        .map(|entry| entry.height)
+        .collect(),
+    )
   }
 
-  fn tap_export_sidecar_delta_rows_for_block(
+  pub(crate) fn tap_export_sidecar_delta_rows_for_block(
     &self,
     height: u32,
   ) -> Result<Vec<TapExportDeltaRecord>> {
-    let path = self.tap_export_delta_file_path(height);
-    if !path.exists() {
-      return Ok(Vec::new());
-    }
-
-    let file = std::fs::File::open(path)?;
-    let reader = BufReader::new(file);
-    let mut rows = Vec::new();
-
-    for line in reader.lines() {
-      let line = line?;
-      if line.trim().is_empty() {
-        continue;
-      }
-      let row: TapExportDeltaRecord = serde_json::from_str(&line)?;
-      ensure!(
-        row.height == height,
-        "tap export delta file for block {height} contains row for block {}",
-        row.height
-      );
-      rows.push(row);
-    }
-
-    Ok(rows)
+    self.tap_export_delta_segments().read_block(height)
   }
 
   fn tap_export_redb_delta_rows_for_block(
@@ -3604,6 +3974,11 @@ impl Index {
 
   pub(crate) fn tap_export_block_digest(&self, height: u32) -> Result<TapExportBlockDigest> {
     let rtx = self.begin_read()?;
+    match rtx.0.open_table(TAP_EXPORT_METADATA) {
+      Ok(table) => Self::tap_export_ensure_retained(&table, height)?,
+      Err(redb::TableError::TableDoesNotExist(_)) => {}
+      Err(err) => return Err(err.into()),
+    }
     let rolling_block_state = match rtx.0.open_table(TAP_EXPORT_BLOCK_STATES) {
       Ok(table) => {
         let key = format!("{height:010}");
@@ -3615,20 +3990,11 @@ impl Index {
       Err(redb::TableError::TableDoesNotExist(_)) => None,
       Err(err) => return Err(err.into()),
     };
-    let mut hasher = Sha256::new();
-    let mut delta_rows = 0;
-
     let mut block_rows = self.tap_export_sidecar_delta_rows_for_block(height)?;
     if block_rows.is_empty() {
       block_rows = Self::tap_export_redb_delta_rows_for_block(&rtx, height)?;
     }
 
-    for row in block_rows {
-      hasher.update(row.row_hash.as_bytes());
-      hasher.update(b"\n");
-      delta_rows += 1;
-    }
-
     Ok(TapExportBlockDigest {
       height,
       block_hash: rtx.block_hash(Some(height))?.map(|hash| hash.to_string()),
@@ -3638,8 +4004,10 @@ impl Index {
         .transpose()?
         .flatten()
         .map(|hash| hash.to_string()),
-      delta_rows,
-      delta_digest: Self::tap_export_digest_hex(hasher),
+      delta_rows: block_rows.len() as u64,
+      delta_digest: Self::tap_export_delta_digest(
+        block_rows.iter().map(|row| row.row_hash.as_str()),
+      ),
       rolling_state_row_count: rolling_block_state
         .as_ref()
         .map(|state| state.rolling_state_row_count),
@@ -3658,7 +4026,10 @@ impl Index {
   ) -> Result<TapExportDeltaPage> {
     let rtx = self.begin_read()?;
     let covered_tip = match rtx.0.open_table(TAP_EXPORT_METADATA) {
-      Ok(table) => Self::tap_export_metadata_get_u32(&table, TAP_EXPORT_COVERAGE_TIP)?,
+      Ok(table) => {
+        Self::tap_export_ensure_retained(&table, from_block)?;
+        Self::tap_export_metadata_get_u32(&table, TAP_EXPORT_COVERAGE_TIP)?
+      }
       Err(redb::TableError::TableDoesNotExist(_)) => None,
       Err(err) => return Err(err.into()),
     };
@@ -3806,6 +4177,99 @@ mod tests {
     assert_eq!(status.export_coverage_tip, None);
   }
 
+  #[test]
+  fn tap_snapshot_import_verifies_digest_and_skips_tap_through_snapshot_height() {
+    let context = Context::builder().build();
+    context.index.tap_test_clear().unwrap();
+
+    let route = format!("kind/{}i0", "1".repeat(64));
+
+    let rows = [
+      ("dc/\"tap\"", serde_json::json!("21000000")),
+      ("hdr/1", serde_json::json!("imported")),
+      (route.as_str(), serde_json::json!("tl")),
+      (
+        "l/\"tap\"",
+        serde_json::json!({"tick": "tap", "amt": [1, 2]}),
+      ),
+    ]
+    .into_iter()
+    .map(|(key, value)| {
+      let mut encoded = Vec::new();
+      ciborium::into_writer(&value, &mut encoded).unwrap();
+      let details = Index::tap_export_value_details(&encoded).unwrap();
+      TapExportSnapshotRow {
+        key: key.into(),
+        value: details.value,
+        value_kind: details.value_kind,
+        source_encoding: details.source_encoding,
+      }
+    })
+    .collect::<Vec<TapExportSnapshotRow>>();
+
+    let mut hasher = Sha256::new();
+    for row in &rows {
+      Index::tap_export_digest_pair(&mut hasher, row.key.as_bytes(), row.value.as_bytes());
+    }
+
+    let digest = TapExportStateDigest {
+      source_height: 3,
+      row_count: rows.len() as u64,
+      state_digest: Index::tap_export_digest_hex(hasher),
+    };
+
+    let tampered = TapExportStateDigest {
+      state_digest: "00".repeat(32),
+      ..digest.clone()
+    };
+    assert!(context
+      .index
+      .import_tap_snapshot(&tampered, rows.iter().cloned().map(Ok))
+      .unwrap_err()
+      .to_string()
+      .starts_with("TAP snapshot does not match the published digest"));
+    assert_eq!(context.index.tap_snapshot_import().unwrap(), None);
+    assert_eq!(
+      context.index.tap_export_state_digest().unwrap().row_count,
+      0
+    );
+
+    let (import, routes) = context
+      .index
+      .import_tap_snapshot(&digest, rows.iter().cloned().map(Ok))
+      .unwrap();
+    assert_eq!(
+      import,
+      TapSnapshotImport {
+        height: 2,
+        row_count: 4,
+        state_digest: digest.state_digest.clone(),
+        verified: true,
+      }
+    );
+    assert_eq!(routes, 1);
+    assert_eq!(context.index.tap_snapshot_import().unwrap(), Some(import));
+    assert!(context
+      .index
+      .import_tap_snapshot(&digest, rows.iter().cloned().map(Ok))
+      .is_err());
+
+    let status = context.index.tap_export_retention_status().unwrap();
+    assert_eq!(status.export_enabled_from_height, Some(3));
+    assert_eq!(status.export_coverage_tip, Some(2));
+    assert_eq!(status.rolling_state_tip, Some(2));
+    assert_eq!(status.rolling_state_row_count, Some(4));
+
+    context.mine_blocks(3);
+
+    assert_eq!(
+      context.index.tap_get_string("hdr/1").unwrap().as_deref(),
+      Some("imported")
+    );
+    assert_eq!(context.index.tap_get_raw("hdr/2").unwrap(), None);
+    assert!(context.index.tap_get_raw("hdr/3").unwrap().is_some());
+  }
+
   #[test]
   fn tap_export_rolling_state_tracks_put_update_and_delete() {
     let mut state = TapExportRollingState {
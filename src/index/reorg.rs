@@ -53,6 +53,7 @@ impl Reorg {
 
   pub(crate) fn handle_reorg(index: &Index, height: u32, depth: u32) -> Result {
     log::info!("rolling back database after reorg of depth {depth} at height {height}");
+    index.tap_metrics().record_reorg(depth);
 
     // Persist a lightweight off-DB reorg record before rollback
     // Block where the reorg occurred is the first divergent block: height - depth + 1
@@ -114,6 +115,15 @@ impl Reorg {
     Index::increment_statistic(&wtx, Statistic::Commits, 1)?;
     wtx.commit()?;
 
+    let rtx = index.begin_read()?;
+    let tip = rtx.block_height()?.map(|height| height.n());
+    let tip_hash = tip
+      .map(|tip| rtx.block_hash(Some(tip)))
+      .transpose()?
+      .flatten();
+
+    index.tap_stream().rollback(tip, tip_hash);
+
     log::info!(
       "successfully rolled back database to height {}",
       index.begin_read()?.block_count()?
@@ -0,0 +1,649 @@
+use {super::*, num_bigint::BigInt};
+
+const TAP_AUDIT_BURN_ADDRESS: &str = "1BitcoinEaterAddressDontSendf59kuE";
+
+/// The invariant a violation broke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TapAuditCheck {
+  /// Address and authority balances differ from minted supply minus burns.
+  Supply,
+  /// A `t/` transferable balance exceeds its address balance.
+  Transferable,
+  /// Unspent `tamt/` transfer inscriptions exceed their address balance.
+  PendingTransfers,
+  /// An `ll/` token lock exceeds its address balance.
+  Locked,
+  /// An `oll/` obligation lock exceeds the balance of its source.
+  ObligationLocked,
+  /// An AMM pool reserve exceeds the authority balance backing the pool.
+  AmmReserves,
+  /// Open stake positions exceed the authority balance holding them.
+  Staked,
+  /// A list length disagrees with its stored items.
+  ListLength,
+}
+
+/// A single broken invariant. For bounded checks `expected` is the balance the
+/// amount must stay within; for supply and list checks it is the exact value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TapAuditViolation {
+  pub(crate) check: TapAuditCheck,
+  pub(crate) key: String,
+  pub(crate) expected: String,
+  pub(crate) actual: String,
+}
+
+/// Per-ticker totals. Obligation locks, AMM reserves and stake positions are
+/// carried inside address and authority balances, so they are reported for
+/// reference and bounded by those balances rather than added to supply.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TapAuditTicker {
+  pub(crate) tick: String,
+  pub(crate) minted: String,
+  pub(crate) burned: String,
+  pub(crate) address_balances: String,
+  pub(crate) authority_balances: String,
+  pub(crate) transferable: String,
+  pub(crate) locked: String,
+  pub(crate) obligation_locked: String,
+  pub(crate) amm_reserves: String,
+  pub(crate) staked: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TapAudit {
+  pub(crate) height: Option<u32>,
+  pub(crate) keys: u64,
+  pub(crate) lists: u64,
+  pub(crate) tickers: Vec<TapAuditTicker>,
+  pub(crate) violations: Vec<TapAuditViolation>,
+}
+
+#[derive(Default)]
+struct TapAuditTotals {
+  deployed: bool,
+  max: BigInt,
+  remaining: Option<BigInt>,
+  burned: BigInt,
+  address_balances: BigInt,
+  authority_balances: BigInt,
+  transferable: BigInt,
+  locked: BigInt,
+  obligation_locked: BigInt,
+  amm_reserves: BigInt,
+  staked: BigInt,
+}
+
+#[derive(Default)]
+struct TapAuditList {
+  items: u64,
+  next_index: u64,
+}
+
+impl Index {
+  /// Scan TAP_KV once and check that every ticker's balances add up to its
+  /// minted supply, that transferables and locks stay within the balances they
+  /// draw on, and that every list length matches its stored items.
+  pub(crate) fn tap_audit(&self) -> Result<TapAudit> {
+    let rtx = self.begin_read()?;
+    let height = rtx.block_count()?.checked_sub(1);
+    let table = rtx.0.open_table(TAP_KV)?;
+
+    let mut keys = 0;
+    let mut tickers = BTreeMap::<String, TapAuditTotals>::new();
+    let mut lists = HashMap::<String, TapAuditList>::new();
+    let mut pending = BTreeMap::<(String, String), BigInt>::new();
+    let mut pools = HashMap::<String, [Option<BigInt>; 2]>::new();
+    let mut amm_locks = Vec::new();
+    let mut staked = BTreeMap::<(String, String), BigInt>::new();
+    let mut violations = Vec::new();
+
+    for result in table.iter()? {
+      let (key, value) = result?;
+      let key = std::str::from_utf8(key.value())?;
+      let value = value.value();
+      keys += 1;
+
+      if let Some(tick_key) = key.strip_prefix("d/") {
+        let record = Self::tap_audit_record(value);
+        let totals = tickers.entry(tick_key.to_string()).or_default();
+        totals.deployed = true;
+        totals.max = record
+          .get("max")
+          .and_then(|max| max.as_str())
+          .and_then(|max| max.parse().ok())
+          .unwrap_or_default();
+      } else if let Some(tick_key) = key.strip_prefix("dc/") {
+        tickers.entry(tick_key.to_string()).or_default().remaining =
+          Some(Self::tap_audit_decode_amount(value));
+      } else if let Some((address, tick_key)) = Self::tap_audit_split(key, "b/") {
+        let amount = Self::tap_audit_decode_amount(value);
+        let totals = tickers.entry(tick_key.to_string()).or_default();
+        if address == TAP_AUDIT_BURN_ADDRESS {
+          totals.burned += amount;
+        } else {
+          totals.address_balances += amount;
+        }
+      } else if let Some((_, tick_key)) = Self::tap_audit_split(key, "ab/") {
+        tickers
+          .entry(tick_key.to_string())
+          .or_default()
+          .authority_balances += Self::tap_audit_decode_amount(value);
+      } else if let Some((address, tick_key)) = Self::tap_audit_split(key, "t/") {
+        let amount = Self::tap_audit_decode_amount(value);
+        Self::tap_audit_bound(
+          &mut violations,
+          TapAuditCheck::Transferable,
+          key,
+          &amount,
+          &Self::tap_audit_amount(&table, &format!("b/{address}/{tick_key}"))?,
+        );
+        tickers
+          .entry(tick_key.to_string())
+          .or_default()
+          .transferable += amount;
+      } else if let Some((address, tick_key)) = Self::tap_audit_split(key, "ll/") {
+        let amount = Self::tap_audit_decode_amount(value);
+        Self::tap_audit_bound(
+          &mut violations,
+          TapAuditCheck::Locked,
+          key,
+          &amount,
+          &Self::tap_audit_amount(&table, &format!("b/{address}/{tick_key}"))?,
+        );
+        tickers.entry(tick_key.to_string()).or_default().locked += amount;
+      } else if let Some(source) = key.strip_prefix("oll/") {
+        let amount = Self::tap_audit_decode_amount(value);
+        if let Some((address, tick_key)) = source.strip_prefix("a/").and_then(|s| s.split_once('/'))
+        {
+          Self::tap_audit_bound(
+            &mut violations,
+            TapAuditCheck::ObligationLocked,
+            key,
+            &amount,
+            &Self::tap_audit_amount(&table, &format!("b/{address}/{tick_key}"))?,
+          );
+          tickers
+            .entry(tick_key.to_string())
+            .or_default()
+            .obligation_locked += amount;
+        } else if let Some((auth, tick_key)) =
+          source.strip_prefix("h/").and_then(|s| s.split_once('/'))
+        {
+          Self::tap_audit_bound(
+            &mut violations,
+            TapAuditCheck::ObligationLocked,
+            key,
+            &amount,
+            &Self::tap_audit_amount(&table, &format!("ab/{auth}/{tick_key}"))?,
+          );
+          tickers
+            .entry(tick_key.to_string())
+            .or_default()
+            .obligation_locked += amount;
+        } else if let Some((pool, rest)) =
+          source.strip_prefix("amm/").and_then(|s| s.split_once('/'))
+        {
+          if let Some((side, tick_key)) = rest
+            .split_once('/')
+            .and_then(|(side, tick_key)| Some((side.parse::<usize>().ok()?, tick_key)))
+          {
+            tickers
+              .entry(tick_key.to_string())
+              .or_default()
+              .obligation_locked += amount.clone();
+            amm_locks.push((key.to_string(), pool.to_string(), side, amount));
+          }
+        }
+      } else if let Some(pool) = key.strip_prefix("amm/").filter(|pool| !pool.contains('/')) {
+        let record = Self::tap_audit_record(value);
+        if record.get("k").and_then(|k| k.as_str()) != Some("amm") {
+          continue;
+        }
+        let mut sides = [None, None];
+        for (side, reserve) in sides.iter_mut().enumerate() {
+          let Some(asset) = record.get("a").and_then(|assets| assets.get(side)) else {
+            continue;
+          };
+          if asset.get("ty").and_then(|ty| ty.as_str()) != Some("tap") {
+            continue;
+          }
+          let Some(tick) = asset.get("tick").and_then(|tick| tick.as_str()) else {
+            continue;
+          };
+          let amount = record
+            .get("r")
+            .and_then(|reserves| reserves.get(side))
+            .and_then(|amount| amount.as_str())
+            .and_then(|amount| amount.parse::<BigInt>().ok())
+            .unwrap_or_default();
+          let tick_key = tap_js_json_stringify_str(&tap_js_to_lowercase(tick));
+          Self::tap_audit_bound(
+            &mut violations,
+            TapAuditCheck::AmmReserves,
+            key,
+            &amount,
+            &Self::tap_audit_amount(&table, &format!("ab/{pool}/{tick_key}"))?,
+          );
+          tickers.entry(tick_key).or_default().amm_reserves += amount.clone();
+          *reserve = Some(amount);
+        }
+        pools.insert(pool.to_string(), sides);
+      } else if key.starts_with("sp/") {
+        let record = Self::tap_audit_record(value);
+        if record.get("status").and_then(|status| status.as_str()) != Some("open") {
+          continue;
+        }
+        let field = |name: &str| {
+          record
+            .get(name)
+            .and_then(|value| value.as_str())
+            .unwrap_or_default()
+            .to_string()
+        };
+        let tick_key = tap_js_json_stringify_str(&tap_js_to_lowercase(&field("tick")));
+        let amount = field("amt").parse::<BigInt>().unwrap_or_default();
+        tickers.entry(tick_key.clone()).or_default().staked += amount.clone();
+        *staked.entry((field("auth"), tick_key)).or_default() += amount;
+      } else if let Some(inscription) = key.strip_prefix("tamt/") {
+        let amount = Self::tap_audit_decode_amount(value);
+        if amount == BigInt::default() {
+          continue;
+        }
+        let pointer = table
+          .get(format!("tl/{inscription}").as_bytes())?
+          .and_then(|pointer| Self::tap_decode_string_bytes(pointer.value()))
+          .unwrap_or_default();
+        if let Some((address, tick_key)) = pointer
+          .strip_prefix("atrli/")
+          .and_then(|pointer| pointer.rsplit_once('/'))
+          .and_then(|(pointer, _)| pointer.split_once('/'))
+        {
+          *pending
+            .entry((address.to_string(), tick_key.to_string()))
+            .or_default() += amount;
+        }
+      }
+
+      if let Some((prefix, index)) = key
+        .rsplit_once('/')
+        .filter(|(prefix, _)| {
+          prefix
+            .split('/')
+            .any(|segment| segment.len() > 1 && segment.ends_with('i'))
+        })
+        .and_then(|(prefix, index)| Some((prefix, index.parse::<u64>().ok()?)))
+      {
+        let list = lists.entry(prefix.to_string()).or_default();
+        list.items += 1;
+        list.next_index = list.next_index.max(index + 1);
+      }
+    }
+
+    for ((address, tick_key), amount) in pending {
+      Self::tap_audit_bound(
+        &mut violations,
+        TapAuditCheck::PendingTransfers,
+        &format!("t/{address}/{tick_key}"),
+        &amount,
+        &Self::tap_audit_amount(&table, &format!("b/{address}/{tick_key}"))?,
+      );
+    }
+
+    for (key, pool, side, amount) in amm_locks {
+      let reserve = pools
+        .get(&pool)
+        .and_then(|sides| sides.get(side))
+        .cloned()
+        .flatten()
+        .unwrap_or_default();
+      Self::tap_audit_bound(
+        &mut violations,
+        TapAuditCheck::ObligationLocked,
+        &key,
+        &amount,
+        &reserve,
+      );
+    }
+
+    for ((auth, tick_key), amount) in staked {
+      let key = format!("ab/{auth}/{tick_key}");
+      let balance = Self::tap_audit_amount(&table, &key)?;
+      Self::tap_audit_bound(
+        &mut violations,
+        TapAuditCheck::Staked,
+        &key,
+        &amount,
+        &balance,
+      );
+    }
+
+    let mut checked_lists = 0;
+    for (prefix, list) in lists {
+      let Some((length_key, length)) = Self::tap_audit_list_length(&table, &prefix)? else {
+        continue;
+      };
+      checked_lists += 1;
+      if list.items != length || list.next_index != length {
+        violations.push(TapAuditViolation {
+          check: TapAuditCheck::ListLength,
+          key: length_key,
+          expected: length.to_string(),
+          actual: list.items.to_string(),
+        });
+      }
+    }
+
+    let tickers = tickers
+      .into_iter()
+      .map(|(tick_key, totals)| {
+        let minted = totals
+          .remaining
+          .as_ref()
+          .map(|remaining| &totals.max - remaining)
+          .unwrap_or_default();
+        let held = &totals.address_balances + &totals.authority_balances;
+        let circulating = &minted - &totals.burned;
+        if !totals.deployed || held != circulating {
+          violations.push(TapAuditViolation {
+            check: TapAuditCheck::Supply,
+            key: format!("d/{tick_key}"),
+            expected: circulating.to_string(),
+            actual: held.to_string(),
+          });
+        }
+        TapAuditTicker {
+          tick: serde_json::from_str::<String>(&tick_key).unwrap_or(tick_key),
+          minted: minted.to_string(),
+          burned: totals.burned.to_string(),
+          address_balances: totals.address_balances.to_string(),
+          authority_balances: totals.authority_balances.to_string(),
+          transferable: totals.transferable.to_string(),
+          locked: totals.locked.to_string(),
+          obligation_locked: totals.obligation_locked.to_string(),
+          amm_reserves: totals.amm_reserves.to_string(),
+          staked: totals.staked.to_string(),
+        }
+      })
+      .collect();
+
+    violations.sort_by(|a, b| (a.check, &a.key).cmp(&(b.check, &b.key)));
+
+    Ok(TapAudit {
+      height,
+      keys,
+      lists: checked_lists,
+      tickers,
+      violations,
+    })
+  }
+
+  fn tap_audit_split<'a>(key: &'a str, prefix: &str) -> Option<(&'a str, &'a str)> {
+    key.strip_prefix(prefix)?.split_once('/')
+  }
+
+  fn tap_audit_record(value: &[u8]) -> serde_json::Value {
+    Self::tap_export_value_string(value)
+      .and_then(|value| serde_json::from_str(&value).ok())
+      .unwrap_or_default()
+  }
+
+  fn tap_audit_decode_amount(value: &[u8]) -> BigInt {
+    Self::tap_decode_string_bytes(value)
+      .and_then(|amount| amount.parse().ok())
+      .unwrap_or_default()
+  }
+
+  fn tap_audit_amount(
+    table: &impl ReadableTable<&'static [u8], &'static [u8]>,
+    key: &str,
+  ) -> Result<BigInt> {
+    Ok(
+      table
+        .get(key.as_bytes())?
+        .map(|value| Self::tap_audit_decode_amount(value.value()))
+        .unwrap_or_default(),
+    )
+  }
+
+  fn tap_audit_bound(
+    violations: &mut Vec<TapAuditViolation>,
+    check: TapAuditCheck,
+    key: &str,
+    amount: &BigInt,
+    balance: &BigInt,
+  ) {
+    if amount > balance {
+      violations.push(TapAuditViolation {
+        check,
+        key: key.to_string(),
+        expected: balance.to_string(),
+        actual: amount.to_string(),
+      });
+    }
+  }
+
+  /// Items live under an iterator prefix that marks one segment of the length
+  /// key with a trailing `i`, as in `atrl/..` and `atrli/..`, or swaps a
+  /// trailing `l` for it, as in `perp/certl` and `perp/certi`. Prefixes whose
+  /// candidate length keys hold no count are not lists and are skipped.
+  fn tap_audit_list_length(
+    table: &impl ReadableTable<&'static [u8], &'static [u8]>,
+    prefix: &str,
+  ) -> Result<Option<(String, u64)>> {
+    let segments = prefix.split('/').collect::<Vec<&str>>();
+    for (i, segment) in segments.iter().enumerate() {
+      let Some(name) = segment.strip_suffix('i').filter(|name| !name.is_empty()) else {
+        continue;
+      };
+      for candidate in [name.to_string(), format!("{name}l")] {
+        let mut key = segments.clone();
+        key[i] = &candidate;
+        let key = key.join("/");
+        if let Some(length) = table
+          .get(key.as_bytes())?
+          .and_then(|value| Self::tap_decode_string_bytes(value.value()))
+          .and_then(|length| length.parse::<u64>().ok())
+        {
+          return Ok(Some((key, length)));
+        }
+      }
+    }
+    Ok(None)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::index::testing::Context};
+
+  const TAP: &str = "\"tap\"";
+
+  fn deploy(max: &str) -> String {
+    format!(r#"{{"tick":"tap","max":"{max}","lim":"{max}","dec":0}}"#)
+  }
+
+  fn pointer(key: &str) -> String {
+    serde_json::to_string(key).unwrap()
+  }
+
+  fn ticker(audit: &TapAudit) -> &TapAuditTicker {
+    audit
+      .tickers
+      .iter()
+      .find(|ticker| ticker.tick == "tap")
+      .unwrap()
+  }
+
+  #[test]
+  fn audit_accepts_balances_that_add_up_to_minted_supply() {
+    let context = Context::builder().build();
+    context
+      .index
+      .tap_test_put_raw_rows([
+        (format!("d/{TAP}"), deploy("1000")),
+        (format!("dc/{TAP}"), "400".to_string()),
+        (format!("b/alice/{TAP}"), "300".to_string()),
+        (
+          format!("b/{TAP_AUDIT_BURN_ADDRESS}/{TAP}"),
+          "50".to_string(),
+        ),
+        (format!("ab/pool/{TAP}"), "150".to_string()),
+        (format!("ab/stake/{TAP}"), "100".to_string()),
+        (format!("t/alice/{TAP}"), "120".to_string()),
+        ("tamt/transferi0".to_string(), "120".to_string()),
+        (
+          "tl/transferi0".to_string(),
+          pointer(&format!("atrli/alice/{TAP}/0")),
+        ),
+        (format!("atrl/alice/{TAP}"), "1".to_string()),
+        (format!("atrli/alice/{TAP}/0"), "{}".to_string()),
+        (format!("ll/alice/{TAP}"), "80".to_string()),
+        (format!("oll/a/alice/{TAP}"), "100".to_string()),
+        (format!("oll/amm/pool/0/{TAP}"), "150".to_string()),
+        (
+          "amm/pool".to_string(),
+          r#"{"k":"amm","a":[{"ty":"tap","tick":"TAP"},{"ty":"btc"}],"r":["150","9"]}"#.to_string(),
+        ),
+        (
+          "sp/position".to_string(),
+          r#"{"auth":"stake","tick":"TAP","amt":"100","status":"open"}"#.to_string(),
+        ),
+      ])
+      .unwrap();
+
+    let audit = context.index.tap_audit().unwrap();
+
+    assert_eq!(audit.violations, Vec::new());
+    assert_eq!(audit.lists, 1);
+    assert_eq!(
+      ticker(&audit),
+      &TapAuditTicker {
+        tick: "tap".into(),
+        minted: "600".into(),
+        burned: "50".into(),
+        address_balances: "300".into(),
+        authority_balances: "250".into(),
+        transferable: "120".into(),
+        locked: "80".into(),
+        obligation_locked: "250".into(),
+        amm_reserves: "150".into(),
+        staked: "100".into(),
+      }
+    );
+  }
+
+  #[test]
+  fn audit_reports_supply_drift_and_overdrawn_balances() {
+    let context = Context::builder().build();
+    context
+      .index
+      .tap_test_put_raw_rows([
+        (format!("d/{TAP}"), deploy("1000")),
+        (format!("dc/{TAP}"), "900".to_string()),
+        (format!("b/alice/{TAP}"), "110".to_string()),
+        (format!("t/alice/{TAP}"), "120".to_string()),
+        ("tamt/transferi0".to_string(), "150".to_string()),
+        (
+          "tl/transferi0".to_string(),
+          pointer(&format!("atrli/alice/{TAP}/0")),
+        ),
+        (format!("oll/h/auth/{TAP}"), "5".to_string()),
+        ("b/bob/\"ghost\"".to_string(), "1".to_string()),
+      ])
+      .unwrap();
+
+    let violations = context
+      .index
+      .tap_audit()
+      .unwrap()
+      .violations
+      .into_iter()
+      .map(|violation| {
+        (
+          violation.check,
+          violation.key,
+          violation.expected,
+          violation.actual,
+        )
+      })
+      .collect::<Vec<_>>();
+
+    assert_eq!(
+      violations,
+      [
+        (
+          TapAuditCheck::Supply,
+          "d/\"ghost\"".to_string(),
+          "0".to_string(),
+          "1".to_string()
+        ),
+        (
+          TapAuditCheck::Supply,
+          format!("d/{TAP}"),
+          "100".to_string(),
+          "110".to_string()
+        ),
+        (
+          TapAuditCheck::Transferable,
+          format!("t/alice/{TAP}"),
+          "110".to_string(),
+          "120".to_string()
+        ),
+        (
+          TapAuditCheck::PendingTransfers,
+          format!("t/alice/{TAP}"),
+          "110".to_string(),
+          "150".to_string()
+        ),
+        (
+          TapAuditCheck::ObligationLocked,
+          format!("oll/h/auth/{TAP}"),
+          "0".to_string(),
+          "5".to_string()
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn audit_checks_list_lengths_against_their_items() {
+    let context = Context::builder().build();
+    context
+      .index
+      .tap_test_put_raw_rows([
+        ("dl", "2"),
+        ("dli/0", "tap"),
+        ("dli/1", "dmt"),
+        ("perp/certl", "2"),
+        ("perp/certi/0", "{}"),
+        ("sftrl", "3"),
+        ("sftrli/0", "{}"),
+        ("sftrli/2", "{}"),
+        ("tri/not-a-list/0", "{}"),
+      ])
+      .unwrap();
+
+    let audit = context.index.tap_audit().unwrap();
+
+    assert_eq!(audit.lists, 3);
+    assert_eq!(
+      audit.violations,
+      [
+        TapAuditViolation {
+          check: TapAuditCheck::ListLength,
+          key: "perp/certl".into(),
+          expected: "2".into(),
+          actual: "1".into(),
+        },
+        TapAuditViolation {
+          check: TapAuditCheck::ListLength,
+          key: "sftrl".into(),
+          expected: "3".into(),
+          actual: "2".into(),
+        },
+      ]
+    );
+  }
+}
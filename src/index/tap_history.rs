@@ -0,0 +1,508 @@
+use super::*;
+
+/// A point-in-time TAP query that cannot be answered from the retained writer
+/// export deltas. Server routes surface this as a client error instead of
+/// falling back to tip values.
+#[derive(Debug, PartialEq)]
+pub(crate) struct TapHistoryUnavailable(pub(crate) String);
+
+impl Display for TapHistoryUnavailable {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl std::error::Error for TapHistoryUnavailable {}
+
+/// Consistent read view over TAP_KV, either at the indexed tip or as of the end
+/// of a historic block.
+///
+/// Historic views look each key up in the key history index: the last value
+/// written at or before the requested block wins, and keys untouched since
+/// then are read from TAP_KV.
+pub(crate) struct TapStateView {
+  rtx: rtx::Rtx,
+  history: Option<TapHistory>,
+}
+
+struct TapHistory {
+  height: u32,
+  coverage_start: u32,
+  baseline_empty: bool,
+}
+
+impl TapStateView {
+  /// Block the view answers for: the requested historic block, or the
+  /// indexed tip of the read transaction.
+  pub(crate) fn height(&self) -> Result<Option<u32>> {
+    match &self.history {
+      Some(history) => Ok(Some(history.height)),
+      None => Ok(self.rtx.block_height()?.map(|height| height.n())),
+    }
+  }
+
+  /// Whether the view answers for a historic block rather than the tip.
+  pub(crate) fn historic(&self) -> bool {
+    self.history.is_some()
+  }
+
+  pub(crate) fn get_string(&self, key: &str) -> Result<Option<String>> {
+    Ok(
+      self
+        .get_raw(key)?
+        .map(|bytes| Index::tap_decode_string_bytes(&bytes).unwrap_or_default()),
+    )
+  }
+
+  pub(crate) fn get_raw(&self, key: &str) -> Result<Option<Vec<u8>>> {
+    if let Some(value) = self.history_value(key)? {
+      return Ok(value);
+    }
+
+    let table = self.rtx.0.open_table(TAP_KV)?;
+    Ok(table.get(key.as_bytes())?.map(|v| v.value().to_vec()))
+  }
+
+  /// JSON object stored at `key`, whether written as CBOR or as JSON text.
+  /// Values that are not objects read as `None`.
+  pub(crate) fn get_json_record(&self, key: &str) -> Result<Option<serde_json::Value>> {
+    Ok(
+      self
+        .get_raw(key)?
+        .and_then(|bytes| Self::json_record(&bytes)),
+    )
+  }
+
+  /// Value of a TAP_DERIVED key. Derived views are only kept at the tip, so
+  /// historic views refuse them.
+  pub(crate) fn get_derived_raw(&self, key: &str) -> Result<Option<Vec<u8>>> {
+    if let Some(history) = &self.history {
+      bail!(TapHistoryUnavailable(format!(
+        "TAP key `{key}` is a derived view that is only kept at the tip, not as of block {}",
+        history.height
+      )));
+    }
+
+    match self.rtx.0.open_table(TAP_DERIVED) {
+      Ok(table) => Ok(table.get(key.as_bytes())?.map(|v| v.value().to_vec())),
+      Err(redb::TableError::TableDoesNotExist(_)) => Ok(None),
+      Err(err) => Err(err.into()),
+    }
+  }
+
+  pub(crate) fn get_derived_json_record(&self, key: &str) -> Result<Option<serde_json::Value>> {
+    Ok(
+      self
+        .get_derived_raw(key)?
+        .and_then(|bytes| Self::json_record(&bytes)),
+    )
+  }
+
+  fn json_record(bytes: &[u8]) -> Option<serde_json::Value> {
+    ciborium::from_reader::<serde_json::Value, _>(std::io::Cursor::new(bytes))
+      .ok()
+      .or_else(|| serde_json::from_slice(bytes).ok())
+      .filter(serde_json::Value::is_object)
+  }
+
+  /// Value of `key` as of the view's block, or `None` when the key has not
+  /// changed since and the tip value applies.
+  fn history_value(&self, key: &str) -> Result<Option<Option<Vec<u8>>>> {
+    let Some(history) = &self.history else {
+      return Ok(None);
+    };
+
+    let table = match self.rtx.0.open_table(TAP_KEY_HISTORY) {
+      Ok(table) => table,
+      Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+      Err(err) => return Err(err.into()),
+    };
+
+    let (start, end) = Index::tap_key_history_range(key);
+    let at = Index::tap_key_history_key(key, history.height);
+
+    if let Some(result) = table.range(start.as_slice()..=at.as_slice())?.next_back() {
+      let (_key, value) = result?;
+      return Ok(Some(Index::tap_key_history_decode(value.value())));
+    }
+
+    let Some(after) = history
+      .height
+      .checked_add(1)
+      .map(|height| Index::tap_key_history_key(key, height))
+    else {
+      return Ok(None);
+    };
+
+    if table
+      .range(after.as_slice()..end.as_slice())?
+      .next()
+      .is_none()
+    {
+      return Ok(None);
+    }
+
+    if history.baseline_empty {
+      return Ok(Some(None));
+    }
+
+    Err(anyhow!(TapHistoryUnavailable(format!(
+      "TAP key `{key}` changed after block {} but its earlier value predates the retained export deltas starting at block {}",
+      history.height, history.coverage_start
+    ))))
+  }
+
+  pub(crate) fn get_length(&self, length_key: &str) -> Result<u64> {
+    Self::length(self.get_raw(length_key)?)
+  }
+
+  pub(crate) fn get_derived_length(&self, length_key: &str) -> Result<u64> {
+    Self::length(self.get_derived_raw(length_key)?)
+  }
+
+  fn length(bytes: Option<Vec<u8>>) -> Result<u64> {
+    Ok(
+      bytes
+        .and_then(|bytes| Index::tap_decode_string_bytes(&bytes))
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(0),
+    )
+  }
+
+  pub(crate) fn list_strings(
+    &self,
+    length_key: &str,
+    iterator_key: &str,
+    offset: u64,
+    max: u64,
+  ) -> Result<Vec<String>> {
+    let length = self.get_length(length_key)?;
+    Self::list(length, iterator_key, offset, max, |key| self.get_raw(key))
+  }
+
+  pub(crate) fn list_derived_strings(
+    &self,
+    length_key: &str,
+    iterator_key: &str,
+    offset: u64,
+    max: u64,
+  ) -> Result<Vec<String>> {
+    let length = self.get_derived_length(length_key)?;
+    Self::list(length, iterator_key, offset, max, |key| {
+      self.get_derived_raw(key)
+    })
+  }
+
+  fn list(
+    length: u64,
+    iterator_key: &str,
+    offset: u64,
+    max: u64,
+    get: impl Fn(&str) -> Result<Option<Vec<u8>>>,
+  ) -> Result<Vec<String>> {
+    let mut out = Vec::new();
+    let end = std::cmp::min(length, offset.saturating_add(max));
+    for i in offset..end {
+      if let Some(bytes) = get(&format!("{}/{}", iterator_key, i))? {
+        out.push(Index::tap_decode_string_bytes(&bytes).unwrap_or_default());
+      }
+    }
+    Ok(out)
+  }
+}
+
+impl Index {
+  /// Key history rows sort by TAP key, then block. TAP keys never contain NUL,
+  /// so the separator keeps one key's rows apart from keys it prefixes.
+  pub(crate) fn tap_key_history_key(key: &str, height: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(key.len() + 5);
+    out.extend_from_slice(key.as_bytes());
+    out.push(0);
+    out.extend_from_slice(&height.to_be_bytes());
+    out
+  }
+
+  fn tap_key_history_range(key: &str) -> (Vec<u8>, Vec<u8>) {
+    let mut start = key.as_bytes().to_vec();
+    let mut end = start.clone();
+    start.push(0);
+    end.push(1);
+    (start, end)
+  }
+
+  /// Puts keep the value as stored in TAP_KV behind a marker byte, deletes are
+  /// the bare marker.
+  pub(crate) fn tap_key_history_encode(value: Option<&[u8]>) -> Vec<u8> {
+    match value {
+      Some(value) => {
+        let mut out = Vec::with_capacity(value.len() + 1);
+        out.push(1);
+        out.extend_from_slice(value);
+        out
+      }
+      None => vec![0],
+    }
+  }
+
+  fn tap_key_history_decode(bytes: &[u8]) -> Option<Vec<u8>> {
+    match bytes.split_first() {
+      Some((1, value)) => Some(value.to_vec()),
+      _ => None,
+    }
+  }
+
+  /// Drop key history rows below `start`, keeping each key's last row before
+  /// it so views at `start` and later still resolve the value that applied
+  /// when the retained range begins.
+  pub(crate) fn prune_tap_key_history(
+    table: &mut Table<'_, &'static [u8], &'static [u8]>,
+    start: u32,
+  ) -> Result {
+    let mut stale = Vec::new();
+    let mut last_below: Option<Vec<u8>> = None;
+
+    for result in table.iter()? {
+      let (key, _value) = result?;
+      let key = key.value();
+      let Some((tap_key, height)) = key.split_last_chunk::<4>() else {
+        continue;
+      };
+
+      if last_below
+        .as_ref()
+        .is_some_and(|previous| previous[..previous.len() - 4] != *tap_key)
+      {
+        last_below = None;
+      }
+
+      if u32::from_be_bytes(*height) < start {
+        if let Some(previous) = last_below.replace(key.to_vec()) {
+          stale.push(previous);
+        }
+      }
+    }
+
+    for key in &stale {
+      table.remove(key.as_slice())?;
+    }
+
+    Ok(())
+  }
+
+  /// Record the first block covered by the key history index. Blocks indexed
+  /// before it have no history rows, so point-in-time reads start there.
+  pub(crate) fn ensure_tap_key_history_metadata(
+    table: &mut Table<'_, &'static [u8], &'static [u8]>,
+    next_uncovered_height: u32,
+  ) -> Result {
+    if Self::tap_export_metadata_get_u32(table, TAP_KEY_HISTORY_ENABLED_FROM_HEIGHT)?.is_none() {
+      Self::tap_export_metadata_put_u32(
+        table,
+        TAP_KEY_HISTORY_ENABLED_FROM_HEIGHT,
+        next_uncovered_height,
+      )?;
+    }
+    Ok(())
+  }
+
+  /// Open a TAP read view at the tip, or as of the end of `at_block` when set.
+  ///
+  /// Historic heights must lie inside both the writer export coverage range
+  /// and the key history index, since those are the only record of how TAP_KV
+  /// looked at earlier blocks.
+  pub(crate) fn tap_state_view(&self, at_block: Option<u32>) -> Result<TapStateView> {
+    let rtx = self.begin_read()?;
+    let Some(height) = at_block else {
+      return Ok(TapStateView { rtx, history: None });
+    };
+
+    let (enabled_from, coverage_tip, rolling_enabled_from, history_from, snapshot_import) =
+      match rtx.0.open_table(TAP_EXPORT_METADATA) {
+        Ok(table) => (
+          Self::tap_export_metadata_get_u32(&table, TAP_EXPORT_ENABLED_FROM_HEIGHT)?,
+          Self::tap_export_metadata_get_u32(&table, TAP_EXPORT_COVERAGE_TIP)?,
+          Self::tap_export_metadata_get_u32(&table, TAP_EXPORT_ROLLING_ENABLED_FROM_HEIGHT)?,
+          Self::tap_export_metadata_get_u32(&table, TAP_KEY_HISTORY_ENABLED_FROM_HEIGHT)?,
+          Self::tap_snapshot_import_from_table(&table)?,
+        ),
+        Err(redb::TableError::TableDoesNotExist(_)) => (None, None, None, None, None),
+        Err(err) => return Err(err.into()),
+      };
+
+    let (Some(enabled_from), Some(coverage_tip), Some(history_from)) =
+      (enabled_from, coverage_tip, history_from)
+    else {
+      bail!(TapHistoryUnavailable(
+        "point-in-time TAP queries require writer export deltas; enable ORD_TAP_WRITER_EXPORT and index past the export start".to_string()
+      ));
+    };
+
+    let coverage_start = enabled_from.max(history_from);
+    let tip = coverage_tip.min(rtx.block_count()?.saturating_sub(1));
+
+    if height > tip {
+      bail!(TapHistoryUnavailable(format!(
+        "block {height} is above the covered TAP tip {tip}"
+      )));
+    }
+
+    if height < coverage_start {
+      bail!(TapHistoryUnavailable(format!(
+        "block {height} is before the retained TAP export range {coverage_start}..={tip}"
+      )));
+    }
+
+    Ok(TapStateView {
+      rtx,
+      history: Some(TapHistory {
+        height,
+        coverage_start,
+        // Rolling export can only start on an empty TAP_KV, so when it began
+        // together with key history every earlier value is known absent. An
+        // imported snapshot is the exception, it fills TAP_KV before export.
+        baseline_empty: snapshot_import.is_none()
+          && (coverage_start == 0 || rolling_enabled_from == Some(coverage_start)),
+      }),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::index::testing::Context};
+
+  fn set_coverage(index: &Index, enabled_from: u32) {
+    let tx = index.begin_write().unwrap();
+    {
+      let mut metadata = tx.open_table(TAP_EXPORT_METADATA).unwrap();
+      Index::tap_export_metadata_put_u32(
+        &mut metadata,
+        TAP_EXPORT_ENABLED_FROM_HEIGHT,
+        enabled_from,
+      )
+      .unwrap();
+      Index::tap_export_metadata_put_u32(
+        &mut metadata,
+        TAP_KEY_HISTORY_ENABLED_FROM_HEIGHT,
+        enabled_from,
+      )
+      .unwrap();
+      Index::tap_export_metadata_put_u32(&mut metadata, TAP_EXPORT_COVERAGE_TIP, 3).unwrap();
+      metadata
+        .remove(TAP_EXPORT_ROLLING_ENABLED_FROM_HEIGHT)
+        .unwrap();
+    }
+    tx.commit().unwrap();
+  }
+
+  #[test]
+  fn tap_state_view_resolves_values_as_of_historic_block() {
+    let context = Context::builder().build();
+    context.mine_blocks(3);
+    context
+      .index
+      .tap_test_write_export(
+        &BTreeMap::from([
+          (0, vec![("y", Some("7"))]),
+          (1, vec![("b/addr/tap", Some("10")), ("x", Some("1"))]),
+          (2, vec![("b/addr/tap", Some("25"))]),
+          (3, vec![("b/addr/tap", Some("40")), ("x", None)]),
+        ]),
+        0,
+      )
+      .unwrap();
+
+    let view = context.index.tap_state_view(Some(0)).unwrap();
+    assert_eq!(view.get_string("b/addr/tap").unwrap(), None);
+    assert_eq!(view.get_string("y").unwrap().as_deref(), Some("7"));
+
+    let view = context.index.tap_state_view(Some(1)).unwrap();
+    assert_eq!(
+      view.get_string("b/addr/tap").unwrap().as_deref(),
+      Some("10")
+    );
+    assert_eq!(view.get_string("x").unwrap().as_deref(), Some("1"));
+    assert_eq!(view.get_string("y").unwrap().as_deref(), Some("7"));
+
+    let view = context.index.tap_state_view(Some(2)).unwrap();
+    assert_eq!(
+      view.get_string("b/addr/tap").unwrap().as_deref(),
+      Some("25")
+    );
+    assert_eq!(view.get_string("x").unwrap().as_deref(), Some("1"));
+
+    let view = context.index.tap_state_view(Some(3)).unwrap();
+    assert_eq!(
+      view.get_string("b/addr/tap").unwrap().as_deref(),
+      Some("40")
+    );
+    assert_eq!(view.get_string("x").unwrap(), None);
+
+    let view = context.index.tap_state_view(None).unwrap();
+    assert_eq!(
+      view.get_string("b/addr/tap").unwrap().as_deref(),
+      Some("40")
+    );
+  }
+
+  #[test]
+  fn tap_key_history_rows_do_not_leak_into_prefixed_keys() {
+    let context = Context::builder().build();
+    context.mine_blocks(3);
+    context
+      .index
+      .tap_test_write_export(
+        &BTreeMap::from([
+          (1, vec![("b/addr/tap", Some("10"))]),
+          (3, vec![("b/addr/tapx", Some("5"))]),
+        ]),
+        0,
+      )
+      .unwrap();
+
+    let view = context.index.tap_state_view(Some(2)).unwrap();
+    assert_eq!(
+      view.get_string("b/addr/tap").unwrap().as_deref(),
+      Some("10")
+    );
+    assert_eq!(view.get_string("b/addr/tapx").unwrap(), None);
+  }
+
+  #[test]
+  fn tap_state_view_refuses_heights_outside_coverage() {
+    let context = Context::builder().build();
+    context.mine_blocks(3);
+
+    let error = context.index.tap_state_view(Some(2)).err().unwrap();
+    assert!(error.downcast_ref::<TapHistoryUnavailable>().is_some());
+    assert!(error.to_string().contains("ORD_TAP_WRITER_EXPORT"));
+
+    set_coverage(&context.index, 2);
+
+    let error = context.index.tap_state_view(Some(1)).err().unwrap();
+    assert!(error
+      .to_string()
+      .contains("before the retained TAP export range 2..=3"));
+
+    let error = context.index.tap_state_view(Some(4)).err().unwrap();
+    assert!(error.to_string().contains("above the covered TAP tip 3"));
+  }
+
+  #[test]
+  fn tap_state_view_refuses_keys_whose_history_predates_coverage() {
+    let context = Context::builder().build();
+    context.mine_blocks(3);
+    context
+      .index
+      .tap_test_write_export(&BTreeMap::from([(3, vec![("b/addr/tap", Some("40"))])]), 2)
+      .unwrap();
+    set_coverage(&context.index, 2);
+
+    let view = context.index.tap_state_view(Some(2)).unwrap();
+    let error = view.get_string("b/addr/tap").unwrap_err();
+    assert!(error.downcast_ref::<TapHistoryUnavailable>().is_some());
+    assert!(error
+      .to_string()
+      .contains("predates the retained export deltas starting at block 2"));
+  }
+}
@@ -0,0 +1,526 @@
+use {
+  super::*,
+  std::{fmt::Write as _, time::UNIX_EPOCH},
+};
+
+const TAP_METRICS_BLOCK_SECONDS_BUCKETS: &[f64] = &[
+  0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+const TAP_METRICS_REQUEST_SECONDS_BUCKETS: &[f64] = &[
+  0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0,
+];
+const TAP_METRICS_REORG_DEPTH_BUCKETS: &[f64] = &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+
+/// Route index lookups and hot-owner cache activity since the counters were
+/// last taken. The updater drains these into `TapMetrics` after every block.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct TapRouteCounters {
+  pub(crate) hits: u64,
+  pub(crate) misses: u64,
+  pub(crate) unready: u64,
+  pub(crate) verified: u64,
+  pub(crate) bitmap_hot_hits: u64,
+  pub(crate) bitmap_hot_misses: u64,
+  pub(crate) bitmap_hot_evictions: u64,
+  pub(crate) dmt_hot_hits: u64,
+  pub(crate) dmt_hot_misses: u64,
+  pub(crate) dmt_hot_evictions: u64,
+}
+
+impl TapRouteCounters {
+  fn add(&mut self, other: Self) {
+    self.hits += other.hits;
+    self.misses += other.misses;
+    self.unready += other.unready;
+    self.verified += other.verified;
+    self.bitmap_hot_hits += other.bitmap_hot_hits;
+    self.bitmap_hot_misses += other.bitmap_hot_misses;
+    self.bitmap_hot_evictions += other.bitmap_hot_evictions;
+    self.dmt_hot_hits += other.dmt_hot_hits;
+    self.dmt_hot_misses += other.dmt_hot_misses;
+    self.dmt_hot_evictions += other.dmt_hot_evictions;
+  }
+}
+
+/// Time spent in one TAP op handler family during a block.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TapOpTiming {
+  pub(crate) phase: &'static str,
+  pub(crate) op: &'static str,
+  pub(crate) count: u64,
+  pub(crate) micros: u128,
+}
+
+#[derive(Debug, Default)]
+struct TapHistogram {
+  buckets: Vec<u64>,
+  count: u64,
+  sum: f64,
+}
+
+impl TapHistogram {
+  fn observe(&mut self, bounds: &[f64], value: f64) {
+    if self.buckets.is_empty() {
+      self.buckets = vec![0; bounds.len()];
+    }
+    for (bucket, bound) in self.buckets.iter_mut().zip(bounds) {
+      if value <= *bound {
+        *bucket += 1;
+      }
+    }
+    self.count += 1;
+    self.sum += value;
+  }
+
+  fn render(&self, out: &mut String, name: &str, labels: &str, bounds: &[f64]) {
+    let separator = if labels.is_empty() { "" } else { "," };
+    for (i, bound) in bounds.iter().enumerate() {
+      let count = self.buckets.get(i).copied().unwrap_or_default();
+      writeln!(
+        out,
+        "{name}_bucket{{{labels}{separator}le=\"{bound}\"}} {count}"
+      )
+      .unwrap();
+    }
+    writeln!(
+      out,
+      "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {}",
+      self.count
+    )
+    .unwrap();
+    writeln!(out, "{name}_sum{} {}", TapMetrics::braces(labels), self.sum).unwrap();
+    writeln!(
+      out,
+      "{name}_count{} {}",
+      TapMetrics::braces(labels),
+      self.count
+    )
+    .unwrap();
+  }
+}
+
+#[derive(Debug, Default)]
+struct TapMetricsState {
+  block_seconds: TapHistogram,
+  last_block_height: Option<u32>,
+  last_block_timestamp: Option<f64>,
+  ops: BTreeMap<(&'static str, &'static str), (u64, u128)>,
+  routes: TapRouteCounters,
+  reorg_depth: TapHistogram,
+  requests: BTreeMap<(String, String, u16), TapHistogram>,
+}
+
+/// In-process counters behind `/metrics`, rendered in the Prometheus text
+/// exposition format. Counters start from zero whenever ord restarts.
+#[derive(Debug, Default)]
+pub(crate) struct TapMetrics {
+  state: Mutex<TapMetricsState>,
+  delta_bytes: Mutex<Option<(u32, u64)>>,
+}
+
+impl TapMetrics {
+  pub(crate) fn record_block(
+    &self,
+    height: u32,
+    duration: Duration,
+    ops: &[TapOpTiming],
+    routes: TapRouteCounters,
+  ) {
+    let mut state = self.state.lock().unwrap();
+    state
+      .block_seconds
+      .observe(TAP_METRICS_BLOCK_SECONDS_BUCKETS, duration.as_secs_f64());
+    state.last_block_height = Some(height);
+    state.last_block_timestamp = Some(
+      SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64(),
+    );
+    for timing in ops {
+      let (count, micros) = state.ops.entry((timing.phase, timing.op)).or_default();
+      *count += timing.count;
+      *micros += timing.micros;
+    }
+    state.routes.add(routes);
+  }
+
+  pub(crate) fn record_reorg(&self, depth: u32) {
+    self
+      .state
+      .lock()
+      .unwrap()
+      .reorg_depth
+      .observe(TAP_METRICS_REORG_DEPTH_BUCKETS, depth.into());
+  }
+
+  pub(crate) fn record_request(&self, method: &str, route: &str, status: u16, duration: Duration) {
+    self
+      .state
+      .lock()
+      .unwrap()
+      .requests
+      .entry((method.into(), route.into(), status))
+      .or_default()
+      .observe(TAP_METRICS_REQUEST_SECONDS_BUCKETS, duration.as_secs_f64());
+  }
+
+  /// Size of the writer export delta directory, measured by `size` at most
+  /// once per indexed block count rather than on every scrape.
+  fn delta_bytes(&self, block_count: u32, size: impl FnOnce() -> Result<u64>) -> Result<u64> {
+    let mut cached = self.delta_bytes.lock().unwrap();
+    if let Some((counted_at, bytes)) = *cached {
+      if counted_at == block_count {
+        return Ok(bytes);
+      }
+    }
+    let bytes = size()?;
+    *cached = Some((block_count, bytes));
+    Ok(bytes)
+  }
+
+  /// Forget the cached delta directory size after retention removes segments.
+  pub(crate) fn invalidate_delta_bytes(&self) {
+    *self.delta_bytes.lock().unwrap() = None;
+  }
+
+  fn render(&self, out: &mut String) {
+    let state = self.state.lock().unwrap();
+
+    Self::header(
+      out,
+      "ord_tap_block_index_seconds",
+      "histogram",
+      "Time to index the transactions of one block, excluding the database commit.",
+    );
+    state.block_seconds.render(
+      out,
+      "ord_tap_block_index_seconds",
+      "",
+      TAP_METRICS_BLOCK_SECONDS_BUCKETS,
+    );
+
+    if let Some(height) = state.last_block_height {
+      Self::header(
+        out,
+        "ord_tap_last_block_height",
+        "gauge",
+        "Height of the last block indexed by this process.",
+      );
+      writeln!(out, "ord_tap_last_block_height {height}").unwrap();
+    }
+
+    if let Some(timestamp) = state.last_block_timestamp {
+      Self::header(
+        out,
+        "ord_tap_last_block_timestamp_seconds",
+        "gauge",
+        "Unix time at which this process finished indexing its last block.",
+      );
+      writeln!(out, "ord_tap_last_block_timestamp_seconds {timestamp}").unwrap();
+    }
+
+    Self::header(
+      out,
+      "ord_tap_op_total",
+      "counter",
+      "TAP operations handled, by phase and op family.",
+    );
+    for ((phase, op), (count, _)) in &state.ops {
+      writeln!(
+        out,
+        "ord_tap_op_total{{phase=\"{phase}\",op=\"{op}\"}} {count}"
+      )
+      .unwrap();
+    }
+
+    Self::header(
+      out,
+      "ord_tap_op_seconds_total",
+      "counter",
+      "Time spent in TAP op handlers, by phase and op family.",
+    );
+    for ((phase, op), (_, micros)) in &state.ops {
+      writeln!(
+        out,
+        "ord_tap_op_seconds_total{{phase=\"{phase}\",op=\"{op}\"}} {}",
+        *micros as f64 / 1_000_000.0
+      )
+      .unwrap();
+    }
+
+    let routes = state.routes;
+    Self::header(
+      out,
+      "ord_tap_route_lookups_total",
+      "counter",
+      "Transfer route index lookups. `unready` and `verified` lookups take the slow path.",
+    );
+    for (result, count) in [
+      ("hit", routes.hits),
+      ("miss", routes.misses),
+      ("unready", routes.unready),
+      ("verified", routes.verified),
+    ] {
+      writeln!(
+        out,
+        "ord_tap_route_lookups_total{{result=\"{result}\"}} {count}"
+      )
+      .unwrap();
+    }
+
+    Self::header(
+      out,
+      "ord_tap_hot_cache_lookups_total",
+      "counter",
+      "Hot-owner cache lookups, by cache and result.",
+    );
+    for (cache, result, count) in [
+      ("bitmap", "hit", routes.bitmap_hot_hits),
+      ("bitmap", "miss", routes.bitmap_hot_misses),
+      ("dmt", "hit", routes.dmt_hot_hits),
+      ("dmt", "miss", routes.dmt_hot_misses),
+    ] {
+      writeln!(
+        out,
+        "ord_tap_hot_cache_lookups_total{{cache=\"{cache}\",result=\"{result}\"}} {count}"
+      )
+      .unwrap();
+    }
+
+    Self::header(
+      out,
+      "ord_tap_hot_cache_evictions_total",
+      "counter",
+      "Entries evicted from the hot-owner caches.",
+    );
+    for (cache, count) in [
+      ("bitmap", routes.bitmap_hot_evictions),
+      ("dmt", routes.dmt_hot_evictions),
+    ] {
+      writeln!(
+        out,
+        "ord_tap_hot_cache_evictions_total{{cache=\"{cache}\"}} {count}"
+      )
+      .unwrap();
+    }
+
+    Self::header(
+      out,
+      "ord_tap_reorg_depth_blocks",
+      "histogram",
+      "Depth of each reorg rolled back by this process.",
+    );
+    state.reorg_depth.render(
+      out,
+      "ord_tap_reorg_depth_blocks",
+      "",
+      TAP_METRICS_REORG_DEPTH_BUCKETS,
+    );
+
+    Self::header(
+      out,
+      "ord_tap_http_request_duration_seconds",
+      "histogram",
+      "REST request latency, by method, matched route and status.",
+    );
+    for ((method, route, status), histogram) in &state.requests {
+      histogram.render(
+        out,
+        "ord_tap_http_request_duration_seconds",
+        &format!(
+          "method=\"{}\",route=\"{}\",status=\"{status}\"",
+          Self::escape(method),
+          Self::escape(route)
+        ),
+        TAP_METRICS_REQUEST_SECONDS_BUCKETS,
+      );
+    }
+  }
+
+  fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {name} {help}").unwrap();
+    writeln!(out, "# TYPE {name} {kind}").unwrap();
+  }
+
+  fn braces(labels: &str) -> String {
+    if labels.is_empty() {
+      String::new()
+    } else {
+      format!("{{{labels}}}")
+    }
+  }
+
+  fn escape(value: &str) -> String {
+    value
+      .replace('\\', "\\\\")
+      .replace('"', "\\\"")
+      .replace('\n', "\\n")
+  }
+}
+
+impl Index {
+  pub(crate) fn tap_metrics(&self) -> &TapMetrics {
+    &self.tap_metrics
+  }
+
+  /// Render `/metrics`: the in-process counters plus gauges read from the
+  /// index, so a stalled updater still reports how far behind it is.
+  pub(crate) fn tap_metrics_text(&self) -> Result<String> {
+    let mut out = String::new();
+
+    let block_count = self.block_count()?;
+    TapMetrics::header(
+      &mut out,
+      "ord_tap_indexed_height",
+      "gauge",
+      "Height of the last block committed to the index.",
+    );
+    writeln!(out, "ord_tap_indexed_height {}", i64::from(block_count) - 1).unwrap();
+
+    self.tap_metrics.render(&mut out);
+
+    if self.settings.tap_writer_export_enabled() {
+      let status = self.tap_export_retention_status()?;
+
+      TapMetrics::header(
+        &mut out,
+        "ord_tap_export_consumer_lag_blocks",
+        "gauge",
+        "Blocks indexed but not yet acknowledged, by writer export consumer.",
+      );
+      for (consumer, next_block) in &status.consumer_cursors {
+        writeln!(
+          out,
+          "ord_tap_export_consumer_lag_blocks{{consumer=\"{}\"}} {}",
+          TapMetrics::escape(consumer),
+          (status.watermark + 1).saturating_sub(*next_block)
+        )
+        .unwrap();
+      }
+
+      TapMetrics::header(
+        &mut out,
+        "ord_tap_export_delta_bytes",
+        "gauge",
+        "Size on disk of the writer export delta directory.",
+      );
+      writeln!(
+        out,
+        "ord_tap_export_delta_bytes {}",
+        self.tap_metrics.delta_bytes(block_count, || {
+          Self::tap_metrics_dir_size(&self.tap_export_delta_dir())
+        })?
+      )
+      .unwrap();
+    }
+
+    Ok(out)
+  }
+
+  fn tap_metrics_dir_size(path: &Path) -> Result<u64> {
+    let entries = match fs::read_dir(path) {
+      Ok(entries) => entries,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+      Err(err) => return Err(err.into()),
+    };
+
+    let mut size = 0;
+    for entry in entries {
+      let entry = entry?;
+      let metadata = entry.metadata()?;
+      size += if metadata.is_dir() {
+        Self::tap_metrics_dir_size(&entry.path())?
+      } else {
+        metadata.len()
+      };
+    }
+
+    Ok(size)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::index::testing::Context};
+
+  #[test]
+  fn histograms_render_cumulative_buckets() {
+    let metrics = TapMetrics::default();
+    metrics.record_reorg(1);
+    metrics.record_reorg(3);
+
+    let mut out = String::new();
+    metrics.render(&mut out);
+
+    assert!(out.contains("ord_tap_reorg_depth_blocks_bucket{le=\"1\"} 1\n"));
+    assert!(out.contains("ord_tap_reorg_depth_blocks_bucket{le=\"2\"} 1\n"));
+    assert!(out.contains("ord_tap_reorg_depth_blocks_bucket{le=\"3\"} 2\n"));
+    assert!(out.contains("ord_tap_reorg_depth_blocks_bucket{le=\"+Inf\"} 2\n"));
+    assert!(out.contains("ord_tap_reorg_depth_blocks_sum 4\n"));
+    assert!(out.contains("ord_tap_reorg_depth_blocks_count 2\n"));
+  }
+
+  #[test]
+  fn block_records_accumulate_op_timings_and_route_counters() {
+    let metrics = TapMetrics::default();
+    let ops = [TapOpTiming {
+      phase: "created",
+      op: "token-mint",
+      count: 2,
+      micros: 1_500,
+    }];
+    let routes = TapRouteCounters {
+      hits: 3,
+      dmt_hot_evictions: 1,
+      ..default()
+    };
+    metrics.record_block(5, Duration::from_millis(20), &ops, routes);
+    metrics.record_block(6, Duration::from_millis(20), &ops, routes);
+
+    let mut out = String::new();
+    metrics.render(&mut out);
+
+    assert!(out.contains("ord_tap_last_block_height 6\n"));
+    assert!(out.contains("ord_tap_op_total{phase=\"created\",op=\"token-mint\"} 4\n"));
+    assert!(out.contains("ord_tap_op_seconds_total{phase=\"created\",op=\"token-mint\"} 0.003\n"));
+    assert!(out.contains("ord_tap_route_lookups_total{result=\"hit\"} 6\n"));
+    assert!(out.contains("ord_tap_hot_cache_evictions_total{cache=\"dmt\"} 2\n"));
+    assert!(out.contains("ord_tap_block_index_seconds_count 2\n"));
+  }
+
+  #[test]
+  fn request_labels_are_escaped() {
+    let metrics = TapMetrics::default();
+    metrics.record_request("GET", "/r/tap/\"x\"", 200, Duration::from_millis(2));
+
+    let mut out = String::new();
+    metrics.render(&mut out);
+
+    assert!(out.contains(
+      "ord_tap_http_request_duration_seconds_count{method=\"GET\",route=\"/r/tap/\\\"x\\\"\",status=\"200\"} 1\n"
+    ));
+  }
+
+  #[test]
+  fn delta_bytes_are_measured_once_per_block() {
+    let metrics = TapMetrics::default();
+    assert_eq!(metrics.delta_bytes(5, || Ok(10)).unwrap(), 10);
+    assert_eq!(metrics.delta_bytes(5, || unreachable!()).unwrap(), 10);
+    assert_eq!(metrics.delta_bytes(6, || Ok(20)).unwrap(), 20);
+    metrics.invalidate_delta_bytes();
+    assert_eq!(metrics.delta_bytes(6, || Ok(15)).unwrap(), 15);
+  }
+
+  #[test]
+  fn metrics_text_reports_indexed_height() {
+    let context = Context::builder().build();
+    context.mine_blocks(2);
+
+    assert!(context
+      .index
+      .tap_metrics_text()
+      .unwrap()
+      .contains("ord_tap_indexed_height 2\n"));
+  }
+}
@@ -0,0 +1,450 @@
+use super::*;
+
+const TAP_EXPORT_CONSUMER_CURSOR_PREFIX: &str = "consumer_cursor/";
+
+/// A writer export read below the retained coverage start after retention has
+/// pruned it. Consumers must take a fresh snapshot before following deltas
+/// again.
+#[derive(Debug, PartialEq)]
+pub(crate) struct TapExportResnapshotRequired {
+  pub(crate) height: u32,
+  pub(crate) coverage_start: u32,
+}
+
+impl Display for TapExportResnapshotRequired {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    write!(
+      f,
+      "resnapshot required: block {} was pruned, writer export deltas are retained from block {}",
+      self.height, self.coverage_start
+    )
+  }
+}
+
+impl std::error::Error for TapExportResnapshotRequired {}
+
+/// How much writer export history to keep. Every configured limit applies, and
+/// `consumers` additionally holds back pruning until the slowest consumer has
+/// acknowledged the blocks being dropped.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct TapExportRetention {
+  pub(crate) blocks: Option<u32>,
+  pub(crate) bytes: Option<u64>,
+  pub(crate) consumers: bool,
+}
+
+impl TapExportRetention {
+  pub(crate) fn from_settings(settings: &Settings) -> Option<Self> {
+    settings.tap_writer_export_retention().then(|| Self {
+      blocks: settings.tap_writer_export_retain_blocks(),
+      bytes: settings.tap_writer_export_retain_bytes(),
+      consumers: settings.tap_writer_export_retain_consumers(),
+    })
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct TapExportPrune {
+  pub(crate) coverage_start: u32,
+  pub(crate) bytes_removed: u64,
+}
+
+impl Index {
+  /// Record that `consumer` has applied every block below `next_block`. Cursors
+  /// are held in memory and persisted by the next retention pass, so acks never
+  /// wait on the updater's write transaction.
+  pub(crate) fn tap_export_ack(&self, consumer: &str, next_block: u32) {
+    self
+      .tap_export_consumer_cursors
+      .lock()
+      .unwrap()
+      .insert(consumer.to_string(), next_block);
+  }
+
+  pub(crate) fn tap_export_consumer_cursors_from_table(
+    table: &impl ReadableTable<&'static [u8], &'static [u8]>,
+  ) -> Result<BTreeMap<String, u32>> {
+    let mut cursors = BTreeMap::new();
+    for result in table.range(TAP_EXPORT_CONSUMER_CURSOR_PREFIX.as_bytes()..)? {
+      let (key, value) = result?;
+      let Some(consumer) = key
+        .value()
+        .strip_prefix(TAP_EXPORT_CONSUMER_CURSOR_PREFIX.as_bytes())
+      else {
+        break;
+      };
+      cursors.insert(
+        String::from_utf8(consumer.to_vec())?,
+        std::str::from_utf8(value.value())?.parse()?,
+      );
+    }
+    Ok(cursors)
+  }
+
+  /// Refuse reads of `height` when retention has pruned it, rather than
+  /// answering as if the block had no deltas.
+  pub(crate) fn tap_export_ensure_retained(
+    table: &impl ReadableTable<&'static [u8], &'static [u8]>,
+    height: u32,
+  ) -> Result {
+    if Self::tap_export_metadata_get_u32(table, TAP_EXPORT_PRUNED_BEFORE_HEIGHT)?.is_none() {
+      return Ok(());
+    }
+
+    if let Some(coverage_start) =
+      Self::tap_export_metadata_get_u32(table, TAP_EXPORT_ENABLED_FROM_HEIGHT)?
+    {
+      if height < coverage_start {
+        bail!(TapExportResnapshotRequired {
+          height,
+          coverage_start,
+        });
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Move the coverage start back up to the pruned height recorded next to the
+  /// delta segments, in case a savepoint restore rewound it.
+  pub(crate) fn tap_export_restore_pruned_start(
+    table: &mut Table<'_, &'static [u8], &'static [u8]>,
+    pruned_before: u32,
+  ) -> Result {
+    if Self::tap_export_metadata_get_u32(table, TAP_EXPORT_ENABLED_FROM_HEIGHT)?
+      .is_some_and(|start| start < pruned_before)
+    {
+      log::warn!("tap export: restoring coverage start to pruned height {pruned_before}");
+      Self::tap_export_metadata_put_u32(table, TAP_EXPORT_ENABLED_FROM_HEIGHT, pruned_before)?;
+      Self::tap_export_metadata_put_u32(table, TAP_EXPORT_PRUNED_BEFORE_HEIGHT, pruned_before)?;
+    }
+    Ok(())
+  }
+
+  /// First block `retention` keeps, never past the savepoint reorg window.
+  fn tap_export_retention_start(
+    &self,
+    retention: TapExportRetention,
+    coverage_start: u32,
+    coverage_tip: u32,
+    slowest_consumer: Option<u32>,
+  ) -> Result<u32> {
+    let next = coverage_tip.saturating_add(1);
+    let mut start = coverage_start;
+
+    if let Some(blocks) = retention.blocks {
+      start = start.max(next.saturating_sub(blocks));
+    }
+
+    if let Some(bytes) = retention.bytes {
+      let mut retained = 0u64;
+      for entry in self
+        .tap_export_delta_segments()
+        .block_entries(coverage_start, coverage_tip)?
+        .into_iter()
+        .rev()
+      {
+        retained = retained.saturating_add(entry.len);
+        if retained > bytes {
+          start = start.max(entry.height + 1);
+          break;
+        }
+      }
+    }
+
+    if retention.consumers {
+      let Some(cursor) = slowest_consumer else {
+        return Ok(coverage_start);
+      };
+      start = if retention.blocks.is_none() && retention.bytes.is_none() {
+        cursor
+      } else {
+        start.min(cursor)
+      };
+    }
+
+    let reorg_window = u32::try_from(
+      self
+        .settings
+        .savepoint_interval()
+        .saturating_mul(self.settings.max_savepoints()),
+    )
+    .unwrap_or(u32::MAX);
+
+    Ok(
+      start
+        .min(next.saturating_sub(reorg_window))
+        .max(coverage_start),
+    )
+  }
+
+  /// Apply `retention` once: advance the advertised coverage start and drop
+  /// block states, redb deltas and superseded key history rows below it in a
+  /// single transaction.
+  ///
+  /// Segment files below the previous pass's start are removed afterwards, so
+  /// a reader still holding a transaction from before this pass never loses
+  /// rows underneath it.
+  pub(crate) fn prune_tap_export(
+    &self,
+    retention: TapExportRetention,
+  ) -> Result<Option<TapExportPrune>> {
+    let cursors = self.tap_export_consumer_cursors.lock().unwrap().clone();
+
+    let tx = self.begin_write()?;
+    let (pruned_before, coverage_start) = {
+      let mut metadata = tx.open_table(TAP_EXPORT_METADATA)?;
+      for (consumer, next_block) in &cursors {
+        metadata.insert(
+          format!("{TAP_EXPORT_CONSUMER_CURSOR_PREFIX}{consumer}").as_bytes(),
+          next_block.to_string().as_bytes(),
+        )?;
+      }
+
+      let pruned_before =
+        Self::tap_export_metadata_get_u32(&metadata, TAP_EXPORT_PRUNED_BEFORE_HEIGHT)?;
+
+      let (Some(coverage_start), Some(coverage_tip)) = (
+        Self::tap_export_metadata_get_u32(&metadata, TAP_EXPORT_ENABLED_FROM_HEIGHT)?,
+        Self::tap_export_metadata_get_u32(&metadata, TAP_EXPORT_COVERAGE_TIP)?,
+      ) else {
+        drop(metadata);
+        tx.commit()?;
+        return Ok(None);
+      };
+
+      let slowest_consumer = Self::tap_export_consumer_cursors_from_table(&metadata)?
+        .into_values()
+        .min();
+
+      let start = self.tap_export_retention_start(
+        retention,
+        coverage_start,
+        coverage_tip,
+        slowest_consumer,
+      )?;
+
+      if start > coverage_start {
+        Self::tap_export_metadata_put_u32(&mut metadata, TAP_EXPORT_ENABLED_FROM_HEIGHT, start)?;
+        Self::tap_export_metadata_put_u32(&mut metadata, TAP_EXPORT_PRUNED_BEFORE_HEIGHT, start)?;
+
+        let block_state_end = format!("{start:010}");
+        tx.open_table(TAP_EXPORT_BLOCK_STATES)?
+          .retain(|key, _| key >= block_state_end.as_bytes())?;
+
+        let delta_end = format!("{start:010}/");
+        tx.open_table(TAP_EXPORT_DELTAS)?
+          .retain(|key, _| key >= delta_end.as_bytes())?;
+
+        Self::prune_tap_key_history(&mut tx.open_table(TAP_KEY_HISTORY)?, start)?;
+      }
+
+      (pruned_before, start)
+    };
+    tx.commit()?;
+
+    let bytes_removed = match pruned_before {
+      Some(pruned_before) => self
+        .tap_export_delta_segments()
+        .prune_before(pruned_before)?,
+      None => 0,
+    };
+
+    if bytes_removed > 0 {
+      self.tap_metrics.invalidate_delta_bytes();
+    }
+
+    Ok(Some(TapExportPrune {
+      coverage_start,
+      bytes_removed,
+    }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::index::testing::Context};
+
+  fn write_export(context: &Context, blocks: u32) {
+    context.mine_blocks(blocks.into());
+    let values = (0..context.index.block_count().unwrap())
+      .map(|height| height.to_string())
+      .collect::<Vec<String>>();
+    let blocks = values
+      .iter()
+      .enumerate()
+      .map(|(height, value)| (height as u32, vec![("k", Some(value.as_str()))]))
+      .collect::<BTreeMap<u32, Vec<(&str, Option<&str>)>>>();
+    context.index.tap_test_write_export(&blocks, 0).unwrap();
+  }
+
+  fn retention(blocks: Option<u32>, consumers: bool) -> TapExportRetention {
+    TapExportRetention {
+      blocks,
+      bytes: None,
+      consumers,
+    }
+  }
+
+  #[test]
+  fn retention_advances_coverage_start_and_refuses_pruned_reads() {
+    let context = Context::builder().build();
+    write_export(&context, 40);
+
+    let prune = context
+      .index
+      .prune_tap_export(retention(Some(5), false))
+      .unwrap()
+      .unwrap();
+
+    // 41 blocks with a 20 block reorg window keep 21..=40 despite the limit.
+    assert_eq!(prune.coverage_start, 21);
+    assert_eq!(prune.bytes_removed, 0);
+
+    let status = context.index.tap_export_retention_status().unwrap();
+    assert_eq!(status.export_enabled_from_height, Some(21));
+    assert_eq!(status.earliest_retained_block, Some(21));
+
+    let error = context
+      .index
+      .tap_export_deltas(20, 0, 10, None)
+      .unwrap_err();
+    assert_eq!(
+      error.downcast_ref::<TapExportResnapshotRequired>(),
+      Some(&TapExportResnapshotRequired {
+        height: 20,
+        coverage_start: 21,
+      })
+    );
+    assert!(context.index.tap_export_block_digest(3).is_err());
+    assert_eq!(
+      context
+        .index
+        .tap_export_block_digest(21)
+        .unwrap()
+        .rolling_state_row_count,
+      Some(1)
+    );
+    assert_eq!(
+      context
+        .index
+        .tap_export_deltas(21, 0, 10, None)
+        .unwrap()
+        .rows[0]
+        .height,
+      21
+    );
+  }
+
+  #[test]
+  fn retention_drops_key_history_superseded_before_coverage_start() {
+    let context = Context::builder().build();
+    context.mine_blocks(40);
+    let values = (0..=40)
+      .map(|height: u32| height.to_string())
+      .collect::<Vec<String>>();
+    let mut blocks = values
+      .iter()
+      .enumerate()
+      .map(|(height, value)| (height as u32, vec![("k", Some(value.as_str()))]))
+      .collect::<BTreeMap<u32, Vec<(&str, Option<&str>)>>>();
+    blocks.get_mut(&2).unwrap().push(("early", Some("a")));
+    blocks.get_mut(&5).unwrap().push(("early", Some("b")));
+    context.index.tap_test_write_export(&blocks, 0).unwrap();
+
+    context
+      .index
+      .prune_tap_export(retention(Some(5), false))
+      .unwrap();
+
+    let rtx = context.index.begin_read().unwrap();
+    let history = rtx
+      .0
+      .open_table(TAP_KEY_HISTORY)
+      .unwrap()
+      .iter()
+      .unwrap()
+      .map(|result| result.unwrap().0.value().to_vec())
+      .collect::<Vec<Vec<u8>>>();
+    assert_eq!(
+      history,
+      std::iter::once(Index::tap_key_history_key("early", 5))
+        .chain((20..=40).map(|height| Index::tap_key_history_key("k", height)))
+        .collect::<Vec<Vec<u8>>>()
+    );
+
+    let view = context.index.tap_state_view(Some(21)).unwrap();
+    assert_eq!(view.get_string("k").unwrap().as_deref(), Some("21"));
+    assert_eq!(view.get_string("early").unwrap().as_deref(), Some("b"));
+  }
+
+  #[test]
+  fn retention_waits_for_the_slowest_consumer() {
+    let context = Context::builder().build();
+    write_export(&context, 40);
+
+    assert_eq!(
+      context
+        .index
+        .prune_tap_export(retention(None, true))
+        .unwrap()
+        .unwrap()
+        .coverage_start,
+      0
+    );
+
+    context.index.tap_export_ack("fast", 35);
+    context.index.tap_export_ack("slow", 12);
+
+    let prune = context
+      .index
+      .prune_tap_export(retention(Some(5), true))
+      .unwrap()
+      .unwrap();
+    assert_eq!(prune.coverage_start, 12);
+
+    context.index.tap_export_ack("slow", 30);
+    assert_eq!(
+      context
+        .index
+        .prune_tap_export(retention(None, true))
+        .unwrap()
+        .unwrap()
+        .coverage_start,
+      21
+    );
+  }
+
+  #[test]
+  fn pruned_height_is_recorded_one_pass_later_and_survives_restore() {
+    let context = Context::builder().build();
+    write_export(&context, 40);
+
+    let segments = context.index.tap_export_delta_segments();
+
+    context
+      .index
+      .prune_tap_export(retention(Some(1), false))
+      .unwrap();
+    assert_eq!(segments.pruned_before().unwrap(), None);
+    assert_eq!(segments.block_entries(0, 0).unwrap().len(), 1);
+
+    context
+      .index
+      .prune_tap_export(retention(Some(1), false))
+      .unwrap();
+    assert_eq!(segments.pruned_before().unwrap(), Some(21));
+
+    let tx = context.index.begin_write().unwrap();
+    {
+      let mut metadata = tx.open_table(TAP_EXPORT_METADATA).unwrap();
+      Index::tap_export_metadata_put_u32(&mut metadata, TAP_EXPORT_ENABLED_FROM_HEIGHT, 0).unwrap();
+      Index::tap_export_restore_pruned_start(&mut metadata, 21).unwrap();
+      assert_eq!(
+        Index::tap_export_metadata_get_u32(&metadata, TAP_EXPORT_ENABLED_FROM_HEIGHT).unwrap(),
+        Some(21)
+      );
+    }
+    tx.abort().unwrap();
+  }
+}
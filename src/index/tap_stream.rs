@@ -0,0 +1,705 @@
+use {super::*, std::collections::VecDeque, tokio::sync::broadcast};
+
+// Recent events kept in memory so reconnecting subscribers can resume from a
+// cursor without walking the per-block list routes.
+const TAP_STREAM_RETAINED_EVENTS: usize = 100_000;
+const TAP_STREAM_CHANNEL_CAPACITY: usize = 4_096;
+
+/// Position of an event in the TAP block event stream. Record events are
+/// numbered from zero within each block; a rollback event uses the end of the
+/// surviving tip block so resuming after it skips nothing that is still valid.
+/// The hash of the cursor's block lets a resume from a branch that has since
+/// been reorged out be detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TapStreamCursor {
+  pub block: u32,
+  pub sequence: u64,
+  pub hash: Option<BlockHash>,
+}
+
+impl TapStreamCursor {
+  fn position(&self) -> (u32, u64) {
+    (self.block, self.sequence)
+  }
+}
+
+impl Display for TapStreamCursor {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    write!(f, "{}:{}", self.block, self.sequence)?;
+    if let Some(hash) = self.hash {
+      write!(f, ":{hash}")?;
+    }
+    Ok(())
+  }
+}
+
+impl FromStr for TapStreamCursor {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    let mut parts = s.splitn(3, ':');
+    let (Some(block), Some(sequence)) = (parts.next(), parts.next()) else {
+      bail!("invalid TAP stream cursor `{s}`, expected `<block>:<sequence>:<block hash>`");
+    };
+    Ok(Self {
+      block: block.parse()?,
+      sequence: sequence.parse()?,
+      hash: parts.next().map(str::parse).transpose()?,
+    })
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub(crate) enum TapStreamEvent {
+  /// One entry appended to a `blck/{family}/{block}` list.
+  Record {
+    block: u32,
+    block_hash: BlockHash,
+    sequence: u64,
+    family: String,
+    key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pointer: Option<String>,
+    record: serde_json::Value,
+  },
+  /// Blocks above `tip` were rolled back after a reorg and their events are
+  /// no longer valid. `tip` is `None` when nothing survived.
+  Rollback {
+    tip: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tip_hash: Option<BlockHash>,
+  },
+}
+
+impl TapStreamEvent {
+  pub(crate) fn cursor(&self) -> TapStreamCursor {
+    match self {
+      Self::Record {
+        block,
+        block_hash,
+        sequence,
+        ..
+      } => TapStreamCursor {
+        block: *block,
+        sequence: *sequence,
+        hash: Some(*block_hash),
+      },
+      Self::Rollback { tip, tip_hash } => match tip {
+        Some(tip) => TapStreamCursor {
+          block: *tip,
+          sequence: u64::MAX,
+          hash: *tip_hash,
+        },
+        None => TapStreamCursor {
+          block: 0,
+          sequence: 0,
+          hash: None,
+        },
+      },
+    }
+  }
+}
+
+/// Subscriber-side filter. Every set field must match; rollback events always
+/// pass.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TapStreamFilter {
+  pub ticker: Option<String>,
+  pub address: Option<String>,
+  pub authority: Option<String>,
+  pub families: Vec<String>,
+}
+
+impl TapStreamFilter {
+  pub(crate) fn matches(&self, event: &TapStreamEvent) -> bool {
+    let TapStreamEvent::Record { family, record, .. } = event else {
+      return true;
+    };
+
+    if !self.families.is_empty()
+      && !self.families.iter().any(|wanted| {
+        family == wanted
+          || family
+            .strip_prefix(wanted.as_str())
+            .is_some_and(|rest| rest.starts_with('/'))
+      })
+    {
+      return false;
+    }
+
+    if let Some(ticker) = &self.ticker {
+      let tick = record.get("tick").and_then(|tick| tick.as_str());
+      if !tick.is_some_and(|tick| tap_js_to_lowercase(tick) == tap_js_to_lowercase(ticker)) {
+        return false;
+      }
+    }
+
+    for wanted in [&self.address, &self.authority].into_iter().flatten() {
+      let found = record.as_object().is_some_and(|fields| {
+        fields
+          .values()
+          .any(|value| value.as_str() == Some(wanted.as_str()))
+      });
+      if !found {
+        return false;
+      }
+    }
+
+    true
+  }
+}
+
+/// Events of indexed blocks waiting for their write transaction to commit.
+/// Holds at most the retained window, since anything older would be evicted
+/// as soon as it was published.
+#[derive(Default)]
+pub(crate) struct TapStreamPending {
+  events: VecDeque<TapStreamEvent>,
+  hashes: Vec<(u32, BlockHash)>,
+  evicted: Option<TapStreamCursor>,
+}
+
+impl TapStreamPending {
+  pub(crate) fn push_block(&mut self, height: u32, hash: BlockHash, events: Vec<TapStreamEvent>) {
+    self.hashes.push((height, hash));
+    for event in events {
+      self.events.push_back(event);
+      if self.events.len() > TAP_STREAM_RETAINED_EVENTS {
+        if let Some(evicted) = self.events.pop_front() {
+          self.evicted = Some(evicted.cursor());
+        }
+      }
+    }
+  }
+}
+
+struct TapStreamRetained {
+  events: VecDeque<TapStreamEvent>,
+  tip: Option<u32>,
+  // Cursors before this can't be resumed: their events were indexed before
+  // the process started or have been evicted from the retained window.
+  floor: Option<TapStreamCursor>,
+  // Hashes of the blocks from the floor up to the tip.
+  hashes: BTreeMap<u32, BlockHash>,
+  // Blocks rolled back by a reorg, with the deepest tip they were rolled
+  // back to.
+  orphaned: HashMap<BlockHash, (u32, Option<u32>)>,
+}
+
+impl TapStreamRetained {
+  fn raise_floor(&mut self, cursor: TapStreamCursor) {
+    if self
+      .floor
+      .map_or(true, |floor| floor.position() < cursor.position())
+    {
+      self.floor = Some(cursor);
+    }
+    let floor_block = cursor.block;
+    self.hashes.retain(|block, _| *block >= floor_block);
+    self.orphaned.retain(|_, (block, _)| *block >= floor_block);
+  }
+
+  fn stale(&self, after: TapStreamCursor) -> Result {
+    if let Some(floor) = self.floor {
+      ensure!(
+        after.position() >= floor.position(),
+        "TAP stream cursor {after} is older than the retained events, which resume from {floor}; walk the …ByBlock routes to catch up"
+      );
+    }
+    Ok(())
+  }
+
+  fn replay_after(
+    &self,
+    after: Option<TapStreamCursor>,
+  ) -> impl Iterator<Item = TapStreamEvent> + '_ {
+    self
+      .events
+      .iter()
+      .filter(move |event| after.map_or(true, |after| event.cursor().position() > after.position()))
+      .cloned()
+  }
+}
+
+pub(crate) struct TapStream {
+  enabled: bool,
+  sender: broadcast::Sender<TapStreamEvent>,
+  retained: Mutex<TapStreamRetained>,
+}
+
+impl TapStream {
+  pub(crate) fn new(tip: Option<(u32, BlockHash)>, enabled: bool) -> Self {
+    Self {
+      enabled,
+      sender: broadcast::channel(TAP_STREAM_CHANNEL_CAPACITY).0,
+      retained: Mutex::new(TapStreamRetained {
+        events: VecDeque::new(),
+        tip: tip.map(|(block, _)| block),
+        // Everything up to and including the tip block was indexed before
+        // the process started.
+        floor: tip.map(|(block, hash)| TapStreamCursor {
+          block,
+          sequence: u64::MAX,
+          hash: Some(hash),
+        }),
+        hashes: tip.into_iter().collect(),
+        orphaned: HashMap::new(),
+      }),
+    }
+  }
+
+  /// Whether the updater should collect block events at all.
+  pub(crate) fn enabled(&self) -> bool {
+    self.enabled
+  }
+
+  /// Publish the events of committed blocks up to and including `tip`.
+  pub(crate) fn publish(&self, tip: u32, pending: TapStreamPending) {
+    let mut retained = self.retained.lock().unwrap();
+    retained.tip = Some(tip);
+    retained.hashes.extend(pending.hashes);
+    if let Some(evicted) = pending.evicted {
+      retained.raise_floor(evicted);
+    }
+    for event in pending.events {
+      retained.events.push_back(event.clone());
+      if retained.events.len() > TAP_STREAM_RETAINED_EVENTS {
+        if let Some(evicted) = retained.events.pop_front() {
+          retained.raise_floor(evicted.cursor());
+        }
+      }
+      // No receivers is not an error; events stay retained for resumption.
+      let _ = self.sender.send(event);
+    }
+  }
+
+  pub(crate) fn rollback(&self, tip: Option<u32>, tip_hash: Option<BlockHash>) {
+    let mut retained = self.retained.lock().unwrap();
+    let survives = |block: u32| tip.is_some_and(|tip| block <= tip);
+
+    let rolled_back = retained
+      .hashes
+      .iter()
+      .filter(|(block, _)| !survives(**block))
+      .map(|(block, hash)| (*hash, (*block, tip)))
+      .collect::<Vec<_>>();
+    for (_, (_, orphaned_tip)) in retained.orphaned.iter_mut() {
+      *orphaned_tip = (*orphaned_tip).min(tip);
+    }
+    retained.orphaned.extend(rolled_back);
+    retained.hashes.retain(|block, _| survives(*block));
+
+    // Nothing above the new tip is lost: everything indexed from here on is
+    // retained.
+    let tip_cursor = tip.map(|block| TapStreamCursor {
+      block,
+      sequence: u64::MAX,
+      hash: tip_hash,
+    });
+    retained.floor = match (retained.floor, tip_cursor) {
+      (Some(floor), Some(tip)) if tip.position() < floor.position() => Some(tip),
+      (floor, Some(_)) => floor,
+      (_, None) => None,
+    };
+
+    retained.tip = tip;
+    retained.events.retain(|event| match event {
+      TapStreamEvent::Record { block, .. } => survives(*block),
+      TapStreamEvent::Rollback { .. } => false,
+    });
+    let _ = self.sender.send(TapStreamEvent::Rollback { tip, tip_hash });
+  }
+
+  /// Subscribe to live events, first replaying retained events after `after`.
+  ///
+  /// A cursor on a block that has since been rolled back replays a rollback
+  /// to the surviving tip first. Fails when the cursor is older than the
+  /// retained window, or names a block hash that is neither current nor known
+  /// to have been rolled back, since events in between can no longer be
+  /// replayed.
+  pub(crate) fn subscribe(
+    &self,
+    after: Option<TapStreamCursor>,
+  ) -> Result<(Vec<TapStreamEvent>, broadcast::Receiver<TapStreamEvent>)> {
+    let retained = self.retained.lock().unwrap();
+    let receiver = self.sender.subscribe();
+
+    let Some(after) = after else {
+      return Ok((Vec::new(), receiver));
+    };
+
+    if let Some(&(_, tip)) = after.hash.and_then(|hash| retained.orphaned.get(&hash)) {
+      // The subscriber followed a branch that has since been rolled back.
+      let tip_hash = tip.and_then(|tip| retained.hashes.get(&tip).copied());
+      let rollback = TapStreamEvent::Rollback { tip, tip_hash };
+      retained.stale(rollback.cursor())?;
+      let resume = tip.map(|_| rollback.cursor());
+      let mut replay = vec![rollback];
+      replay.extend(retained.replay_after(resume));
+      return Ok((replay, receiver));
+    }
+
+    if let (Some(hash), Some(known)) = (after.hash, retained.hashes.get(&after.block)) {
+      ensure!(
+        hash == *known,
+        "TAP stream cursor {after} is on block {} hash {hash}, which is not in the indexed chain; walk the …ByBlock routes to resync",
+        after.block
+      );
+    }
+
+    retained.stale(after)?;
+
+    let mut replay = Vec::new();
+    if let Some(tip) = retained.tip {
+      if after.block > tip {
+        // The subscriber saw blocks that have since been rolled back.
+        replay.push(TapStreamEvent::Rollback {
+          tip: Some(tip),
+          tip_hash: retained.hashes.get(&tip).copied(),
+        });
+      }
+    }
+    replay.extend(retained.replay_after(Some(after)));
+
+    Ok((replay, receiver))
+  }
+}
+
+impl Index {
+  pub(crate) fn tap_stream(&self) -> &TapStream {
+    &self.tap_stream
+  }
+
+  /// Decode a committed `blcki/{family}/{block}/{i}` row into a stream event,
+  /// resolving list entries that point at records stored elsewhere.
+  pub(crate) fn tap_stream_record_event(
+    key: &str,
+    value: &[u8],
+    block_hash: BlockHash,
+    sequence: u64,
+    mut resolve: impl FnMut(&str) -> Result<Option<Vec<u8>>>,
+  ) -> Result<Option<TapStreamEvent>> {
+    let Some(rest) = key.strip_prefix("blcki/") else {
+      return Ok(None);
+    };
+    let Some((rest, _i)) = rest.rsplit_once('/') else {
+      return Ok(None);
+    };
+    let Some((family, block)) = rest.rsplit_once('/') else {
+      return Ok(None);
+    };
+    let Ok(block) = block.parse::<u32>() else {
+      return Ok(None);
+    };
+
+    let mut record = Self::tap_stream_decode_value(value);
+    let mut pointer = None;
+    if let serde_json::Value::String(target) = &record {
+      if let Some(bytes) = resolve(target)? {
+        pointer = Some(target.clone());
+        record = Self::tap_stream_decode_value(&bytes);
+      }
+    }
+
+    Ok(Some(TapStreamEvent::Record {
+      block,
+      block_hash,
+      sequence,
+      family: family.to_string(),
+      key: key.to_string(),
+      pointer,
+      record,
+    }))
+  }
+
+  fn tap_stream_decode_value(bytes: &[u8]) -> serde_json::Value {
+    match Self::tap_export_value_details(bytes) {
+      Some(details) if details.value_kind.starts_with("json-") => {
+        serde_json::from_str(&details.value).unwrap_or(serde_json::Value::String(details.value))
+      }
+      Some(details) => serde_json::Value::String(details.value),
+      None => serde_json::Value::Null,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn hash(n: u8) -> BlockHash {
+    BlockHash::from_byte_array([n; 32])
+  }
+
+  fn cursor(block: u32, sequence: u64, hash: Option<BlockHash>) -> TapStreamCursor {
+    TapStreamCursor {
+      block,
+      sequence,
+      hash,
+    }
+  }
+
+  fn record(block: u32, sequence: u64, family: &str, record: serde_json::Value) -> TapStreamEvent {
+    TapStreamEvent::Record {
+      block,
+      block_hash: hash(block as u8),
+      sequence,
+      family: family.to_string(),
+      key: format!("blcki/{family}/{block}/{sequence}"),
+      pointer: None,
+      record,
+    }
+  }
+
+  #[test]
+  fn tap_stream_record_event_parses_family_and_resolves_pointers() {
+    let mint = serde_json::json!({"tick": "tap", "addr": "bc1qminer", "amt": "10"});
+    let event = Index::tap_stream_record_event(
+      "blcki/perp/settle/120/3",
+      serde_json::to_string(&mint).unwrap().as_bytes(),
+      hash(120),
+      7,
+      |_| Ok(None),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(
+      event,
+      TapStreamEvent::Record {
+        block: 120,
+        block_hash: hash(120),
+        sequence: 7,
+        family: "perp/settle".into(),
+        key: "blcki/perp/settle/120/3".into(),
+        pointer: None,
+        record: mint.clone(),
+      }
+    );
+
+    let mut pointer_value = Vec::new();
+    ciborium::into_writer(&"mnt/ptr/0".to_string(), &mut pointer_value).unwrap();
+    let event =
+      Index::tap_stream_record_event("blcki/mnt/121/0", &pointer_value, hash(121), 0, |key| {
+        assert_eq!(key, "mnt/ptr/0");
+        Ok(Some(serde_json::to_vec(&mint).unwrap()))
+      })
+      .unwrap()
+      .unwrap();
+    let TapStreamEvent::Record {
+      pointer, record, ..
+    } = event
+    else {
+      panic!("expected record event");
+    };
+    assert_eq!(pointer.as_deref(), Some("mnt/ptr/0"));
+    assert_eq!(record, mint);
+
+    assert!(
+      Index::tap_stream_record_event("b/addr/tap", b"1", hash(0), 0, |_| Ok(None))
+        .unwrap()
+        .is_none()
+    );
+  }
+
+  #[test]
+  fn tap_stream_filter_matches_ticker_address_authority_and_family() {
+    let event = record(
+      5,
+      0,
+      "perp/settle",
+      serde_json::json!({"tick": "TAP", "addr": "bc1qa", "auth": "authi0"}),
+    );
+
+    assert!(TapStreamFilter::default().matches(&event));
+    assert!(TapStreamFilter {
+      ticker: Some("tap".into()),
+      address: Some("bc1qa".into()),
+      authority: Some("authi0".into()),
+      families: vec!["perp".into()],
+    }
+    .matches(&event));
+    assert!(!TapStreamFilter {
+      families: vec!["per".into()],
+      ..default()
+    }
+    .matches(&event));
+    assert!(!TapStreamFilter {
+      ticker: Some("other".into()),
+      ..default()
+    }
+    .matches(&event));
+    assert!(!TapStreamFilter {
+      address: Some("bc1qb".into()),
+      ..default()
+    }
+    .matches(&event));
+    assert!(TapStreamFilter {
+      ticker: Some("other".into()),
+      ..default()
+    }
+    .matches(&TapStreamEvent::Rollback {
+      tip: Some(4),
+      tip_hash: None,
+    }));
+  }
+
+  fn pending(blocks: &[(u32, Vec<TapStreamEvent>)]) -> TapStreamPending {
+    let mut pending = TapStreamPending::default();
+    for (block, events) in blocks {
+      pending.push_block(*block, hash(*block as u8), events.clone());
+    }
+    pending
+  }
+
+  #[test]
+  fn tap_stream_cursor_round_trips_with_and_without_hash() {
+    let with_hash = cursor(10, 3, Some(hash(10)));
+    assert_eq!(
+      with_hash.to_string().parse::<TapStreamCursor>().unwrap(),
+      with_hash
+    );
+    assert_eq!(
+      "10:3".parse::<TapStreamCursor>().unwrap(),
+      cursor(10, 3, None)
+    );
+    assert!("10".parse::<TapStreamCursor>().is_err());
+  }
+
+  #[test]
+  fn tap_stream_resumes_after_cursor_and_rolls_back() {
+    let stream = TapStream::new(Some((9, hash(9))), true);
+    stream.publish(
+      11,
+      pending(&[
+        (
+          10,
+          vec![
+            record(10, 0, "mnt", serde_json::json!({})),
+            record(10, 1, "trf", serde_json::json!({})),
+          ],
+        ),
+        (11, vec![record(11, 0, "mnt", serde_json::json!({}))]),
+      ]),
+    );
+
+    let (replay, _) = stream
+      .subscribe(Some(cursor(10, 0, Some(hash(10)))))
+      .unwrap();
+    assert_eq!(
+      replay
+        .iter()
+        .map(TapStreamEvent::cursor)
+        .collect::<Vec<_>>(),
+      vec![cursor(10, 1, Some(hash(10))), cursor(11, 0, Some(hash(11)))]
+    );
+
+    let (_, mut receiver) = stream.subscribe(None).unwrap();
+    stream.rollback(Some(10), Some(hash(10)));
+    assert_eq!(
+      receiver.try_recv().unwrap(),
+      TapStreamEvent::Rollback {
+        tip: Some(10),
+        tip_hash: Some(hash(10)),
+      }
+    );
+
+    let (replay, _) = stream.subscribe(Some(cursor(11, 0, None))).unwrap();
+    assert_eq!(
+      replay,
+      vec![TapStreamEvent::Rollback {
+        tip: Some(10),
+        tip_hash: Some(hash(10)),
+      }]
+    );
+
+    let error = stream
+      .subscribe(Some(cursor(8, 3, Some(hash(8)))))
+      .unwrap_err();
+    assert!(error.to_string().contains("older than the retained events"));
+  }
+
+  #[test]
+  fn tap_stream_refuses_resume_inside_the_startup_tip_block() {
+    let stream = TapStream::new(Some((9, hash(9))), true);
+
+    let error = stream.subscribe(Some(cursor(9, 2, None))).unwrap_err();
+    assert!(error.to_string().contains("older than the retained events"));
+
+    assert!(stream
+      .subscribe(Some(cursor(9, u64::MAX, Some(hash(9)))))
+      .unwrap()
+      .0
+      .is_empty());
+  }
+
+  #[test]
+  fn tap_stream_resume_on_a_reorged_branch_replays_a_rollback() {
+    let stream = TapStream::new(Some((9, hash(9))), true);
+    stream.publish(
+      10,
+      pending(&[(10, vec![record(10, 0, "mnt", serde_json::json!({}))])]),
+    );
+    stream.rollback(Some(9), Some(hash(9)));
+
+    let replacement = TapStreamEvent::Record {
+      block: 10,
+      block_hash: hash(110),
+      sequence: 0,
+      family: "trf".into(),
+      key: "blcki/trf/10/0".into(),
+      pointer: None,
+      record: serde_json::json!({}),
+    };
+    let mut replacement_pending = TapStreamPending::default();
+    replacement_pending.push_block(10, hash(110), vec![replacement.clone()]);
+    stream.publish(10, replacement_pending);
+
+    // Same block number as the new tip, but on the rolled back branch.
+    let (replay, _) = stream
+      .subscribe(Some(cursor(10, 0, Some(hash(10)))))
+      .unwrap();
+    assert_eq!(
+      replay,
+      vec![
+        TapStreamEvent::Rollback {
+          tip: Some(9),
+          tip_hash: Some(hash(9)),
+        },
+        replacement,
+      ]
+    );
+
+    let error = stream
+      .subscribe(Some(cursor(10, 0, Some(hash(200)))))
+      .unwrap_err();
+    assert!(error.to_string().contains("not in the indexed chain"));
+  }
+
+  #[test]
+  fn tap_stream_pending_is_bounded_and_raises_the_floor() {
+    let mut pending = TapStreamPending::default();
+    pending.push_block(
+      10,
+      hash(10),
+      (0..TAP_STREAM_RETAINED_EVENTS as u64 + 2)
+        .map(|sequence| record(10, sequence, "mnt", serde_json::json!({})))
+        .collect(),
+    );
+    assert_eq!(pending.events.len(), TAP_STREAM_RETAINED_EVENTS);
+    assert_eq!(pending.evicted, Some(cursor(10, 1, Some(hash(10)))));
+
+    let stream = TapStream::new(Some((9, hash(9))), true);
+    stream.publish(10, pending);
+
+    let error = stream
+      .subscribe(Some(cursor(10, 0, Some(hash(10)))))
+      .unwrap_err();
+    assert!(error.to_string().contains("older than the retained events"));
+
+    let (replay, _) = stream
+      .subscribe(Some(cursor(10, 1, Some(hash(10)))))
+      .unwrap();
+    assert_eq!(replay.len(), TAP_STREAM_RETAINED_EVENTS);
+  }
+}
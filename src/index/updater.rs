@@ -1,6 +1,9 @@
 use serde::Serialize;
 use {
-  self::{inscription_updater::InscriptionUpdater, rune_updater::RuneUpdater},
+  self::{
+    inscription_updater::{AuthorityConfigRecord, InscriptionUpdater},
+    rune_updater::RuneUpdater,
+  },
   super::{fetcher::Fetcher, *},
   futures::future::try_join_all,
   tokio::sync::{
@@ -11,6 +14,14 @@ use {
 
 pub(super) mod inscription_updater;
 mod rune_updater;
+pub(super) mod tap_amm;
+pub(super) mod tap_lock;
+pub(super) mod tap_perp;
+mod tap_preview;
+pub(super) mod tap_sale;
+pub(super) mod tap_stake;
+pub(super) mod tap_trade;
+mod tap_validate;
 
 pub(crate) struct BlockData {
   pub(crate) header: Header,
@@ -46,6 +57,10 @@ pub(crate) struct Updater<'index> {
   pub(super) tap_route_index_enabled: bool,
   pub(super) tap_route_index_verify: bool,
   pub(super) tap_route_index_initialized: bool,
+  // TAP: last block covered by an imported TAP snapshot, if any.
+  pub(super) tap_snapshot_height: Option<u32>,
+  // TAP stream events for indexed blocks, published once they are committed.
+  pub(super) tap_stream_pending: crate::index::TapStreamPending,
 }
 
 impl Updater<'_> {
@@ -55,7 +70,7 @@ impl Updater<'_> {
       .or_else(|| std::str::from_utf8(bytes).ok().map(str::to_string))
   }
 
-  fn rebuild_tap_route_index(
+  pub(super) fn rebuild_tap_route_index(
     tap_kv: &mut Table<'_, &'static [u8], &'static [u8]>,
     route_index: &mut inscription_updater::TapRouteIndex,
     coverage_height: u32,
@@ -255,6 +270,15 @@ impl Updater<'_> {
         &self.index.tap_export_delta_dir(),
         self.height,
       )?;
+      if let Some(pruned_before) =
+        inscription_updater::TapDeltaSegments::new(self.index.tap_export_delta_dir())
+          .pruned_before()?
+      {
+        Index::tap_export_restore_pruned_start(
+          &mut wtx.open_table(TAP_EXPORT_METADATA)?,
+          pruned_before,
+        )?;
+      }
     }
 
     let mut uncommitted = 0;
@@ -614,24 +638,27 @@ impl Updater<'_> {
     let mut transaction_id_to_transaction = wtx.open_table(TRANSACTION_ID_TO_TRANSACTION)?;
     // TAP KV store: generic bytes->bytes for TAP protocol state
     let mut tap_kv = wtx.open_table(TAP_KV)?;
-    let tap_export_delta_dir = self
-      .index
-      .settings
-      .tap_writer_export_enabled()
-      .then(|| self.index.tap_export_delta_dir());
-    let mut tap_export_metadata = self
-      .index
-      .settings
-      .tap_writer_export_enabled()
+    let mut tap_derived = wtx.open_table(TAP_DERIVED)?;
+    // Blocks covered by an imported TAP snapshot are indexed without TAP; their
+    // state and export coverage came with the snapshot.
+    let tap_snapshot_block = self
+      .tap_snapshot_height
+      .is_some_and(|height| self.height <= height);
+    let tap_export_enabled = self.index.settings.tap_writer_export_enabled() && !tap_snapshot_block;
+    let tap_export_delta_dir = tap_export_enabled.then(|| self.index.tap_export_delta_dir());
+    let mut tap_export_metadata = tap_export_enabled
       .then(|| wtx.open_table(TAP_EXPORT_METADATA))
       .transpose()?;
-    let mut tap_export_block_states = (self.index.settings.tap_writer_export_enabled()
+    let mut tap_export_block_states = (tap_export_enabled
       && self.index.settings.tap_writer_export_rolling_state())
     .then(|| wtx.open_table(TAP_EXPORT_BLOCK_STATES))
     .transpose()?;
     if let Some(table) = tap_export_block_states.as_mut() {
       inscription_updater::TapDeltaBatch::delete_block_states_from_height(table, self.height)?;
     }
+    let mut tap_key_history = tap_export_enabled
+      .then(|| wtx.open_table(TAP_KEY_HISTORY))
+      .transpose()?;
     let tap_export_rolling_state = if let (Some(metadata), Some(block_states)) = (
       tap_export_metadata.as_mut(),
       tap_export_block_states.as_mut(),
@@ -647,6 +674,7 @@ impl Updater<'_> {
     };
     if let Some(table) = tap_export_metadata.as_mut() {
       Index::ensure_tap_export_coverage_metadata(table, self.height)?;
+      Index::ensure_tap_key_history_metadata(table, self.height)?;
     }
 
     let index_inscriptions = self.height >= self.index.settings.first_inscription_height()
@@ -765,10 +793,11 @@ impl Updater<'_> {
       transaction_id_to_transaction: &mut transaction_id_to_transaction,
       unbound_inscriptions,
       tap_db: inscription_updater::TapBatch::new(&mut tap_kv),
+      tap_derived_db: inscription_updater::TapBatch::new(&mut tap_derived),
       tap_delta_db: tap_export_delta_dir
         .as_ref()
         .map(|delta_dir| {
-          if let (Some(block_states), Some(rolling_state)) = (
+          let batch = if let (Some(block_states), Some(rolling_state)) = (
             tap_export_block_states.as_mut(),
             tap_export_rolling_state.clone(),
           ) {
@@ -777,71 +806,78 @@ impl Updater<'_> {
               block_states,
               self.height,
               rolling_state,
-            )
+            )?
           } else {
-            inscription_updater::TapDeltaBatch::new(delta_dir.clone(), self.height)
-          }
+            inscription_updater::TapDeltaBatch::new(delta_dir.clone(), self.height)?
+          };
+          Ok::<_, Error>(match tap_key_history.as_mut() {
+            Some(table) => batch.with_key_history(table),
+            None => batch,
+          })
         })
         .transpose()?,
       tap_atomic_writes: None,
       tap_atomic_overlay: None,
       tap_atomic_list_len_cache: None,
+      tap_atomic_derived: None,
       tap_route_index: self
         .tap_route_index_enabled
         .then(|| self.tap_route_index.clone()),
       tap_route_index_verify: self.tap_route_index_verify,
       list_len_cache: HashMap::new(),
       block_availability_cache: HashMap::new(),
-      profile: self.index.settings.tap_profile(),
-      prof_bm_tr_ms: 0,
+      // Op timings also feed `/metrics`, so they are collected whether or not
+      // `--tap-profile` logs them.
+      profile: true,
+      prof_bm_tr_us: 0,
       prof_bm_tr_ct: 0,
-      prof_dmt_tr_ms: 0,
+      prof_dmt_tr_us: 0,
       prof_dmt_tr_ct: 0,
-      prof_prv_tr_ms: 0,
+      prof_prv_tr_us: 0,
       prof_prv_tr_ct: 0,
-      prof_ttr_ex_ms: 0,
+      prof_ttr_ex_us: 0,
       prof_ttr_ex_ct: 0,
-      prof_tsend_ex_ms: 0,
+      prof_tsend_ex_us: 0,
       prof_tsend_ex_ct: 0,
-      prof_ttrade_ex_ms: 0,
+      prof_ttrade_ex_us: 0,
       prof_ttrade_ex_ct: 0,
-      prof_tauth_ex_ms: 0,
+      prof_tauth_ex_us: 0,
       prof_tauth_ex_ct: 0,
-      prof_pra_ex_ms: 0,
+      prof_pra_ex_us: 0,
       prof_pra_ex_ct: 0,
-      prof_blk_ex_ms: 0,
+      prof_blk_ex_us: 0,
       prof_blk_ex_ct: 0,
-      prof_unblk_ex_ms: 0,
+      prof_unblk_ex_us: 0,
       prof_unblk_ex_ct: 0,
-      prof_created_total_ms: 0,
+      prof_created_total_us: 0,
       prof_created_ct: 0,
-      prof_bm_cr_ms: 0,
+      prof_bm_cr_us: 0,
       prof_bm_cr_ct: 0,
-      prof_dmt_el_cr_ms: 0,
+      prof_dmt_el_cr_us: 0,
       prof_dmt_el_cr_ct: 0,
-      prof_dpl_cr_ms: 0,
+      prof_dpl_cr_us: 0,
       prof_dpl_cr_ct: 0,
-      prof_dmtmint_cr_ms: 0,
+      prof_dmtmint_cr_us: 0,
       prof_dmtmint_cr_ct: 0,
-      prof_mint_cr_ms: 0,
+      prof_mint_cr_us: 0,
       prof_mint_cr_ct: 0,
-      prof_ttr_cr_ms: 0,
+      prof_ttr_cr_us: 0,
       prof_ttr_cr_ct: 0,
-      prof_tsend_cr_ms: 0,
+      prof_tsend_cr_us: 0,
       prof_tsend_cr_ct: 0,
-      prof_ttrade_cr_ms: 0,
+      prof_ttrade_cr_us: 0,
       prof_ttrade_cr_ct: 0,
-      prof_tauth_cr_ms: 0,
+      prof_tauth_cr_us: 0,
       prof_tauth_cr_ct: 0,
-      prof_dmtdep_cr_ms: 0,
+      prof_dmtdep_cr_us: 0,
       prof_dmtdep_cr_ct: 0,
-      prof_pra_cr_ms: 0,
+      prof_pra_cr_us: 0,
       prof_pra_cr_ct: 0,
-      prof_prv_cr_ms: 0,
+      prof_prv_cr_us: 0,
       prof_prv_cr_ct: 0,
-      prof_blk_cr_ms: 0,
+      prof_blk_cr_us: 0,
       prof_blk_cr_ct: 0,
-      prof_unblk_cr_ms: 0,
+      prof_unblk_cr_us: 0,
       prof_unblk_cr_ct: 0,
       // Core inscription-path profiling
       prof_core_env_ms: 0,
@@ -874,6 +910,10 @@ impl Updater<'_> {
       delegate_cache: std::collections::HashMap::new(),
       delegate_payload_cache: std::collections::HashMap::new(),
       btc_network: self.index.settings.chain().network(),
+      tap_snapshot_height: self.tap_snapshot_height,
+      emit_events: true,
+      tap_amm_candle_intervals: self.index.settings.tap_amm_candle_intervals(),
+      preview_base: None,
     };
 
     // Store a compact header snapshot for TAP (bits, nonce, ntx, time)
@@ -890,7 +930,9 @@ impl Updater<'_> {
       ntx: u32::try_from(block.txdata.len()).unwrap_or(u32::MAX),
       time: block.header.time,
     };
-    inscription_updater.tap_put(&format!("hdr/{}", self.height), &hdr)?;
+    if !tap_snapshot_block {
+      inscription_updater.tap_put(&format!("hdr/{}", self.height), &hdr)?;
+    }
 
     // Writer prepends NAT rewards before block events, so same-block TAP ops can
     // spend rewards credited to the miner in that block.
@@ -1105,6 +1147,14 @@ impl Updater<'_> {
       &inscription_updater.unbound_inscriptions,
     )?;
 
+    if self.index.tap_stream().enabled() {
+      let block_hash = block.header.block_hash();
+      let events = inscription_updater.tap_stream_block_events(block_hash)?;
+      self
+        .tap_stream_pending
+        .push_block(self.height, block_hash, events);
+    }
+
     // TAP hook: finalize block and flush TAP batch (no-op if nothing written)
     let tap_export_rolling_state = inscription_updater.tap_finalize_block()?;
     if let Some(table) = tap_export_metadata.as_mut() {
@@ -1124,6 +1174,47 @@ impl Updater<'_> {
       }
     }
 
+    let iu = &inscription_updater;
+    #[rustfmt::skip]
+    let ops = [
+      ("created", "bitmap", iu.prof_bm_cr_us, iu.prof_bm_cr_ct),
+      ("created", "dmt-element", iu.prof_dmt_el_cr_us, iu.prof_dmt_el_cr_ct),
+      ("created", "token-deploy", iu.prof_dpl_cr_us, iu.prof_dpl_cr_ct),
+      ("created", "dmt-mint", iu.prof_dmtmint_cr_us, iu.prof_dmtmint_cr_ct),
+      ("created", "token-mint", iu.prof_mint_cr_us, iu.prof_mint_cr_ct),
+      ("created", "token-transfer", iu.prof_ttr_cr_us, iu.prof_ttr_cr_ct),
+      ("created", "token-send", iu.prof_tsend_cr_us, iu.prof_tsend_cr_ct),
+      ("created", "token-trade", iu.prof_ttrade_cr_us, iu.prof_ttrade_cr_ct),
+      ("created", "token-auth", iu.prof_tauth_cr_us, iu.prof_tauth_cr_ct),
+      ("created", "dmt-deploy", iu.prof_dmtdep_cr_us, iu.prof_dmtdep_cr_ct),
+      ("created", "privilege-auth", iu.prof_pra_cr_us, iu.prof_pra_cr_ct),
+      ("created", "privilege-verify", iu.prof_prv_cr_us, iu.prof_prv_cr_ct),
+      ("created", "block-transferables", iu.prof_blk_cr_us, iu.prof_blk_cr_ct),
+      ("created", "unblock-transferables", iu.prof_unblk_cr_us, iu.prof_unblk_cr_ct),
+      ("transferred", "bitmap", iu.prof_bm_tr_us, iu.prof_bm_tr_ct),
+      ("transferred", "dmt-mint", iu.prof_dmt_tr_us, iu.prof_dmt_tr_ct),
+      ("transferred", "privilege-verify", iu.prof_prv_tr_us, iu.prof_prv_tr_ct),
+      ("transferred", "token-transfer", iu.prof_ttr_ex_us, iu.prof_ttr_ex_ct),
+      ("transferred", "token-send", iu.prof_tsend_ex_us, iu.prof_tsend_ex_ct),
+      ("transferred", "token-trade", iu.prof_ttrade_ex_us, iu.prof_ttrade_ex_ct),
+      ("transferred", "token-auth", iu.prof_tauth_ex_us, iu.prof_tauth_ex_ct),
+      ("transferred", "privilege-auth", iu.prof_pra_ex_us, iu.prof_pra_ex_ct),
+      ("transferred", "block-transferables", iu.prof_blk_ex_us, iu.prof_blk_ex_ct),
+      ("transferred", "unblock-transferables", iu.prof_unblk_ex_us, iu.prof_unblk_ex_ct),
+    ]
+    .map(|(phase, op, micros, count)| TapOpTiming {
+      phase,
+      op,
+      count,
+      micros,
+    });
+    self.index.tap_metrics().record_block(
+      self.height,
+      prof_start_all.elapsed(),
+      &ops,
+      self.tap_route_index.borrow_mut().take_counters(),
+    );
+
     if profile_enabled {
       let total_ms = prof_start_all.elapsed().as_millis();
       log::info!(
@@ -1137,53 +1228,53 @@ impl Updater<'_> {
       log::info!(
         "tap_profile_handlers: block {} bm_tr={}ms({}) dmt_tr={}ms({}) prv_tr={}ms({}) ttr_exe={}ms({}) tsend_exe={}ms({}) ttrade_exe={}ms({}) tauth_exe={}ms({}) pra_exe={}ms({}) blk_exe={}ms({}) unblk_exe={}ms({})",
         self.height,
-        inscription_updater.prof_bm_tr_ms, inscription_updater.prof_bm_tr_ct,
-        inscription_updater.prof_dmt_tr_ms, inscription_updater.prof_dmt_tr_ct,
-        inscription_updater.prof_prv_tr_ms, inscription_updater.prof_prv_tr_ct,
-        inscription_updater.prof_ttr_ex_ms, inscription_updater.prof_ttr_ex_ct,
-        inscription_updater.prof_tsend_ex_ms, inscription_updater.prof_tsend_ex_ct,
-        inscription_updater.prof_ttrade_ex_ms, inscription_updater.prof_ttrade_ex_ct,
-        inscription_updater.prof_tauth_ex_ms, inscription_updater.prof_tauth_ex_ct,
-        inscription_updater.prof_pra_ex_ms, inscription_updater.prof_pra_ex_ct,
-        inscription_updater.prof_blk_ex_ms, inscription_updater.prof_blk_ex_ct,
-        inscription_updater.prof_unblk_ex_ms, inscription_updater.prof_unblk_ex_ct,
+        inscription_updater.prof_bm_tr_us / 1000, inscription_updater.prof_bm_tr_ct,
+        inscription_updater.prof_dmt_tr_us / 1000, inscription_updater.prof_dmt_tr_ct,
+        inscription_updater.prof_prv_tr_us / 1000, inscription_updater.prof_prv_tr_ct,
+        inscription_updater.prof_ttr_ex_us / 1000, inscription_updater.prof_ttr_ex_ct,
+        inscription_updater.prof_tsend_ex_us / 1000, inscription_updater.prof_tsend_ex_ct,
+        inscription_updater.prof_ttrade_ex_us / 1000, inscription_updater.prof_ttrade_ex_ct,
+        inscription_updater.prof_tauth_ex_us / 1000, inscription_updater.prof_tauth_ex_ct,
+        inscription_updater.prof_pra_ex_us / 1000, inscription_updater.prof_pra_ex_ct,
+        inscription_updater.prof_blk_ex_us / 1000, inscription_updater.prof_blk_ex_ct,
+        inscription_updater.prof_unblk_ex_us / 1000, inscription_updater.prof_unblk_ex_ct,
       );
-      let created_sum = inscription_updater.prof_bm_cr_ms
-        + inscription_updater.prof_dmt_el_cr_ms
-        + inscription_updater.prof_dpl_cr_ms
-        + inscription_updater.prof_dmtmint_cr_ms
-        + inscription_updater.prof_mint_cr_ms
-        + inscription_updater.prof_ttr_cr_ms
-        + inscription_updater.prof_tsend_cr_ms
-        + inscription_updater.prof_ttrade_cr_ms
-        + inscription_updater.prof_tauth_cr_ms
-        + inscription_updater.prof_dmtdep_cr_ms
-        + inscription_updater.prof_pra_cr_ms
-        + inscription_updater.prof_prv_cr_ms
-        + inscription_updater.prof_blk_cr_ms
-        + inscription_updater.prof_unblk_cr_ms;
+      let created_sum = inscription_updater.prof_bm_cr_us
+        + inscription_updater.prof_dmt_el_cr_us
+        + inscription_updater.prof_dpl_cr_us
+        + inscription_updater.prof_dmtmint_cr_us
+        + inscription_updater.prof_mint_cr_us
+        + inscription_updater.prof_ttr_cr_us
+        + inscription_updater.prof_tsend_cr_us
+        + inscription_updater.prof_ttrade_cr_us
+        + inscription_updater.prof_tauth_cr_us
+        + inscription_updater.prof_dmtdep_cr_us
+        + inscription_updater.prof_pra_cr_us
+        + inscription_updater.prof_prv_cr_us
+        + inscription_updater.prof_blk_cr_us
+        + inscription_updater.prof_unblk_cr_us;
       let created_base = inscription_updater
-        .prof_created_total_ms
+        .prof_created_total_us
         .saturating_sub(created_sum);
       log::info!(
         "tap_profile_created: block {} total={}ms base={}ms bm_cr={}ms({}) dmt_el={}ms({}) dpl={}ms({}) dmt_mint={}ms({}) mint={}ms({}) ttr_cr={}ms({}) tsend_cr={}ms({}) ttrade_cr={}ms({}) tauth_cr={}ms({}) dmt_dep={}ms({}) pra_cr={}ms({}) prv_cr={}ms({}) blk_cr={}ms({}) unblk_cr={}ms({})",
         self.height,
-        inscription_updater.prof_created_total_ms,
-        created_base,
-        inscription_updater.prof_bm_cr_ms, inscription_updater.prof_bm_cr_ct,
-        inscription_updater.prof_dmt_el_cr_ms, inscription_updater.prof_dmt_el_cr_ct,
-        inscription_updater.prof_dpl_cr_ms, inscription_updater.prof_dpl_cr_ct,
-        inscription_updater.prof_dmtmint_cr_ms, inscription_updater.prof_dmtmint_cr_ct,
-        inscription_updater.prof_mint_cr_ms, inscription_updater.prof_mint_cr_ct,
-        inscription_updater.prof_ttr_cr_ms, inscription_updater.prof_ttr_cr_ct,
-        inscription_updater.prof_tsend_cr_ms, inscription_updater.prof_tsend_cr_ct,
-        inscription_updater.prof_ttrade_cr_ms, inscription_updater.prof_ttrade_cr_ct,
-        inscription_updater.prof_tauth_cr_ms, inscription_updater.prof_tauth_cr_ct,
-        inscription_updater.prof_dmtdep_cr_ms, inscription_updater.prof_dmtdep_cr_ct,
-        inscription_updater.prof_pra_cr_ms, inscription_updater.prof_pra_cr_ct,
-        inscription_updater.prof_prv_cr_ms, inscription_updater.prof_prv_cr_ct,
-        inscription_updater.prof_blk_cr_ms, inscription_updater.prof_blk_cr_ct,
-        inscription_updater.prof_unblk_cr_ms, inscription_updater.prof_unblk_cr_ct,
+        inscription_updater.prof_created_total_us / 1000,
+        created_base / 1000,
+        inscription_updater.prof_bm_cr_us / 1000, inscription_updater.prof_bm_cr_ct,
+        inscription_updater.prof_dmt_el_cr_us / 1000, inscription_updater.prof_dmt_el_cr_ct,
+        inscription_updater.prof_dpl_cr_us / 1000, inscription_updater.prof_dpl_cr_ct,
+        inscription_updater.prof_dmtmint_cr_us / 1000, inscription_updater.prof_dmtmint_cr_ct,
+        inscription_updater.prof_mint_cr_us / 1000, inscription_updater.prof_mint_cr_ct,
+        inscription_updater.prof_ttr_cr_us / 1000, inscription_updater.prof_ttr_cr_ct,
+        inscription_updater.prof_tsend_cr_us / 1000, inscription_updater.prof_tsend_cr_ct,
+        inscription_updater.prof_ttrade_cr_us / 1000, inscription_updater.prof_ttrade_cr_ct,
+        inscription_updater.prof_tauth_cr_us / 1000, inscription_updater.prof_tauth_cr_ct,
+        inscription_updater.prof_dmtdep_cr_us / 1000, inscription_updater.prof_dmtdep_cr_ct,
+        inscription_updater.prof_pra_cr_us / 1000, inscription_updater.prof_pra_cr_ct,
+        inscription_updater.prof_prv_cr_us / 1000, inscription_updater.prof_prv_cr_ct,
+        inscription_updater.prof_blk_cr_us / 1000, inscription_updater.prof_blk_cr_ct,
+        inscription_updater.prof_unblk_cr_us / 1000, inscription_updater.prof_unblk_cr_ct,
       );
       log::info!(
         "tap_profile_core: block {} env={}ms old={}ms({}) new={}ms({}) parents={}ms txdb={}ms({}) addr={}ms({}) update={}ms({}) event={}ms({})",
@@ -1366,6 +1457,13 @@ impl Updater<'_> {
     // transaction before last.
     self.index.begin_write()?.commit()?;
 
+    if let Some(tip) = self.height.checked_sub(1) {
+      self
+        .index
+        .tap_stream()
+        .publish(tip, mem::take(&mut self.tap_stream_pending));
+    }
+
     Reorg::update_savepoints(self.index, self.height)?;
 
     Ok(())
@@ -13,7 +13,9 @@ pub(crate) use tap::{
   tap_js_json_stringify_value,
   tap_js_preprocess_json_for_serde,
   tap_js_to_lowercase,
+  tap_token_send_item_check,
   // records
+  AuthorityConfigRecord,
   BitmapRecord,
   DeployRecord,
   DmtElementRecord,
@@ -24,12 +26,16 @@ pub(crate) use tap::{
   TapAccumulatorEntry,
   TapBatch,
   TapDeltaBatch,
+  TapDeltaSegments,
   TapFeature,
+  TapRejection,
   TapRoute,
   TapRouteIndex,
   TapRouteRebuildStats,
   TokenAuthCreateRecord,
   TokenAuthRedeemRecord,
+  TradeBookEntryRecord,
+  TradeBookOfferRecord,
   TradeBuyBuyerRecord,
   TradeBuySellerRecord,
   TradeOfferRecord,
@@ -42,6 +48,8 @@ pub(crate) use tap::{
   TransferSendSuperflatRecord,
   BURN_ADDRESS,
   MAX_DEC_U64_STR,
+  TAP_DMT_REWARD_ADDRESS_PREFIX,
+  TAP_TOKEN_TRADE_EMERGENCY_ACTIVATION_HEIGHT,
 };
 // address/segmentation helpers live in tap::mod; no direct imports needed here
 
@@ -110,10 +118,13 @@ pub(super) struct InscriptionUpdater<'a, 'tx> {
   pub(super) timestamp: u32,
   pub(super) unbound_inscriptions: u64,
   pub(super) tap_db: TapBatch<'a, 'tx>,
+  // Non-consensus TAP_DERIVED views, see `tap_derived_put`
+  pub(super) tap_derived_db: TapBatch<'a, 'tx>,
   pub(super) tap_delta_db: Option<TapDeltaBatch<'a, 'tx>>,
   pub(super) tap_atomic_writes: Option<Vec<String>>,
   pub(super) tap_atomic_overlay: Option<HashMap<String, Option<(Vec<u8>, serde_json::Value)>>>,
   pub(super) tap_atomic_list_len_cache: Option<HashMap<String, usize>>,
+  pub(super) tap_atomic_derived: Option<HashMap<String, Option<Vec<u8>>>>,
   // Exact transfer route index (shared with block updater via Rc)
   pub(super) tap_route_index: Option<Rc<RefCell<TapRouteIndex>>>,
   pub(super) tap_route_index_verify: bool,
@@ -123,56 +134,56 @@ pub(super) struct InscriptionUpdater<'a, 'tx> {
   pub(super) block_availability_cache: HashMap<String, bool>,
   // TAP profiling
   pub(super) profile: bool,
-  pub(super) prof_bm_tr_ms: u128,
+  pub(super) prof_bm_tr_us: u128,
   pub(super) prof_bm_tr_ct: u64,
-  pub(super) prof_dmt_tr_ms: u128,
+  pub(super) prof_dmt_tr_us: u128,
   pub(super) prof_dmt_tr_ct: u64,
-  pub(super) prof_prv_tr_ms: u128,
+  pub(super) prof_prv_tr_us: u128,
   pub(super) prof_prv_tr_ct: u64,
-  pub(super) prof_ttr_ex_ms: u128,
+  pub(super) prof_ttr_ex_us: u128,
   pub(super) prof_ttr_ex_ct: u64,
-  pub(super) prof_tsend_ex_ms: u128,
+  pub(super) prof_tsend_ex_us: u128,
   pub(super) prof_tsend_ex_ct: u64,
-  pub(super) prof_ttrade_ex_ms: u128,
+  pub(super) prof_ttrade_ex_us: u128,
   pub(super) prof_ttrade_ex_ct: u64,
-  pub(super) prof_tauth_ex_ms: u128,
+  pub(super) prof_tauth_ex_us: u128,
   pub(super) prof_tauth_ex_ct: u64,
-  pub(super) prof_pra_ex_ms: u128,
+  pub(super) prof_pra_ex_us: u128,
   pub(super) prof_pra_ex_ct: u64,
-  pub(super) prof_blk_ex_ms: u128,
+  pub(super) prof_blk_ex_us: u128,
   pub(super) prof_blk_ex_ct: u64,
-  pub(super) prof_unblk_ex_ms: u128,
+  pub(super) prof_unblk_ex_us: u128,
   pub(super) prof_unblk_ex_ct: u64,
   // Created-path profiling
-  pub(super) prof_created_total_ms: u128,
+  pub(super) prof_created_total_us: u128,
   pub(super) prof_created_ct: u64,
-  pub(super) prof_bm_cr_ms: u128,
+  pub(super) prof_bm_cr_us: u128,
   pub(super) prof_bm_cr_ct: u64,
-  pub(super) prof_dmt_el_cr_ms: u128,
+  pub(super) prof_dmt_el_cr_us: u128,
   pub(super) prof_dmt_el_cr_ct: u64,
-  pub(super) prof_dpl_cr_ms: u128,
+  pub(super) prof_dpl_cr_us: u128,
   pub(super) prof_dpl_cr_ct: u64,
-  pub(super) prof_dmtmint_cr_ms: u128,
+  pub(super) prof_dmtmint_cr_us: u128,
   pub(super) prof_dmtmint_cr_ct: u64,
-  pub(super) prof_mint_cr_ms: u128,
+  pub(super) prof_mint_cr_us: u128,
   pub(super) prof_mint_cr_ct: u64,
-  pub(super) prof_ttr_cr_ms: u128,
+  pub(super) prof_ttr_cr_us: u128,
   pub(super) prof_ttr_cr_ct: u64,
-  pub(super) prof_tsend_cr_ms: u128,
+  pub(super) prof_tsend_cr_us: u128,
   pub(super) prof_tsend_cr_ct: u64,
-  pub(super) prof_ttrade_cr_ms: u128,
+  pub(super) prof_ttrade_cr_us: u128,
   pub(super) prof_ttrade_cr_ct: u64,
-  pub(super) prof_tauth_cr_ms: u128,
+  pub(super) prof_tauth_cr_us: u128,
   pub(super) prof_tauth_cr_ct: u64,
-  pub(super) prof_dmtdep_cr_ms: u128,
+  pub(super) prof_dmtdep_cr_us: u128,
   pub(super) prof_dmtdep_cr_ct: u64,
-  pub(super) prof_pra_cr_ms: u128,
+  pub(super) prof_pra_cr_us: u128,
   pub(super) prof_pra_cr_ct: u64,
-  pub(super) prof_prv_cr_ms: u128,
+  pub(super) prof_prv_cr_us: u128,
   pub(super) prof_prv_cr_ct: u64,
-  pub(super) prof_blk_cr_ms: u128,
+  pub(super) prof_blk_cr_us: u128,
   pub(super) prof_blk_cr_ct: u64,
-  pub(super) prof_unblk_cr_ms: u128,
+  pub(super) prof_unblk_cr_us: u128,
   pub(super) prof_unblk_cr_ct: u64,
   // Core inscription-path profiling (deeper than TAP)
   pub(super) prof_core_env_ms: u128,
@@ -210,9 +221,55 @@ pub(super) struct InscriptionUpdater<'a, 'tx> {
   pub(super) delegate_payload_cache: HashMap<InscriptionId, Inscription>,
   // Active Bitcoin network for address validation in TAP
   pub(super) btc_network: bitcoin::Network,
+  // Last block covered by an imported TAP snapshot; TAP is skipped through it.
+  pub(super) tap_snapshot_height: Option<u32>,
+  // Previews run the indexer against an aborted transaction and must not
+  // announce inscription events.
+  pub(super) emit_events: bool,
+  // Wall-clock AMM candle intervals in seconds, alongside per-block candles.
+  pub(super) tap_amm_candle_intervals: Vec<u32>,
+  // Committed inscription tables behind a preview's scratch tables
+  pub(super) preview_base: Option<InscriptionPreviewBase>,
+}
+
+/// Read-only inscription tables a preview updater falls back to. Its own
+/// tables live in a scratch database and only hold the previewed writes.
+pub(super) struct InscriptionPreviewBase {
+  pub(super) id_to_sequence_number: ReadOnlyTable<InscriptionIdValue, u32>,
+  pub(super) sequence_number_to_entry: ReadOnlyTable<u32, InscriptionEntryValue>,
 }
 
 impl InscriptionUpdater<'_, '_> {
+  fn sequence_number_for_id(&self, id: &InscriptionId) -> Result<Option<u32>> {
+    if let Some(sequence_number) = self.id_to_sequence_number.get(&id.store())? {
+      return Ok(Some(sequence_number.value()));
+    }
+    match &self.preview_base {
+      Some(base) => Ok(
+        base
+          .id_to_sequence_number
+          .get(&id.store())?
+          .map(|sequence_number| sequence_number.value()),
+      ),
+      None => Ok(None),
+    }
+  }
+
+  fn inscription_entry(&self, sequence_number: u32) -> Result<Option<InscriptionEntry>> {
+    if let Some(entry) = self.sequence_number_to_entry.get(sequence_number)? {
+      return Ok(Some(InscriptionEntry::load(entry.value())));
+    }
+    match &self.preview_base {
+      Some(base) => Ok(
+        base
+          .sequence_number_to_entry
+          .get(sequence_number)?
+          .map(|entry| InscriptionEntry::load(entry.value())),
+      ),
+      None => Ok(None),
+    }
+  }
+
   pub(super) fn is_dmt_nat_rewards_enabled(&self) -> bool {
     self.tap_feature_enabled(TapFeature::DmtNatRewards)
   }
@@ -232,13 +289,7 @@ impl InscriptionUpdater<'_, '_> {
     if let Some(hit) = self.block_availability_cache.get(&key_s) {
       return *hit;
     }
-    if self
-      .id_to_sequence_number
-      .get(&id.store())
-      .ok()
-      .flatten()
-      .is_some()
-    {
+    if self.sequence_number_for_id(&id).ok().flatten().is_some() {
       self.block_availability_cache.insert(key_s, true);
       return true;
     }
@@ -546,14 +597,7 @@ impl InscriptionUpdater<'_, '_> {
           offset: old_satpoint_offset,
         };
 
-        let inscription_id = InscriptionEntry::load(
-          self
-            .sequence_number_to_entry
-            .get(sequence_number)?
-            .unwrap()
-            .value(),
-        )
-        .id;
+        let inscription_id = self.inscription_entry(sequence_number)?.unwrap().id;
 
         let offset = total_input_value + old_satpoint_offset;
         floating_inscriptions.push(Flotsam {
@@ -612,16 +656,11 @@ impl InscriptionUpdater<'_, '_> {
           if *count > 1 {
             Some(Curse::Reinscription)
           } else {
-            let initial_inscription_sequence_number =
-              self.id_to_sequence_number.get(id.store())?.unwrap().value();
-
-            let entry = InscriptionEntry::load(
-              self
-                .sequence_number_to_entry
-                .get(initial_inscription_sequence_number)?
-                .unwrap()
-                .value(),
-            );
+            let initial_inscription_sequence_number = self.sequence_number_for_id(id)?.unwrap();
+
+            let entry = self
+              .inscription_entry(initial_inscription_sequence_number)?
+              .unwrap();
 
             let initial_inscription_was_cursed_or_vindicated =
               entry.inscription_number < 0 || Charm::Vindicated.is_set(entry.charms);
@@ -883,13 +922,7 @@ impl InscriptionUpdater<'_, '_> {
           None
         };
         if op_return {
-          let entry = InscriptionEntry::load(
-            self
-              .sequence_number_to_entry
-              .get(&sequence_number)?
-              .unwrap()
-              .value(),
-          );
+          let entry = self.inscription_entry(sequence_number)?.unwrap();
 
           let mut charms = entry.charms;
           Charm::Burned.set(&mut charms);
@@ -900,7 +933,7 @@ impl InscriptionUpdater<'_, '_> {
           )?;
         }
 
-        if let Some(ref sender) = index.event_sender {
+        if let Some(sender) = index.event_sender.as_ref().filter(|_| self.emit_events) {
           let __core_start_evt = std::time::Instant::now();
           sender.blocking_send(Event::InscriptionTransferred {
             block_height: self.height,
@@ -1039,11 +1072,7 @@ impl InscriptionUpdater<'_, '_> {
         let parent_sequence_numbers = parents
           .iter()
           .map(|parent| {
-            let parent_sequence_number = self
-              .id_to_sequence_number
-              .get(&parent.store())?
-              .unwrap()
-              .value();
+            let parent_sequence_number = self.sequence_number_for_id(parent)?.unwrap();
 
             self
               .sequence_number_to_children
@@ -1162,7 +1191,7 @@ impl InscriptionUpdater<'_, '_> {
           }
         }
 
-        if let Some(ref sender) = index.event_sender {
+        if let Some(sender) = index.event_sender.as_ref().filter(|_| self.emit_events) {
           let __core_start_evt = std::time::Instant::now();
           sender.blocking_send(Event::InscriptionCreated {
             block_height: self.height,
@@ -1226,10 +1255,51 @@ impl InscriptionUpdater<'_, '_> {
     Ok(())
   }
 
+  /// Stream events for the `blcki/{family}/{block}/{i}` rows written in this
+  /// block, ordered by family and list position. Must run before the batch is
+  /// flushed, while the block's writes are still in the overlay.
+  pub(super) fn tap_stream_block_events(
+    &mut self,
+    block_hash: BlockHash,
+  ) -> Result<Vec<crate::index::TapStreamEvent>> {
+    let mut rows = Vec::new();
+    for (key, value) in &self.tap_db.overlay {
+      let Ok(key) = std::str::from_utf8(key) else {
+        continue;
+      };
+      let Some((list, i)) = key.rsplit_once('/') else {
+        continue;
+      };
+      if !list.starts_with("blcki/") {
+        continue;
+      }
+      let Ok(i) = i.parse::<u64>() else {
+        continue;
+      };
+      rows.push((list.to_string(), i, key.to_string(), value.clone()));
+    }
+    rows.sort_unstable_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+
+    let mut events = Vec::new();
+    for (_, _, key, value) in rows {
+      let sequence = events.len() as u64;
+      if let Some(event) =
+        Index::tap_stream_record_event(&key, &value, block_hash, sequence, |pointer| {
+          self.tap_db.get(pointer.as_bytes())
+        })?
+      {
+        events.push(event);
+      }
+    }
+    Ok(events)
+  }
+
   pub(super) fn tap_finalize_block(
     &mut self,
   ) -> Result<Option<crate::index::TapExportRollingState>> {
+    self.expire_trade_book_offers();
     self.tap_db.flush()?;
+    self.tap_derived_db.flush()?;
     let rolling_state = self
       .tap_delta_db
       .as_mut()
@@ -1326,7 +1396,7 @@ impl InscriptionUpdater<'_, '_> {
         output_value_sat,
       );
       if self.profile {
-        self.prof_bm_cr_ms += __st.elapsed().as_millis();
+        self.prof_bm_cr_us += __st.elapsed().as_micros();
         self.prof_bm_cr_ct += 1;
       }
 
@@ -1341,7 +1411,7 @@ impl InscriptionUpdater<'_, '_> {
         output_value_sat,
       );
       if self.profile {
-        self.prof_dpl_cr_ms += __st.elapsed().as_millis();
+        self.prof_dpl_cr_us += __st.elapsed().as_micros();
         self.prof_dpl_cr_ct += 1;
       }
       return;
@@ -1359,7 +1429,7 @@ impl InscriptionUpdater<'_, '_> {
       output_value_sat,
     );
     if self.profile {
-      self.prof_bm_cr_ms += __st.elapsed().as_millis();
+      self.prof_bm_cr_us += __st.elapsed().as_micros();
       self.prof_bm_cr_ct += 1;
     }
 
@@ -1374,7 +1444,7 @@ impl InscriptionUpdater<'_, '_> {
       output_value_sat,
     );
     if self.profile {
-      self.prof_dmt_el_cr_ms += __st.elapsed().as_millis();
+      self.prof_dmt_el_cr_us += __st.elapsed().as_micros();
       self.prof_dmt_el_cr_ct += 1;
     }
 
@@ -1388,7 +1458,7 @@ impl InscriptionUpdater<'_, '_> {
       output_value_sat,
     );
     if self.profile {
-      self.prof_dpl_cr_ms += __st.elapsed().as_millis();
+      self.prof_dpl_cr_us += __st.elapsed().as_micros();
       self.prof_dpl_cr_ct += 1;
     }
 
@@ -1405,7 +1475,7 @@ impl InscriptionUpdater<'_, '_> {
       index,
     );
     if self.profile {
-      self.prof_dmtmint_cr_ms += __st.elapsed().as_millis();
+      self.prof_dmtmint_cr_us += __st.elapsed().as_micros();
       self.prof_dmtmint_cr_ct += 1;
     }
 
@@ -1420,7 +1490,7 @@ impl InscriptionUpdater<'_, '_> {
       output_value_sat,
     );
     if self.profile {
-      self.prof_mint_cr_ms += __st.elapsed().as_millis();
+      self.prof_mint_cr_us += __st.elapsed().as_micros();
       self.prof_mint_cr_ct += 1;
     }
 
@@ -1435,7 +1505,7 @@ impl InscriptionUpdater<'_, '_> {
       output_value_sat,
     );
     if self.profile {
-      self.prof_ttr_cr_ms += __st.elapsed().as_millis();
+      self.prof_ttr_cr_us += __st.elapsed().as_micros();
       self.prof_ttr_cr_ct += 1;
     }
 
@@ -1450,7 +1520,7 @@ impl InscriptionUpdater<'_, '_> {
       output_value_sat,
     );
     if self.profile {
-      self.prof_tsend_cr_ms += __st.elapsed().as_millis();
+      self.prof_tsend_cr_us += __st.elapsed().as_micros();
       self.prof_tsend_cr_ct += 1;
     }
 
@@ -1465,7 +1535,7 @@ impl InscriptionUpdater<'_, '_> {
       output_value_sat,
     );
     if self.profile {
-      self.prof_ttrade_cr_ms += __st.elapsed().as_millis();
+      self.prof_ttrade_cr_us += __st.elapsed().as_micros();
       self.prof_ttrade_cr_ct += 1;
     }
 
@@ -1480,7 +1550,7 @@ impl InscriptionUpdater<'_, '_> {
       output_value_sat,
     );
     if self.profile {
-      self.prof_tauth_cr_ms += __st.elapsed().as_millis();
+      self.prof_tauth_cr_us += __st.elapsed().as_micros();
       self.prof_tauth_cr_ct += 1;
     }
 
@@ -1496,7 +1566,7 @@ impl InscriptionUpdater<'_, '_> {
       index,
     );
     if self.profile {
-      self.prof_dmtdep_cr_ms += __st.elapsed().as_millis();
+      self.prof_dmtdep_cr_us += __st.elapsed().as_micros();
       self.prof_dmtdep_cr_ct += 1;
     }
 
@@ -1511,7 +1581,7 @@ impl InscriptionUpdater<'_, '_> {
       output_value_sat,
     );
     if self.profile {
-      self.prof_pra_cr_ms += __st.elapsed().as_millis();
+      self.prof_pra_cr_us += __st.elapsed().as_micros();
       self.prof_pra_cr_ct += 1;
     }
 
@@ -1526,7 +1596,7 @@ impl InscriptionUpdater<'_, '_> {
       output_value_sat,
     );
     if self.profile {
-      self.prof_blk_cr_ms += __st.elapsed().as_millis();
+      self.prof_blk_cr_us += __st.elapsed().as_micros();
       self.prof_blk_cr_ct += 1;
     }
     let __st = std::time::Instant::now();
@@ -1539,7 +1609,7 @@ impl InscriptionUpdater<'_, '_> {
       output_value_sat,
     );
     if self.profile {
-      self.prof_unblk_cr_ms += __st.elapsed().as_millis();
+      self.prof_unblk_cr_us += __st.elapsed().as_micros();
       self.prof_unblk_cr_ct += 1;
     }
 
@@ -1554,11 +1624,11 @@ impl InscriptionUpdater<'_, '_> {
       output_value_sat,
     );
     if self.profile {
-      self.prof_prv_cr_ms += __st.elapsed().as_millis();
+      self.prof_prv_cr_us += __st.elapsed().as_micros();
       self.prof_prv_cr_ct += 1;
     }
     if self.profile {
-      self.prof_created_total_ms += __cr_total.elapsed().as_millis();
+      self.prof_created_total_us += __cr_total.elapsed().as_micros();
       self.prof_created_ct += 1;
     }
   }
@@ -1595,11 +1665,10 @@ impl InscriptionUpdater<'_, '_> {
 
     if let Some(route_index) = &self.tap_route_index {
       let (route, ready) = {
-        let route_index = route_index.borrow();
-        (
-          route_index.route_for(inscription_id),
-          route_index.is_ready(),
-        )
+        let mut route_index = route_index.borrow_mut();
+        let route = route_index.route_for(inscription_id);
+        route_index.count_lookup(route.as_ref(), self.tap_route_index_verify);
+        (route, route_index.is_ready())
       };
       if self.tap_route_index_verify && ready {
         let slow_route = self.tap_classify_transfer_route_slow(inscription_id);
@@ -1746,7 +1815,7 @@ impl InscriptionUpdater<'_, '_> {
             None,
           );
           if self.profile {
-            self.prof_bm_tr_ms += __st.elapsed().as_millis();
+            self.prof_bm_tr_us += __st.elapsed().as_micros();
             self.prof_bm_tr_ct += 1;
           }
           return;
@@ -1761,7 +1830,7 @@ impl InscriptionUpdater<'_, '_> {
             output_value_sat,
           );
           if self.profile {
-            self.prof_dmt_tr_ms += __st.elapsed().as_millis();
+            self.prof_dmt_tr_us += __st.elapsed().as_micros();
             self.prof_dmt_tr_ct += 1;
           }
           return;
@@ -1776,7 +1845,7 @@ impl InscriptionUpdater<'_, '_> {
             output_value_sat,
           );
           if self.profile {
-            self.prof_prv_tr_ms += __st.elapsed().as_millis();
+            self.prof_prv_tr_us += __st.elapsed().as_micros();
             self.prof_prv_tr_ct += 1;
           }
           return;
@@ -1791,7 +1860,7 @@ impl InscriptionUpdater<'_, '_> {
             output_value_sat,
           );
           if self.profile {
-            self.prof_ttr_ex_ms += __st.elapsed().as_millis();
+            self.prof_ttr_ex_us += __st.elapsed().as_micros();
             self.prof_ttr_ex_ct += 1;
           }
           return;
@@ -1818,7 +1887,7 @@ impl InscriptionUpdater<'_, '_> {
           None,
         );
         if self.profile {
-          self.prof_bm_tr_ms += __st.elapsed().as_millis();
+          self.prof_bm_tr_us += __st.elapsed().as_micros();
           self.prof_bm_tr_ct += 1;
         }
         return;
@@ -1840,7 +1909,7 @@ impl InscriptionUpdater<'_, '_> {
           output_value_sat,
         );
         if self.profile {
-          self.prof_dmt_tr_ms += __st.elapsed().as_millis();
+          self.prof_dmt_tr_us += __st.elapsed().as_micros();
           self.prof_dmt_tr_ct += 1;
         }
         return;
@@ -1862,7 +1931,7 @@ impl InscriptionUpdater<'_, '_> {
           output_value_sat,
         );
         if self.profile {
-          self.prof_prv_tr_ms += __st.elapsed().as_millis();
+          self.prof_prv_tr_us += __st.elapsed().as_micros();
           self.prof_prv_tr_ct += 1;
         }
         return;
@@ -1885,7 +1954,7 @@ impl InscriptionUpdater<'_, '_> {
               output_value_sat,
             );
             if self.profile {
-              self.prof_ttr_ex_ms += __st.elapsed().as_millis();
+              self.prof_ttr_ex_us += __st.elapsed().as_micros();
               self.prof_ttr_ex_ct += 1;
             }
             return;
@@ -1926,7 +1995,7 @@ impl InscriptionUpdater<'_, '_> {
           block,
         );
         if self.profile {
-          self.prof_bm_tr_ms += __st.elapsed().as_millis();
+          self.prof_bm_tr_us += __st.elapsed().as_micros();
           self.prof_bm_tr_ct += 1;
         }
       }
@@ -1940,7 +2009,7 @@ impl InscriptionUpdater<'_, '_> {
           output_value_sat,
         );
         if self.profile {
-          self.prof_dmt_tr_ms += __st.elapsed().as_millis();
+          self.prof_dmt_tr_us += __st.elapsed().as_micros();
           self.prof_dmt_tr_ct += 1;
         }
       }
@@ -1954,7 +2023,7 @@ impl InscriptionUpdater<'_, '_> {
           output_value_sat,
         );
         if self.profile {
-          self.prof_prv_tr_ms += __st.elapsed().as_millis();
+          self.prof_prv_tr_us += __st.elapsed().as_micros();
           self.prof_prv_tr_ct += 1;
         }
       }
@@ -1968,7 +2037,7 @@ impl InscriptionUpdater<'_, '_> {
           output_value_sat,
         );
         if self.profile {
-          self.prof_ttr_ex_ms += __st.elapsed().as_millis();
+          self.prof_ttr_ex_us += __st.elapsed().as_micros();
           self.prof_ttr_ex_ct += 1;
         }
       }
@@ -2018,7 +2087,7 @@ impl InscriptionUpdater<'_, '_> {
           output_value_sat,
         );
         if self.profile {
-          self.prof_tsend_ex_ms += __st.elapsed().as_millis();
+          self.prof_tsend_ex_us += __st.elapsed().as_micros();
           self.prof_tsend_ex_ct += 1;
         }
       }
@@ -2032,7 +2101,7 @@ impl InscriptionUpdater<'_, '_> {
           output_value_sat,
         );
         if self.profile {
-          self.prof_ttrade_ex_ms += __st.elapsed().as_millis();
+          self.prof_ttrade_ex_us += __st.elapsed().as_micros();
           self.prof_ttrade_ex_ct += 1;
         }
       }
@@ -2046,7 +2115,7 @@ impl InscriptionUpdater<'_, '_> {
           output_value_sat,
         );
         if self.profile {
-          self.prof_tauth_ex_ms += __st.elapsed().as_millis();
+          self.prof_tauth_ex_us += __st.elapsed().as_micros();
           self.prof_tauth_ex_ct += 1;
         }
       }
@@ -2060,7 +2129,7 @@ impl InscriptionUpdater<'_, '_> {
           output_value_sat,
         );
         if self.profile {
-          self.prof_pra_ex_ms += __st.elapsed().as_millis();
+          self.prof_pra_ex_us += __st.elapsed().as_micros();
           self.prof_pra_ex_ct += 1;
         }
       }
@@ -2074,7 +2143,7 @@ impl InscriptionUpdater<'_, '_> {
           output_value_sat,
         );
         if self.profile {
-          self.prof_blk_ex_ms += __st.elapsed().as_millis();
+          self.prof_blk_ex_us += __st.elapsed().as_micros();
           self.prof_blk_ex_ct += 1;
         }
       }
@@ -2088,7 +2157,7 @@ impl InscriptionUpdater<'_, '_> {
           output_value_sat,
         );
         if self.profile {
-          self.prof_unblk_ex_ms += __st.elapsed().as_millis();
+          self.prof_unblk_ex_us += __st.elapsed().as_micros();
           self.prof_unblk_ex_ct += 1;
         }
       }
@@ -2124,8 +2193,7 @@ impl InscriptionUpdater<'_, '_> {
     )
   }
 
-  fn verify_sig_obj_against_msg_with_hash(
-    &self,
+  pub(crate) fn verify_sig_obj_against_msg_with_hash(
     sig_obj: &serde_json::Value,
     recovery_hash_hex: &str,
     msg_hash: &[u8; 32],
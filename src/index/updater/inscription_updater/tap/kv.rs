@@ -1,7 +1,6 @@
 use super::super::super::*;
-use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::io::{BufWriter, Cursor, Write};
+use std::io::Cursor;
 use std::path::{Path, PathBuf};
 
 // Batch-like overlay DB for TAP that buffers writes and provides
@@ -9,6 +8,8 @@ use std::path::{Path, PathBuf};
 pub(crate) struct TapBatch<'a, 'tx> {
   pub table: &'a mut Table<'tx, &'static [u8], &'static [u8]>,
   pub overlay: HashMap<Vec<u8>, Vec<u8>>,
+  // Committed TAP_KV that previews read through to; `table` is then scratch
+  pub base: Option<ReadOnlyTable<&'static [u8], &'static [u8]>>,
 }
 
 impl<'a, 'tx> TapBatch<'a, 'tx> {
@@ -16,14 +17,26 @@ impl<'a, 'tx> TapBatch<'a, 'tx> {
     Self {
       table,
       overlay: HashMap::new(),
+      base: None,
     }
   }
 
+  pub fn with_base(mut self, base: ReadOnlyTable<&'static [u8], &'static [u8]>) -> Self {
+    self.base = Some(base);
+    self
+  }
+
   pub fn get(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
     if let Some(val) = self.overlay.get(key) {
       return Ok(Some(val.clone()));
     }
-    Ok(self.table.get(&key)?.map(|v| v.value().to_vec()))
+    if let Some(val) = self.table.get(&key)? {
+      return Ok(Some(val.value().to_vec()));
+    }
+    match &self.base {
+      Some(base) => Ok(base.get(&key)?.map(|v| v.value().to_vec())),
+      None => Ok(None),
+    }
   }
 
   pub fn put(&mut self, key: &[u8], value: &[u8]) {
@@ -46,8 +59,9 @@ impl<'a, 'tx> TapBatch<'a, 'tx> {
 
 pub(crate) struct TapDeltaBatch<'a, 'tx> {
   delta_dir: PathBuf,
-  writer: Option<BufWriter<std::fs::File>>,
+  rows: Vec<u8>,
   block_state_table: Option<&'a mut Table<'tx, &'static [u8], &'static [u8]>>,
+  key_history_table: Option<&'a mut Table<'tx, &'static [u8], &'static [u8]>>,
   height: u32,
   sequence: u64,
   rolling_state: Option<crate::index::TapExportRollingState>,
@@ -57,8 +71,9 @@ impl<'a, 'tx> TapDeltaBatch<'a, 'tx> {
   pub fn new(delta_dir: PathBuf, height: u32) -> Result<Self> {
     Ok(Self {
       delta_dir,
-      writer: None,
+      rows: Vec::new(),
       block_state_table: None,
+      key_history_table: None,
       height,
       sequence: 0,
       rolling_state: None,
@@ -73,18 +88,41 @@ impl<'a, 'tx> TapDeltaBatch<'a, 'tx> {
   ) -> Result<Self> {
     Ok(Self {
       delta_dir,
-      writer: None,
+      rows: Vec::new(),
       block_state_table: Some(block_state_table),
+      key_history_table: None,
       height,
       sequence: 0,
       rolling_state: Some(rolling_state),
     })
   }
 
+  /// Also record every value this block writes in the key history index
+  /// used by point-in-time reads.
+  pub fn with_key_history(
+    mut self,
+    key_history_table: &'a mut Table<'tx, &'static [u8], &'static [u8]>,
+  ) -> Self {
+    self.key_history_table = Some(key_history_table);
+    self
+  }
+
+  fn record_key_history(&mut self, key: &str, encoded_value: Option<&[u8]>) -> Result {
+    if let Some(table) = self.key_history_table.as_mut() {
+      table.insert(
+        crate::index::Index::tap_key_history_key(key, self.height).as_slice(),
+        crate::index::Index::tap_key_history_encode(encoded_value).as_slice(),
+      )?;
+    }
+    Ok(())
+  }
+
   pub fn needs_old_value(&self) -> bool {
     self.rolling_state.is_some()
   }
 
+  /// Path of a per-block delta file, as written before segment files.
+  #[cfg(test)]
   pub fn delta_file_path(delta_dir: &Path, height: u32) -> PathBuf {
     delta_dir.join(format!("{height:010}.jsonl"))
   }
@@ -99,6 +137,7 @@ impl<'a, 'tx> TapDeltaBatch<'a, 'tx> {
   }
 
   pub fn delete_files_from_height(delta_dir: &Path, height: u32) -> Result {
+    super::TapDeltaSegments::new(delta_dir.to_path_buf()).truncate_from(height)?;
     let Ok(entries) = std::fs::read_dir(delta_dir) else {
       return Ok(());
     };
@@ -130,14 +169,8 @@ impl<'a, 'tx> TapDeltaBatch<'a, 'tx> {
   fn record(&mut self, op: &str, key: &str, value: Option<String>) -> Result {
     let sequence = self.sequence;
     self.sequence += 1;
-    let hash_payload = serde_json::to_vec(&serde_json::json!([
-      self.height,
-      sequence,
-      op,
-      key,
-      value.as_deref()
-    ]))?;
-    let row_hash = hex::encode(Sha256::digest(&hash_payload));
+    let row_hash =
+      crate::index::Index::tap_export_row_hash(self.height, sequence, op, key, value.as_deref())?;
     let row = crate::index::TapExportDeltaRecord {
       height: self.height,
       sequence,
@@ -148,22 +181,8 @@ impl<'a, 'tx> TapDeltaBatch<'a, 'tx> {
       block_hash: None,
       parent_block_hash: None,
     };
-    if self.writer.is_none() {
-      std::fs::create_dir_all(&self.delta_dir)?;
-      self.writer = Some(BufWriter::new(
-        std::fs::OpenOptions::new()
-          .create(true)
-          .write(true)
-          .truncate(true)
-          .open(Self::delta_file_path(&self.delta_dir, self.height))?,
-      ));
-    }
-    let writer = self
-      .writer
-      .as_mut()
-      .ok_or_else(|| anyhow!("TAP export delta writer missing"))?;
-    serde_json::to_writer(&mut *writer, &row)?;
-    writer.write_all(b"\n")?;
+    serde_json::to_writer(&mut self.rows, &row)?;
+    self.rows.push(b'\n');
     Ok(())
   }
 
@@ -189,6 +208,7 @@ impl<'a, 'tx> TapDeltaBatch<'a, 'tx> {
         Some(&value),
       )?;
     }
+    self.record_key_history(key, Some(encoded_value))?;
     self.record("put", key, Some(value))
   }
 
@@ -207,13 +227,13 @@ impl<'a, 'tx> TapDeltaBatch<'a, 'tx> {
         None,
       )?;
     }
+    self.record_key_history(key, None)?;
     self.record("del", key, None)
   }
 
   pub fn finalize_block(&mut self) -> Result<Option<crate::index::TapExportRollingState>> {
-    if let Some(writer) = self.writer.as_mut() {
-      writer.flush()?;
-    }
+    super::TapDeltaSegments::new(self.delta_dir.clone()).append_block(self.height, &self.rows)?;
+    self.rows.clear();
     let Some(rolling_state) = &self.rolling_state else {
       return Ok(None);
     };
@@ -329,12 +349,14 @@ impl InscriptionUpdater<'_, '_> {
     self.tap_atomic_writes = Some(Vec::new());
     self.tap_atomic_overlay = Some(HashMap::new());
     self.tap_atomic_list_len_cache = Some(HashMap::new());
+    self.tap_atomic_derived = Some(HashMap::new());
   }
 
   pub(crate) fn tap_atomic_abort(&mut self) {
     self.tap_atomic_writes = None;
     self.tap_atomic_overlay = None;
     self.tap_atomic_list_len_cache = None;
+    self.tap_atomic_derived = None;
   }
 
   pub(crate) fn tap_atomic_commit(&mut self) -> Result {
@@ -353,6 +375,12 @@ impl InscriptionUpdater<'_, '_> {
     for (key, value) in list_cache {
       self.list_len_cache.insert(key, value);
     }
+    for (key, value) in self.tap_atomic_derived.take().unwrap_or_default() {
+      match value {
+        Some(buf) => self.tap_derived_db.put(key.as_bytes(), &buf),
+        None => self.tap_derived_db.del(key.as_bytes())?,
+      }
+    }
     Ok(())
   }
 
@@ -482,6 +510,68 @@ impl InscriptionUpdater<'_, '_> {
     self.tap_put_json_object_row(&format!("{}/{}", iterator_key, length - 1), data)?;
     Ok(length)
   }
+
+  // Derived views (candles, order books, lock indexes) are kept in
+  // TAP_DERIVED. They bypass the export deltas, digest and route index, so
+  // they never become consensus state. Inside an atomic batch their writes
+  // are held back and dropped with the batch on abort.
+  pub(crate) fn tap_derived_get<T: serde::de::DeserializeOwned>(
+    &mut self,
+    key: &str,
+  ) -> Result<Option<T>> {
+    if let Some(value) = self
+      .tap_atomic_derived
+      .as_ref()
+      .and_then(|overlay| overlay.get(key))
+    {
+      return value
+        .as_deref()
+        .map(Self::tap_decode_value::<T>)
+        .transpose();
+    }
+    self
+      .tap_derived_db
+      .get(key.as_bytes())?
+      .map(|bytes| Self::tap_decode_value(&bytes))
+      .transpose()
+  }
+
+  pub(crate) fn tap_derived_put<T: serde::Serialize>(&mut self, key: &str, value: &T) -> Result {
+    let (buf, _) = Self::tap_encode_value(value)?;
+    match &mut self.tap_atomic_derived {
+      Some(overlay) => {
+        overlay.insert(key.to_string(), Some(buf));
+      }
+      None => self.tap_derived_db.put(key.as_bytes(), &buf),
+    }
+    Ok(())
+  }
+
+  pub(crate) fn tap_derived_del(&mut self, key: &str) -> Result {
+    match &mut self.tap_atomic_derived {
+      Some(overlay) => {
+        overlay.insert(key.to_string(), None);
+        Ok(())
+      }
+      None => self.tap_derived_db.del(key.as_bytes()),
+    }
+  }
+
+  pub(crate) fn tap_derived_set_list_record<T: serde::Serialize>(
+    &mut self,
+    length_key: &str,
+    iterator_key: &str,
+    data: &T,
+  ) -> Result<usize> {
+    let length = self
+      .tap_derived_get::<String>(length_key)?
+      .and_then(|s| s.parse::<usize>().ok())
+      .unwrap_or(0)
+      + 1;
+    self.tap_derived_put(length_key, &length.to_string())?;
+    self.tap_derived_put(&format!("{}/{}", iterator_key, length - 1), data)?;
+    Ok(length)
+  }
 }
 
 #[cfg(test)]
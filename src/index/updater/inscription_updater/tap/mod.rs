@@ -3,7 +3,9 @@
 pub(super) mod jsregex;
 pub(super) mod kv;
 pub(super) mod records;
+pub(super) mod rejection;
 pub(super) mod routes;
+pub(super) mod segments;
 // Shared TAP constants and helpers live here and are re-exported by parent.
 
 // --- TAP feature gating (laddered block heights; mainnet values) ---
@@ -115,7 +117,9 @@ pub(crate) mod ops {
 pub(crate) use kv::{TapBatch, TapDeltaBatch};
 pub(crate) use ops::dmt_element::DmtElementRecord;
 pub(crate) use records::*;
+pub(crate) use rejection::TapRejection;
 pub(crate) use routes::{TapRoute, TapRouteIndex, TapRouteRebuildStats};
+pub(crate) use segments::TapDeltaSegments;
 
 // Helper functions implemented as associated fns on InscriptionUpdater
 use super::super::InscriptionUpdater;
@@ -148,6 +152,16 @@ pub(crate) fn tap_js_to_lowercase(s: &str) -> String {
   InscriptionUpdater::js_to_lowercase(s)
 }
 
+pub(crate) fn tap_token_send_item_check(
+  btc_network: BtcNetwork,
+  height: u32,
+  tick: &str,
+  address: &str,
+  dta: Option<&str>,
+) -> Result<String, String> {
+  InscriptionUpdater::token_send_item_check(btc_network, height, tick, address, dta)
+}
+
 impl InscriptionUpdater<'_, '_> {
   // Visible-length and ticker rules
   pub(crate) fn valid_tap_ticker_visible_len(full_height: u32, height: u32, len: usize) -> bool {
@@ -988,16 +1002,20 @@ impl InscriptionUpdater<'_, '_> {
   }
 
   pub(crate) fn parse_tap_json_value(&self, s: &str) -> Option<serde_json::Value> {
+    self.tap_parse_json(s).ok()
+  }
+
+  pub(crate) fn tap_parse_json(&self, s: &str) -> Result<serde_json::Value, TapRejection> {
     let compat = Self::preprocess_js_json_for_serde(s);
-    let value: serde_json::Value = serde_json::from_str(&compat).ok()?;
+    let value: serde_json::Value = serde_json::from_str(&compat).map_err(TapRejection::NotJson)?;
     // taprest runs tap-writer on Node 20.10.0, where the JSON.parse reviver
     // context argument is undefined. That makes raw numeric max/lim/amt throw.
     if self.tap_feature_enabled(TapFeature::ValueStringifyActivation)
       && Self::tap_writer_node20_value_stringify_would_throw(&value)
     {
-      return None;
+      return Err(TapRejection::NumericValue);
     }
-    Some(value)
+    Ok(value)
   }
 
   fn tap_writer_node20_value_stringify_would_throw(value: &serde_json::Value) -> bool {
@@ -1028,14 +1046,23 @@ impl InscriptionUpdater<'_, '_> {
     }
   }
   pub(crate) fn is_valid_bitcoin_address(&self, addr: &str) -> bool {
+    Self::is_valid_bitcoin_address_at(self.btc_network, self.height, addr)
+  }
+  pub(crate) fn is_valid_bitcoin_address_at(
+    btc_network: BtcNetwork,
+    height: u32,
+    addr: &str,
+  ) -> bool {
     // Absolute parity with tap-writer's isValidBitcoinAddress:
     // - Prefix + type gating (P2PKH/P2SH/P2WPKH/P2TR)
     // - Before the existing testnet fix height, tap-writer uses case-sensitive
     //   startsWith branches. At/after the fix, it validates through WAValidator
     //   first, so uppercase bech32 is accepted when the network matches.
     let raw = Self::trim_js_whitespace(addr);
-    let before_testnet_fix = self.height < self.feature_height(TapFeature::TestnetFixActivation);
-    let network_check_active = self.height >= self.feature_height(TapFeature::FullTicker);
+    let before_testnet_fix =
+      height < Self::network_feature_height(btc_network, TapFeature::TestnetFixActivation);
+    let network_check_active =
+      height >= Self::network_feature_height(btc_network, TapFeature::FullTicker);
     let s = if before_testnet_fix {
       raw.to_string()
     } else {
@@ -1052,7 +1079,7 @@ impl InscriptionUpdater<'_, '_> {
     let test_ok = parsed.clone().require_network(BtcNetwork::Testnet).is_ok();
     let reg_ok = parsed.clone().require_network(BtcNetwork::Regtest).is_ok();
     let signet_ok = parsed.clone().require_network(BtcNetwork::Signet).is_ok();
-    let exact_net_ok = match self.btc_network {
+    let exact_net_ok = match btc_network {
       BtcNetwork::Bitcoin => main_ok,
       BtcNetwork::Testnet => test_ok,
       BtcNetwork::Signet => signet_ok,
@@ -1679,6 +1706,9 @@ impl InscriptionUpdater<'_, '_> {
   }
   pub(crate) fn tap_feature_enabled(&self, feature: TapFeature) -> bool {
     self.height >= self.feature_height(feature)
+      && self
+        .tap_snapshot_height
+        .map_or(true, |height| self.height > height)
   }
   pub(crate) fn tap_token_trade_emergency_active(&self) -> bool {
     matches!(self.btc_network, BtcNetwork::Bitcoin)
@@ -1760,7 +1790,10 @@ impl InscriptionUpdater<'_, '_> {
     }
   }
   pub(crate) fn feature_height(&self, feature: TapFeature) -> u32 {
-    let is_mainnet = matches!(self.btc_network, BtcNetwork::Bitcoin);
+    Self::network_feature_height(self.btc_network, feature)
+  }
+  pub(crate) fn network_feature_height(btc_network: BtcNetwork, feature: TapFeature) -> u32 {
+    let is_mainnet = matches!(btc_network, BtcNetwork::Bitcoin);
     if !is_mainnet {
       return 0;
     }
@@ -1859,7 +1892,7 @@ mod tests {
   use crate::index::{
     entry::Entry, testing::Context, HOME_INSCRIPTIONS, INSCRIPTION_ID_TO_SEQUENCE_NUMBER,
     INSCRIPTION_NUMBER_TO_SEQUENCE_NUMBER, SAT_TO_SEQUENCE_NUMBER, SEQUENCE_NUMBER_TO_CHILDREN,
-    SEQUENCE_NUMBER_TO_INSCRIPTION_ENTRY, TAP_KV, TRANSACTION_ID_TO_TRANSACTION,
+    SEQUENCE_NUMBER_TO_INSCRIPTION_ENTRY, TAP_DERIVED, TAP_KV, TRANSACTION_ID_TO_TRANSACTION,
   };
   use crate::{Chain, Inscription, InscriptionId};
   use bitcoin::{
@@ -1903,6 +1936,7 @@ mod tests {
       .open_table(SEQUENCE_NUMBER_TO_INSCRIPTION_ENTRY)
       .unwrap();
     let mut tap_kv = write_tx.open_table(TAP_KV).unwrap();
+    let mut tap_derived = write_tx.open_table(TAP_DERIVED).unwrap();
 
     let mut updater = InscriptionUpdater {
       blessed_inscription_count: 0,
@@ -1924,64 +1958,66 @@ mod tests {
       timestamp: 0,
       unbound_inscriptions: 0,
       tap_db: TapBatch::new(&mut tap_kv),
+      tap_derived_db: TapBatch::new(&mut tap_derived),
       tap_delta_db: None,
       tap_atomic_writes: None,
       tap_atomic_overlay: None,
       tap_atomic_list_len_cache: None,
+      tap_atomic_derived: None,
       tap_route_index: None,
       tap_route_index_verify: false,
       list_len_cache: HashMap::new(),
       block_availability_cache: HashMap::new(),
       profile: false,
-      prof_bm_tr_ms: 0,
+      prof_bm_tr_us: 0,
       prof_bm_tr_ct: 0,
-      prof_dmt_tr_ms: 0,
+      prof_dmt_tr_us: 0,
       prof_dmt_tr_ct: 0,
-      prof_prv_tr_ms: 0,
+      prof_prv_tr_us: 0,
       prof_prv_tr_ct: 0,
-      prof_ttr_ex_ms: 0,
+      prof_ttr_ex_us: 0,
       prof_ttr_ex_ct: 0,
-      prof_tsend_ex_ms: 0,
+      prof_tsend_ex_us: 0,
       prof_tsend_ex_ct: 0,
-      prof_ttrade_ex_ms: 0,
+      prof_ttrade_ex_us: 0,
       prof_ttrade_ex_ct: 0,
-      prof_tauth_ex_ms: 0,
+      prof_tauth_ex_us: 0,
       prof_tauth_ex_ct: 0,
-      prof_pra_ex_ms: 0,
+      prof_pra_ex_us: 0,
       prof_pra_ex_ct: 0,
-      prof_blk_ex_ms: 0,
+      prof_blk_ex_us: 0,
       prof_blk_ex_ct: 0,
-      prof_unblk_ex_ms: 0,
+      prof_unblk_ex_us: 0,
       prof_unblk_ex_ct: 0,
-      prof_created_total_ms: 0,
+      prof_created_total_us: 0,
       prof_created_ct: 0,
-      prof_bm_cr_ms: 0,
+      prof_bm_cr_us: 0,
       prof_bm_cr_ct: 0,
-      prof_dmt_el_cr_ms: 0,
+      prof_dmt_el_cr_us: 0,
       prof_dmt_el_cr_ct: 0,
-      prof_dpl_cr_ms: 0,
+      prof_dpl_cr_us: 0,
       prof_dpl_cr_ct: 0,
-      prof_dmtmint_cr_ms: 0,
+      prof_dmtmint_cr_us: 0,
       prof_dmtmint_cr_ct: 0,
-      prof_mint_cr_ms: 0,
+      prof_mint_cr_us: 0,
       prof_mint_cr_ct: 0,
-      prof_ttr_cr_ms: 0,
+      prof_ttr_cr_us: 0,
       prof_ttr_cr_ct: 0,
-      prof_tsend_cr_ms: 0,
+      prof_tsend_cr_us: 0,
       prof_tsend_cr_ct: 0,
-      prof_ttrade_cr_ms: 0,
+      prof_ttrade_cr_us: 0,
       prof_ttrade_cr_ct: 0,
-      prof_tauth_cr_ms: 0,
+      prof_tauth_cr_us: 0,
       prof_tauth_cr_ct: 0,
-      prof_dmtdep_cr_ms: 0,
+      prof_dmtdep_cr_us: 0,
       prof_dmtdep_cr_ct: 0,
-      prof_pra_cr_ms: 0,
+      prof_pra_cr_us: 0,
       prof_pra_cr_ct: 0,
-      prof_prv_cr_ms: 0,
+      prof_prv_cr_us: 0,
       prof_prv_cr_ct: 0,
-      prof_blk_cr_ms: 0,
+      prof_blk_cr_us: 0,
       prof_blk_cr_ct: 0,
-      prof_unblk_cr_ms: 0,
+      prof_unblk_cr_us: 0,
       prof_unblk_cr_ct: 0,
       prof_core_env_ms: 0,
       prof_core_old_ms: 0,
@@ -2013,6 +2049,10 @@ mod tests {
       delegate_cache: HashMap::new(),
       delegate_payload_cache: HashMap::new(),
       btc_network: network,
+      tap_snapshot_height: None,
+      emit_events: true,
+      tap_amm_candle_intervals: Vec::new(),
+      preview_base: None,
     };
 
     test(&mut updater)
@@ -3608,6 +3648,53 @@ mod tests {
     });
   }
 
+  #[test]
+  fn stream_block_events_follow_block_lists_written_in_the_block() {
+    with_test_updater(BtcNetwork::Signet, 1, |updater| {
+      put_deploy_with_supply(updater, "xfer", USER_ADDRESS, 0, "100", "100");
+      put_balance(updater, USER_ADDRESS, "xfer", "100");
+
+      for seed in [94, 95] {
+        let transfer_id = inscription_id_from_seed(seed);
+        updater.index_token_transfer_created(
+          transfer_id,
+          0,
+          satpoint_from_inscription(transfer_id, 0),
+          &inscription_from_body(r#"{"p":"tap","op":"token-transfer","tick":"xfer","amt":"5"}"#),
+          USER_ADDRESS,
+          1_000,
+        );
+      }
+
+      let events = updater
+        .tap_stream_block_events(BlockHash::all_zeros())
+        .unwrap();
+      let transfers = events
+        .iter()
+        .filter_map(|event| match event {
+          crate::index::TapStreamEvent::Record {
+            block,
+            sequence,
+            family,
+            record,
+            ..
+          } if family == "trf" => Some((*block, *sequence, record.clone())),
+          _ => None,
+        })
+        .collect::<Vec<_>>();
+      assert_eq!(transfers.len(), 2);
+      assert!(transfers.iter().all(|(block, _, record)| {
+        *block == 1
+          && record.get("tick").and_then(|tick| tick.as_str()) == Some("xfer")
+          && record.get("addr").and_then(|addr| addr.as_str()) == Some(USER_ADDRESS)
+      }));
+      assert!(events
+        .iter()
+        .enumerate()
+        .all(|(i, event)| event.cursor().sequence == i as u64));
+    });
+  }
+
   #[test]
   fn trade_side_valid_amount_caps_and_duplicate_accepts_match_tap_writer() {
     with_test_updater(BtcNetwork::Signet, 1, |updater| {
@@ -4230,6 +4317,117 @@ mod tests {
     });
   }
 
+  #[test]
+  fn token_trade_open_offer_book_drops_filled_cancelled_and_expired_offers() {
+    with_test_updater(BtcNetwork::Signet, 1, |updater| {
+      put_deploy_with_supply(updater, "FoO", USER_ADDRESS, 2, "100.00", "100.00");
+      put_deploy_with_supply(updater, "BaR", RECIPIENT_ADDRESS, 2, "100.00", "100.00");
+      put_balance(updater, USER_ADDRESS, "FoO", "10000");
+      put_balance(updater, RECIPIENT_ADDRESS, "BaR", "10000");
+
+      let inscribe =
+        |updater: &mut InscriptionUpdater<'_, '_>, seed: u8, owner: &str, body: &str| {
+          let id = inscription_id_from_seed(seed);
+          updater.index_token_trade_created(
+            id,
+            0,
+            satpoint_from_inscription(id, 0),
+            &inscription_from_body(body),
+            owner,
+            1_000,
+          );
+          updater.index_token_trade_executed(id, 0, transfer_satpoint(seed + 1, 0), owner, 1_000);
+          id
+        };
+      let offer = |updater: &mut InscriptionUpdater<'_, '_>, seed: u8, accept: &str, valid: u32| {
+        inscribe(
+          updater,
+          seed,
+          USER_ADDRESS,
+          &format!(
+            r#"{{"p":"tap","op":"token-trade","side":"0","tick":"FoO","amt":"1.00","accept":[{{"tick":"BaR","amt":"{accept}"}}],"valid":{valid}}}"#
+          ),
+        )
+      };
+
+      let filled = offer(updater, 90, "2.00", 100);
+      let expiring = offer(updater, 92, "3.00", 5);
+      let cancelled = offer(updater, 94, "4.00", 100);
+
+      let pair = format!(
+        "{}/{}",
+        InscriptionUpdater::json_stringify_lower("FoO"),
+        InscriptionUpdater::json_stringify_lower("BaR")
+      );
+      let book = |updater: &mut InscriptionUpdater<'_, '_>| {
+        let length = updater
+          .tap_derived_get::<String>(&format!("tob/{pair}"))
+          .unwrap()
+          .unwrap()
+          .parse::<u64>()
+          .unwrap();
+        (0..length)
+          .map(|i| {
+            updater
+              .tap_derived_get::<TradeBookEntryRecord>(&format!("tobi/{pair}/{i}"))
+              .unwrap()
+              .unwrap()
+              .id
+          })
+          .collect::<Vec<_>>()
+      };
+      assert_eq!(
+        book(updater),
+        [
+          filled.to_string(),
+          expiring.to_string(),
+          cancelled.to_string()
+        ]
+      );
+
+      inscribe(
+        updater,
+        96,
+        RECIPIENT_ADDRESS,
+        &format!(
+          r#"{{"p":"tap","op":"token-trade","side":"1","trade":"{filled}","tick":"BaR","amt":"2.00"}}"#
+        ),
+      );
+      assert_eq!(book(updater), [cancelled.to_string(), expiring.to_string()]);
+      assert_eq!(
+        updater
+          .tap_derived_get::<String>(&format!(
+            "tobs/{cancelled}/{}",
+            InscriptionUpdater::json_stringify_lower("BaR")
+          ))
+          .unwrap()
+          .as_deref(),
+        Some("0")
+      );
+      assert_eq!(get_string(updater, &format!("tob/{pair}")), None);
+
+      inscribe(
+        updater,
+        98,
+        USER_ADDRESS,
+        &format!(r#"{{"p":"tap","op":"token-trade","side":"0","trade":"{cancelled}"}}"#),
+      );
+      assert_eq!(book(updater), [expiring.to_string()]);
+
+      updater.height = 4;
+      updater.expire_trade_book_offers();
+      assert_eq!(book(updater), [expiring.to_string()]);
+
+      updater.height = 5;
+      updater.expire_trade_book_offers();
+      assert!(book(updater).is_empty());
+      assert!(updater
+        .tap_derived_get::<TradeBookOfferRecord>(&format!("tobt/{expiring}"))
+        .unwrap()
+        .is_none());
+    });
+  }
+
   #[test]
   fn token_trade_failed_fill_keeps_writer_metadata_without_balance_changes() {
     with_test_updater(BtcNetwork::Signet, 1, |updater| {
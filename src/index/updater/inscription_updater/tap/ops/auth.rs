@@ -131,11 +131,11 @@ struct AmmAsset {
 }
 
 #[derive(Clone)]
-struct AmmSwapCalc {
-  amount_in: BigInt,
-  amount_out: BigInt,
-  gross_fee: BigInt,
-  protocol_fee: BigInt,
+pub(crate) struct AmmSwapCalc {
+  pub(crate) amount_in: BigInt,
+  pub(crate) amount_out: BigInt,
+  pub(crate) gross_fee: BigInt,
+  pub(crate) protocol_fee: BigInt,
 }
 
 struct AmmAddValidation {
@@ -394,7 +394,7 @@ impl InscriptionUpdater<'_, '_> {
     None
   }
 
-  fn obligation_lock_key(source: &serde_json::Value, tick_key: &str) -> Option<String> {
+  pub(crate) fn obligation_lock_key(source: &serde_json::Value, tick_key: &str) -> Option<String> {
     Some(format!(
       "{}/{}",
       Self::obligation_source_key(source)?,
@@ -1341,7 +1341,157 @@ impl InscriptionUpdater<'_, '_> {
           &format!("txi/amm/{}", transaction),
           &ptr,
         );
+        self.record_amm_candles(pid, event, block);
+      }
+    }
+  }
+
+  // Candles are kept per pool as derived `ammc/{pid}/{interval}` lists: `blk`
+  // buckets by block height, and each configured interval by block timestamp.
+  // A block whose timestamp falls before the open bucket is folded into it, so
+  // every series stays in order. Prices are reserve 1 per reserve 0 after each
+  // event.
+  fn record_amm_candles(&mut self, pid: &str, event: &serde_json::Value, block: u32) {
+    let Some(reserves) = event
+      .get("r")
+      .and_then(|r| r.as_array())
+      .filter(|r| r.len() == 2)
+      .and_then(|r| {
+        Some([
+          r[0].as_str()?.parse::<BigInt>().ok()?,
+          r[1].as_str()?.parse::<BigInt>().ok()?,
+        ])
+      })
+    else {
+      return;
+    };
+    let timestamp = event.get("ts").and_then(|v| v.as_u64()).unwrap_or(0);
+    let mut buckets = vec![("blk".to_string(), u64::from(block))];
+    for interval in self.tap_amm_candle_intervals.iter().filter(|i| **i > 0) {
+      let interval = u64::from(*interval);
+      buckets.push((interval.to_string(), timestamp - timestamp % interval));
+    }
+    for (interval, start) in buckets {
+      self.update_amm_candle(pid, &interval, start, block, &reserves, event);
+    }
+  }
+
+  fn update_amm_candle(
+    &mut self,
+    pid: &str,
+    interval: &str,
+    start: u64,
+    block: u32,
+    reserves: &[BigInt; 2],
+    event: &serde_json::Value,
+  ) {
+    let length_key = format!("ammc/{}/{}", pid, interval);
+    let iterator_key = format!("ammci/{}/{}", pid, interval);
+    let length = self
+      .tap_derived_get::<String>(&length_key)
+      .ok()
+      .flatten()
+      .and_then(|s| s.parse::<usize>().ok())
+      .unwrap_or(0);
+    let last_key = format!("{}/{}", iterator_key, length.saturating_sub(1));
+    let last = if length > 0 {
+      self
+        .tap_derived_get::<serde_json::Value>(&last_key)
+        .ok()
+        .flatten()
+    } else {
+      None
+    };
+    let open = last
+      .as_ref()
+      .filter(|candle| {
+        candle
+          .get("t")
+          .and_then(|v| v.as_u64())
+          .map(|t| start <= t)
+          .unwrap_or(false)
+      })
+      .is_some();
+    let mut candle = match last {
+      Some(candle) if open => candle,
+      previous => {
+        let close = previous
+          .as_ref()
+          .and_then(|candle| candle.get("c").cloned())
+          .unwrap_or(serde_json::Value::Null);
+        serde_json::json!({
+          "t": start,
+          "o": close,
+          "h": close,
+          "l": close,
+          "c": close,
+          "r": ["0", "0"],
+          "v": ["0", "0"],
+          "f": ["0", "0"],
+          "pf": ["0", "0"],
+          "n": 0,
+          "fb": block,
+          "lb": block
+        })
+      }
+    };
+
+    if reserves[0] > BigInt::from(0) {
+      let scale = BigInt::from(10u64.pow(18));
+      let price = &reserves[1] * &scale / &reserves[0];
+      let parse = |candle: &serde_json::Value, field: &str| {
+        candle
+          .get(field)
+          .and_then(|v| v.as_str())
+          .and_then(|s| s.replace('.', "").parse::<BigInt>().ok())
+      };
+      let (high, low, has_open) = (
+        parse(&candle, "h"),
+        parse(&candle, "l"),
+        parse(&candle, "o").is_some(),
+      );
+      let text = format!("{}.{:0>18}", &price / &scale, (&price % &scale).to_string());
+      if !has_open {
+        candle["o"] = text.clone().into();
+      }
+      if high.map(|high| price > high).unwrap_or(true) {
+        candle["h"] = text.clone().into();
       }
+      if low.map(|low| price < low).unwrap_or(true) {
+        candle["l"] = text.clone().into();
+      }
+      candle["c"] = text.into();
+    }
+    candle["r"] = serde_json::json!([reserves[0].to_string(), reserves[1].to_string()]);
+
+    if event.get("op").and_then(|v| v.as_str()) == Some("swap") {
+      let side = usize::from(event.get("i").and_then(|v| v.as_u64()) == Some(1));
+      for (field, index, amount) in [
+        ("v", side, "ain"),
+        ("v", 1 - side, "out"),
+        ("f", side, "fee"),
+        ("pf", side, "pf"),
+      ] {
+        let add = event
+          .get(amount)
+          .and_then(|v| v.as_str())
+          .and_then(|s| s.parse::<BigInt>().ok())
+          .unwrap_or_else(|| BigInt::from(0));
+        let total = candle[field][index]
+          .as_str()
+          .and_then(|s| s.parse::<BigInt>().ok())
+          .unwrap_or_else(|| BigInt::from(0))
+          + add;
+        candle[field][index] = total.to_string().into();
+      }
+    }
+    candle["n"] = (candle["n"].as_u64().unwrap_or(0) + 1).into();
+    candle["lb"] = block.into();
+
+    if open {
+      let _ = self.tap_derived_put(&last_key, &candle);
+    } else {
+      let _ = self.tap_derived_set_list_record(&length_key, &iterator_key, &candle);
     }
   }
 
@@ -1401,6 +1551,13 @@ impl InscriptionUpdater<'_, '_> {
       &normalized.shares_after,
     );
     self.put_amm_pool(&pool);
+    self.update_amm_position_basis(
+      &pool,
+      &normalized.to,
+      &normalized.minted,
+      Some(&normalized.amounts),
+      block,
+    );
     let event_id = format!("ammadd:{}:{}", inscription, action_index);
     self.mark_amm_ref(
       &pool.id,
@@ -1477,6 +1634,13 @@ impl InscriptionUpdater<'_, '_> {
       &normalized.shares_after,
     );
     self.put_amm_pool(&pool);
+    self.update_amm_position_basis(
+      &pool,
+      &normalized.owner,
+      &(-&normalized.shares),
+      None,
+      block,
+    );
     let event_id = format!("ammrm:{}:{}", inscription, action_index);
     self.mark_amm_ref(
       &pool.id,
@@ -1726,7 +1890,7 @@ impl InscriptionUpdater<'_, '_> {
         return None;
       }
       let (ok, _, pubkey) =
-        self.verify_sig_obj_against_msg_with_hash(entry.get("sig")?, hash_str, &msg_hash)?;
+        Self::verify_sig_obj_against_msg_with_hash(entry.get("sig")?, hash_str, &msg_hash)?;
       let signer = Self::token_proof_compressed_delegation_pubkey(&pubkey)?;
       if ok && signer_set.contains(&signer) {
         valid_signers.insert(signer);
@@ -2013,7 +2177,7 @@ impl InscriptionUpdater<'_, '_> {
     true
   }
 
-  fn authority_reward_precision() -> BigInt {
+  pub(crate) fn authority_reward_precision() -> BigInt {
     BigInt::from(1_000_000_000_000_000_000i128)
   }
 
@@ -2022,7 +2186,11 @@ impl InscriptionUpdater<'_, '_> {
     Some(value.to_string())
   }
 
-  fn authority_reward_pending_i128(shares: &BigInt, acc: &BigInt, paid: &BigInt) -> Option<i128> {
+  pub(crate) fn authority_reward_pending_i128(
+    shares: &BigInt,
+    acc: &BigInt,
+    paid: &BigInt,
+  ) -> Option<i128> {
     let value = shares * acc / Self::authority_reward_precision() - paid;
     if value <= BigInt::from(0) {
       return Some(0);
@@ -2252,7 +2420,7 @@ impl InscriptionUpdater<'_, '_> {
     Some(x0)
   }
 
-  fn div_ceil(numerator: &BigInt, denominator: &BigInt) -> Option<BigInt> {
+  pub(crate) fn div_ceil(numerator: &BigInt, denominator: &BigInt) -> Option<BigInt> {
     if denominator <= &BigInt::from(0) {
       return None;
     }
@@ -2356,7 +2524,7 @@ impl InscriptionUpdater<'_, '_> {
     let _ = self.tap_put(&format!("amm/{}", pool.id), pool);
   }
 
-  fn amm_pool_assets_are_tap(pool: &AuthorityConfigRecord) -> bool {
+  pub(crate) fn amm_pool_assets_are_tap(pool: &AuthorityConfigRecord) -> bool {
     pool
       .a
       .iter()
@@ -2372,18 +2540,18 @@ impl InscriptionUpdater<'_, '_> {
       == Some("tap")
   }
 
-  fn amm_pool_tick_key(pool: &AuthorityConfigRecord, index: usize) -> Option<String> {
+  pub(crate) fn amm_pool_tick_key(pool: &AuthorityConfigRecord, index: usize) -> Option<String> {
     let tick = pool.a.get(index)?.get("tick")?.as_str()?;
     Some(Self::json_stringify_lower(tick))
   }
 
-  fn amm_pool_tick(pool: &AuthorityConfigRecord, index: usize) -> Option<String> {
+  pub(crate) fn amm_pool_tick(pool: &AuthorityConfigRecord, index: usize) -> Option<String> {
     Some(Self::js_to_lowercase(
       pool.a.get(index)?.get("tick")?.as_str()?,
     ))
   }
 
-  fn amm_pool_reserves(pool: &AuthorityConfigRecord) -> Option<[BigInt; 2]> {
+  pub(crate) fn amm_pool_reserves(pool: &AuthorityConfigRecord) -> Option<[BigInt; 2]> {
     let arr = pool.r.as_array()?;
     if arr.len() != 2 {
       return None;
@@ -2425,7 +2593,7 @@ impl InscriptionUpdater<'_, '_> {
     true
   }
 
-  fn amm_pool_shares(pool: &AuthorityConfigRecord) -> Option<BigInt> {
+  pub(crate) fn amm_pool_shares(pool: &AuthorityConfigRecord) -> Option<BigInt> {
     pool.sh.parse::<BigInt>().ok()
   }
 
@@ -2692,11 +2860,90 @@ impl InscriptionUpdater<'_, '_> {
     self.set_amm_lp_shares(pool_id, target, &next)
   }
 
+  /// Invariant liquidity `sqrt(r0 * r1)` behind `shares`, with 18 decimals.
+  /// It only grows per share as swap fees stay in the pool.
+  pub(crate) fn amm_share_liquidity(
+    reserves: &[BigInt; 2],
+    shares: &BigInt,
+    total: &BigInt,
+  ) -> BigInt {
+    if total <= &BigInt::from(0) {
+      return BigInt::from(0);
+    }
+    (&reserves[0] * &reserves[1]).sqrt() * shares * BigInt::from(10u64.pow(18)) / total
+  }
+
+  // Derived cost basis for `ammpb/{pid}/{tt}/{to}`, which position valuation
+  // compares against current reserves: deposited amounts plus the invariant
+  // liquidity the minted shares stood for at entry, both cut pro rata on remove.
+  fn update_amm_position_basis(
+    &mut self,
+    pool: &AuthorityConfigRecord,
+    target: &AmmTarget,
+    shares_delta: &BigInt,
+    deposits: Option<&[BigInt; 2]>,
+    block: u32,
+  ) {
+    let (Some(reserves), Some(total)) =
+      (Self::amm_pool_reserves(pool), Self::amm_pool_shares(pool))
+    else {
+      return;
+    };
+    let key = format!("ammpb/{}/{}", pool.id, Self::amm_target_key(target));
+    let basis = self
+      .tap_derived_get::<serde_json::Value>(&key)
+      .ok()
+      .flatten();
+    let field = |name: &str, index: Option<usize>| {
+      let value = basis.as_ref().and_then(|basis| basis.get(name));
+      let value = match index {
+        Some(index) => value.and_then(|v| v.get(index)),
+        None => value,
+      };
+      value
+        .and_then(|v| v.as_str())
+        .and_then(|s| s.parse::<BigInt>().ok())
+        .unwrap_or_else(|| BigInt::from(0))
+    };
+    let mut deposited = [field("dep", Some(0)), field("dep", Some(1))];
+    let mut liquidity = field("liq", None);
+    let shares = self.get_amm_lp_shares(&pool.id, target);
+    match deposits {
+      Some(amounts) => {
+        deposited[0] += &amounts[0];
+        deposited[1] += &amounts[1];
+        liquidity += Self::amm_share_liquidity(&reserves, shares_delta, &total);
+      }
+      None => {
+        let before = &shares - shares_delta;
+        if before > BigInt::from(0) {
+          for amount in deposited.iter_mut().chain(std::iter::once(&mut liquidity)) {
+            *amount = &*amount * &shares / &before;
+          }
+        }
+      }
+    }
+    let _ = self.tap_derived_put(
+      &key,
+      &serde_json::json!({
+        "pid": pool.id,
+        "tt": target.tt,
+        "to": target.to,
+        "sh": shares.to_string(),
+        "dep": [deposited[0].to_string(), deposited[1].to_string()],
+        "liq": liquidity.to_string(),
+        "r": pool.r,
+        "tsh": pool.sh,
+        "blck": block
+      }),
+    );
+  }
+
   fn amm_protocol_fee(gross_fee: &BigInt, protocol_share_bps: &BigInt) -> BigInt {
     gross_fee * protocol_share_bps / BigInt::from(10000)
   }
 
-  fn calculate_amm_exact_in(
+  pub(crate) fn calculate_amm_exact_in(
     amount_in: &BigInt,
     reserve_in: &BigInt,
     reserve_out: &BigInt,
@@ -2724,7 +2971,7 @@ impl InscriptionUpdater<'_, '_> {
     })
   }
 
-  fn calculate_amm_exact_out(
+  pub(crate) fn calculate_amm_exact_out(
     amount_out: &BigInt,
     reserve_in: &BigInt,
     reserve_out: &BigInt,
@@ -2754,7 +3001,10 @@ impl InscriptionUpdater<'_, '_> {
     })
   }
 
-  fn token_sale_status_default(auth: &str, config: &AuthorityConfigRecord) -> serde_json::Value {
+  pub(crate) fn token_sale_status_default(
+    auth: &str,
+    config: &AuthorityConfigRecord,
+  ) -> serde_json::Value {
     serde_json::json!({
       "auth": auth,
       "st": config.st.clone().unwrap_or_default(),
@@ -2780,14 +3030,14 @@ impl InscriptionUpdater<'_, '_> {
       .to_string()
   }
 
-  fn token_sale_status_i128(status: &serde_json::Value, key: &str) -> i128 {
+  pub(crate) fn token_sale_status_i128(status: &serde_json::Value, key: &str) -> i128 {
     Self::token_sale_status_str(status, key)
       .parse::<i128>()
       .ok()
       .unwrap_or(0)
   }
 
-  fn token_sale_status_bool(status: &serde_json::Value, key: &str) -> bool {
+  pub(crate) fn token_sale_status_bool(status: &serde_json::Value, key: &str) -> bool {
     status.get(key).and_then(|v| v.as_bool()).unwrap_or(false)
   }
 
@@ -2873,8 +3123,7 @@ impl InscriptionUpdater<'_, '_> {
     Some(after)
   }
 
-  fn token_sale_contribution_allocation(
-    &self,
+  pub(crate) fn token_sale_contribution_allocation(
     payment_amount: i128,
     config: &AuthorityConfigRecord,
   ) -> Option<i128> {
@@ -2895,7 +3144,14 @@ impl InscriptionUpdater<'_, '_> {
     hex::encode(hasher.finalize())
   }
 
-  fn token_sale_merkle_parent(left: &str, right: &str) -> Option<String> {
+  pub(crate) fn token_sale_allowlist_leaf(claim: &str, cap: Option<i128>) -> String {
+    match cap {
+      Some(cap) => Self::token_sale_hash_hex_bytes(format!("{}:{}", claim, cap).as_bytes()),
+      None => Self::token_sale_hash_hex_bytes(claim.as_bytes()),
+    }
+  }
+
+  pub(crate) fn token_sale_merkle_parent(left: &str, right: &str) -> Option<String> {
     let mut pair = [left.to_lowercase(), right.to_lowercase()];
     pair.sort();
     let bytes = hex::decode(format!("{}{}", pair[0], pair[1])).ok()?;
@@ -2939,7 +3195,7 @@ impl InscriptionUpdater<'_, '_> {
       return false;
     }
 
-    let mut leaf_content = claim.to_string();
+    let mut leaf_cap = None;
     if allowlist.get("lf").and_then(|v| v.as_str()) == Some("addr-cap") {
       let Some(cap_value) = proof_obj.get("max") else {
         return false;
@@ -2950,10 +3206,10 @@ impl InscriptionUpdater<'_, '_> {
       if payment_amount > cap {
         return false;
       }
-      leaf_content = format!("{}:{}", claim, cap);
+      leaf_cap = Some(cap);
     }
 
-    let mut node = Self::token_sale_hash_hex_bytes(leaf_content.as_bytes());
+    let mut node = Self::token_sale_allowlist_leaf(claim, leaf_cap);
     for sibling in proof {
       let Some(sibling) = sibling.as_str() else {
         return false;
@@ -3084,7 +3340,7 @@ impl InscriptionUpdater<'_, '_> {
     Some((out, total))
   }
 
-  fn token_proof_compressed_delegation_pubkey(signer: &str) -> Option<String> {
+  pub(crate) fn token_proof_compressed_delegation_pubkey(signer: &str) -> Option<String> {
     if !Self::token_proof_valid_delegation_signer(signer) {
       return None;
     }
@@ -3140,7 +3396,7 @@ impl InscriptionUpdater<'_, '_> {
     }
   }
 
-  fn certified_control_hash(value: &serde_json::Value) -> Option<String> {
+  pub(crate) fn certified_control_hash(value: &serde_json::Value) -> Option<String> {
     let canonical = Self::certified_control_canonical_json(value)?;
     let mut hasher = Sha256::new();
     hasher.update(canonical.as_bytes());
@@ -3469,7 +3725,7 @@ impl InscriptionUpdater<'_, '_> {
       {
         return None;
       }
-      let (ok, _, pubkey) = self.verify_sig_obj_against_msg_with_hash(
+      let (ok, _, pubkey) = Self::verify_sig_obj_against_msg_with_hash(
         entry.get("sig")?,
         entry.get("hash")?.as_str()?,
         &msg_hash,
@@ -3966,7 +4222,7 @@ impl InscriptionUpdater<'_, '_> {
         return false;
       };
       if let Some((ok, _, pubkey)) =
-        self.verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
+        Self::verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
       {
         let Some(normalized) = Self::token_proof_compressed_delegation_pubkey(&pubkey) else {
           return false;
@@ -4048,7 +4304,7 @@ impl InscriptionUpdater<'_, '_> {
         return false;
       };
       if let Some((ok, _, pubkey)) =
-        self.verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
+        Self::verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
       {
         let Some(normalized) = Self::token_proof_compressed_delegation_pubkey(&pubkey) else {
           return false;
@@ -4087,7 +4343,7 @@ impl InscriptionUpdater<'_, '_> {
       &link.slt,
     );
     let (ok, _, pubkey) =
-      self.verify_sig_obj_against_msg_with_hash(&link.sig, &link.hash, &auth_msg_hash)?;
+      Self::verify_sig_obj_against_msg_with_hash(&link.sig, &link.hash, &auth_msg_hash)?;
     if !ok {
       return None;
     }
@@ -4152,7 +4408,7 @@ impl InscriptionUpdater<'_, '_> {
         return false;
       };
       if let Some((ok, _, pubkey)) =
-        self.verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
+        Self::verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
       {
         let Some(normalized) = Self::token_proof_compressed_delegation_pubkey(&pubkey) else {
           return false;
@@ -4233,7 +4489,7 @@ impl InscriptionUpdater<'_, '_> {
         return false;
       };
       if let Some((ok, _, pubkey)) =
-        self.verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
+        Self::verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
       {
         let Some(normalized) = Self::token_proof_compressed_delegation_pubkey(&pubkey) else {
           return false;
@@ -5090,9 +5346,68 @@ impl InscriptionUpdater<'_, '_> {
         &ptr,
       );
     }
+    self.tap_open_lock_insert("lcop", "lcops", &rec.claim, &id);
+    if let Some(refund) = &rec.refund {
+      self.tap_open_lock_insert("lrop", "lrops", refund, &id);
+      if let Some(refund_after) = rec.refund_after {
+        self.tap_open_lock_insert("lrh", "lrhs", &refund_after.to_string(), &id);
+      }
+    }
     true
   }
 
+  // Unconsumed locks are listed per claim address under `lcop/` and per refund
+  // address under `lrop/`. The lists are unordered: removing a lock moves the
+  // last entry into its slot, and `{slots}/{id}` holds each lock's slot.
+  // `lrh/{refund_after}` lists them by the first block they can be refunded,
+  // with slots under `lrhs/`.
+  // These are derived views in TAP_DERIVED; claims and refunds check `l/` and
+  // `lc/`.
+  fn tap_open_lock_insert(&mut self, list: &str, slots: &str, address: &str, id: &str) {
+    let length = self.tap_open_lock_length(list, address);
+    let _ = self.tap_derived_put(
+      &format!("{}i/{}/{}", list, address, length),
+      &id.to_string(),
+    );
+    let _ = self.tap_derived_put(&format!("{}/{}", list, address), &(length + 1).to_string());
+    let _ = self.tap_derived_put(&format!("{}/{}", slots, id), &length.to_string());
+  }
+
+  fn tap_open_lock_remove(&mut self, list: &str, slots: &str, address: &str, id: &str) {
+    let slot_key = format!("{}/{}", slots, id);
+    let Some(slot) = self
+      .tap_derived_get::<String>(&slot_key)
+      .ok()
+      .flatten()
+      .and_then(|s| s.parse::<u64>().ok())
+    else {
+      return;
+    };
+    let last = self.tap_open_lock_length(list, address).saturating_sub(1);
+    if slot < last {
+      if let Some(moved) = self
+        .tap_derived_get::<String>(&format!("{}i/{}/{}", list, address, last))
+        .ok()
+        .flatten()
+      {
+        let _ = self.tap_derived_put(&format!("{}/{}", slots, moved), &slot.to_string());
+        let _ = self.tap_derived_put(&format!("{}i/{}/{}", list, address, slot), &moved);
+      }
+    }
+    let _ = self.tap_derived_del(&format!("{}i/{}/{}", list, address, last));
+    let _ = self.tap_derived_put(&format!("{}/{}", list, address), &last.to_string());
+    let _ = self.tap_derived_del(&slot_key);
+  }
+
+  fn tap_open_lock_length(&mut self, list: &str, address: &str) -> u64 {
+    self
+      .tap_derived_get::<String>(&format!("{}/{}", list, address))
+      .ok()
+      .flatten()
+      .and_then(|s| s.parse::<u64>().ok())
+      .unwrap_or(0)
+  }
+
   fn validate_token_proof_release_action(
     &mut self,
     action: &serde_json::Value,
@@ -7003,6 +7318,13 @@ impl InscriptionUpdater<'_, '_> {
       let _ = self.tap_put(&key, &consume);
     }
     let _ = self.tap_put(&format!("lc/{}", lock_id), &consume);
+    self.tap_open_lock_remove("lcop", "lcops", &lock.claim, lock_id);
+    if let Some(refund) = &lock.refund {
+      self.tap_open_lock_remove("lrop", "lrops", refund, lock_id);
+      if let Some(refund_after) = lock.refund_after {
+        self.tap_open_lock_remove("lrh", "lrhs", &refund_after.to_string(), lock_id);
+      }
+    }
     if let Ok(list_len) = self.tap_set_list_record("slc", "slci", &consume) {
       let _ = self.tap_set_list_record(
         &format!("lca/{}", lock.owner),
@@ -8040,7 +8362,7 @@ impl InscriptionUpdater<'_, '_> {
     {
       return None;
     }
-    let allocation = self.token_sale_contribution_allocation(amount, &config)?;
+    let allocation = Self::token_sale_contribution_allocation(amount, &config)?;
     if allocation <= 0 {
       return None;
     }
@@ -9322,7 +9644,7 @@ impl InscriptionUpdater<'_, '_> {
     true
   }
 
-  fn sale_withdraw_allowed(
+  pub(crate) fn sale_withdraw_allowed(
     config: &AuthorityConfigRecord,
     status: &serde_json::Value,
     block: u32,
@@ -9345,7 +9667,7 @@ impl InscriptionUpdater<'_, '_> {
     i128::from(block) > end_height && Self::token_sale_status_i128(status, "tc") < soft_cap
   }
 
-  fn sale_outstanding_allocation(status: &serde_json::Value) -> i128 {
+  pub(crate) fn sale_outstanding_allocation(status: &serde_json::Value) -> i128 {
     let allocated = Self::token_sale_status_i128(status, "alc");
     let claimed = Self::token_sale_status_i128(status, "clm");
     if allocated > claimed {
@@ -9355,7 +9677,7 @@ impl InscriptionUpdater<'_, '_> {
     }
   }
 
-  fn sale_withdrawal_reserve(
+  pub(crate) fn sale_withdrawal_reserve(
     config: &AuthorityConfigRecord,
     status: &serde_json::Value,
     tick: &str,
@@ -9511,15 +9833,18 @@ impl InscriptionUpdater<'_, '_> {
     format!("{}:{}", inscription, action_index)
   }
 
-  fn parse_perp_uint(value: &serde_json::Value, allow_zero: bool) -> Option<BigInt> {
+  pub(crate) fn parse_perp_uint(value: &serde_json::Value, allow_zero: bool) -> Option<BigInt> {
     Self::parse_amm_uint_value(value, allow_zero)
   }
 
-  fn parse_perp_height(value: &serde_json::Value) -> Option<u32> {
+  pub(crate) fn parse_perp_height(value: &serde_json::Value) -> Option<u32> {
     Self::parse_amm_height(value)
   }
 
-  fn parse_perp_ratio(value: &serde_json::Value, allow_zero: bool) -> Option<(BigInt, BigInt)> {
+  pub(crate) fn parse_perp_ratio(
+    value: &serde_json::Value,
+    allow_zero: bool,
+  ) -> Option<(BigInt, BigInt)> {
     if let Some(raw) = value.as_str() {
       let parts = raw.split('/').collect::<Vec<_>>();
       if parts.len() == 1 {
@@ -9543,7 +9868,7 @@ impl InscriptionUpdater<'_, '_> {
     ))
   }
 
-  fn serialize_perp_ratio(ratio: &(BigInt, BigInt)) -> String {
+  pub(crate) fn serialize_perp_ratio(ratio: &(BigInt, BigInt)) -> String {
     if ratio.1 == BigInt::from(1) {
       ratio.0.to_string()
     } else {
@@ -9551,7 +9876,7 @@ impl InscriptionUpdater<'_, '_> {
     }
   }
 
-  fn compare_perp_ratio(left: &(BigInt, BigInt), right: &(BigInt, BigInt)) -> i8 {
+  pub(crate) fn compare_perp_ratio(left: &(BigInt, BigInt), right: &(BigInt, BigInt)) -> i8 {
     let lhs = &left.0 * &right.1;
     let rhs = &right.0 * &left.1;
     if lhs < rhs {
@@ -9563,7 +9888,10 @@ impl InscriptionUpdater<'_, '_> {
     }
   }
 
-  fn token_perp_payload_hash(action: &serde_json::Value, omitted: &[&str]) -> Option<String> {
+  pub(crate) fn token_perp_payload_hash(
+    action: &serde_json::Value,
+    omitted: &[&str],
+  ) -> Option<String> {
     let mut payload = action.clone();
     let obj = payload.as_object_mut()?;
     for key in omitted {
@@ -9572,7 +9900,7 @@ impl InscriptionUpdater<'_, '_> {
     Self::certified_control_hash(&payload)
   }
 
-  fn token_perp_policy_message(
+  pub(crate) fn token_perp_policy_message(
     policy_id: &str,
     seq: &str,
     payload_hash: &str,
@@ -9580,7 +9908,7 @@ impl InscriptionUpdater<'_, '_> {
     serde_json::json!(["tap-perp-policy-v1", "tap", policy_id, seq, payload_hash])
   }
 
-  fn token_perp_certificate_message(
+  pub(crate) fn token_perp_certificate_message(
     policy: &serde_json::Value,
     purpose: &str,
     group: &str,
@@ -9601,7 +9929,7 @@ impl InscriptionUpdater<'_, '_> {
     ]))
   }
 
-  fn normalize_perp_signers(signers: &serde_json::Value) -> Option<Vec<String>> {
+  pub(crate) fn normalize_perp_signers(signers: &serde_json::Value) -> Option<Vec<String>> {
     let arr = signers.as_array()?;
     let mut set = std::collections::BTreeSet::new();
     for signer in arr {
@@ -9616,8 +9944,7 @@ impl InscriptionUpdater<'_, '_> {
     Some(set.into_iter().collect())
   }
 
-  fn valid_perp_signature_count(
-    &self,
+  pub(crate) fn valid_perp_signature_count(
     sigs: &serde_json::Value,
     signers: &[String],
     msg_hash_hex: &str,
@@ -9658,7 +9985,7 @@ impl InscriptionUpdater<'_, '_> {
       if !signer_set.contains(&declared) {
         return 0;
       }
-      let Some((ok, _, pubkey)) = self.verify_sig_obj_against_msg_with_hash(
+      let Some((ok, _, pubkey)) = Self::verify_sig_obj_against_msg_with_hash(
         entry_obj.get("sig").unwrap_or(&serde_json::Value::Null),
         msg_hash_hex,
         &msg_hash,
@@ -10014,7 +10341,7 @@ impl InscriptionUpdater<'_, '_> {
       .collect()
   }
 
-  fn normalize_perp_price(price: &serde_json::Value) -> Option<serde_json::Value> {
+  pub(crate) fn normalize_perp_price(price: &serde_json::Value) -> Option<serde_json::Value> {
     let p = Self::parse_perp_uint(price.get("p")?, false)?;
     let q = Self::parse_perp_uint(price.get("q")?, false)?;
     let seq = match price.get("seq") {
@@ -10445,7 +10772,7 @@ impl InscriptionUpdater<'_, '_> {
     let msg =
       Self::token_perp_policy_message(action.get("id")?.as_str()?, &seq.to_string(), &payload_hash);
     let msg_hash = Self::certified_control_hash(&msg)?;
-    if self.valid_perp_signature_count(action.get("sigs")?, &signers, &msg_hash) < threshold {
+    if Self::valid_perp_signature_count(action.get("sigs")?, &signers, &msg_hash) < threshold {
       return None;
     }
     if let Some(previous) = self.get_perp_policy(action.get("id")?.as_str()?) {
@@ -10458,7 +10785,7 @@ impl InscriptionUpdater<'_, '_> {
         .collect::<Option<Vec<_>>>()?;
       let previous_threshold = previous.get("threshold")?.as_u64()? as usize;
       if seq <= previous_seq
-        || self.valid_perp_signature_count(action.get("sigs")?, &previous_signers, &msg_hash)
+        || Self::valid_perp_signature_count(action.get("sigs")?, &previous_signers, &msg_hash)
           < previous_threshold
       {
         return None;
@@ -11266,7 +11593,7 @@ impl InscriptionUpdater<'_, '_> {
     Self::compare_perp_ratio(leverage, &min) >= 0 && Self::compare_perp_ratio(leverage, &max) <= 0
   }
 
-  fn perp_group_leverage(group: &serde_json::Value) -> Option<String> {
+  pub(crate) fn perp_group_leverage(group: &serde_json::Value) -> Option<String> {
     group
       .get("leverage")
       .and_then(|v| v.get("value"))
@@ -11275,7 +11602,10 @@ impl InscriptionUpdater<'_, '_> {
       .map(|s| s.to_string())
   }
 
-  fn perp_position_active_state(position: &serde_json::Value, group: &serde_json::Value) -> bool {
+  pub(crate) fn perp_position_active_state(
+    position: &serde_json::Value,
+    group: &serde_json::Value,
+  ) -> bool {
     position.get("state").and_then(|v| v.as_str()) == Some("active")
       || (position.get("state").and_then(|v| v.as_str()) == Some("formation")
         && group.get("state").and_then(|v| v.as_str()) == Some("active"))
@@ -11318,7 +11648,7 @@ impl InscriptionUpdater<'_, '_> {
     true
   }
 
-  fn payable_perp_liquidation_bounty(
+  pub(crate) fn payable_perp_liquidation_bounty(
     _group: &serde_json::Value,
     position: &serde_json::Value,
     _equity: &BigInt,
@@ -11515,7 +11845,7 @@ impl InscriptionUpdater<'_, '_> {
     collateral * &leverage.0 / &leverage.1
   }
 
-  fn compute_perp_open_side_equity(
+  pub(crate) fn compute_perp_open_side_equity(
     group: &serde_json::Value,
     side: &str,
     price: &serde_json::Value,
@@ -12058,7 +12388,7 @@ impl InscriptionUpdater<'_, '_> {
       .map(|v| v.as_str().map(|s| s.to_string()))
       .collect::<Option<Vec<_>>>()?;
     let threshold = policy.get("oracle")?.get("threshold")?.as_u64()? as usize;
-    if self.valid_perp_signature_count(cert.get("sigs")?, &signers, &msg_hash) < threshold {
+    if Self::valid_perp_signature_count(cert.get("sigs")?, &signers, &msg_hash) < threshold {
       return None;
     }
     Some(PerpCertificateValidation {
@@ -12140,7 +12470,7 @@ impl InscriptionUpdater<'_, '_> {
     })
   }
 
-  fn compute_perp_equity(
+  pub(crate) fn compute_perp_equity(
     collateral: &BigInt,
     leverage_text: &str,
     side: &str,
@@ -14165,7 +14495,7 @@ impl InscriptionUpdater<'_, '_> {
       let salt_str = Self::js_value_to_string(salt_val);
       let msg_hash = Self::build_sha256_json_plus_salt(&redeem_norm, &salt_str);
       let Some((ok, compact_sig, pubkey_hex)) =
-        self.verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
+        Self::verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
       else {
         return;
       };
@@ -14307,7 +14637,7 @@ impl InscriptionUpdater<'_, '_> {
         &link.slt,
       );
       let Some((auth_ok, _, auth_pub)) =
-        self.verify_sig_obj_against_msg_with_hash(&link.sig, &link.hash, &auth_msg_hash)
+        Self::verify_sig_obj_against_msg_with_hash(&link.sig, &link.hash, &auth_msg_hash)
       else {
         return;
       };
@@ -14585,7 +14915,7 @@ impl InscriptionUpdater<'_, '_> {
     let msg_hash =
       Self::build_sha256_json_plus_salt(&serde_json::Value::Array(auth_arr.clone()), &salt_str);
     let Some((ok, compact_sig, _pub)) =
-      self.verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
+      Self::verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
     else {
       delete_acc_and_return!();
     };
@@ -14664,7 +14994,7 @@ mod amm_tests {
   use crate::index::{
     HOME_INSCRIPTIONS, INSCRIPTION_ID_TO_SEQUENCE_NUMBER, INSCRIPTION_NUMBER_TO_SEQUENCE_NUMBER,
     SAT_TO_SEQUENCE_NUMBER, SEQUENCE_NUMBER_TO_CHILDREN, SEQUENCE_NUMBER_TO_INSCRIPTION_ENTRY,
-    TAP_KV, TRANSACTION_ID_TO_TRANSACTION,
+    TAP_DERIVED, TAP_KV, TRANSACTION_ID_TO_TRANSACTION,
   };
   use bitcoin::Network as BtcNetwork;
   use redb::Database;
@@ -14703,6 +15033,7 @@ mod amm_tests {
       .open_table(SEQUENCE_NUMBER_TO_INSCRIPTION_ENTRY)
       .unwrap();
     let mut tap_kv = write_tx.open_table(TAP_KV).unwrap();
+    let mut tap_derived = write_tx.open_table(TAP_DERIVED).unwrap();
 
     let mut updater = InscriptionUpdater {
       blessed_inscription_count: 0,
@@ -14724,64 +15055,66 @@ mod amm_tests {
       timestamp: 0,
       unbound_inscriptions: 0,
       tap_db: TapBatch::new(&mut tap_kv),
+      tap_derived_db: TapBatch::new(&mut tap_derived),
       tap_delta_db: None,
       tap_atomic_writes: None,
       tap_atomic_overlay: None,
       tap_atomic_list_len_cache: None,
+      tap_atomic_derived: None,
       tap_route_index: None,
       tap_route_index_verify: false,
       list_len_cache: HashMap::new(),
       block_availability_cache: HashMap::new(),
       profile: false,
-      prof_bm_tr_ms: 0,
+      prof_bm_tr_us: 0,
       prof_bm_tr_ct: 0,
-      prof_dmt_tr_ms: 0,
+      prof_dmt_tr_us: 0,
       prof_dmt_tr_ct: 0,
-      prof_prv_tr_ms: 0,
+      prof_prv_tr_us: 0,
       prof_prv_tr_ct: 0,
-      prof_ttr_ex_ms: 0,
+      prof_ttr_ex_us: 0,
       prof_ttr_ex_ct: 0,
-      prof_tsend_ex_ms: 0,
+      prof_tsend_ex_us: 0,
       prof_tsend_ex_ct: 0,
-      prof_ttrade_ex_ms: 0,
+      prof_ttrade_ex_us: 0,
       prof_ttrade_ex_ct: 0,
-      prof_tauth_ex_ms: 0,
+      prof_tauth_ex_us: 0,
       prof_tauth_ex_ct: 0,
-      prof_pra_ex_ms: 0,
+      prof_pra_ex_us: 0,
       prof_pra_ex_ct: 0,
-      prof_blk_ex_ms: 0,
+      prof_blk_ex_us: 0,
       prof_blk_ex_ct: 0,
-      prof_unblk_ex_ms: 0,
+      prof_unblk_ex_us: 0,
       prof_unblk_ex_ct: 0,
-      prof_created_total_ms: 0,
+      prof_created_total_us: 0,
       prof_created_ct: 0,
-      prof_bm_cr_ms: 0,
+      prof_bm_cr_us: 0,
       prof_bm_cr_ct: 0,
-      prof_dmt_el_cr_ms: 0,
+      prof_dmt_el_cr_us: 0,
       prof_dmt_el_cr_ct: 0,
-      prof_dpl_cr_ms: 0,
+      prof_dpl_cr_us: 0,
       prof_dpl_cr_ct: 0,
-      prof_dmtmint_cr_ms: 0,
+      prof_dmtmint_cr_us: 0,
       prof_dmtmint_cr_ct: 0,
-      prof_mint_cr_ms: 0,
+      prof_mint_cr_us: 0,
       prof_mint_cr_ct: 0,
-      prof_ttr_cr_ms: 0,
+      prof_ttr_cr_us: 0,
       prof_ttr_cr_ct: 0,
-      prof_tsend_cr_ms: 0,
+      prof_tsend_cr_us: 0,
       prof_tsend_cr_ct: 0,
-      prof_ttrade_cr_ms: 0,
+      prof_ttrade_cr_us: 0,
       prof_ttrade_cr_ct: 0,
-      prof_tauth_cr_ms: 0,
+      prof_tauth_cr_us: 0,
       prof_tauth_cr_ct: 0,
-      prof_dmtdep_cr_ms: 0,
+      prof_dmtdep_cr_us: 0,
       prof_dmtdep_cr_ct: 0,
-      prof_pra_cr_ms: 0,
+      prof_pra_cr_us: 0,
       prof_pra_cr_ct: 0,
-      prof_prv_cr_ms: 0,
+      prof_prv_cr_us: 0,
       prof_prv_cr_ct: 0,
-      prof_blk_cr_ms: 0,
+      prof_blk_cr_us: 0,
       prof_blk_cr_ct: 0,
-      prof_unblk_cr_ms: 0,
+      prof_unblk_cr_us: 0,
       prof_unblk_cr_ct: 0,
       prof_core_env_ms: 0,
       prof_core_old_ms: 0,
@@ -14813,6 +15146,10 @@ mod amm_tests {
       delegate_cache: HashMap::new(),
       delegate_payload_cache: HashMap::new(),
       btc_network: network,
+      tap_snapshot_height: None,
+      emit_events: true,
+      tap_amm_candle_intervals: Vec::new(),
+      preview_base: None,
     };
 
     test(&mut updater)
@@ -18360,6 +18697,101 @@ mod amm_tests {
     });
   }
 
+  #[test]
+  fn open_lock_lists_track_claimants_and_refund_heights() {
+    with_test_updater(BtcNetwork::Signet, 10, |updater| {
+      put_deploy(updater, "tap", 0);
+      put_balance(updater, USER_ADDRESS, "tap", "100");
+      let owner = auth_link(USER_ADDRESS, "owner-authi0");
+      let claimant = auth_link(RECEIVER_ADDRESS, "claim-authi0");
+      let hash = InscriptionUpdater::tap_hash_proof_preimage(&json!("secret"));
+      let lock = json!({
+        "op": "lock",
+        "kind": "htlc",
+        "tick": "tap",
+        "amt": "1",
+        "claim": RECEIVER_ADDRESS,
+        "refund": USER_ADDRESS,
+        "condition": { "type": "hashlock", "hash": hash },
+        "refund_after": "20"
+      });
+      for inscription in ["open-lock-ai0", "open-lock-bi0"] {
+        assert!(apply_actions_at(
+          updater,
+          &owner,
+          inscription,
+          vec![lock.clone()],
+          10
+        ));
+      }
+      let list = |updater: &mut InscriptionUpdater, list: &str, address: &str| {
+        let length = updater
+          .tap_derived_get::<String>(&format!("{list}/{address}"))
+          .unwrap()
+          .unwrap()
+          .parse::<u64>()
+          .unwrap();
+        (0..length)
+          .map(|i| {
+            updater
+              .tap_derived_get::<String>(&format!("{list}i/{address}/{i}"))
+              .unwrap()
+              .unwrap()
+          })
+          .collect::<Vec<_>>()
+      };
+      assert_eq!(
+        list(updater, "lcop", RECEIVER_ADDRESS),
+        ["open-lock-ai0:0", "open-lock-bi0:0"]
+      );
+      assert_eq!(
+        list(updater, "lrop", USER_ADDRESS),
+        ["open-lock-ai0:0", "open-lock-bi0:0"]
+      );
+      assert_eq!(
+        list(updater, "lrh", "20"),
+        ["open-lock-ai0:0", "open-lock-bi0:0"]
+      );
+      assert!(updater
+        .tap_get::<String>(&format!("lcop/{RECEIVER_ADDRESS}"))
+        .unwrap()
+        .is_none());
+
+      assert!(apply_actions_at(
+        updater,
+        &claimant,
+        "open-lock-claimi0",
+        vec![json!({ "op": "claim", "lock": "open-lock-ai0:0", "preimage": "secret" })],
+        11
+      ));
+      assert_eq!(list(updater, "lcop", RECEIVER_ADDRESS), ["open-lock-bi0:0"]);
+      assert_eq!(list(updater, "lrop", USER_ADDRESS), ["open-lock-bi0:0"]);
+      assert_eq!(list(updater, "lrh", "20"), ["open-lock-bi0:0"]);
+      assert_eq!(
+        updater
+          .tap_derived_get::<String>("lcops/open-lock-bi0:0")
+          .unwrap()
+          .as_deref(),
+        Some("0")
+      );
+      assert!(updater
+        .tap_derived_get::<String>("lcops/open-lock-ai0:0")
+        .unwrap()
+        .is_none());
+
+      assert!(apply_actions_at(
+        updater,
+        &owner,
+        "open-lock-refundi0",
+        vec![json!({ "op": "refund", "lock": "open-lock-bi0:0" })],
+        20
+      ));
+      assert!(list(updater, "lcop", RECEIVER_ADDRESS).is_empty());
+      assert!(list(updater, "lrop", USER_ADDRESS).is_empty());
+      assert!(list(updater, "lrh", "20").is_empty());
+    });
+  }
+
   #[test]
   fn certified_control_rejects_malformed_policies_and_misplaced_fields() {
     with_test_updater(BtcNetwork::Signet, 10, |updater| {
@@ -20406,6 +20838,129 @@ mod amm_tests {
     });
   }
 
+  #[test]
+  fn sale_allowlist_tree_proofs_pass_contribution_validation() {
+    use crate::index::TapSaleAllowlistEntry;
+
+    with_test_updater(BtcNetwork::Signet, 10, |updater| {
+      put_deploy(updater, "pay", 2);
+      let deployed = updater.token_proof_get_deploy("pay").unwrap().record;
+      let claims = [
+        USER_ADDRESS,
+        RECEIVER_ADDRESS,
+        "claim-3",
+        "claim-4",
+        "claim-5",
+      ];
+
+      let config = |lf: &str, root: &serde_json::Value| {
+        serde_json::from_value::<AuthorityConfigRecord>(json!({
+          "id": "allowlist-sale:0",
+          "k": "sale",
+          "st": "sale",
+          "pt": "pay",
+          "ctl": { "ty": "ta", "auth": "authority-inscription" },
+          "seq": 0,
+          "s": { "alw": { "ty": "sha256-merkle", "lf": lf, "root": root } },
+          "blck": 10,
+          "tx": "sale-tx",
+          "vo": 0,
+          "val": "0",
+          "ins": "allowlist-sale:0",
+          "num": 0,
+          "ts": 1000
+        }))
+        .unwrap()
+      };
+
+      let capped = Index::tap_sale_allowlist(
+        &claims
+          .iter()
+          .zip(["1.5", "2", "0.25", "10", "3"])
+          .map(|(claim, cap)| TapSaleAllowlistEntry {
+            claim: claim.to_string(),
+            cap: Some(cap.into()),
+          })
+          .collect::<Vec<_>>(),
+        2,
+      )
+      .unwrap();
+      assert_eq!(capped.alw["lf"], json!("addr-cap"));
+      let capped_config = config("addr-cap", &capped.alw["root"]);
+      for (proof, cap) in capped.proofs.iter().zip([150, 200, 25, 1000, 300]) {
+        let action = json!({ "alw": proof.alw });
+        assert!(updater.validate_token_sale_allowlist(
+          &capped_config,
+          &action,
+          &proof.claim,
+          cap,
+          &deployed
+        ));
+        assert!(!updater.validate_token_sale_allowlist(
+          &capped_config,
+          &action,
+          &proof.claim,
+          cap + 1,
+          &deployed
+        ));
+      }
+      let swapped = json!({ "alw": capped.proofs[0].alw });
+      assert!(!updater.validate_token_sale_allowlist(
+        &capped_config,
+        &swapped,
+        &capped.proofs[1].claim,
+        1,
+        &deployed
+      ));
+
+      let open = Index::tap_sale_allowlist(
+        &claims
+          .iter()
+          .map(|claim| TapSaleAllowlistEntry {
+            claim: claim.to_string(),
+            cap: None,
+          })
+          .collect::<Vec<_>>(),
+        2,
+      )
+      .unwrap();
+      assert_eq!(open.alw["lf"], json!("addr"));
+      let open_config = config("addr", &open.alw["root"]);
+      for proof in &open.proofs {
+        assert!(proof.alw.get("max").is_none());
+        assert!(updater.validate_token_sale_allowlist(
+          &open_config,
+          &json!({ "alw": proof.alw }),
+          &proof.claim,
+          1_000_000,
+          &deployed
+        ));
+      }
+      assert!(!updater.validate_token_sale_allowlist(
+        &config("addr", &capped.alw["root"]),
+        &json!({ "alw": open.proofs[2].alw }),
+        &open.proofs[2].claim,
+        1,
+        &deployed
+      ));
+
+      assert!(Index::tap_sale_allowlist(
+        &[
+          TapSaleAllowlistEntry {
+            claim: USER_ADDRESS.into(),
+            cap: Some("1".into()),
+          },
+          TapSaleAllowlistEntry {
+            claim: USER_ADDRESS.into(),
+            cap: Some("2".into()),
+          },
+        ],
+        2,
+      )
+      .is_err());
+    });
+  }
+
   #[test]
   fn sale_public_resolution_finalizes_or_unlocks_refunds() {
     with_test_updater(BtcNetwork::Signet, 1, |updater| {
@@ -20837,6 +21392,203 @@ mod amm_tests {
     });
   }
 
+  #[test]
+  fn amm_events_roll_up_into_block_and_interval_candles() {
+    with_test_updater(BtcNetwork::Signet, 1, |updater| {
+      updater.tap_amm_candle_intervals = vec![3600];
+      put_deploy(updater, "tap", 0);
+      put_deploy(updater, "dmt", 0);
+      put_balance(updater, USER_ADDRESS, "tap", "102000");
+      put_balance(updater, USER_ADDRESS, "dmt", "100000");
+      let link = auth_link(USER_ADDRESS, "authority-inscription");
+      let pool_id = "amm-candle:0";
+      let swap = |reference: &str| {
+        json!({
+          "op": "swap",
+          "auth": pool_id,
+          "m": "xin",
+          "i": 0,
+          "amt": "1000",
+          "min": "1",
+          "to": { "tt": "a", "to": USER_ADDRESS },
+          "exp": "20",
+          "ref": reference
+        })
+      };
+
+      assert!(apply_actions(
+        updater,
+        &link,
+        "amm-candle",
+        vec![
+          amm_config(&link, "100", "5000"),
+          json!({
+            "op": "add-liq",
+            "auth": pool_id,
+            "amts": ["100000", "100000"],
+            "min": "99000",
+            "to": { "tt": "a", "to": USER_ADDRESS },
+            "exp": "20",
+            "ref": "init"
+          }),
+          swap("swap-1"),
+        ],
+      ));
+      assert!(apply_actions_at(
+        updater,
+        &link,
+        "amm-candle-2",
+        vec![swap("swap-2")],
+        11,
+      ));
+
+      let candle = |updater: &mut InscriptionUpdater<'_, '_>, interval: &str, i: usize| {
+        updater
+          .tap_derived_get::<serde_json::Value>(&format!("ammci/{pool_id}/{interval}/{i}"))
+          .unwrap()
+          .unwrap()
+      };
+      let length = |updater: &mut InscriptionUpdater<'_, '_>, interval: &str| {
+        updater
+          .tap_derived_get::<String>(&format!("ammc/{pool_id}/{interval}"))
+          .unwrap()
+      };
+
+      assert_eq!(get_string(updater, &format!("ammc/{pool_id}/blk")), None);
+      assert_eq!(length(updater, "blk").as_deref(), Some("2"));
+      assert_eq!(
+        candle(updater, "blk", 0),
+        json!({
+          "t": 10,
+          "o": "1.000000000000000000",
+          "h": "1.000000000000000000",
+          "l": "0.980444576464181395",
+          "c": "0.980444576464181395",
+          "r": ["100995", "99020"],
+          "v": ["1000", "980"],
+          "f": ["10", "0"],
+          "pf": ["5", "0"],
+          "n": 2,
+          "fb": 10,
+          "lb": 10
+        })
+      );
+      assert_eq!(
+        candle(updater, "blk", 1),
+        json!({
+          "t": 11,
+          "o": "0.980444576464181395",
+          "h": "0.980444576464181395",
+          "l": "0.961457005588783214",
+          "c": "0.961457005588783214",
+          "r": ["101990", "98059"],
+          "v": ["1000", "961"],
+          "f": ["10", "0"],
+          "pf": ["5", "0"],
+          "n": 1,
+          "fb": 11,
+          "lb": 11
+        })
+      );
+
+      assert_eq!(length(updater, "3600").as_deref(), Some("1"));
+      let hourly = candle(updater, "3600", 0);
+      assert_eq!(hourly["t"], json!(0));
+      assert_eq!(hourly["o"], json!("1.000000000000000000"));
+      assert_eq!(hourly["c"], json!("0.961457005588783214"));
+      assert_eq!(hourly["v"], json!(["2000", "1941"]));
+      assert_eq!(hourly["pf"], json!(["10", "0"]));
+      assert_eq!(hourly["n"], json!(3));
+      assert_eq!(
+        (hourly["fb"].clone(), hourly["lb"].clone()),
+        (json!(10), json!(11))
+      );
+    });
+  }
+
+  #[test]
+  fn amm_position_basis_tracks_deposits_and_entry_liquidity() {
+    with_test_updater(BtcNetwork::Signet, 1, |updater| {
+      put_deploy(updater, "tap", 0);
+      put_deploy(updater, "dmt", 0);
+      put_balance(updater, USER_ADDRESS, "tap", "101000");
+      put_balance(updater, USER_ADDRESS, "dmt", "100000");
+      let link = auth_link(USER_ADDRESS, "authority-inscription");
+      let pool_id = "amm-basis:0";
+      let basis_key = format!("ammpb/{pool_id}/a/{USER_ADDRESS}");
+
+      assert!(apply_actions(
+        updater,
+        &link,
+        "amm-basis",
+        vec![
+          amm_config(&link, "100", "5000"),
+          json!({
+            "op": "add-liq",
+            "auth": pool_id,
+            "amts": ["100000", "100000"],
+            "min": "99000",
+            "to": { "tt": "a", "to": USER_ADDRESS },
+            "exp": "20",
+            "ref": "init"
+          }),
+        ],
+      ));
+      let basis = updater
+        .tap_derived_get::<serde_json::Value>(&basis_key)
+        .unwrap()
+        .unwrap();
+      assert_eq!(basis["sh"], json!("99000"));
+      assert_eq!(basis["dep"], json!(["100000", "100000"]));
+      assert_eq!(basis["liq"], json!("99000000000000000000000"));
+      assert_eq!(basis["r"], json!(["100000", "100000"]));
+      assert_eq!(basis["blck"], json!(10));
+      assert_eq!(
+        updater.tap_get::<serde_json::Value>(&basis_key).unwrap(),
+        None
+      );
+
+      assert!(apply_actions_at(
+        updater,
+        &link,
+        "amm-basis-2",
+        vec![
+          json!({
+            "op": "swap",
+            "auth": pool_id,
+            "m": "xin",
+            "i": 0,
+            "amt": "1000",
+            "min": "1",
+            "to": { "tt": "a", "to": USER_ADDRESS },
+            "exp": "20",
+            "ref": "swap"
+          }),
+          json!({
+            "op": "rm-liq",
+            "auth": pool_id,
+            "sh": "49500",
+            "min": ["1", "1"],
+            "to": { "tt": "a", "to": USER_ADDRESS },
+            "exp": "20",
+            "ref": "remove"
+          }),
+        ],
+        11,
+      ));
+      let basis = updater
+        .tap_derived_get::<serde_json::Value>(&basis_key)
+        .unwrap()
+        .unwrap();
+      assert_eq!(basis["sh"], json!("49500"));
+      assert_eq!(basis["dep"], json!(["50000", "50000"]));
+      assert_eq!(basis["liq"], json!("49500000000000000000000"));
+      assert_eq!(basis["r"], json!(["51003", "50006"]));
+      assert_eq!(basis["tsh"], json!("50500"));
+      assert_eq!(basis["blck"], json!(11));
+    });
+  }
+
   #[test]
   fn amm_protocol_fee_routes_atomically_and_external_policy_validates() {
     with_test_updater(BtcNetwork::Signet, 1, |updater| {
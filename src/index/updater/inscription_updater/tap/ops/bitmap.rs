@@ -133,11 +133,9 @@ impl InscriptionUpdater<'_, '_> {
       (block_num, prev.ownr)
     };
 
-    let entry_val = match self.sequence_number_to_entry.get(&sequence_number) {
-      Ok(Some(v)) => v.value(),
-      _ => return,
+    let Ok(Some(entry)) = self.inscription_entry(sequence_number) else {
+      return;
     };
-    let entry = InscriptionEntry::load(entry_val);
     if entry.inscription_number < 0 {
       return;
     }
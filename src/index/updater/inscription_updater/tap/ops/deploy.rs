@@ -1,5 +1,15 @@
 use super::super::super::*;
 
+/// A token-deploy (or brc-20 deploy) body that passed every check.
+pub(crate) struct TapDeploy {
+  pub(crate) tick: String,
+  pub(crate) max: String,
+  pub(crate) lim: u128,
+  pub(crate) dec: u32,
+  pub(crate) prv: Option<String>,
+  pub(crate) dta: Option<String>,
+}
+
 impl InscriptionUpdater<'_, '_> {
   pub(crate) fn index_deployments(
     &mut self,
@@ -24,59 +34,129 @@ impl InscriptionUpdater<'_, '_> {
       None => return,
     };
 
+    let Ok(deploy) = self.tap_check_deploy(&json_val, inscription_number) else {
+      return;
+    };
+
+    let tick_key = Self::js_json_stringify_str(&deploy.tick);
+    let d_key = format!("d/{}", tick_key);
+
+    let record = DeployRecord {
+      tick: deploy.tick,
+      max: deploy.max,
+      lim: deploy.lim.to_string(),
+      dec: deploy.dec,
+      blck: self.height,
+      tx: satpoint.outpoint.txid.to_string(),
+      vo: satpoint.outpoint.vout,
+      val: output_value_sat.to_string(),
+      ins: inscription_id.to_string(),
+      num: inscription_number,
+      ts: self.timestamp,
+      addr: owner_address.to_string(),
+      crsd: inscription_number < 0,
+      dmt: false,
+      elem: None,
+      prj: None,
+      dim: None,
+      dt: None,
+      prv: deploy.prv,
+      dta: deploy.dta,
+    };
+
+    let _ = self.tap_put(&d_key, &record);
+    let _ = self.tap_put(&format!("dc/{}", tick_key), &record.max);
+    if let Ok(list_len) = self.tap_set_list_record("dl", "dli", &record.tick) {
+      let ptr = format!("dli/{}", list_len - 1);
+      let tx = satpoint.outpoint.txid.to_string();
+      let _ = self.tap_set_list_record(&format!("tx/dpl/{}", tx), &format!("txi/dpl/{}", tx), &ptr);
+      let _ = self.tap_set_list_record(
+        &format!("txt/dpl/{}/{}", tick_key, tx),
+        &format!("txti/dpl/{}/{}", tick_key, tx),
+        &ptr,
+      );
+      let _ = self.tap_set_list_record(
+        &format!("blck/dpl/{}", self.height),
+        &format!("blcki/dpl/{}", self.height),
+        &ptr,
+      );
+      let _ = self.tap_set_list_record(
+        &format!("blckt/dpl/{}/{}", tick_key, self.height),
+        &format!("blckti/dpl/{}/{}", tick_key, self.height),
+        &ptr,
+      );
+    }
+  }
+
+  /// Applies tap-writer's deploy rules to a parsed body, in tap-writer's
+  /// order, without writing anything.
+  pub(crate) fn tap_check_deploy(
+    &mut self,
+    json_val: &serde_json::Value,
+    inscription_number: i32,
+  ) -> Result<TapDeploy, TapRejection> {
     let tick_val = json_val.get("tick");
     let max_val = json_val.get("max");
     let p_val = json_val.get("p");
     let op_val = json_val.get("op");
-    if tick_val.is_none() || max_val.is_none() || p_val.is_none() || op_val.is_none() {
-      return;
+    for (field, val) in [
+      ("tick", tick_val),
+      ("max", max_val),
+      ("p", p_val),
+      ("op", op_val),
+    ] {
+      if val.is_none() {
+        return Err(TapRejection::MissingField(field));
+      }
     }
 
     let p = p_val.unwrap().as_str().unwrap_or("").to_lowercase();
     let op = op_val.unwrap().as_str().unwrap_or("").to_lowercase();
     let tick = tick_val.unwrap().as_str().unwrap_or("").to_string();
     if tick.is_empty() {
-      return;
+      return Err(TapRejection::MissingField("tick"));
     }
 
     let is_tap_deploy = p == "tap" && op == "token-deploy";
     let is_brc20_deploy = p == "brc-20" && op == "deploy";
     if !(is_tap_deploy || is_brc20_deploy) {
-      return;
+      return Err(TapRejection::UnsupportedOperation);
     }
 
     let tick_lower = Self::js_to_lowercase(&tick);
     if tick_lower.starts_with('-') || tick_lower.starts_with("dmt-") {
-      return;
+      return Err(TapRejection::ReservedTicker);
     }
 
     if is_tap_deploy && !self.tap_feature_enabled(TapFeature::TapStart) {
-      return;
+      return Err(TapRejection::NotActive(
+        self.feature_height(TapFeature::TapStart),
+      ));
     }
 
     let vis_len = Self::visible_length(&tick);
-    if is_tap_deploy {
-      if !Self::valid_tap_ticker_visible_len(
-        self.feature_height(TapFeature::FullTicker),
-        self.height,
-        vis_len,
-      ) {
-        return;
-      }
-    } else if is_brc20_deploy {
-      if !Self::valid_brc20_ticker_visible_len(
-        self.feature_height(TapFeature::FullTicker),
-        self.height,
-        vis_len,
-      ) {
-        return;
-      }
+    let full_height = self.feature_height(TapFeature::FullTicker);
+    let valid_len = if is_tap_deploy {
+      Self::valid_tap_ticker_visible_len(full_height, self.height, vis_len)
+    } else {
+      Self::valid_brc20_ticker_visible_len(full_height, self.height, vis_len)
+    };
+    if !valid_len {
+      return Err(TapRejection::TickerLength {
+        visible: vis_len,
+        expected: match (is_tap_deploy, self.height < full_height) {
+          (true, true) => "3 or 5 to 32",
+          (true, false) => "1 to 32",
+          (false, true) => "1, 2 or 4",
+          (false, false) => "none for brc-20 deploys",
+        },
+      });
     }
 
     let mut effective_tick = tick_lower.clone();
     if inscription_number < 0 {
       if self.tap_feature_enabled(TapFeature::Jubilee) {
-        return;
+        return Err(TapRejection::Cursed);
       } else {
         effective_tick = format!("-{}", effective_tick);
       }
@@ -85,8 +165,8 @@ impl InscriptionUpdater<'_, '_> {
     let mut ins_data: Option<String> = None;
     if let Some(dta_val) = json_val.get("dta") {
       if let Some(dta_str) = dta_val.as_str() {
-        if dta_str.as_bytes().len() > 512 {
-          return;
+        if dta_str.len() > 512 {
+          return Err(TapRejection::DataTooLong);
         }
         ins_data = Some(dta_str.to_string());
       }
@@ -98,50 +178,33 @@ impl InscriptionUpdater<'_, '_> {
         if parsed >= 0 && parsed < 18 {
           let dec_str = Self::js_value_to_string(dec_val);
           if !Self::is_valid_number(&dec_str) {
-            return;
+            return Err(TapRejection::InvalidDecimals);
           }
           decimals = parsed as u32;
         }
       }
     }
 
-    let max_str_input = Self::js_value_to_string(max_val.unwrap());
-    let max_s = match Self::resolve_number_string(&max_str_input, decimals) {
-      Some(x) => x,
-      None => return,
-    };
-    let max = match max_s.parse::<u128>() {
-      Ok(v) => v,
-      Err(_) => return,
-    };
-    if max == 0 {
-      return;
-    }
     let cap_s = Self::resolve_number_string(MAX_DEC_U64_STR, decimals).unwrap();
     let cap = cap_s.parse::<u128>().unwrap_or(u128::MAX);
-    if max > cap {
-      return;
-    }
-
-    let mut limit: u128 = 0;
-    if let Some(lim_val) = json_val.get("lim") {
-      let lim_str_input = Self::js_value_to_string(lim_val);
-      let lim_s = match Self::resolve_number_string(&lim_str_input, decimals) {
-        Some(x) => x,
-        None => return,
-      };
-      let lim = match lim_s.parse::<u128>() {
-        Ok(v) => v,
-        Err(_) => return,
-      };
-      if lim == 0 {
-        return;
+    let amount = |field: &'static str, val: &serde_json::Value| {
+      let amount = Self::resolve_number_string(&Self::js_value_to_string(val), decimals)
+        .and_then(|s| s.parse::<u128>().ok())
+        .ok_or(TapRejection::InvalidAmount { field, decimals })?;
+      if amount == 0 {
+        return Err(TapRejection::ZeroAmount(field));
       }
-      if lim > cap {
-        return;
+      if amount > cap {
+        return Err(TapRejection::AmountOverCap(field));
       }
-      limit = lim;
-    }
+      Ok(amount)
+    };
+
+    let max = amount("max", max_val.unwrap())?;
+    let limit = match json_val.get("lim") {
+      Some(lim_val) => amount("lim", lim_val)?,
+      None => 0,
+    };
 
     let mut privilege_auth: Option<String> = None;
     if is_brc20_deploy {
@@ -155,70 +218,32 @@ impl InscriptionUpdater<'_, '_> {
           let exists_prains = self.tap_get::<String>(&prains_key).ok().flatten().is_some();
           let exists_prac = self.tap_get::<String>(&prac_key).ok().flatten().is_some();
           if !exists_prains || exists_prac {
-            return;
+            return Err(TapRejection::PrivilegeAuthorityInactive);
           }
         }
         privilege_auth = Some(prv_str.to_string());
       } else {
-        return;
+        return Err(TapRejection::PrivilegeAuthorityNotString);
       }
     }
 
     let tick_key = Self::js_json_stringify_str(&effective_tick);
-    let d_key = format!("d/{}", tick_key);
     if self
-      .tap_get::<DeployRecord>(&d_key)
+      .tap_get::<DeployRecord>(&format!("d/{}", tick_key))
       .ok()
       .flatten()
       .is_some()
     {
-      return;
+      return Err(TapRejection::AlreadyDeployed);
     }
 
-    let record = DeployRecord {
-      tick: effective_tick.clone(),
-      max: max_s,
-      lim: limit.to_string(),
+    Ok(TapDeploy {
+      tick: effective_tick,
+      max: max.to_string(),
+      lim: limit,
       dec: decimals,
-      blck: self.height,
-      tx: satpoint.outpoint.txid.to_string(),
-      vo: u32::from(satpoint.outpoint.vout),
-      val: output_value_sat.to_string(),
-      ins: inscription_id.to_string(),
-      num: inscription_number,
-      ts: self.timestamp,
-      addr: owner_address.to_string(),
-      crsd: inscription_number < 0,
-      dmt: false,
-      elem: None,
-      prj: None,
-      dim: None,
-      dt: None,
       prv: privilege_auth,
       dta: ins_data,
-    };
-
-    let _ = self.tap_put(&d_key, &record);
-    let _ = self.tap_put(&format!("dc/{}", tick_key), &record.max);
-    if let Ok(list_len) = self.tap_set_list_record("dl", "dli", &record.tick) {
-      let ptr = format!("dli/{}", list_len - 1);
-      let tx = satpoint.outpoint.txid.to_string();
-      let _ = self.tap_set_list_record(&format!("tx/dpl/{}", tx), &format!("txi/dpl/{}", tx), &ptr);
-      let _ = self.tap_set_list_record(
-        &format!("txt/dpl/{}/{}", tick_key, tx),
-        &format!("txti/dpl/{}/{}", tick_key, tx),
-        &ptr,
-      );
-      let _ = self.tap_set_list_record(
-        &format!("blck/dpl/{}", self.height),
-        &format!("blcki/dpl/{}", self.height),
-        &ptr,
-      );
-      let _ = self.tap_set_list_record(
-        &format!("blckt/dpl/{}/{}", tick_key, self.height),
-        &format!("blckti/dpl/{}/{}", tick_key, self.height),
-        &ptr,
-      );
-    }
+    })
   }
 }
@@ -1,5 +1,18 @@
 use super::super::super::*;
 
+/// A token-mint body that passed the checks which make the indexer ignore
+/// it. `fail` is set when the mint is still recorded, but as failed.
+pub(crate) struct TapMint {
+  pub(crate) tick: String,
+  pub(crate) tick_key: String,
+  pub(crate) amount: u128,
+  pub(crate) clamped: bool,
+  pub(crate) tokens_left: u128,
+  pub(crate) dta: Option<String>,
+  pub(crate) fail: Option<TapRejection>,
+  pub(crate) used_signature: Option<String>,
+}
+
 impl InscriptionUpdater<'_, '_> {
   pub(crate) fn index_mints(
     &mut self,
@@ -25,157 +38,16 @@ impl InscriptionUpdater<'_, '_> {
       None => return,
     };
 
-    // Entry guard
-    let p = json_val
-      .get("p")
-      .and_then(|v| v.as_str())
-      .unwrap_or("")
-      .to_lowercase();
-    let op = json_val
-      .get("op")
-      .and_then(|v| v.as_str())
-      .unwrap_or("")
-      .to_lowercase();
-    let tick = json_val
-      .get("tick")
-      .and_then(|v| v.as_str())
-      .unwrap_or("")
-      .to_string();
-    let amt_raw = json_val.get("amt").cloned();
-    if p != "tap" || op != "token-mint" || tick.is_empty() || amt_raw.is_none() {
-      return;
-    }
-
-    let tick_lower = Self::js_to_lowercase(&tick);
-    if tick_lower.starts_with('-') || tick_lower.starts_with("dmt-") {
-      return;
-    }
-
-    // visible length guards
-    let vis_len = Self::visible_length(&tick);
-    if !Self::valid_tap_ticker_visible_len(
-      self.feature_height(TapFeature::FullTicker),
-      self.height,
-      vis_len,
-    ) {
+    let Ok(mint) = self.tap_check_mint(&json_val, inscription_number, owner_address) else {
       return;
-    }
-
-    // jubilee (negative numbers)
-    let mut effective_tick = tick_lower.clone();
-    let tmp_tick = tick.clone();
-    if inscription_number < 0 {
-      if self.tap_feature_enabled(TapFeature::Jubilee) {
-        return;
-      } else {
-        effective_tick = format!("-{}", effective_tick);
-      }
-    }
-
-    // optional dta
-    let mut ins_data: Option<String> = None;
-    if let Some(dta_val) = json_val.get("dta") {
-      if let Some(s) = dta_val.as_str() {
-        if s.as_bytes().len() > 512 {
-          return;
-        }
-        ins_data = Some(s.to_string());
-      }
-    }
-
-    // Resolve deployment
-    let tick_key = Self::js_json_stringify_str(&effective_tick);
-    let d_key = format!("d/{}", tick_key);
-    let deployed = match self.tap_get::<DeployRecord>(&d_key).ok().flatten() {
-      Some(d) => d,
-      None => return,
     };
-    let mut tokens_left: u128 = match self
-      .tap_get::<String>(&format!("dc/{}", tick_key))
-      .ok()
-      .flatten()
-      .and_then(|s| s.parse::<u128>().ok())
-    {
-      Some(v) => v,
-      None => return,
-    };
-
-    // Parse amount
-    let decimals = deployed.dec;
-    let amt_str_input = if let Some(a) = &amt_raw {
-      Self::js_value_to_string(a)
-    } else {
-      return;
-    };
-    let amt_norm = match Self::resolve_number_string(&amt_str_input, decimals) {
-      Some(x) => x,
-      None => return,
-    };
-    let mut amount: u128 = match amt_norm.parse::<u128>() {
-      Ok(v) => v,
-      Err(_) => return,
-    };
-
-    let mut fail = false;
-    let limit: u128 = deployed.lim.parse::<u128>().unwrap_or(0);
-    if limit > 0 && amount > limit {
-      fail = true;
-    }
-    if !fail {
-      if tokens_left < amount {
-        amount = tokens_left;
-      }
-      if amount == 0 {
-        fail = true;
-      }
-    }
-
-    // Privilege check if required by deployment
-    let mut used_compact_sig: Option<String> = None;
-    if !fail {
-      if let Some(prv_dep) = &deployed.prv {
-        match Self::legacy_privilege_signature_gate(json_val.get("prv")) {
-          None => return,
-          Some(false) => {
-            fail = true;
-          }
-          Some(true) => {
-            let Some(prv_obj) = json_val.get("prv") else {
-              return;
-            };
-            let prv_salt = prv_obj
-              .get("salt")
-              .map(Self::js_value_to_string)
-              .unwrap_or_default();
-            // Parity: use json.prv.address for message building (not owner_address)
-            let prv_addr_for_msg = prv_obj
-              .get("address")
-              .and_then(|v| v.as_str())
-              .unwrap_or("");
-            let msg_hash = Self::build_mint_privilege_message_hash(
-              &p,
-              &op,
-              &tmp_tick,
-              &amt_str_input,
-              prv_addr_for_msg,
-              ins_data.as_deref(),
-              &prv_salt,
-            );
-            if let Some((ok, comp_hex)) =
-              self.verify_privilege_signature_with_msg(prv_dep, prv_obj, &msg_hash, owner_address)
-            {
-              if !ok {
-                fail = true;
-              } else {
-                used_compact_sig = Some(comp_hex);
-              }
-            } else {
-              return;
-            }
-          }
-        }
-      }
-    }
+    let effective_tick = mint.tick;
+    let tick_key = mint.tick_key;
+    let ins_data = mint.dta;
+    let amount = mint.amount;
+    let mut tokens_left = mint.tokens_left;
+    let fail = mint.fail.is_some();
+    let used_compact_sig = mint.used_signature;
 
     // Balance update
     let bal_key = format!("b/{}/{}", owner_address, tick_key);
@@ -322,4 +194,184 @@ impl InscriptionUpdater<'_, '_> {
       let _ = self.tap_put(&format!("prah/{}", comp), &"".to_string());
     }
   }
+
+  /// Applies tap-writer's mint rules to a parsed body, in tap-writer's
+  /// order, without writing anything. `owner_address` receives the mint and
+  /// must match a privileged mint's `prv.address`.
+  pub(crate) fn tap_check_mint(
+    &mut self,
+    json_val: &serde_json::Value,
+    inscription_number: i32,
+    owner_address: &str,
+  ) -> Result<TapMint, TapRejection> {
+    // Entry guard
+    let p = json_val
+      .get("p")
+      .and_then(|v| v.as_str())
+      .unwrap_or("")
+      .to_lowercase();
+    let op = json_val
+      .get("op")
+      .and_then(|v| v.as_str())
+      .unwrap_or("")
+      .to_lowercase();
+    let tick = json_val
+      .get("tick")
+      .and_then(|v| v.as_str())
+      .unwrap_or("")
+      .to_string();
+    let amt_raw = json_val.get("amt");
+    if p != "tap" || op != "token-mint" {
+      return Err(TapRejection::UnsupportedOperation);
+    }
+    if tick.is_empty() {
+      return Err(TapRejection::MissingField("tick"));
+    }
+    let Some(amt_raw) = amt_raw else {
+      return Err(TapRejection::MissingField("amt"));
+    };
+
+    let tick_lower = Self::js_to_lowercase(&tick);
+    if tick_lower.starts_with('-') || tick_lower.starts_with("dmt-") {
+      return Err(TapRejection::ReservedTicker);
+    }
+
+    // visible length guards
+    let vis_len = Self::visible_length(&tick);
+    let full_height = self.feature_height(TapFeature::FullTicker);
+    if !Self::valid_tap_ticker_visible_len(full_height, self.height, vis_len) {
+      return Err(TapRejection::TickerLength {
+        visible: vis_len,
+        expected: if self.height < full_height {
+          "3 or 5 to 32"
+        } else {
+          "1 to 32"
+        },
+      });
+    }
+
+    // jubilee (negative numbers)
+    let mut effective_tick = tick_lower.clone();
+    let tmp_tick = tick.clone();
+    if inscription_number < 0 {
+      if self.tap_feature_enabled(TapFeature::Jubilee) {
+        return Err(TapRejection::Cursed);
+      } else {
+        effective_tick = format!("-{}", effective_tick);
+      }
+    }
+
+    // optional dta
+    let mut ins_data: Option<String> = None;
+    if let Some(dta_val) = json_val.get("dta") {
+      if let Some(s) = dta_val.as_str() {
+        if s.len() > 512 {
+          return Err(TapRejection::DataTooLong);
+        }
+        ins_data = Some(s.to_string());
+      }
+    }
+
+    // Resolve deployment
+    let tick_key = Self::js_json_stringify_str(&effective_tick);
+    let d_key = format!("d/{}", tick_key);
+    let deployed = self
+      .tap_get::<DeployRecord>(&d_key)
+      .ok()
+      .flatten()
+      .ok_or(TapRejection::NotDeployed)?;
+    let tokens_left: u128 = self
+      .tap_get::<String>(&format!("dc/{}", tick_key))
+      .ok()
+      .flatten()
+      .and_then(|s| s.parse::<u128>().ok())
+      .ok_or(TapRejection::NotDeployed)?;
+
+    // Parse amount
+    let decimals = deployed.dec;
+    let amt_str_input = Self::js_value_to_string(amt_raw);
+    let mut amount: u128 = Self::resolve_number_string(&amt_str_input, decimals)
+      .and_then(|s| s.parse::<u128>().ok())
+      .ok_or(TapRejection::InvalidAmount {
+        field: "amt",
+        decimals,
+      })?;
+
+    let mut fail = None;
+    let mut clamped = false;
+    let limit: u128 = deployed.lim.parse::<u128>().unwrap_or(0);
+    if limit > 0 && amount > limit {
+      fail = Some(TapRejection::OverMintLimit(limit));
+    }
+    if fail.is_none() {
+      if tokens_left < amount {
+        amount = tokens_left;
+        clamped = true;
+      }
+      if amount == 0 {
+        fail = Some(if tokens_left == 0 {
+          TapRejection::MintedOut
+        } else {
+          TapRejection::ZeroAmount("amt")
+        });
+      }
+    }
+
+    // Privilege check if required by deployment
+    let mut used_signature: Option<String> = None;
+    if fail.is_none() {
+      if let Some(prv_dep) = &deployed.prv {
+        match Self::legacy_privilege_signature_gate(json_val.get("prv")) {
+          None => return Err(TapRejection::PrivilegeSignatureMissing),
+          Some(false) => {
+            fail = Some(TapRejection::PrivilegeSignatureIncomplete);
+          }
+          Some(true) => {
+            let Some(prv_obj) = json_val.get("prv") else {
+              return Err(TapRejection::PrivilegeSignatureMissing);
+            };
+            let prv_salt = prv_obj
+              .get("salt")
+              .map(Self::js_value_to_string)
+              .unwrap_or_default();
+            // Parity: use json.prv.address for message building (not owner_address)
+            let prv_addr_for_msg = prv_obj
+              .get("address")
+              .and_then(|v| v.as_str())
+              .unwrap_or("");
+            let msg_hash = Self::build_mint_privilege_message_hash(
+              &p,
+              &op,
+              &tmp_tick,
+              &amt_str_input,
+              prv_addr_for_msg,
+              ins_data.as_deref(),
+              &prv_salt,
+            );
+            match self.verify_privilege_signature_with_msg(
+              prv_dep,
+              prv_obj,
+              &msg_hash,
+              owner_address,
+            ) {
+              Some((true, comp_hex)) => used_signature = Some(comp_hex),
+              Some((false, _)) => fail = Some(TapRejection::PrivilegeSignatureInvalid),
+              None => return Err(TapRejection::PrivilegeSignatureMalformed),
+            }
+          }
+        }
+      }
+    }
+
+    Ok(TapMint {
+      tick: effective_tick,
+      tick_key,
+      amount,
+      clamped,
+      tokens_left,
+      dta: ins_data,
+      fail,
+      used_signature,
+    })
+  }
 }
@@ -220,7 +220,7 @@ impl InscriptionUpdater<'_, '_> {
     let salt_str = Self::js_value_to_string(salt_val);
     let msg_hash = Self::build_sha256_json_plus_salt(auth_obj, &salt_str);
     let Some((ok, compact_sig, _pubkey_hex)) =
-      self.verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
+      Self::verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
     else {
       delete_acc_and_return!();
     };
@@ -390,7 +390,7 @@ impl InscriptionUpdater<'_, '_> {
     let msg_hash =
       Self::build_sha256_privilege_verify(prv, &col_norm, verify, &seq_str, addr_field, &salt);
     let Some((is_valid, compact_sig, pubkey_hex)) =
-      self.verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
+      Self::verify_sig_obj_against_msg_with_hash(sig_obj, hash_str, &msg_hash)
     else {
       return;
     };
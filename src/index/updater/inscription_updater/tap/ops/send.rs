@@ -63,26 +63,15 @@ impl InscriptionUpdater<'_, '_> {
         return;
       }
 
-      let tick_for_len = Self::strip_prefix_for_len_check(&tick);
-      let vis_len = Self::visible_length(tick_for_len);
-      if !Self::valid_tap_ticker_visible_len(
-        self.feature_height(TapFeature::FullTicker),
+      let Ok(addr_norm) = Self::token_send_item_check(
+        self.btc_network,
         self.height,
-        vis_len,
-      ) {
+        &tick,
+        addr_raw,
+        it.get("dta").and_then(|v| v.as_str()),
+      ) else {
         return;
-      }
-
-      let addr_norm = Self::normalize_address(addr_raw);
-      if !self.is_valid_bitcoin_address(&addr_norm) {
-        return;
-      }
-
-      if let Some(d) = it.get("dta").and_then(|v| v.as_str()) {
-        if d.as_bytes().len() > 512 {
-          return;
-        }
-      }
+      };
 
       if let Some(addr_field) = it.get_mut("address") {
         *addr_field = serde_json::Value::String(addr_norm);
@@ -127,6 +116,37 @@ impl InscriptionUpdater<'_, '_> {
     }
   }
 
+  /// Checks a token-send item must pass at `height` for the indexer to accept
+  /// the inscription, returning the item's normalized address. Any failing
+  /// item voids the whole token-send.
+  pub(crate) fn token_send_item_check(
+    btc_network: bitcoin::Network,
+    height: u32,
+    tick: &str,
+    address: &str,
+    dta: Option<&str>,
+  ) -> Result<String, String> {
+    let tick_for_len = Self::strip_prefix_for_len_check(tick);
+    if !Self::valid_tap_ticker_visible_len(
+      Self::network_feature_height(btc_network, TapFeature::FullTicker),
+      height,
+      Self::visible_length(tick_for_len),
+    ) {
+      return Err(format!("ticker `{tick}` has an invalid length"));
+    }
+
+    let address = Self::normalize_address(address);
+    if !Self::is_valid_bitcoin_address_at(btc_network, height, &address) {
+      return Err(format!("`{address}` is not a valid TAP address"));
+    }
+
+    if dta.is_some_and(|dta| dta.len() > 512) {
+      return Err("dta is longer than 512 bytes".into());
+    }
+
+    Ok(address)
+  }
+
   pub(crate) fn index_token_send_executed(
     &mut self,
     inscription_id: InscriptionId,
@@ -240,6 +240,7 @@ impl InscriptionUpdater<'_, '_> {
           {
             if lock.addr == acc.addr {
               let _ = self.tap_del(&format!("tol/{}", trade_id_trimmed));
+              self.trade_book_remove(trade_id_trimmed);
             }
           }
         }
@@ -478,6 +479,7 @@ impl InscriptionUpdater<'_, '_> {
             &format!("tori/{}", owner_address),
             &trade_id,
           );
+          self.trade_book_insert(&trade_id, &offer_tick_key, &atick_key, &ptr, vld);
         }
       }
       let _ = self.tap_del(&key);
@@ -1332,9 +1334,136 @@ impl InscriptionUpdater<'_, '_> {
 
       // Clear lock and accumulator
       let _ = self.tap_del(&format!("tol/{}", trade_id_trimmed));
+      self.trade_book_remove(trade_id_trimmed);
       let _ = self.tap_del(&key);
     } else {
       let _ = self.tap_del(&key);
     }
   }
+
+  // Live offers are kept per ticker pair in `tob/{tick}/{atick}`, an unordered
+  // list whose removals move the last entry into the freed slot. `tobs/` holds
+  // each entry's slot, `tobt/` the pairs an offer is listed under, and
+  // `tobe/{vld}` the offers to drop once their `valid` height has passed. The
+  // book is a derived view in TAP_DERIVED; fills and cancels check `tol/`.
+  fn trade_book_insert(
+    &mut self,
+    trade_id: &str,
+    tick_key: &str,
+    atick_key: &str,
+    ptr: &str,
+    vld: i64,
+  ) {
+    let pair = format!("{}/{}", tick_key, atick_key);
+    let length = self.trade_book_length(&pair);
+    let _ = self.tap_derived_put(
+      &format!("tobi/{}/{}", pair, length),
+      &TradeBookEntryRecord {
+        id: trade_id.to_string(),
+        ptr: ptr.to_string(),
+      },
+    );
+    let _ = self.tap_derived_put(&format!("tob/{}", pair), &(length + 1).to_string());
+    let _ = self.tap_derived_put(
+      &format!("tobs/{}/{}", trade_id, atick_key),
+      &length.to_string(),
+    );
+
+    let offer_key = format!("tobt/{}", trade_id);
+    let offer = match self
+      .tap_derived_get::<TradeBookOfferRecord>(&offer_key)
+      .ok()
+      .flatten()
+    {
+      Some(mut offer) => {
+        offer.accept.push(atick_key.to_string());
+        offer
+      }
+      None => {
+        if let Ok(expiry) = u32::try_from(vld) {
+          let _ = self.tap_derived_set_list_record(
+            &format!("tobe/{}", expiry),
+            &format!("tobei/{}", expiry),
+            &trade_id.to_string(),
+          );
+        }
+        TradeBookOfferRecord {
+          tick: tick_key.to_string(),
+          accept: vec![atick_key.to_string()],
+        }
+      }
+    };
+    let _ = self.tap_derived_put(&offer_key, &offer);
+  }
+
+  fn trade_book_remove(&mut self, trade_id: &str) {
+    let offer_key = format!("tobt/{}", trade_id);
+    let Some(offer) = self
+      .tap_derived_get::<TradeBookOfferRecord>(&offer_key)
+      .ok()
+      .flatten()
+    else {
+      return;
+    };
+    for atick_key in &offer.accept {
+      let pair = format!("{}/{}", offer.tick, atick_key);
+      let slot_key = format!("tobs/{}/{}", trade_id, atick_key);
+      let Some(slot) = self
+        .tap_derived_get::<String>(&slot_key)
+        .ok()
+        .flatten()
+        .and_then(|s| s.parse::<u64>().ok())
+      else {
+        continue;
+      };
+      let last = self.trade_book_length(&pair).saturating_sub(1);
+      if slot < last {
+        if let Some(moved) = self
+          .tap_derived_get::<TradeBookEntryRecord>(&format!("tobi/{}/{}", pair, last))
+          .ok()
+          .flatten()
+        {
+          let _ = self.tap_derived_put(
+            &format!("tobs/{}/{}", moved.id, atick_key),
+            &slot.to_string(),
+          );
+          let _ = self.tap_derived_put(&format!("tobi/{}/{}", pair, slot), &moved);
+        }
+      }
+      let _ = self.tap_derived_del(&format!("tobi/{}/{}", pair, last));
+      let _ = self.tap_derived_put(&format!("tob/{}", pair), &last.to_string());
+      let _ = self.tap_derived_del(&slot_key);
+    }
+    let _ = self.tap_derived_del(&offer_key);
+  }
+
+  fn trade_book_length(&mut self, pair: &str) -> u64 {
+    self
+      .tap_derived_get::<String>(&format!("tob/{}", pair))
+      .ok()
+      .flatten()
+      .and_then(|s| s.parse::<u64>().ok())
+      .unwrap_or(0)
+  }
+
+  /// Drop offers whose `valid` height is the current block from the open-offer
+  /// book; fills from the next block on fail.
+  pub(crate) fn expire_trade_book_offers(&mut self) {
+    let expiry = self.height;
+    let length = self
+      .tap_derived_get::<String>(&format!("tobe/{}", expiry))
+      .ok()
+      .flatten()
+      .and_then(|s| s.parse::<u64>().ok())
+      .unwrap_or(0);
+    for i in 0..length {
+      if let Some(trade_id) = self
+        .tap_derived_get::<String>(&format!("tobei/{}/{}", expiry, i))
+        .ok()
+        .flatten()
+      {
+        self.trade_book_remove(&trade_id);
+      }
+    }
+  }
 }
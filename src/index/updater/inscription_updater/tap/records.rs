@@ -312,6 +312,20 @@ pub(crate) struct TradeOfferRecord {
   pub(crate) fail: bool,
 }
 
+// Open-offer book: one entry per accepted ticker of a live offer, pointing at
+// its `atrofi/` record.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct TradeBookEntryRecord {
+  pub(crate) id: String,
+  pub(crate) ptr: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct TradeBookOfferRecord {
+  pub(crate) tick: String,
+  pub(crate) accept: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub(crate) struct TradeBuySellerRecord {
   pub(crate) addr: String,
@@ -0,0 +1,99 @@
+use super::MAX_DEC_U64_STR;
+use std::fmt::{self, Display, Formatter};
+
+/// Why a deploy or mint body is ignored, or recorded as a failed mint, by
+/// the indexer. The deploy and mint checks return these so the `/r/tap/validate`
+/// pre-flight reports the same rule the indexer applies.
+#[derive(Debug)]
+pub(crate) enum TapRejection {
+  NotJson(serde_json::Error),
+  NumericValue,
+  MissingField(&'static str),
+  UnsupportedOperation,
+  ReservedTicker,
+  NotActive(u32),
+  TickerLength {
+    visible: usize,
+    expected: &'static str,
+  },
+  Cursed,
+  DataTooLong,
+  InvalidDecimals,
+  InvalidAmount {
+    field: &'static str,
+    decimals: u32,
+  },
+  ZeroAmount(&'static str),
+  AmountOverCap(&'static str),
+  PrivilegeAuthorityNotString,
+  PrivilegeAuthorityInactive,
+  AlreadyDeployed,
+  NotDeployed,
+  OverMintLimit(u128),
+  MintedOut,
+  PrivilegeSignatureMissing,
+  PrivilegeSignatureIncomplete,
+  PrivilegeSignatureMalformed,
+  PrivilegeSignatureInvalid,
+}
+
+impl Display for TapRejection {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match self {
+      Self::NotJson(err) => write!(f, "body is not valid JSON: {err}"),
+      Self::NumericValue => write!(
+        f,
+        "`max`, `lim` and `amt` must be strings, raw numbers make the inscription invalid"
+      ),
+      Self::MissingField(field) => write!(f, "missing `{field}`"),
+      Self::UnsupportedOperation => write!(f, "not a TAP token-deploy or token-mint"),
+      Self::ReservedTicker => write!(
+        f,
+        "tickers starting with `-` or `dmt-` cannot be deployed or minted with this operation"
+      ),
+      Self::NotActive(height) => write!(f, "TAP token deployments start at block {height}"),
+      Self::TickerLength { visible, expected } => write!(
+        f,
+        "ticker has {visible} visible characters, {expected} are accepted at this height"
+      ),
+      Self::Cursed => write!(f, "cursed inscriptions are ignored since the jubilee"),
+      Self::DataTooLong => write!(f, "`dta` is longer than 512 bytes"),
+      Self::InvalidDecimals => write!(f, "`dec` is not a plain decimal number"),
+      Self::InvalidAmount { field, decimals } => write!(
+        f,
+        "`{field}` is not a valid amount with {decimals} decimals"
+      ),
+      Self::ZeroAmount(field) => write!(f, "`{field}` must be greater than zero"),
+      Self::AmountOverCap(field) => write!(
+        f,
+        "`{field}` exceeds the cap of {MAX_DEC_U64_STR} whole tokens"
+      ),
+      Self::PrivilegeAuthorityNotString => {
+        write!(f, "`prv` must be a privilege authority inscription ID")
+      }
+      Self::PrivilegeAuthorityInactive => {
+        write!(f, "`prv` is not an active privilege authority")
+      }
+      Self::AlreadyDeployed => write!(f, "ticker is already deployed"),
+      Self::NotDeployed => write!(f, "ticker is not deployed"),
+      Self::OverMintLimit(limit) => write!(
+        f,
+        "`amt` exceeds the mint limit of {limit} base units, the mint would fail"
+      ),
+      Self::MintedOut => write!(f, "no tokens are left to mint, the mint would fail"),
+      Self::PrivilegeSignatureMissing => {
+        write!(f, "ticker requires a privilege signature in `prv.sig`")
+      }
+      Self::PrivilegeSignatureIncomplete => write!(
+        f,
+        "`prv` needs `sig.v`, `sig.r`, `sig.s`, `hash`, `address` and `salt`, the mint would fail"
+      ),
+      Self::PrivilegeSignatureMalformed => write!(f, "`prv` signature cannot be decoded"),
+      Self::PrivilegeSignatureInvalid => write!(
+        f,
+        "privilege signature is invalid, already used, or not for the minting address, \
+        the mint would fail"
+      ),
+    }
+  }
+}
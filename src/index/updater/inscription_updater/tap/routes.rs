@@ -1,6 +1,7 @@
 use super::super::*;
 use super::ops::dmt_mint::DmtMintMetaRecord;
 use crate::index::entry::InscriptionIdValue;
+use crate::index::TapRouteCounters;
 use std::collections::{HashMap, VecDeque};
 use std::str::FromStr;
 
@@ -50,6 +51,7 @@ pub(crate) struct TapRouteIndex {
   max_hot_entries: usize,
   ready: bool,
   coverage_height: u32,
+  counters: TapRouteCounters,
 }
 
 impl TapRouteIndex {
@@ -64,6 +66,7 @@ impl TapRouteIndex {
       max_hot_entries,
       ready: false,
       coverage_height: 0,
+      counters: TapRouteCounters::default(),
     }
   }
 
@@ -100,6 +103,24 @@ impl TapRouteIndex {
     self.routes.get(&inscription_id.store()).cloned()
   }
 
+  /// Count a transfer lookup. Lookups before the index is ready, or made in
+  /// verify mode, fall back to the slow classifier.
+  pub(crate) fn count_lookup(&mut self, route: Option<&TapRoute>, verify: bool) {
+    if !self.ready {
+      self.counters.unready += 1;
+    } else if verify {
+      self.counters.verified += 1;
+    } else if route.is_some() {
+      self.counters.hits += 1;
+    } else {
+      self.counters.misses += 1;
+    }
+  }
+
+  pub(crate) fn take_counters(&mut self) -> TapRouteCounters {
+    std::mem::take(&mut self.counters)
+  }
+
   pub(crate) fn insert_route(&mut self, inscription_id: InscriptionId, route: TapRoute) {
     self.insert_route_key(inscription_id.store(), route);
   }
@@ -244,7 +265,11 @@ impl TapRouteIndex {
 
   pub(crate) fn bitmap_hot(&mut self, inscription_id: InscriptionId) -> Option<BitmapHotEntry> {
     let key = inscription_id.store();
-    let value = self.bitmap_hot.get(&key).map(|entry| entry.value.clone())?;
+    let Some(value) = self.bitmap_hot.get(&key).map(|entry| entry.value.clone()) else {
+      self.counters.bitmap_hot_misses += 1;
+      return None;
+    };
+    self.counters.bitmap_hot_hits += 1;
     self.clock = self.clock.saturating_add(1);
     let stamp = self.clock;
     if let Some(entry) = self.bitmap_hot.get_mut(&key) {
@@ -279,7 +304,11 @@ impl TapRouteIndex {
 
   pub(crate) fn dmt_hot(&mut self, inscription_id: InscriptionId) -> Option<DmtHotEntry> {
     let key = inscription_id.store();
-    let value = self.dmt_hot.get(&key).map(|entry| entry.value.clone())?;
+    let Some(value) = self.dmt_hot.get(&key).map(|entry| entry.value.clone()) else {
+      self.counters.dmt_hot_misses += 1;
+      return None;
+    };
+    self.counters.dmt_hot_hits += 1;
     self.clock = self.clock.saturating_add(1);
     let stamp = self.clock;
     if let Some(entry) = self.dmt_hot.get_mut(&key) {
@@ -300,6 +329,7 @@ impl TapRouteIndex {
         .is_some_and(|entry| entry.stamp == stamp)
       {
         self.bitmap_hot.remove(&key);
+        self.counters.bitmap_hot_evictions += 1;
       }
     }
   }
@@ -315,6 +345,7 @@ impl TapRouteIndex {
         .is_some_and(|entry| entry.stamp == stamp)
       {
         self.dmt_hot.remove(&key);
+        self.counters.dmt_hot_evictions += 1;
       }
     }
   }
@@ -0,0 +1,497 @@
+use super::super::super::*;
+use std::io::{BufRead, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Number of consecutive blocks whose delta rows share one segment file.
+pub(crate) const TAP_DELTA_SEGMENT_BLOCKS: u32 = 1_000;
+
+// Offset index entries are block height (u32), data offset (u64), and data
+// length (u64), big-endian.
+const INDEX_ENTRY_LEN: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TapDeltaSegmentEntry {
+  pub height: u32,
+  pub offset: u64,
+  pub len: u64,
+}
+
+impl TapDeltaSegmentEntry {
+  fn end(&self) -> u64 {
+    self.offset + self.len
+  }
+
+  fn encode(&self) -> [u8; INDEX_ENTRY_LEN] {
+    let mut bytes = [0; INDEX_ENTRY_LEN];
+    bytes[..4].copy_from_slice(&self.height.to_be_bytes());
+    bytes[4..12].copy_from_slice(&self.offset.to_be_bytes());
+    bytes[12..].copy_from_slice(&self.len.to_be_bytes());
+    bytes
+  }
+
+  fn decode(bytes: &[u8]) -> Self {
+    Self {
+      height: u32::from_be_bytes(bytes[..4].try_into().unwrap()),
+      offset: u64::from_be_bytes(bytes[4..12].try_into().unwrap()),
+      len: u64::from_be_bytes(bytes[12..20].try_into().unwrap()),
+    }
+  }
+}
+
+/// Writer export delta rows packed into append-only segment files.
+///
+/// `segment-{start}.jsonl` holds the rows of every non-empty block in
+/// `start..start + TAP_DELTA_SEGMENT_BLOCKS`, one block after another, and
+/// `segment-{start}.idx` holds one entry per block locating its rows. An entry
+/// is only appended once its rows are written, so data past the last entry is
+/// left over from an interrupted write and is dropped on the next append or
+/// truncate.
+pub(crate) struct TapDeltaSegments {
+  dir: PathBuf,
+}
+
+impl TapDeltaSegments {
+  pub fn new(dir: PathBuf) -> Self {
+    Self { dir }
+  }
+
+  fn segment_start(height: u32) -> u32 {
+    height - height % TAP_DELTA_SEGMENT_BLOCKS
+  }
+
+  fn data_path(&self, start: u32) -> PathBuf {
+    self.dir.join(format!("segment-{start:010}.jsonl"))
+  }
+
+  fn index_path(&self, start: u32) -> PathBuf {
+    self.dir.join(format!("segment-{start:010}.idx"))
+  }
+
+  pub fn segment_file_start(path: &Path) -> Option<u32> {
+    let file_name = path.file_name()?.to_str()?;
+    let stem = file_name.strip_prefix("segment-")?;
+    let start = stem
+      .strip_suffix(".jsonl")
+      .or_else(|| stem.strip_suffix(".idx"))?;
+    if start.len() != 10 || !start.bytes().all(|byte| byte.is_ascii_digit()) {
+      return None;
+    }
+    start.parse().ok()
+  }
+
+  fn segment_starts(&self) -> Result<Vec<u32>> {
+    let Ok(entries) = fs::read_dir(&self.dir) else {
+      return Ok(Vec::new());
+    };
+    let mut starts = Vec::new();
+    for entry in entries {
+      if let Some(start) = Self::segment_file_start(&entry?.path()) {
+        starts.push(start);
+      }
+    }
+    starts.sort_unstable();
+    starts.dedup();
+    Ok(starts)
+  }
+
+  fn entries(&self, start: u32) -> Result<Vec<TapDeltaSegmentEntry>> {
+    let bytes = match fs::read(self.index_path(start)) {
+      Ok(bytes) => bytes,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+      Err(err) => return Err(err.into()),
+    };
+    Ok(
+      bytes
+        .chunks_exact(INDEX_ENTRY_LEN)
+        .map(TapDeltaSegmentEntry::decode)
+        .collect(),
+    )
+  }
+
+  /// Entries of non-empty blocks in `from..=to`, in height order.
+  pub fn block_entries(&self, from: u32, to: u32) -> Result<Vec<TapDeltaSegmentEntry>> {
+    let mut entries = Vec::new();
+    for start in self.segment_starts()? {
+      if start > to || start.saturating_add(TAP_DELTA_SEGMENT_BLOCKS) <= from {
+        continue;
+      }
+      entries.extend(
+        self
+          .entries(start)?
+          .into_iter()
+          .filter(|entry| entry.height >= from && entry.height <= to),
+      );
+    }
+    Ok(entries)
+  }
+
+  pub fn read_block(&self, height: u32) -> Result<Vec<crate::index::TapExportDeltaRecord>> {
+    let start = Self::segment_start(height);
+    let Some(entry) = self
+      .entries(start)?
+      .into_iter()
+      .find(|entry| entry.height == height)
+    else {
+      return Ok(Vec::new());
+    };
+
+    let mut file = File::open(self.data_path(start))?;
+    file.seek(SeekFrom::Start(entry.offset))?;
+
+    let mut rows = Vec::new();
+    for line in BufReader::new(file.take(entry.len)).lines() {
+      let line = line?;
+      if line.trim().is_empty() {
+        continue;
+      }
+      let row: crate::index::TapExportDeltaRecord = serde_json::from_str(&line)?;
+      ensure!(
+        row.height == height,
+        "tap export delta segment entry for block {height} contains row for block {}",
+        row.height
+      );
+      rows.push(row);
+    }
+
+    Ok(rows)
+  }
+
+  /// Append the rows of block `height`. Blocks at or above `height` already
+  /// in its segment are truncated first.
+  pub fn append_block(&self, height: u32, rows: &[u8]) -> Result {
+    if rows.is_empty() {
+      return Ok(());
+    }
+
+    let start = Self::segment_start(height);
+    let mut entries = self.entries(start)?;
+    if entries.last().is_some_and(|last| last.height >= height) {
+      self.truncate_from(height)?;
+      entries = self.entries(start)?;
+    }
+
+    fs::create_dir_all(&self.dir)?;
+
+    let entry = TapDeltaSegmentEntry {
+      height,
+      offset: entries.last().map_or(0, TapDeltaSegmentEntry::end),
+      len: rows.len() as u64,
+    };
+
+    let mut data = fs::OpenOptions::new()
+      .create(true)
+      .truncate(false)
+      .write(true)
+      .open(self.data_path(start))?;
+    data.set_len(entry.offset)?;
+    data.seek(SeekFrom::Start(entry.offset))?;
+    data.write_all(rows)?;
+    data.flush()?;
+
+    let mut index = fs::OpenOptions::new()
+      .create(true)
+      .truncate(false)
+      .write(true)
+      .open(self.index_path(start))?;
+    let index_len = (entries.len() * INDEX_ENTRY_LEN) as u64;
+    index.set_len(index_len)?;
+    index.seek(SeekFrom::Start(index_len))?;
+    index.write_all(&entry.encode())?;
+    index.flush()?;
+
+    Ok(())
+  }
+
+  /// Drop the rows of every block at or above `height`.
+  pub fn truncate_from(&self, height: u32) -> Result {
+    for start in self.segment_starts()? {
+      if start.saturating_add(TAP_DELTA_SEGMENT_BLOCKS) <= height {
+        continue;
+      }
+
+      if start >= height {
+        for path in [self.index_path(start), self.data_path(start)] {
+          if path.exists() {
+            fs::remove_file(path)?;
+          }
+        }
+        continue;
+      }
+
+      let entries = self.entries(start)?;
+      let keep = entries
+        .iter()
+        .take_while(|entry| entry.height < height)
+        .count();
+      let data_len = keep.checked_sub(1).map_or(0, |last| entries[last].end());
+
+      for (path, len) in [
+        (self.index_path(start), (keep * INDEX_ENTRY_LEN) as u64),
+        (self.data_path(start), data_len),
+      ] {
+        if path.exists() {
+          fs::OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(len)?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  fn pruned_before_path(&self) -> PathBuf {
+    self.dir.join("pruned-before")
+  }
+
+  /// Height below which rows have been pruned by retention, if any.
+  ///
+  /// This lives next to the segments rather than in the index so that it
+  /// survives a savepoint restore, which would otherwise bring back a coverage
+  /// start whose rows are already gone.
+  pub fn pruned_before(&self) -> Result<Option<u32>> {
+    match fs::read_to_string(self.pruned_before_path()) {
+      Ok(height) => Ok(Some(height.trim().parse()?)),
+      Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+      Err(err) => Err(err.into()),
+    }
+  }
+
+  /// Mark rows below `height` as pruned and remove segments lying entirely
+  /// below it. Returns the number of bytes removed.
+  pub fn prune_before(&self, height: u32) -> Result<u64> {
+    if self.pruned_before()?.map_or(true, |pruned| pruned < height) {
+      fs::create_dir_all(&self.dir)?;
+      let tmp = self.dir.join("pruned-before.tmp");
+      fs::write(&tmp, height.to_string())?;
+      fs::rename(tmp, self.pruned_before_path())?;
+    }
+
+    let mut removed = 0;
+    for start in self.segment_starts()? {
+      if start.saturating_add(TAP_DELTA_SEGMENT_BLOCKS) > height {
+        break;
+      }
+      for path in [self.index_path(start), self.data_path(start)] {
+        if let Ok(metadata) = fs::metadata(&path) {
+          removed += metadata.len();
+          fs::remove_file(path)?;
+        }
+      }
+    }
+
+    Ok(removed)
+  }
+
+  /// Pack per-block `{height:010}.jsonl` files written before segments into
+  /// segment files, oldest first. Blocks already in a segment are skipped, so
+  /// an interrupted migration resumes where it stopped.
+  pub fn migrate_block_files(&self) -> Result<u64> {
+    let Ok(entries) = fs::read_dir(&self.dir) else {
+      return Ok(0);
+    };
+
+    let mut files = Vec::new();
+    for entry in entries {
+      let path = entry?.path();
+      if let Some(height) = super::TapDeltaBatch::delta_file_height(&path) {
+        files.push((height, path));
+      }
+    }
+
+    if files.is_empty() {
+      return Ok(0);
+    }
+
+    files.sort_unstable();
+
+    let mut migrated = 0;
+    for (height, path) in files {
+      let segmented = self
+        .entries(Self::segment_start(height))?
+        .last()
+        .is_some_and(|last| last.height >= height);
+      if !segmented {
+        let rows = fs::read(&path)?;
+        if !rows.is_empty() {
+          self.append_block(height, &rows)?;
+          migrated += 1;
+        }
+      }
+      fs::remove_file(path)?;
+    }
+
+    log::info!(
+      "tap export: migrated {migrated} per-block delta files in {} into segments",
+      self.dir.display()
+    );
+
+    Ok(migrated)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rows(height: u32, keys: &[&str]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (sequence, key) in keys.iter().enumerate() {
+      let row = crate::index::TapExportDeltaRecord {
+        height,
+        sequence: sequence as u64,
+        op: "put".into(),
+        key: key.to_string(),
+        value: Some("1".into()),
+        row_hash: String::new(),
+        block_hash: None,
+        parent_block_hash: None,
+      };
+      serde_json::to_writer(&mut out, &row).unwrap();
+      out.push(b'\n');
+    }
+    out
+  }
+
+  fn keys(segments: &TapDeltaSegments, height: u32) -> Vec<String> {
+    segments
+      .read_block(height)
+      .unwrap()
+      .into_iter()
+      .map(|row| row.key)
+      .collect()
+  }
+
+  fn heights(segments: &TapDeltaSegments) -> Vec<u32> {
+    segments
+      .block_entries(0, u32::MAX)
+      .unwrap()
+      .into_iter()
+      .map(|entry| entry.height)
+      .collect()
+  }
+
+  #[test]
+  fn blocks_are_read_back_across_segments_and_truncated_on_reorg() {
+    let dir = tempfile::tempdir().unwrap();
+    let segments = TapDeltaSegments::new(dir.path().into());
+
+    segments.append_block(5, &rows(5, &["a", "b"])).unwrap();
+    segments.append_block(6, &[]).unwrap();
+    segments.append_block(999, &rows(999, &["c"])).unwrap();
+    segments.append_block(1000, &rows(1000, &["d"])).unwrap();
+    segments.append_block(1001, &rows(1001, &["e"])).unwrap();
+
+    assert_eq!(heights(&segments), [5, 999, 1000, 1001]);
+    assert_eq!(keys(&segments, 5), ["a", "b"]);
+    assert_eq!(keys(&segments, 6), Vec::<String>::new());
+    assert_eq!(keys(&segments, 1001), ["e"]);
+    assert_eq!(
+      segments
+        .block_entries(999, 1000)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.height)
+        .collect::<Vec<u32>>(),
+      [999, 1000]
+    );
+
+    segments.truncate_from(999).unwrap();
+    assert_eq!(heights(&segments), [5]);
+    assert!(!segments.data_path(1000).exists());
+    assert_eq!(
+      fs::metadata(segments.data_path(0)).unwrap().len(),
+      rows(5, &["a", "b"]).len() as u64
+    );
+
+    segments.append_block(999, &rows(999, &["f"])).unwrap();
+    assert_eq!(keys(&segments, 999), ["f"]);
+
+    segments.append_block(999, &rows(999, &["g"])).unwrap();
+    assert_eq!(heights(&segments), [5, 999]);
+    assert_eq!(keys(&segments, 999), ["g"]);
+  }
+
+  #[test]
+  fn rows_past_the_last_index_entry_are_dropped() {
+    let dir = tempfile::tempdir().unwrap();
+    let segments = TapDeltaSegments::new(dir.path().into());
+
+    segments.append_block(1, &rows(1, &["a"])).unwrap();
+
+    let mut data = fs::OpenOptions::new()
+      .append(true)
+      .open(segments.data_path(0))
+      .unwrap();
+    data.write_all(&rows(2, &["lost"])).unwrap();
+    let mut index = fs::OpenOptions::new()
+      .append(true)
+      .open(segments.index_path(0))
+      .unwrap();
+    index.write_all(&[0; 7]).unwrap();
+
+    segments.append_block(2, &rows(2, &["b"])).unwrap();
+
+    assert_eq!(heights(&segments), [1, 2]);
+    assert_eq!(keys(&segments, 1), ["a"]);
+    assert_eq!(keys(&segments, 2), ["b"]);
+  }
+
+  #[test]
+  fn pruning_removes_whole_segments_and_records_the_pruned_height() {
+    let dir = tempfile::tempdir().unwrap();
+    let segments = TapDeltaSegments::new(dir.path().into());
+
+    for height in [5, 1500, 2100] {
+      segments
+        .append_block(height, &rows(height, &["a"]))
+        .unwrap();
+    }
+
+    assert_eq!(segments.pruned_before().unwrap(), None);
+    assert_eq!(
+      segments.prune_before(1999).unwrap(),
+      rows(5, &["a"]).len() as u64 + INDEX_ENTRY_LEN as u64
+    );
+    assert_eq!(segments.pruned_before().unwrap(), Some(1999));
+    assert_eq!(heights(&segments), [1500, 2100]);
+
+    assert_eq!(segments.prune_before(1000).unwrap(), 0);
+    assert_eq!(segments.pruned_before().unwrap(), Some(1999));
+
+    segments.prune_before(2000).unwrap();
+    assert_eq!(segments.pruned_before().unwrap(), Some(2000));
+    assert_eq!(heights(&segments), [2100]);
+  }
+
+  #[test]
+  fn per_block_files_are_migrated_into_segments() {
+    let dir = tempfile::tempdir().unwrap();
+    let segments = TapDeltaSegments::new(dir.path().into());
+
+    for (height, key) in [(3, "a"), (4, "b"), (1200, "c")] {
+      fs::write(
+        super::super::TapDeltaBatch::delta_file_path(dir.path(), height),
+        rows(height, &[key]),
+      )
+      .unwrap();
+    }
+    fs::write(
+      super::super::TapDeltaBatch::delta_file_path(dir.path(), 5),
+      b"",
+    )
+    .unwrap();
+
+    segments.append_block(3, &rows(3, &["a"])).unwrap();
+
+    assert_eq!(segments.migrate_block_files().unwrap(), 2);
+
+    assert_eq!(heights(&segments), [3, 4, 1200]);
+    assert_eq!(keys(&segments, 4), ["b"]);
+    assert_eq!(keys(&segments, 1200), ["c"]);
+    assert!(!super::super::TapDeltaBatch::delta_file_path(dir.path(), 4).exists());
+    assert!(!super::super::TapDeltaBatch::delta_file_path(dir.path(), 5).exists());
+    assert_eq!(segments.migrate_block_files().unwrap(), 0);
+  }
+}
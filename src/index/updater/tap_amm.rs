@@ -0,0 +1,828 @@
+use {super::*, num_bigint::BigInt};
+
+const TAP_AMM_BPS: u32 = 10_000;
+
+/// Longest path the route search explores; every hop is one more swap action.
+pub(crate) const TAP_AMM_ROUTE_MAX_HOPS: usize = 3;
+
+/// Pools read per asset while searching routes, in `getAmmPoolsByAsset` order.
+const TAP_AMM_ROUTE_MAX_POOLS_PER_ASSET: u64 = 100;
+
+/// A swap the indexer would reject against the pool's current state, such as a
+/// paused pool or an amount that drains a reserve. Server routes surface this
+/// as a client error.
+#[derive(Debug, PartialEq)]
+pub(crate) struct TapAmmUnquotable(pub(crate) String);
+
+impl Display for TapAmmUnquotable {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl std::error::Error for TapAmmUnquotable {}
+
+/// The amount a quote holds fixed, matching the swap action's `xin` and `xout`
+/// modes. Amounts are in token base units, like the pool's `r` reserves.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TapAmmQuoteAmount {
+  ExactIn(BigInt),
+  ExactOut(BigInt),
+}
+
+/// Result of running one swap through the indexer's AMM math without applying
+/// it. `min_out` and `max_in` apply `slippage_bps` and are ready to be used as
+/// the swap action's `min` (for `xin`) or `max` (for `xout`) limit.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapAmmQuote {
+  pub(crate) pool_id: String,
+  pub(crate) mode: String,
+  pub(crate) side: usize,
+  pub(crate) tick_in: String,
+  pub(crate) tick_out: String,
+  pub(crate) amount_in: String,
+  pub(crate) amount_out: String,
+  pub(crate) fee: String,
+  pub(crate) protocol_fee: String,
+  pub(crate) lp_fee: String,
+  pub(crate) price_impact_bps: String,
+  pub(crate) slippage_bps: u32,
+  pub(crate) min_out: String,
+  pub(crate) max_in: String,
+  pub(crate) reserves: [String; 2],
+  pub(crate) reserves_after: [String; 2],
+}
+
+/// Best exact-in path between two TAP tokens, one quote per hop. Each hop
+/// spends the previous hop's full output.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapAmmRouteQuote {
+  pub(crate) path: Vec<String>,
+  pub(crate) pools: Vec<String>,
+  pub(crate) amount_in: String,
+  pub(crate) amount_out: String,
+  pub(crate) slippage_bps: u32,
+  pub(crate) min_out: String,
+  pub(crate) hops: Vec<TapAmmQuote>,
+}
+
+/// One LP position valued at the pool's current reserves. `value` and
+/// `hold_value` are in units of the pool's second asset. Basis fields are
+/// `None` for positions last changed before entry basis was recorded.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapAmmPositionValue {
+  pub(crate) pool_id: String,
+  pub(crate) target_type: String,
+  pub(crate) target: String,
+  pub(crate) ticks: [Option<String>; 2],
+  pub(crate) shares: String,
+  pub(crate) total_shares: String,
+  pub(crate) reserves: [String; 2],
+  pub(crate) amounts: [String; 2],
+  pub(crate) value: String,
+  pub(crate) deposited: Option<[String; 2]>,
+  pub(crate) entry_reserves: Option<[String; 2]>,
+  pub(crate) basis_block: Option<u32>,
+  pub(crate) fees_earned: Option<[String; 2]>,
+  pub(crate) hold_value: Option<String>,
+  pub(crate) impermanent_loss_bps: Option<String>,
+}
+
+struct TapAmmRouteSearch<'a> {
+  view: &'a TapStateView,
+  to: String,
+  max_hops: usize,
+  pools: HashMap<String, Vec<AuthorityConfigRecord>>,
+  best: Option<(BigInt, Vec<(AuthorityConfigRecord, usize)>)>,
+}
+
+impl Index {
+  /// Quote a swap against `pool_id` as of `view`, using the same fee, reserve
+  /// and obligation-lock checks as swap validation. Returns `None` when the
+  /// pool does not exist.
+  pub(crate) fn tap_amm_quote(
+    view: &TapStateView,
+    pool_id: &str,
+    side: usize,
+    amount: &TapAmmQuoteAmount,
+    slippage_bps: u32,
+  ) -> Result<Option<TapAmmQuote>> {
+    let Some(pool) = Self::tap_amm_pool(view, pool_id)? else {
+      return Ok(None);
+    };
+    Self::tap_amm_quote_pool(view, &pool, side, amount, slippage_bps).map(Some)
+  }
+
+  /// Find the exact-in path from `from` to `to` with the largest output,
+  /// walking pools through the `getAmmPoolsByAsset` lists. Paths never revisit
+  /// a token and are at most `max_hops` swaps long.
+  pub(crate) fn tap_amm_route_quote(
+    view: &TapStateView,
+    from: &str,
+    to: &str,
+    amount_in: &BigInt,
+    max_hops: usize,
+    slippage_bps: u32,
+  ) -> Result<Option<TapAmmRouteQuote>> {
+    let from = tap_js_to_lowercase(from);
+    let to = tap_js_to_lowercase(to);
+    if from == to {
+      return Err(anyhow!(TapAmmUnquotable(
+        "route needs two different tokens".into()
+      )));
+    }
+
+    let mut search = TapAmmRouteSearch {
+      view,
+      to,
+      max_hops: max_hops.clamp(1, TAP_AMM_ROUTE_MAX_HOPS),
+      pools: HashMap::new(),
+      best: None,
+    };
+    search.visit(&from, amount_in, &mut vec![from.clone()], &mut Vec::new())?;
+
+    let Some((_, hops)) = search.best else {
+      return Ok(None);
+    };
+
+    let mut path = vec![from];
+    let mut quotes = Vec::new();
+    let mut amount = amount_in.clone();
+    for (pool, side) in &hops {
+      let quote = Self::tap_amm_quote_pool(
+        view,
+        pool,
+        *side,
+        &TapAmmQuoteAmount::ExactIn(amount),
+        slippage_bps,
+      )?;
+      amount = quote.amount_out.parse()?;
+      path.push(quote.tick_out.clone());
+      quotes.push(quote);
+    }
+
+    Ok(Some(TapAmmRouteQuote {
+      path,
+      pools: hops.iter().map(|(pool, _)| pool.id.clone()).collect(),
+      amount_in: amount_in.to_string(),
+      amount_out: amount.to_string(),
+      slippage_bps,
+      min_out: Self::tap_amm_apply_slippage(&amount, slippage_bps, false).to_string(),
+      hops: quotes,
+    }))
+  }
+
+  /// Value the LP shares `target_type`/`target` holds in `pool_id`. Fees
+  /// earned are the growth of the shares' invariant liquidity since entry;
+  /// impermanent loss compares the rest against holding the deposits.
+  pub(crate) fn tap_amm_position_value(
+    view: &TapStateView,
+    pool_id: &str,
+    target_type: &str,
+    target: &str,
+  ) -> Result<Option<TapAmmPositionValue>> {
+    let Some(pool) = Self::tap_amm_pool(view, pool_id)? else {
+      return Ok(None);
+    };
+    let position = format!("{pool_id}/{target_type}/{target}");
+    let Some(shares) = view
+      .get_string(&format!("ammp/{position}"))?
+      .and_then(|shares| shares.parse::<BigInt>().ok())
+    else {
+      return Ok(None);
+    };
+    let (Some(reserves), Some(total)) = (
+      InscriptionUpdater::amm_pool_reserves(&pool),
+      InscriptionUpdater::amm_pool_shares(&pool),
+    ) else {
+      return Ok(None);
+    };
+
+    let zero = BigInt::from(0);
+    let amounts = if total > zero {
+      [
+        &shares * &reserves[0] / &total,
+        &shares * &reserves[1] / &total,
+      ]
+    } else {
+      [zero.clone(), zero.clone()]
+    };
+    let in_second_asset = |amounts: &[BigInt; 2]| {
+      if reserves[0] > zero {
+        &amounts[0] * &reserves[1] / &reserves[0] + &amounts[1]
+      } else {
+        amounts[1].clone()
+      }
+    };
+    let value = in_second_asset(&amounts);
+
+    let mut out = TapAmmPositionValue {
+      pool_id: pool_id.into(),
+      target_type: target_type.into(),
+      target: target.into(),
+      ticks: [
+        InscriptionUpdater::amm_pool_tick(&pool, 0),
+        InscriptionUpdater::amm_pool_tick(&pool, 1),
+      ],
+      shares: shares.to_string(),
+      total_shares: total.to_string(),
+      reserves: reserves.clone().map(|reserve| reserve.to_string()),
+      amounts: amounts.clone().map(|amount| amount.to_string()),
+      value: value.to_string(),
+      deposited: None,
+      entry_reserves: None,
+      basis_block: None,
+      fees_earned: None,
+      hold_value: None,
+      impermanent_loss_bps: None,
+    };
+
+    // Entry basis is a derived view, only kept at the tip.
+    if view.historic() {
+      return Ok(Some(out));
+    }
+    let Some(basis) = view.get_derived_json_record(&format!("ammpb/{position}"))? else {
+      return Ok(Some(out));
+    };
+    let number = |value: &serde_json::Value| {
+      value
+        .as_str()
+        .and_then(|value| value.parse::<BigInt>().ok())
+        .unwrap_or_else(|| zero.clone())
+    };
+    let deposited = [number(&basis["dep"][0]), number(&basis["dep"][1])];
+    let entry_liquidity = number(&basis["liq"]);
+    let liquidity = InscriptionUpdater::amm_share_liquidity(&reserves, &shares, &total);
+    let fees = if liquidity > entry_liquidity {
+      [
+        &amounts[0] * (&liquidity - &entry_liquidity) / &liquidity,
+        &amounts[1] * (&liquidity - &entry_liquidity) / &liquidity,
+      ]
+    } else {
+      [zero.clone(), zero.clone()]
+    };
+    let hold_value = in_second_asset(&deposited);
+    let principal = &value - in_second_asset(&fees);
+
+    out.entry_reserves = Some([
+      number(&basis["r"][0]).to_string(),
+      number(&basis["r"][1]).to_string(),
+    ]);
+    out.basis_block = basis["blck"]
+      .as_u64()
+      .and_then(|block| u32::try_from(block).ok());
+    out.impermanent_loss_bps = (hold_value > zero)
+      .then(|| ((principal - &hold_value) * TAP_AMM_BPS / &hold_value).to_string());
+    out.deposited = Some(deposited.map(|amount| amount.to_string()));
+    out.fees_earned = Some(fees.map(|fee| fee.to_string()));
+    out.hold_value = Some(hold_value.to_string());
+    Ok(Some(out))
+  }
+
+  /// Value a page of the positions listed for `target_type`/`target` under
+  /// `getAmmPositionsByTarget`, skipping pools that no longer exist.
+  pub(crate) fn tap_amm_position_values_by_target(
+    view: &TapStateView,
+    target_type: &str,
+    target: &str,
+    offset: u64,
+    max: u64,
+  ) -> Result<Vec<TapAmmPositionValue>> {
+    let mut out = Vec::new();
+    for pointer in view.list_strings(
+      &format!("amma/{target_type}/{target}"),
+      &format!("ammai/{target_type}/{target}"),
+      offset,
+      max,
+    )? {
+      let Some(pool_id) = view
+        .get_json_record(&pointer)?
+        .and_then(|position| position["pid"].as_str().map(str::to_string))
+      else {
+        continue;
+      };
+      if let Some(value) = Self::tap_amm_position_value(view, &pool_id, target_type, target)? {
+        out.push(value);
+      }
+    }
+    Ok(out)
+  }
+
+  fn tap_amm_pool(view: &TapStateView, pool_id: &str) -> Result<Option<AuthorityConfigRecord>> {
+    Ok(
+      view
+        .get_raw(&format!("amm/{pool_id}"))?
+        .and_then(|bytes| Self::tap_amm_decode_pool(&bytes)),
+    )
+  }
+
+  fn tap_amm_decode_pool(bytes: &[u8]) -> Option<AuthorityConfigRecord> {
+    ciborium::from_reader(std::io::Cursor::new(bytes))
+      .ok()
+      .or_else(|| {
+        let raw = std::str::from_utf8(bytes).ok()?;
+        serde_json::from_str(&tap_js_preprocess_json_for_serde(raw)).ok()
+      })
+      .filter(|pool: &AuthorityConfigRecord| pool.k == "amm")
+  }
+
+  fn tap_amm_quote_pool(
+    view: &TapStateView,
+    pool: &AuthorityConfigRecord,
+    side: usize,
+    amount: &TapAmmQuoteAmount,
+    slippage_bps: u32,
+  ) -> Result<TapAmmQuote> {
+    let unquotable = |message: &str| anyhow!(TapAmmUnquotable(message.into()));
+
+    if side > 1 {
+      return Err(unquotable("side must be 0 or 1"));
+    }
+    if pool.p {
+      return Err(unquotable("pool is paused"));
+    }
+    if !InscriptionUpdater::amm_pool_assets_are_tap(pool) {
+      return Err(unquotable("pool has non-TAP assets"));
+    }
+    if slippage_bps > TAP_AMM_BPS {
+      return Err(unquotable("slippage_bps must be at most 10000"));
+    }
+
+    let out_side = 1 - side;
+    let (Some(reserves), Some(tick_in), Some(tick_out), Ok(fee_bps), Ok(protocol_share_bps)) = (
+      InscriptionUpdater::amm_pool_reserves(pool),
+      InscriptionUpdater::amm_pool_tick(pool, side),
+      InscriptionUpdater::amm_pool_tick(pool, out_side),
+      pool.fee.parse::<BigInt>(),
+      pool.pf.parse::<BigInt>(),
+    ) else {
+      return Err(unquotable("pool record is malformed"));
+    };
+
+    let (mode, calc) = match amount {
+      TapAmmQuoteAmount::ExactIn(amount_in) => (
+        "xin",
+        InscriptionUpdater::calculate_amm_exact_in(
+          amount_in,
+          &reserves[side],
+          &reserves[out_side],
+          &fee_bps,
+          &protocol_share_bps,
+        )
+        .filter(|calc| calc.amount_out > BigInt::from(0)),
+      ),
+      TapAmmQuoteAmount::ExactOut(amount_out) => (
+        "xout",
+        InscriptionUpdater::calculate_amm_exact_out(
+          amount_out,
+          &reserves[side],
+          &reserves[out_side],
+          &fee_bps,
+          &protocol_share_bps,
+        ),
+      ),
+    };
+    let Some(calc) = calc else {
+      return Err(unquotable(
+        "amount cannot be swapped against current reserves",
+      ));
+    };
+
+    let mut reserves_after = reserves.clone();
+    reserves_after[side] += &calc.amount_in - &calc.protocol_fee;
+    reserves_after[out_side] -= &calc.amount_out;
+    for (i, reserve) in reserves_after.iter().enumerate() {
+      let locked = match InscriptionUpdater::amm_pool_tick_key(pool, i).and_then(|tick_key| {
+        InscriptionUpdater::obligation_lock_key(
+          &serde_json::json!({ "tt": "amm", "pid": pool.id, "i": i }),
+          &tick_key,
+        )
+      }) {
+        Some(key) => view
+          .get_string(&format!("oll/{key}"))?
+          .and_then(|s| s.parse::<BigInt>().ok())
+          .unwrap_or_else(|| BigInt::from(0)),
+        None => return Err(unquotable("pool record is malformed")),
+      };
+      if *reserve < BigInt::from(0) || *reserve < locked {
+        return Err(unquotable(
+          "swap would take reserves below their obligation locks",
+        ));
+      }
+    }
+
+    // Impact is measured on the post-fee input, so it reflects only the curve
+    // and not the fee, which is reported separately.
+    let in_after_fee = &calc.amount_in - &calc.gross_fee;
+    let spot = &in_after_fee * &reserves[out_side];
+    let filled = &calc.amount_out * &reserves[side];
+    let price_impact_bps = InscriptionUpdater::div_ceil(&((&spot - filled) * TAP_AMM_BPS), &spot)
+      .unwrap_or_else(|| BigInt::from(0));
+
+    let (min_out, max_in) = match amount {
+      TapAmmQuoteAmount::ExactIn(_) => (
+        Self::tap_amm_apply_slippage(&calc.amount_out, slippage_bps, false),
+        calc.amount_in.clone(),
+      ),
+      TapAmmQuoteAmount::ExactOut(_) => (
+        calc.amount_out.clone(),
+        Self::tap_amm_apply_slippage(&calc.amount_in, slippage_bps, true),
+      ),
+    };
+
+    Ok(TapAmmQuote {
+      pool_id: pool.id.clone(),
+      mode: mode.into(),
+      side,
+      tick_in,
+      tick_out,
+      amount_in: calc.amount_in.to_string(),
+      amount_out: calc.amount_out.to_string(),
+      fee: calc.gross_fee.to_string(),
+      protocol_fee: calc.protocol_fee.to_string(),
+      lp_fee: (&calc.gross_fee - &calc.protocol_fee).to_string(),
+      price_impact_bps: price_impact_bps.to_string(),
+      slippage_bps,
+      min_out: min_out.to_string(),
+      max_in: max_in.to_string(),
+      reserves: reserves.map(|reserve| reserve.to_string()),
+      reserves_after: reserves_after.map(|reserve| reserve.to_string()),
+    })
+  }
+
+  /// Shrink an output bound (rounding down) or grow an input bound (rounding
+  /// up) by `slippage_bps`, so the limit never tightens past the quote.
+  fn tap_amm_apply_slippage(amount: &BigInt, slippage_bps: u32, grow: bool) -> BigInt {
+    if grow {
+      InscriptionUpdater::div_ceil(
+        &(amount * (TAP_AMM_BPS + slippage_bps)),
+        &BigInt::from(TAP_AMM_BPS),
+      )
+      .unwrap_or_else(|| amount.clone())
+    } else {
+      amount * (TAP_AMM_BPS - slippage_bps) / TAP_AMM_BPS
+    }
+  }
+}
+
+impl TapAmmRouteSearch<'_> {
+  fn visit(
+    &mut self,
+    tick: &str,
+    amount: &BigInt,
+    visited: &mut Vec<String>,
+    hops: &mut Vec<(AuthorityConfigRecord, usize)>,
+  ) -> Result {
+    if tick == self.to {
+      if self
+        .best
+        .as_ref()
+        .map(|(best, _)| amount > best)
+        .unwrap_or(true)
+      {
+        self.best = Some((amount.clone(), hops.clone()));
+      }
+      return Ok(());
+    }
+    if hops.len() == self.max_hops {
+      return Ok(());
+    }
+
+    for pool in self.pools_for(tick)? {
+      let Some(side) = (0..2)
+        .find(|side| InscriptionUpdater::amm_pool_tick(&pool, *side).as_deref() == Some(tick))
+      else {
+        continue;
+      };
+      let Some(next) = InscriptionUpdater::amm_pool_tick(&pool, 1 - side) else {
+        continue;
+      };
+      if visited.contains(&next) {
+        continue;
+      }
+      let quote = match Index::tap_amm_quote_pool(
+        self.view,
+        &pool,
+        side,
+        &TapAmmQuoteAmount::ExactIn(amount.clone()),
+        0,
+      ) {
+        Ok(quote) => quote,
+        Err(error) if error.downcast_ref::<TapAmmUnquotable>().is_some() => continue,
+        Err(error) => return Err(error),
+      };
+      let amount_out = quote.amount_out.parse::<BigInt>()?;
+
+      visited.push(next.clone());
+      hops.push((pool, side));
+      self.visit(&next, &amount_out, visited, hops)?;
+      hops.pop();
+      visited.pop();
+    }
+
+    Ok(())
+  }
+
+  fn pools_for(&mut self, tick: &str) -> Result<Vec<AuthorityConfigRecord>> {
+    if let Some(pools) = self.pools.get(tick) {
+      return Ok(pools.clone());
+    }
+
+    let asset_key = format!("tap:{tick}");
+    let mut pools = Vec::new();
+    for pointer in self.view.list_strings(
+      &format!("ammat/{asset_key}"),
+      &format!("ammati/{asset_key}"),
+      0,
+      TAP_AMM_ROUTE_MAX_POOLS_PER_ASSET,
+    )? {
+      if let Some(pool) = self
+        .view
+        .get_raw(&pointer)?
+        .and_then(|bytes| Index::tap_amm_decode_pool(&bytes))
+      {
+        pools.push(pool);
+      }
+    }
+
+    self.pools.insert(tick.into(), pools.clone());
+    Ok(pools)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::index::testing::Context};
+
+  fn pool(id: &str, ticks: [&str; 2], reserves: [&str; 2], fee: &str, paused: bool) -> String {
+    serde_json::json!({
+      "id": id,
+      "k": "amm",
+      "ctl": {"ty": "ta", "auth": "owner"},
+      "seq": 0,
+      "r": reserves,
+      "a": [{"ty": "tap", "tick": ticks[0]}, {"ty": "tap", "tick": ticks[1]}],
+      "ak": [format!("tap:{}", ticks[0]), format!("tap:{}", ticks[1])],
+      "sh": "1000",
+      "fee": fee,
+      "pf": "2000",
+      "min": "0",
+      "p": paused,
+      "blck": 1,
+      "tx": "tx",
+      "vo": 0,
+      "val": "546",
+      "ins": "ins",
+      "num": 0,
+      "ts": 0
+    })
+    .to_string()
+  }
+
+  fn quote(
+    context: &Context,
+    pool_id: &str,
+    side: usize,
+    amount: TapAmmQuoteAmount,
+  ) -> Result<Option<TapAmmQuote>> {
+    Index::tap_amm_quote(
+      &context.index.tap_state_view(None).unwrap(),
+      pool_id,
+      side,
+      &amount,
+      50,
+    )
+  }
+
+  #[test]
+  fn quote_exact_in_reports_fees_impact_and_limit() {
+    let context = Context::builder().build();
+    context
+      .index
+      .tap_test_put_raw_rows([(
+        "amm/p0",
+        pool("p0", ["aaa", "bbb"], ["1000000", "2000000"], "30", false),
+      )])
+      .unwrap();
+
+    let quote = quote(
+      &context,
+      "p0",
+      0,
+      TapAmmQuoteAmount::ExactIn(BigInt::from(10_000)),
+    )
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(quote.mode, "xin");
+    assert_eq!(quote.tick_in, "aaa");
+    assert_eq!(quote.tick_out, "bbb");
+    assert_eq!(quote.amount_in, "10000");
+    assert_eq!(quote.amount_out, "19743");
+    assert_eq!(quote.fee, "30");
+    assert_eq!(quote.protocol_fee, "6");
+    assert_eq!(quote.lp_fee, "24");
+    assert_eq!(quote.price_impact_bps, "99");
+    assert_eq!(quote.min_out, "19644");
+    assert_eq!(quote.max_in, "10000");
+    assert_eq!(quote.reserves_after, ["1009994", "1980257"]);
+  }
+
+  #[test]
+  fn quote_exact_out_and_rejections() {
+    let context = Context::builder().build();
+    context
+      .index
+      .tap_test_put_raw_rows([
+        (
+          "amm/p0",
+          pool("p0", ["aaa", "bbb"], ["1000000", "2000000"], "30", false),
+        ),
+        (
+          "amm/p1",
+          pool("p1", ["aaa", "bbb"], ["1000000", "2000000"], "30", true),
+        ),
+      ])
+      .unwrap();
+
+    let exact_out = quote(
+      &context,
+      "p0",
+      1,
+      TapAmmQuoteAmount::ExactOut(BigInt::from(5_000)),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(exact_out.mode, "xout");
+    assert_eq!(exact_out.tick_in, "bbb");
+    assert_eq!(exact_out.amount_out, "5000");
+    assert_eq!(exact_out.amount_in, "10082");
+    assert_eq!(exact_out.min_out, "5000");
+    assert_eq!(exact_out.max_in, "10133");
+
+    assert_eq!(
+      quote(
+        &context,
+        "missing",
+        0,
+        TapAmmQuoteAmount::ExactIn(BigInt::from(1))
+      )
+      .unwrap(),
+      None
+    );
+
+    for (pool_id, amount, message) in [
+      (
+        "p1",
+        TapAmmQuoteAmount::ExactIn(BigInt::from(10_000)),
+        "pool is paused",
+      ),
+      (
+        "p0",
+        TapAmmQuoteAmount::ExactOut(BigInt::from(2_000_000)),
+        "amount cannot be swapped against current reserves",
+      ),
+    ] {
+      assert_eq!(
+        quote(&context, pool_id, 0, amount)
+          .unwrap_err()
+          .downcast::<TapAmmUnquotable>()
+          .unwrap(),
+        TapAmmUnquotable(message.into())
+      );
+    }
+  }
+
+  #[test]
+  fn route_quote_prefers_deeper_multi_hop_path() {
+    let context = Context::builder().build();
+    let pointer = |id: &str| serde_json::to_string(&format!("amm/{id}")).unwrap();
+    context
+      .index
+      .tap_test_put_raw_rows([
+        (
+          "amm/direct".to_string(),
+          pool("direct", ["aaa", "ccc"], ["1000", "1000"], "30", false),
+        ),
+        (
+          "amm/ab".to_string(),
+          pool("ab", ["aaa", "bbb"], ["1000000", "1000000"], "30", false),
+        ),
+        (
+          "amm/bc".to_string(),
+          pool("bc", ["bbb", "ccc"], ["1000000", "1000000"], "30", false),
+        ),
+        ("ammat/tap:aaa".to_string(), "2".to_string()),
+        ("ammati/tap:aaa/0".to_string(), pointer("direct")),
+        ("ammati/tap:aaa/1".to_string(), pointer("ab")),
+        ("ammat/tap:bbb".to_string(), "2".to_string()),
+        ("ammati/tap:bbb/0".to_string(), pointer("ab")),
+        ("ammati/tap:bbb/1".to_string(), pointer("bc")),
+        ("ammat/tap:ccc".to_string(), "2".to_string()),
+        ("ammati/tap:ccc/0".to_string(), pointer("direct")),
+        ("ammati/tap:ccc/1".to_string(), pointer("bc")),
+      ])
+      .unwrap();
+    let view = context.index.tap_state_view(None).unwrap();
+
+    let route = Index::tap_amm_route_quote(&view, "AAA", "ccc", &BigInt::from(10_000), 3, 100)
+      .unwrap()
+      .unwrap();
+    assert_eq!(route.path, ["aaa", "bbb", "ccc"]);
+    assert_eq!(route.pools, ["ab", "bc"]);
+    assert_eq!(route.hops.len(), 2);
+    assert_eq!(route.hops[1].amount_in, route.hops[0].amount_out);
+    assert_eq!(route.amount_out, route.hops[1].amount_out);
+    assert_eq!(
+      route.min_out,
+      (route.amount_out.parse::<BigInt>().unwrap() * 99u32 / 100u32).to_string()
+    );
+
+    let direct = Index::tap_amm_route_quote(&view, "aaa", "ccc", &BigInt::from(10_000), 1, 100)
+      .unwrap()
+      .unwrap();
+    assert_eq!(direct.pools, ["direct"]);
+
+    assert_eq!(
+      Index::tap_amm_route_quote(&view, "aaa", "zzz", &BigInt::from(10_000), 3, 100).unwrap(),
+      None
+    );
+  }
+
+  #[test]
+  fn position_value_reports_fees_and_impermanent_loss_against_basis() {
+    let context = Context::builder().build();
+    let pool = |id: &str, reserves: [&str; 2]| {
+      let mut pool: serde_json::Value =
+        serde_json::from_str(&pool(id, ["aaa", "bbb"], reserves, "30", false)).unwrap();
+      pool["sh"] = "100000".into();
+      pool.to_string()
+    };
+    let pointer = |id: &str| serde_json::to_string(&format!("ammpr/{id}/a/owner")).unwrap();
+    let position = |id: &str| {
+      serde_json::json!({"pid": id, "tt": "a", "to": "owner", "sh": "50000"}).to_string()
+    };
+    context
+      .index
+      .tap_test_put_raw_rows([
+        ("amm/p0".to_string(), pool("p0", ["200000", "55000"])),
+        ("amm/p1".to_string(), pool("p1", ["1000", "4000"])),
+        ("ammp/p0/a/owner".to_string(), "50000".to_string()),
+        ("ammp/p1/a/owner".to_string(), "50000".to_string()),
+        ("ammpr/p0/a/owner".to_string(), position("p0")),
+        ("ammpr/p1/a/owner".to_string(), position("p1")),
+        ("amma/a/owner".to_string(), "2".to_string()),
+        ("ammai/a/owner/0".to_string(), pointer("p0")),
+        ("ammai/a/owner/1".to_string(), pointer("p1")),
+      ])
+      .unwrap();
+    context
+      .index
+      .tap_test_put_derived_rows([(
+        "ammpb/p0/a/owner".to_string(),
+        serde_json::json!({
+          "pid": "p0",
+          "tt": "a",
+          "to": "owner",
+          "sh": "50000",
+          "dep": ["50000", "50000"],
+          "liq": "50000000000000000000000",
+          "r": ["100000", "100000"],
+          "tsh": "100000",
+          "blck": 7
+        })
+        .to_string(),
+      )])
+      .unwrap();
+    let view = context.index.tap_state_view(None).unwrap();
+
+    let value = Index::tap_amm_position_value(&view, "p0", "a", "owner")
+      .unwrap()
+      .unwrap();
+    assert_eq!(value.ticks, [Some("aaa".into()), Some("bbb".into())]);
+    assert_eq!(value.amounts, ["100000", "27500"]);
+    assert_eq!(value.value, "55000");
+    assert_eq!(value.deposited, Some(["50000".into(), "50000".into()]));
+    assert_eq!(
+      value.entry_reserves,
+      Some(["100000".into(), "100000".into()])
+    );
+    assert_eq!(value.basis_block, Some(7));
+    assert_eq!(value.fees_earned, Some(["4652".into(), "1279".into()]));
+    assert_eq!(value.hold_value, Some("63750".into()));
+    assert_eq!(value.impermanent_loss_bps, Some("-1773".into()));
+
+    let values = Index::tap_amm_position_values_by_target(&view, "a", "owner", 0, 25).unwrap();
+    assert_eq!(values.len(), 2);
+    assert_eq!(values[0], value);
+    assert_eq!(values[1].amounts, ["500", "2000"]);
+    assert_eq!(values[1].value, "4000");
+    assert_eq!(values[1].fees_earned, None);
+    assert_eq!(values[1].impermanent_loss_bps, None);
+
+    assert_eq!(
+      Index::tap_amm_position_value(&view, "p0", "a", "stranger").unwrap(),
+      None
+    );
+  }
+}
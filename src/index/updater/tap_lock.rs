@@ -0,0 +1,301 @@
+use {super::*, num_bigint::BigInt};
+
+/// A lock that passes `claim` or `refund` validation in the block after the
+/// view, given the inputs in `requires`: `preimage` for hashlock claims,
+/// `authority` when the condition's authority must submit the claim, and
+/// `cert` for actions under certified control. `until_block` is the last block
+/// the action passes, when the lock sets one.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapLockRelease {
+  pub(crate) lock: serde_json::Value,
+  pub(crate) action: String,
+  pub(crate) requires: Vec<String>,
+  pub(crate) until_block: Option<u32>,
+}
+
+impl Index {
+  /// Unconsumed locks paying `address` on claim that can be claimed now.
+  pub(crate) fn tap_claimable_locks(
+    view: &TapStateView,
+    address: &str,
+  ) -> Result<Vec<TapLockRelease>> {
+    Self::tap_releasable_locks(view, "lcop", address, "claim")
+  }
+
+  /// Unconsumed locks refunding to `address` that can be refunded now.
+  pub(crate) fn tap_refundable_locks(
+    view: &TapStateView,
+    address: &str,
+  ) -> Result<Vec<TapLockRelease>> {
+    Self::tap_releasable_locks(view, "lrop", address, "refund")
+  }
+
+  /// Unconsumed locks whose refund window opens at `height`.
+  pub(crate) fn tap_locks_by_refund_height(
+    view: &TapStateView,
+    height: u32,
+    offset: u64,
+    max: u64,
+  ) -> Result<Vec<serde_json::Value>> {
+    let mut locks = Vec::new();
+    for id in view.list_derived_strings(
+      &format!("lrh/{height}"),
+      &format!("lrhi/{height}"),
+      offset,
+      max,
+    )? {
+      if let Some(lock) = view.get_json_record(&format!("l/{id}"))? {
+        locks.push(lock);
+      }
+    }
+    Ok(locks)
+  }
+
+  fn tap_releasable_locks(
+    view: &TapStateView,
+    list: &str,
+    address: &str,
+    action: &str,
+  ) -> Result<Vec<TapLockRelease>> {
+    let block = view.height()?.map_or(0, |height| height + 1);
+    let mut releases = Vec::new();
+    for id in view.list_derived_strings(
+      &format!("{list}/{address}"),
+      &format!("{list}i/{address}"),
+      0,
+      u64::MAX,
+    )? {
+      let Some(lock) = view.get_json_record(&format!("l/{id}"))? else {
+        continue;
+      };
+      if let Some(release) = Self::tap_lock_release(view, lock, action, block)? {
+        releases.push(release);
+      }
+    }
+    Ok(releases)
+  }
+
+  /// Applies the checks of `claim` and `refund` validation that depend on
+  /// indexed state rather than on the action's own inputs.
+  fn tap_lock_release(
+    view: &TapStateView,
+    lock: serde_json::Value,
+    action: &str,
+    block: u32,
+  ) -> Result<Option<TapLockRelease>> {
+    let id = lock["id"].as_str().unwrap_or_default();
+    if view.get_raw(&format!("lc/{id}"))?.is_some() || !Self::tap_lock_funded(view, &lock)? {
+      return Ok(None);
+    }
+
+    let height = |value: &serde_json::Value| value.as_u64().and_then(|v| u32::try_from(v).ok());
+    let refund_after = height(&lock["refund_after"]);
+    let mut requires = Vec::new();
+    let mut until_block = None;
+
+    if action == "claim" {
+      match lock["condition"]["type"].as_str() {
+        Some("hashlock") => {
+          let Some(refund_after) = refund_after.filter(|refund_after| block < *refund_after) else {
+            return Ok(None);
+          };
+          requires.push("preimage".to_string());
+          until_block = Some(refund_after - 1);
+        }
+        Some("height") => {
+          if height(&lock["condition"]["min"]).map_or(true, |min| block < min) {
+            return Ok(None);
+          }
+        }
+        Some("authority") => requires.push("authority".to_string()),
+        _ => return Ok(None),
+      }
+    } else if lock["refund"].as_str().is_none()
+      || refund_after.map_or(true, |refund_after| block < refund_after)
+    {
+      return Ok(None);
+    }
+
+    let control = &lock["control"];
+    let scoped = control["scope"]
+      .as_array()
+      .is_some_and(|scope| scope.iter().any(|entry| entry.as_str() == Some(action)));
+    let terminal = action == "refund"
+      && control["rules"]["terminal_refund_after"]
+        .as_u64()
+        .is_some_and(|terminal| u64::from(block) >= terminal);
+    if scoped && !terminal {
+      if let Some(expires) = height(&control["expires"]) {
+        if block > expires {
+          return Ok(None);
+        }
+        until_block = Some(until_block.map_or(expires, |until: u32| until.min(expires)));
+      }
+      requires.push("cert".to_string());
+    }
+
+    Ok(Some(TapLockRelease {
+      lock,
+      action: action.to_string(),
+      requires,
+      until_block,
+    }))
+  }
+
+  /// Release validation rejects locks whose owner no longer holds, or no longer
+  /// has locked, the lock's amount plus its allocations.
+  fn tap_lock_funded(view: &TapStateView, lock: &serde_json::Value) -> Result<bool> {
+    let parse = |value: &serde_json::Value| {
+      value
+        .as_str()
+        .and_then(|value| value.parse::<BigInt>().ok())
+    };
+    let Some(mut total) = parse(&lock["remaining"]) else {
+      return Ok(false);
+    };
+    let allocations = match lock["al"].as_array() {
+      Some(allocations) => allocations.iter().map(|al| &al["amt"]).collect(),
+      None if lock["fee"].is_object() => vec![&lock["fee"]["amt"]],
+      None => Vec::new(),
+    };
+    for amount in allocations {
+      match parse(amount) {
+        Some(amount) if amount > BigInt::default() => total += amount,
+        _ => return Ok(false),
+      }
+    }
+    if total <= BigInt::default() {
+      return Ok(false);
+    }
+
+    let owner = lock["owner"].as_str().unwrap_or_default();
+    let tick_key =
+      InscriptionUpdater::json_stringify_lower(lock["tick"].as_str().unwrap_or_default());
+    let amount = |key: String| -> Result<BigInt> {
+      Ok(
+        view
+          .get_string(&key)?
+          .and_then(|value| value.parse::<BigInt>().ok())
+          .unwrap_or_default(),
+      )
+    };
+    Ok(
+      amount(format!("b/{owner}/{tick_key}"))? >= total
+        && amount(format!("ll/{owner}/{tick_key}"))? >= total,
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::index::testing::Context, serde_json::json};
+
+  fn lock(
+    id: &str,
+    refund_after: Option<u32>,
+    condition: serde_json::Value,
+    control: Option<serde_json::Value>,
+  ) -> (String, String) {
+    let mut lock = json!({
+      "id": id,
+      "owner": "owner",
+      "tick": "TAP",
+      "remaining": "10",
+      "claim": "alice",
+      "refund": "bob",
+      "refund_after": refund_after,
+      "condition": condition,
+    });
+    if let Some(control) = control {
+      lock["control"] = control;
+    }
+    (format!("l/{id}"), lock.to_string())
+  }
+
+  fn list(key: &str, ids: &[&str]) -> Vec<(String, String)> {
+    let mut rows = vec![(key.to_string(), ids.len().to_string())];
+    for (i, id) in ids.iter().enumerate() {
+      let (length, item) = key.split_once('/').unwrap();
+      rows.push((format!("{length}i/{item}/{i}"), json!(id).to_string()));
+    }
+    rows
+  }
+
+  #[test]
+  fn releasable_locks_follow_conditions_expiry_and_control() {
+    let hashlock = json!({"type": "hashlock", "hash": "00"});
+    let rows = vec![
+      ("b/owner/\"tap\"".to_string(), "\"100\"".to_string()),
+      ("ll/owner/\"tap\"".to_string(), "\"100\"".to_string()),
+      lock("open", Some(10), hashlock.clone(), None),
+      lock("early", None, json!({"type": "height", "min": 5}), None),
+      lock(
+        "certified",
+        None,
+        json!({"type": "authority", "auth": "a"}),
+        Some(json!({"scope": ["claim"], "expires": 100})),
+      ),
+      lock("expired", Some(1), hashlock.clone(), None),
+      lock("consumed", Some(1), hashlock, None),
+      (
+        "lc/consumed".into(),
+        json!({"lock": "consumed"}).to_string(),
+      ),
+    ];
+    let mut lists = list(
+      "lcop/alice",
+      &["open", "early", "certified", "expired", "consumed"],
+    );
+    lists.extend(list("lrop/bob", &["open", "expired", "consumed"]));
+    lists.extend(list("lrh/1", &["expired"]));
+
+    let context = Context::builder().build();
+    context.index.tap_test_put_raw_rows(rows).unwrap();
+    context.index.tap_test_put_derived_rows(lists).unwrap();
+    let view = context.index.tap_state_view(None).unwrap();
+
+    let claimable = Index::tap_claimable_locks(&view, "alice").unwrap();
+    assert_eq!(
+      claimable
+        .iter()
+        .map(|release| (
+          release.lock["id"].as_str().unwrap(),
+          release.requires.clone(),
+          release.until_block
+        ))
+        .collect::<Vec<_>>(),
+      [
+        ("open", vec!["preimage".to_string()], Some(9)),
+        (
+          "certified",
+          vec!["authority".to_string(), "cert".to_string()],
+          Some(100)
+        ),
+      ]
+    );
+
+    let refundable = Index::tap_refundable_locks(&view, "bob").unwrap();
+    assert_eq!(refundable.len(), 1);
+    assert_eq!(refundable[0].lock["id"], "expired");
+    assert_eq!(refundable[0].action, "refund");
+    assert!(refundable[0].requires.is_empty());
+
+    assert_eq!(
+      Index::tap_locks_by_refund_height(&view, 1, 0, 500)
+        .unwrap()
+        .iter()
+        .map(|lock| lock["id"].as_str().unwrap())
+        .collect::<Vec<_>>(),
+      ["expired"]
+    );
+
+    context
+      .index
+      .tap_test_put_raw_rows([("ll/owner/\"tap\"", "\"5\"")])
+      .unwrap();
+    let view = context.index.tap_state_view(None).unwrap();
+    assert!(Index::tap_claimable_locks(&view, "alice")
+      .unwrap()
+      .is_empty());
+  }
+}
@@ -0,0 +1,1125 @@
+use {
+  super::*,
+  num_bigint::{BigInt, BigUint},
+  secp256k1::{PublicKey, Secp256k1, SecretKey},
+};
+
+const TAP_PERP_BPS: u32 = 10_000;
+
+const TAP_PERP_PRICE_DOMAIN: &str = "tap-perp-price-v1";
+
+/// Margin state of one perp position against its group's latest accepted
+/// price certificate. `liquidatable` applies the same checks as
+/// `perp-liquidate` validation for an action in the next block.
+///
+/// Formation positions have no entry price yet, so they are measured from the
+/// worst entry their bound allows (`max` for longs, `min` for shorts), and
+/// `entry_source` is `bound` instead of `group`.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapPerpPositionRisk {
+  pub(crate) position_id: String,
+  pub(crate) group_id: String,
+  pub(crate) side: String,
+  pub(crate) state: String,
+  pub(crate) leverage: String,
+  pub(crate) open_collateral: String,
+  pub(crate) maintenance_bps: String,
+  pub(crate) entry_price: Option<serde_json::Value>,
+  pub(crate) entry_source: Option<String>,
+  pub(crate) mark_price: Option<serde_json::Value>,
+  pub(crate) mark_certificate: Option<String>,
+  pub(crate) equity: Option<String>,
+  pub(crate) margin_ratio_bps: Option<String>,
+  pub(crate) margin_buffer: Option<String>,
+  pub(crate) liquidation_price: Option<serde_json::Value>,
+  pub(crate) distance_to_liquidation_bps: Option<String>,
+  pub(crate) liquidation_bounty: String,
+  pub(crate) liquidatable: bool,
+}
+
+/// Open collateral of one side of a group, valued at group leverage the way
+/// settlement values it.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapPerpSideRisk {
+  pub(crate) open_collateral: String,
+  pub(crate) equity: Option<String>,
+  pub(crate) margin_ratio_bps: Option<String>,
+  pub(crate) liquidation_price: Option<serde_json::Value>,
+}
+
+/// Group-level margin state plus a page of its positions, in
+/// `getPerpPositionsByGroup` order.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapPerpGroupRisk {
+  pub(crate) group_id: String,
+  pub(crate) state: String,
+  pub(crate) expiry: Option<u64>,
+  pub(crate) leverage: Option<String>,
+  pub(crate) maintenance_bps: String,
+  pub(crate) entry_price: Option<serde_json::Value>,
+  pub(crate) mark_price: Option<serde_json::Value>,
+  pub(crate) mark_certificate: Option<String>,
+  pub(crate) long: TapPerpSideRisk,
+  pub(crate) short: TapPerpSideRisk,
+  pub(crate) positions: Vec<TapPerpPositionRisk>,
+}
+
+/// Oracle side of a perp policy: what certificates must name and who may sign
+/// them. Built from a stored `getPerpPolicy` record or a `perp-policy` action.
+#[derive(Debug, PartialEq)]
+pub(crate) struct TapPerpOracle {
+  pub(crate) id: String,
+  pub(crate) net: String,
+  pub(crate) hash: String,
+  pub(crate) signers: Vec<String>,
+  pub(crate) threshold: usize,
+}
+
+/// An action after adding local signatures. `signatures` counts what the
+/// indexer would accept, so `complete` means the action can be inscribed.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapPerpSigned {
+  pub(crate) action: serde_json::Value,
+  pub(crate) message_hash: String,
+  pub(crate) signatures: usize,
+  pub(crate) threshold: usize,
+  pub(crate) complete: bool,
+}
+
+/// One entry of a certificate's `sigs`, checked in the order
+/// `valid_perp_signature_count` checks it. A `fatal` entry makes the indexer
+/// reject every signature in the certificate.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapPerpSignatureCheck {
+  pub(crate) index: usize,
+  pub(crate) signer: Option<String>,
+  pub(crate) valid: bool,
+  pub(crate) fatal: bool,
+  pub(crate) error: Option<String>,
+}
+
+/// Offline verification of a perp price certificate. Covers every check of
+/// certificate validation except those that need chain state: nonce reuse,
+/// sequence ordering against earlier certificates, and resolving the pair's
+/// assets. The group and height checks only run when they are supplied.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapPerpCertificateReport {
+  pub(crate) purpose: Option<String>,
+  pub(crate) message: Option<serde_json::Value>,
+  pub(crate) message_hash: Option<String>,
+  pub(crate) errors: Vec<String>,
+  pub(crate) signatures: Vec<TapPerpSignatureCheck>,
+  pub(crate) valid_signatures: usize,
+  pub(crate) threshold: usize,
+  pub(crate) valid: bool,
+}
+
+struct TapPerpMark {
+  price: serde_json::Value,
+  certificate: String,
+}
+
+impl Index {
+  /// Risk of `position_id` as of `view`. Returns `None` when the position or
+  /// its group does not exist.
+  pub(crate) fn tap_perp_position_risk(
+    view: &TapStateView,
+    position_id: &str,
+  ) -> Result<Option<TapPerpPositionRisk>> {
+    let Some(position) = view.get_json_record(&format!("perp/pos/{position_id}"))? else {
+      return Ok(None);
+    };
+    let Some(group_id) = position["group"].as_str() else {
+      return Ok(None);
+    };
+    let Some(group) = view.get_json_record(&format!("perp/g/{group_id}"))? else {
+      return Ok(None);
+    };
+    let mark = Self::tap_perp_mark(view, group_id)?;
+    Ok(Self::tap_perp_assess_position(
+      &position,
+      &group,
+      mark.as_ref(),
+      view.height()?,
+    ))
+  }
+
+  /// Risk of `group_id` as of `view`, with `max` of its positions from
+  /// `offset`. Returns `None` when the group does not exist.
+  pub(crate) fn tap_perp_group_risk(
+    view: &TapStateView,
+    group_id: &str,
+    offset: u64,
+    max: u64,
+  ) -> Result<Option<TapPerpGroupRisk>> {
+    let Some(group) = view.get_json_record(&format!("perp/g/{group_id}"))? else {
+      return Ok(None);
+    };
+    let mark = Self::tap_perp_mark(view, group_id)?;
+    let height = view.height()?;
+
+    let mut positions = Vec::new();
+    for position_id in view.list_strings(
+      &format!("perp/pgl/{group_id}"),
+      &format!("perp/pgli/{group_id}"),
+      offset,
+      max,
+    )? {
+      if let Some(risk) = view
+        .get_json_record(&format!("perp/pos/{position_id}"))?
+        .and_then(|position| {
+          Self::tap_perp_assess_position(&position, &group, mark.as_ref(), height)
+        })
+      {
+        positions.push(risk);
+      }
+    }
+
+    Ok(Some(TapPerpGroupRisk {
+      group_id: group_id.into(),
+      state: group["state"].as_str().unwrap_or_default().into(),
+      expiry: group["expiry"].as_u64(),
+      leverage: InscriptionUpdater::perp_group_leverage(&group),
+      maintenance_bps: Self::tap_perp_number(&group["maintenance_bps"]).to_string(),
+      entry_price: Some(group["entry_price"].clone()).filter(|price| !price.is_null()),
+      long: Self::tap_perp_assess_side(&group, "long", mark.as_ref()),
+      short: Self::tap_perp_assess_side(&group, "short", mark.as_ref()),
+      mark_price: mark.as_ref().map(|mark| mark.price.clone()),
+      mark_certificate: mark.map(|mark| mark.certificate),
+      positions,
+    }))
+  }
+
+  /// Price of the last certificate the indexer accepted for the group, which
+  /// is the newest entry of its `perp/cg` certificate list.
+  fn tap_perp_mark(view: &TapStateView, group_id: &str) -> Result<Option<TapPerpMark>> {
+    let length = view.get_length(&format!("perp/cg/{group_id}"))?;
+    if length == 0 {
+      return Ok(None);
+    }
+    let Some(certificate) = view.get_string(&format!("perp/cgi/{group_id}/{}", length - 1))? else {
+      return Ok(None);
+    };
+    Ok(
+      view
+        .get_json_record(&format!("perp/c/{certificate}"))?
+        .map(|record| record["price"].clone())
+        .filter(|price| !price.is_null())
+        .map(|price| TapPerpMark { price, certificate }),
+    )
+  }
+
+  fn tap_perp_assess_position(
+    position: &serde_json::Value,
+    group: &serde_json::Value,
+    mark: Option<&TapPerpMark>,
+    height: Option<u32>,
+  ) -> Option<TapPerpPositionRisk> {
+    let side = position["side"].as_str()?;
+    let leverage_text = position["leverage"].as_str()?;
+    let leverage = InscriptionUpdater::parse_perp_ratio(&position["leverage"], false)?;
+    let open_collateral = Self::tap_perp_number(&position["open_collateral"]);
+    let maintenance_bps = Self::tap_perp_number(&group["maintenance_bps"]);
+    let bounty =
+      InscriptionUpdater::payable_perp_liquidation_bounty(group, position, &BigInt::from(0), true);
+
+    let (entry_price, entry_source) = if !group["entry_price"].is_null() {
+      (Some(group["entry_price"].clone()), Some("group"))
+    } else {
+      let bound = &position["entry"][if side == "long" { "max" } else { "min" }];
+      if bound.is_null() {
+        (None, None)
+      } else {
+        (Some(bound.clone()), Some("bound"))
+      }
+    };
+
+    let mut risk = TapPerpPositionRisk {
+      position_id: position["id"].as_str()?.into(),
+      group_id: group["id"].as_str()?.into(),
+      side: side.into(),
+      state: position["state"].as_str().unwrap_or_default().into(),
+      leverage: leverage_text.into(),
+      open_collateral: open_collateral.to_string(),
+      maintenance_bps: maintenance_bps.to_string(),
+      entry_price: entry_price.clone(),
+      entry_source: entry_source.map(str::to_string),
+      mark_price: mark.map(|mark| mark.price.clone()),
+      mark_certificate: mark.map(|mark| mark.certificate.clone()),
+      equity: None,
+      margin_ratio_bps: None,
+      margin_buffer: None,
+      liquidation_price: None,
+      distance_to_liquidation_bps: None,
+      liquidation_bounty: bounty.to_string(),
+      liquidatable: false,
+    };
+
+    let Some(entry_price) = entry_price else {
+      return Some(risk);
+    };
+    let liquidation_price =
+      Self::tap_perp_liquidation_price(side, &leverage, &maintenance_bps, &entry_price);
+    risk.liquidation_price = liquidation_price.clone();
+
+    let Some(mark) = mark else {
+      return Some(risk);
+    };
+    let Some(equity) = InscriptionUpdater::compute_perp_equity(
+      &open_collateral,
+      leverage_text,
+      side,
+      &entry_price,
+      &mark.price,
+    ) else {
+      return Some(risk);
+    };
+
+    // Same cross-multiplied comparison as `perp-liquidate`:
+    // equity * 10000 <= open_collateral * maintenance_bps.
+    let under_maintenance = InscriptionUpdater::compare_perp_ratio(
+      &(equity.clone(), open_collateral.clone()),
+      &(maintenance_bps.clone(), BigInt::from(TAP_PERP_BPS)),
+    ) <= 0;
+    let next_block = height.map_or(0, |height| u64::from(height) + 1);
+    risk.liquidatable = under_maintenance
+      && group["state"].as_str() == Some("active")
+      && group["expiry"]
+        .as_u64()
+        .is_some_and(|expiry| next_block < expiry)
+      && InscriptionUpdater::perp_position_active_state(position, group)
+      && bounty > BigInt::from(0);
+
+    if open_collateral > BigInt::from(0) {
+      risk.margin_ratio_bps = Some((&equity * TAP_PERP_BPS / &open_collateral).to_string());
+    }
+    risk.margin_buffer =
+      Some((&equity - &open_collateral * &maintenance_bps / TAP_PERP_BPS).to_string());
+    risk.distance_to_liquidation_bps = liquidation_price
+      .as_ref()
+      .and_then(|liquidation| Self::tap_perp_distance_bps(side, &mark.price, liquidation));
+    risk.equity = Some(equity.to_string());
+    Some(risk)
+  }
+
+  fn tap_perp_assess_side(
+    group: &serde_json::Value,
+    side: &str,
+    mark: Option<&TapPerpMark>,
+  ) -> TapPerpSideRisk {
+    let open_collateral = Self::tap_perp_number(
+      group
+        .get(format!("{side}_open_collateral"))
+        .or_else(|| group.get(format!("{side}_collateral")))
+        .unwrap_or(&serde_json::Value::Null),
+    );
+    let equity = mark
+      .and_then(|mark| InscriptionUpdater::compute_perp_open_side_equity(group, side, &mark.price));
+    let liquidation_price = InscriptionUpdater::perp_group_leverage(group)
+      .and_then(|leverage| {
+        InscriptionUpdater::parse_perp_ratio(&serde_json::Value::String(leverage), false)
+      })
+      .filter(|_| !group["entry_price"].is_null())
+      .and_then(|leverage| {
+        Self::tap_perp_liquidation_price(
+          side,
+          &leverage,
+          &Self::tap_perp_number(&group["maintenance_bps"]),
+          &group["entry_price"],
+        )
+      });
+    TapPerpSideRisk {
+      margin_ratio_bps: equity
+        .as_ref()
+        .filter(|_| open_collateral > BigInt::from(0))
+        .map(|equity| (equity * TAP_PERP_BPS / &open_collateral).to_string()),
+      open_collateral: open_collateral.to_string(),
+      equity: equity.map(|equity| equity.to_string()),
+      liquidation_price,
+    }
+  }
+
+  /// Mark price at which equity falls to the maintenance margin, as a reduced
+  /// `{p, q}` ratio. Solves `compute_perp_equity` without its integer
+  /// rounding, so `liquidatable` stays authoritative right at the boundary.
+  /// Longs with leverage low enough to stay above maintenance down to a zero
+  /// price have none.
+  fn tap_perp_liquidation_price(
+    side: &str,
+    leverage: &(BigInt, BigInt),
+    maintenance_bps: &BigInt,
+    entry_price: &serde_json::Value,
+  ) -> Option<serde_json::Value> {
+    let entry_p = Self::tap_perp_number(entry_price.get("p")?);
+    let entry_q = Self::tap_perp_number(entry_price.get("q")?);
+    let scale = BigInt::from(TAP_PERP_BPS) * &leverage.0;
+    let buffer = (BigInt::from(TAP_PERP_BPS) - maintenance_bps) * &leverage.1;
+    let factor = if side == "short" {
+      &scale + buffer
+    } else {
+      &scale - buffer
+    };
+    if factor <= BigInt::from(0) || entry_q <= BigInt::from(0) {
+      return None;
+    }
+    let p = entry_p * factor;
+    let q = entry_q * scale;
+    let gcd = Self::tap_perp_gcd(&p, &q);
+    Some(serde_json::json!({
+      "p": (p / &gcd).to_string(),
+      "q": (q / &gcd).to_string()
+    }))
+  }
+
+  /// How far the mark can move against `side` before reaching `liquidation`,
+  /// in bps of the mark. Negative once the mark is past it.
+  fn tap_perp_distance_bps(
+    side: &str,
+    mark: &serde_json::Value,
+    liquidation: &serde_json::Value,
+  ) -> Option<String> {
+    let mark_p = Self::tap_perp_number(mark.get("p")?);
+    let mark_q = Self::tap_perp_number(mark.get("q")?);
+    let liquidation_p = Self::tap_perp_number(liquidation.get("p")?);
+    let liquidation_q = Self::tap_perp_number(liquidation.get("q")?);
+    let denominator = &mark_p * &liquidation_q;
+    if denominator <= BigInt::from(0) {
+      return None;
+    }
+    let gap = &denominator - liquidation_p * mark_q;
+    let gap = if side == "short" { -gap } else { gap };
+    Some((gap * TAP_PERP_BPS / denominator).to_string())
+  }
+
+  /// The certificate purpose each price-carrying action is validated with.
+  pub(crate) fn tap_perp_certificate_purpose(op: &str) -> Option<&'static str> {
+    match op.to_lowercase().as_str() {
+      "perp-activate" => Some("entry"),
+      "perp-close" => Some("close"),
+      "perp-liquidate" => Some("liquidation"),
+      "perp-settle" => Some("settlement"),
+      _ => None,
+    }
+  }
+
+  /// Read the oracle fields certificates are checked against. A `perp-policy`
+  /// action goes through the same signer normalization and payload hashing as
+  /// policy validation, so its `hash` matches the record the indexer stores.
+  pub(crate) fn tap_perp_oracle(policy: &serde_json::Value) -> Result<TapPerpOracle> {
+    let field = |name: &str| {
+      policy[name]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("policy has no `{name}`"))
+    };
+
+    if policy["op"].as_str() == Some("perp-policy") {
+      let signers = InscriptionUpdater::normalize_perp_signers(&policy["signers"])
+        .ok_or_else(|| anyhow!("policy `signers` must be 1 to 16 distinct public keys"))?;
+      let threshold = InscriptionUpdater::parse_perp_uint(&policy["thr"], false)
+        .and_then(|threshold| usize::try_from(threshold).ok())
+        .ok_or_else(|| anyhow!("policy `thr` must be a positive integer"))?;
+      return Ok(TapPerpOracle {
+        id: field("id")?,
+        net: field("net")?,
+        hash: InscriptionUpdater::token_perp_payload_hash(policy, &["hash", "sigs"])
+          .ok_or_else(|| anyhow!("policy cannot be hashed"))?,
+        signers,
+        threshold,
+      });
+    }
+
+    let signers = policy["oracle"]["signers"]
+      .as_array()
+      .and_then(|signers| {
+        signers
+          .iter()
+          .map(|signer| signer.as_str().map(str::to_string))
+          .collect::<Option<Vec<_>>>()
+      })
+      .ok_or_else(|| anyhow!("policy has no `oracle.signers`"))?;
+    let threshold = policy["oracle"]["threshold"]
+      .as_u64()
+      .and_then(|threshold| usize::try_from(threshold).ok())
+      .ok_or_else(|| anyhow!("policy has no `oracle.threshold`"))?;
+    Ok(TapPerpOracle {
+      id: field("id")?,
+      net: field("net")?,
+      hash: field("hash")?,
+      signers,
+      threshold,
+    })
+  }
+
+  /// Fill in `hash` on a `perp-policy` action and sign its policy message.
+  /// Existing signatures over the same message are kept, so signers can add
+  /// theirs one at a time. Updating a published policy also needs the
+  /// previous policy's signers, which this does not check.
+  pub(crate) fn tap_perp_sign_policy(
+    action: &serde_json::Value,
+    keys: &[SecretKey],
+  ) -> Result<TapPerpSigned> {
+    let oracle = Self::tap_perp_oracle(action)?;
+    let seq = InscriptionUpdater::parse_perp_uint(&action["seq"], false)
+      .ok_or_else(|| anyhow!("policy `seq` must be a positive integer"))?;
+    let message =
+      InscriptionUpdater::token_perp_policy_message(&oracle.id, &seq.to_string(), &oracle.hash);
+    let message_hash = InscriptionUpdater::certified_control_hash(&message)
+      .ok_or_else(|| anyhow!("policy message cannot be hashed"))?;
+
+    let mut action = action.clone();
+    action["hash"] = oracle.hash.clone().into();
+    action["sigs"] =
+      Self::tap_perp_collect_signatures(&action["sigs"], &oracle.signers, &message_hash, keys)?;
+    let sigs = action["sigs"].clone();
+    Ok(Self::tap_perp_signed(action, &sigs, &oracle, message_hash))
+  }
+
+  /// Complete and sign the `cert` of a price-carrying perp action. Fields the
+  /// policy and action determine (`v`, `dom`, `net`, `pid`, `ph`, `purpose`,
+  /// `state_hash`) are filled in when missing; the group, sequence, validity
+  /// window and price must already be set. Existing signatures over the same
+  /// certificate message are kept.
+  pub(crate) fn tap_perp_sign_certificate(
+    policy: &serde_json::Value,
+    action: &serde_json::Value,
+    keys: &[SecretKey],
+  ) -> Result<TapPerpSigned> {
+    let oracle = Self::tap_perp_oracle(policy)?;
+    let purpose = action["op"]
+      .as_str()
+      .and_then(Self::tap_perp_certificate_purpose)
+      .ok_or_else(|| {
+        anyhow!("action `op` must be perp-activate, perp-close, perp-liquidate or perp-settle")
+      })?;
+    if !action["cert"].is_object() {
+      bail!("action has no `cert` object");
+    }
+
+    let mut action = action.clone();
+    let state_hash = InscriptionUpdater::token_perp_payload_hash(&action, &["cert"])
+      .ok_or_else(|| anyhow!("action cannot be hashed"))?;
+    let cert = &mut action["cert"];
+    for (name, value) in [
+      ("v", "1"),
+      ("dom", TAP_PERP_PRICE_DOMAIN),
+      ("net", &oracle.net),
+      ("pid", &oracle.id),
+      ("ph", &oracle.hash),
+      ("purpose", purpose),
+    ] {
+      if cert[name].is_null() {
+        cert[name] = value.into();
+      }
+    }
+    cert["state_hash"] = state_hash.into();
+    if cert["sigs"].is_null() {
+      cert["sigs"] = serde_json::json!([]);
+    }
+
+    let (_, message_hash) = Self::tap_perp_certificate_message(&oracle, cert)?;
+    cert["sigs"] =
+      Self::tap_perp_collect_signatures(&cert["sigs"], &oracle.signers, &message_hash, keys)?;
+    let sigs = cert["sigs"].clone();
+    Ok(Self::tap_perp_signed(action, &sigs, &oracle, message_hash))
+  }
+
+  /// Check the `cert` of a price-carrying perp action against `policy`, and
+  /// against `group` and the block `height` it would be mined at when given.
+  pub(crate) fn tap_perp_verify_certificate(
+    policy: &serde_json::Value,
+    action: &serde_json::Value,
+    group: Option<&serde_json::Value>,
+    height: Option<u32>,
+  ) -> Result<TapPerpCertificateReport> {
+    let oracle = Self::tap_perp_oracle(policy)?;
+    let purpose = action["op"]
+      .as_str()
+      .and_then(Self::tap_perp_certificate_purpose);
+    let cert = &action["cert"];
+    let mut report = TapPerpCertificateReport {
+      purpose: purpose.map(str::to_string),
+      message: None,
+      message_hash: None,
+      errors: Vec::new(),
+      signatures: Vec::new(),
+      valid_signatures: 0,
+      threshold: oracle.threshold,
+      valid: false,
+    };
+    if !cert.is_object() {
+      report.errors.push("action has no `cert` object".into());
+      return Ok(report);
+    }
+
+    let mut expect = |name: &str, expected: Option<&str>| {
+      let actual = cert[name].as_str();
+      match expected {
+        Some(expected) if actual != Some(expected) => report.errors.push(format!(
+          "`{name}` is {}, expected `{expected}`",
+          actual.map_or_else(|| "missing".into(), |actual| format!("`{actual}`"))
+        )),
+        _ => {}
+      }
+    };
+    expect("dom", Some(TAP_PERP_PRICE_DOMAIN));
+    expect("net", Some(&oracle.net));
+    expect("pid", Some(&oracle.id));
+    expect("ph", Some(&oracle.hash));
+    expect("purpose", purpose);
+    expect("gid", group.and_then(|group| group["id"].as_str()));
+    expect("gh", group.and_then(|group| group["gh"].as_str()));
+
+    if purpose.is_none() {
+      report.errors.push(
+        "action `op` must be perp-activate, perp-close, perp-liquidate or perp-settle".into(),
+      );
+    }
+    if cert["gid"].as_str().is_none() {
+      report.errors.push("`gid` is missing".into());
+    }
+    if InscriptionUpdater::normalize_perp_price(&cert["price"]).is_none() {
+      report
+        .errors
+        .push("`price` must have positive integer `p` and `q`".into());
+    }
+    if InscriptionUpdater::parse_perp_uint(&cert["seq"], false).is_none() {
+      report
+        .errors
+        .push("`seq` must be a positive integer string".into());
+    }
+    match (
+      InscriptionUpdater::parse_perp_height(&cert["valid_from"]),
+      InscriptionUpdater::parse_perp_height(&cert["valid_until"]),
+    ) {
+      (Some(from), Some(until)) => {
+        if let Some(height) = height.filter(|height| *height < from || *height > until) {
+          report.errors.push(format!(
+            "block {height} is outside the validity window {from} to {until}"
+          ));
+        }
+      }
+      _ => report
+        .errors
+        .push("`valid_from` and `valid_until` must be block heights".into()),
+    }
+    if cert["pair"]
+      .get("price_dir")
+      .is_some_and(|direction| direction.as_str() != Some("quote-per-base"))
+    {
+      report
+        .errors
+        .push("`pair.price_dir` must be `quote-per-base`".into());
+    }
+    let state_hash = InscriptionUpdater::token_perp_payload_hash(action, &["cert"]);
+    if cert["state_hash"]
+      .as_str()
+      .map(str::to_lowercase)
+      .as_deref()
+      != state_hash.as_deref()
+    {
+      report.errors.push(format!(
+        "`state_hash` does not match the action, expected `{}`",
+        state_hash.unwrap_or_default()
+      ));
+    }
+    if !cert["sigs"].is_array() {
+      report.errors.push("`sigs` must be an array".into());
+    }
+
+    if let Ok((message, message_hash)) = Self::tap_perp_certificate_message(&oracle, cert) {
+      report.signatures =
+        Self::tap_perp_check_signatures(&cert["sigs"], &oracle.signers, &message_hash);
+      report.valid_signatures = InscriptionUpdater::valid_perp_signature_count(
+        &cert["sigs"],
+        &oracle.signers,
+        &message_hash,
+      );
+      report.message = Some(message);
+      report.message_hash = Some(message_hash);
+    }
+    if report.valid_signatures < oracle.threshold {
+      report.errors.push(format!(
+        "{} of {} required signatures are valid",
+        report.valid_signatures, oracle.threshold
+      ));
+    }
+    report.valid = report.errors.is_empty();
+    Ok(report)
+  }
+
+  /// The signed message and its hash for `cert`, exactly as certificate
+  /// validation builds them.
+  fn tap_perp_certificate_message(
+    oracle: &TapPerpOracle,
+    cert: &serde_json::Value,
+  ) -> Result<(serde_json::Value, String)> {
+    let payload_hash = InscriptionUpdater::token_perp_payload_hash(cert, &["sigs"])
+      .ok_or_else(|| anyhow!("certificate cannot be hashed"))?;
+    let message = InscriptionUpdater::token_perp_certificate_message(
+      &serde_json::json!({ "id": oracle.id, "hash": oracle.hash }),
+      cert["purpose"]
+        .as_str()
+        .ok_or_else(|| anyhow!("certificate has no `purpose`"))?,
+      cert["gid"]
+        .as_str()
+        .ok_or_else(|| anyhow!("certificate has no `gid`"))?,
+      &payload_hash,
+      cert["seq"]
+        .as_str()
+        .ok_or_else(|| anyhow!("certificate has no `seq`"))?,
+      InscriptionUpdater::parse_perp_height(&cert["valid_until"])
+        .ok_or_else(|| anyhow!("certificate has no valid `valid_until`"))?,
+    )
+    .ok_or_else(|| anyhow!("certificate message cannot be built"))?;
+    let message_hash = InscriptionUpdater::certified_control_hash(&message)
+      .ok_or_else(|| anyhow!("certificate message cannot be hashed"))?;
+    Ok((message, message_hash))
+  }
+
+  /// Existing signatures over `message_hash`, minus any from the signing
+  /// keys, plus a fresh signature from each key.
+  fn tap_perp_collect_signatures(
+    existing: &serde_json::Value,
+    signers: &[String],
+    message_hash: &str,
+    keys: &[SecretKey],
+  ) -> Result<serde_json::Value> {
+    let secp = Secp256k1::new();
+    let message = secp256k1::Message::from_digest_slice(&hex::decode(message_hash)?)?;
+    let signing = keys
+      .iter()
+      .map(|key| hex::encode(PublicKey::from_secret_key(&secp, key).serialize()))
+      .collect::<Vec<_>>();
+    for signer in &signing {
+      if !signers.contains(signer) {
+        bail!("key {signer} is not a policy signer");
+      }
+    }
+
+    let mut sigs = existing
+      .as_array()
+      .into_iter()
+      .flatten()
+      .filter(|entry| {
+        entry["hash"].as_str().map(str::to_lowercase).as_deref() == Some(message_hash)
+          && entry["signer"]
+            .as_str()
+            .and_then(InscriptionUpdater::token_proof_compressed_delegation_pubkey)
+            .is_some_and(|signer| !signing.contains(&signer))
+      })
+      .cloned()
+      .collect::<Vec<_>>();
+    for (key, signer) in keys.iter().zip(signing) {
+      let (recovery_id, compact) = secp
+        .sign_ecdsa_recoverable(&message, key)
+        .serialize_compact();
+      sigs.push(serde_json::json!({
+        "signer": signer,
+        "hash": message_hash,
+        "sig": {
+          "v": recovery_id.to_i32().to_string(),
+          "r": BigUint::from_bytes_be(&compact[..32]).to_string(),
+          "s": BigUint::from_bytes_be(&compact[32..]).to_string()
+        }
+      }));
+    }
+    Ok(serde_json::Value::Array(sigs))
+  }
+
+  fn tap_perp_signed(
+    action: serde_json::Value,
+    sigs: &serde_json::Value,
+    oracle: &TapPerpOracle,
+    message_hash: String,
+  ) -> TapPerpSigned {
+    let signatures =
+      InscriptionUpdater::valid_perp_signature_count(sigs, &oracle.signers, &message_hash);
+    TapPerpSigned {
+      action,
+      message_hash,
+      signatures,
+      threshold: oracle.threshold,
+      complete: signatures >= oracle.threshold,
+    }
+  }
+
+  fn tap_perp_check_signatures(
+    sigs: &serde_json::Value,
+    signers: &[String],
+    message_hash: &str,
+  ) -> Vec<TapPerpSignatureCheck> {
+    let Some(message_bytes) = hex::decode(message_hash)
+      .ok()
+      .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+    else {
+      return Vec::new();
+    };
+    let mut seen = HashSet::new();
+    let mut checks = Vec::new();
+    for (index, entry) in sigs.as_array().into_iter().flatten().enumerate() {
+      let signer = entry["signer"]
+        .as_str()
+        .and_then(InscriptionUpdater::token_proof_compressed_delegation_pubkey);
+      let check = |error: Option<&str>, fatal: bool| TapPerpSignatureCheck {
+        index,
+        signer: signer.clone(),
+        valid: error.is_none(),
+        fatal,
+        error: error.map(str::to_string),
+      };
+      checks.push(if !entry.is_object() {
+        check(Some("entry is not an object"), true)
+      } else if entry["hash"].as_str().map(str::to_lowercase).as_deref() != Some(message_hash) {
+        check(Some("`hash` is not the certificate message hash"), true)
+      } else if signer.is_none() {
+        check(Some("`signer` is not a public key"), true)
+      } else if !signer
+        .as_ref()
+        .is_some_and(|signer| signers.contains(signer))
+      {
+        check(Some("`signer` is not a policy signer"), true)
+      } else {
+        match InscriptionUpdater::verify_sig_obj_against_msg_with_hash(
+          &entry["sig"],
+          message_hash,
+          &message_bytes,
+        ) {
+          None => check(Some("`sig` is malformed"), false),
+          Some((ok, _, pubkey))
+            if !ok
+              || InscriptionUpdater::token_proof_compressed_delegation_pubkey(&pubkey)
+                != signer =>
+          {
+            check(Some("`sig` was not made by `signer`"), false)
+          }
+          Some(_) if !seen.insert(signer.clone()) => check(Some("`signer` already signed"), false),
+          Some(_) => check(None, false),
+        }
+      });
+    }
+    checks
+  }
+
+  fn tap_perp_gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    while b != BigInt::from(0) {
+      let r = &a % &b;
+      a = b;
+      b = r;
+    }
+    if a == BigInt::from(0) {
+      BigInt::from(1)
+    } else {
+      a
+    }
+  }
+
+  fn tap_perp_number(value: &serde_json::Value) -> BigInt {
+    value
+      .as_str()
+      .and_then(|value| value.parse::<BigInt>().ok())
+      .unwrap_or_else(|| BigInt::from(0))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::index::testing::Context, serde_json::json};
+
+  fn rows(mark: &str) -> Vec<(String, String)> {
+    let position = |id: &str, group: &str, side: &str, state: &str, entry: serde_json::Value| {
+      json!({
+        "id": id,
+        "group": group,
+        "owner": "owner",
+        "side": side,
+        "open_collateral": "1000",
+        "open_liquidation_bounty_reserve": "10",
+        "leverage": "5",
+        "entry": entry,
+        "state": state
+      })
+      .to_string()
+    };
+    let string = |value: &str| serde_json::to_string(value).unwrap();
+    vec![
+      (
+        "perp/g/g0".into(),
+        json!({
+          "id": "g0",
+          "state": "active",
+          "expiry": 100,
+          "leverage": {"min": "5", "max": "5", "value": "5"},
+          "maintenance_bps": "500",
+          "entry_price": {"p": "100", "q": "1", "seq": "1"},
+          "long_open_collateral": "1000",
+          "short_open_collateral": "1000"
+        })
+        .to_string(),
+      ),
+      (
+        "perp/g/g1".into(),
+        json!({
+          "id": "g1",
+          "state": "formation",
+          "expiry": 100,
+          "leverage": {"min": "5", "max": "5", "value": "5"},
+          "maintenance_bps": "500",
+          "entry_price": null
+        })
+        .to_string(),
+      ),
+      (
+        "perp/pos/long".into(),
+        position("long", "g0", "long", "active", json!(null)),
+      ),
+      (
+        "perp/pos/short".into(),
+        position("short", "g0", "short", "active", json!(null)),
+      ),
+      (
+        "perp/pos/pending".into(),
+        position(
+          "pending",
+          "g1",
+          "long",
+          "formation",
+          json!({"max": {"p": "110", "q": "1"}}),
+        ),
+      ),
+      ("perp/pgl/g0".into(), "2".into()),
+      ("perp/pgli/g0/0".into(), string("long")),
+      ("perp/pgli/g0/1".into(), string("short")),
+      ("perp/cg/g0".into(), "1".into()),
+      ("perp/cgi/g0/0".into(), string("c1")),
+      (
+        "perp/c/c1".into(),
+        json!({"id": "c1", "price": {"p": mark, "q": "1", "seq": "2"}}).to_string(),
+      ),
+    ]
+  }
+
+  fn risk(mark: &str, position_id: &str) -> TapPerpPositionRisk {
+    let context = Context::builder().build();
+    context.index.tap_test_put_raw_rows(rows(mark)).unwrap();
+    Index::tap_perp_position_risk(&context.index.tap_state_view(None).unwrap(), position_id)
+      .unwrap()
+      .unwrap()
+  }
+
+  #[test]
+  fn position_risk_matches_liquidation_check() {
+    let long = risk("85", "long");
+    assert_eq!(long.entry_source.as_deref(), Some("group"));
+    assert_eq!(long.mark_certificate.as_deref(), Some("c1"));
+    assert_eq!(long.equity.as_deref(), Some("250"));
+    assert_eq!(long.margin_ratio_bps.as_deref(), Some("2500"));
+    assert_eq!(long.margin_buffer.as_deref(), Some("200"));
+    assert_eq!(long.liquidation_price, Some(json!({"p": "81", "q": "1"})));
+    assert_eq!(long.distance_to_liquidation_bps.as_deref(), Some("470"));
+    assert!(!long.liquidatable);
+
+    let short = risk("85", "short");
+    assert_eq!(short.equity.as_deref(), Some("1750"));
+    assert_eq!(short.liquidation_price, Some(json!({"p": "119", "q": "1"})));
+    assert_eq!(short.distance_to_liquidation_bps.as_deref(), Some("4000"));
+
+    let long = risk("80", "long");
+    assert_eq!(long.equity.as_deref(), Some("0"));
+    assert_eq!(long.margin_buffer.as_deref(), Some("-50"));
+    assert_eq!(long.distance_to_liquidation_bps.as_deref(), Some("-125"));
+    assert_eq!(long.liquidation_bounty, "10");
+    assert!(long.liquidatable);
+
+    let pending = risk("80", "pending");
+    assert_eq!(pending.entry_source.as_deref(), Some("bound"));
+    assert_eq!(pending.mark_price, None);
+    assert_eq!(pending.equity, None);
+    assert_eq!(
+      pending.liquidation_price,
+      Some(json!({"p": "891", "q": "10"}))
+    );
+    assert!(!pending.liquidatable);
+  }
+
+  #[test]
+  fn group_risk_values_sides_and_pages_positions() {
+    let context = Context::builder().build();
+    context.index.tap_test_put_raw_rows(rows("80")).unwrap();
+    let view = context.index.tap_state_view(None).unwrap();
+
+    let group = Index::tap_perp_group_risk(&view, "g0", 1, 25)
+      .unwrap()
+      .unwrap();
+    assert_eq!(group.leverage.as_deref(), Some("5"));
+    assert_eq!(group.long.equity.as_deref(), Some("0"));
+    assert_eq!(group.long.margin_ratio_bps.as_deref(), Some("0"));
+    assert_eq!(group.short.equity.as_deref(), Some("2000"));
+    assert_eq!(
+      group.short.liquidation_price,
+      Some(json!({"p": "119", "q": "1"}))
+    );
+    assert_eq!(group.positions.len(), 1);
+    assert_eq!(group.positions[0].position_id, "short");
+
+    assert_eq!(
+      Index::tap_perp_group_risk(&view, "missing", 0, 25).unwrap(),
+      None
+    );
+  }
+
+  fn keys() -> Vec<SecretKey> {
+    [1, 2]
+      .into_iter()
+      .map(|byte| SecretKey::from_slice(&[byte; 32]).unwrap())
+      .collect()
+  }
+
+  fn policy() -> serde_json::Value {
+    let secp = Secp256k1::new();
+    json!({
+      "op": "perp-policy",
+      "id": "perp-main",
+      "v": "1",
+      "dom": "tap-perp-policy-v1",
+      "net": "bitcoin:signet",
+      "seq": "1",
+      "thr": "2",
+      "signers": keys()
+        .iter()
+        .map(|key| hex::encode(PublicKey::from_secret_key(&secp, key).serialize()))
+        .collect::<Vec<_>>(),
+      "sigs": []
+    })
+  }
+
+  fn close() -> serde_json::Value {
+    json!({
+      "op": "perp-close",
+      "pos": "p0",
+      "cert": {
+        "gid": "g0",
+        "gh": "gh0",
+        "seq": "3",
+        "nonce": "n0",
+        "valid_from": "100",
+        "valid_until": "110",
+        "price": {"p": "120", "q": "1"}
+      }
+    })
+  }
+
+  #[test]
+  fn policy_signing_matches_policy_validation() {
+    let keys = keys();
+    let signed = Index::tap_perp_sign_policy(&policy(), &keys[..1]).unwrap();
+    assert_eq!(
+      (signed.signatures, signed.threshold, signed.complete),
+      (1, 2, false)
+    );
+
+    let signed = Index::tap_perp_sign_policy(&signed.action, &keys[1..]).unwrap();
+    assert_eq!((signed.signatures, signed.complete), (2, true));
+
+    let hash =
+      InscriptionUpdater::token_perp_payload_hash(&signed.action, &["hash", "sigs"]).unwrap();
+    assert_eq!(signed.action["hash"], json!(hash));
+    let message = InscriptionUpdater::token_perp_policy_message("perp-main", "1", &hash);
+    assert_eq!(
+      signed.message_hash,
+      InscriptionUpdater::certified_control_hash(&message).unwrap()
+    );
+    assert_eq!(
+      InscriptionUpdater::valid_perp_signature_count(
+        &signed.action["sigs"],
+        &Index::tap_perp_oracle(&policy()).unwrap().signers,
+        &signed.message_hash,
+      ),
+      2
+    );
+  }
+
+  #[test]
+  fn certificates_verify_and_report_failing_fields_and_signers() {
+    let policy = policy();
+    let keys = keys();
+    let oracle = Index::tap_perp_oracle(&policy).unwrap();
+    let group = json!({"id": "g0", "gh": "gh0"});
+
+    let partial = Index::tap_perp_sign_certificate(&policy, &close(), &keys[..1]).unwrap();
+    assert!(!partial.complete);
+    let cert = &partial.action["cert"];
+    assert_eq!(cert["dom"], json!("tap-perp-price-v1"));
+    assert_eq!(cert["purpose"], json!("close"));
+    assert_eq!(cert["ph"], json!(oracle.hash));
+    let report =
+      Index::tap_perp_verify_certificate(&policy, &partial.action, Some(&group), Some(105))
+        .unwrap();
+    assert_eq!(report.errors, ["1 of 2 required signatures are valid"]);
+
+    let signed = Index::tap_perp_sign_certificate(&policy, &partial.action, &keys[1..]).unwrap();
+    assert!(signed.complete);
+    let report =
+      Index::tap_perp_verify_certificate(&policy, &signed.action, Some(&group), Some(105)).unwrap();
+    assert!(report.valid, "{:?}", report.errors);
+    assert_eq!(report.purpose.as_deref(), Some("close"));
+    assert_eq!(report.message_hash, Some(signed.message_hash.clone()));
+    assert!(report.signatures.iter().all(|check| check.valid));
+
+    let stored = json!({
+      "id": "perp-main",
+      "net": "bitcoin:signet",
+      "hash": oracle.hash,
+      "oracle": {"signers": oracle.signers, "threshold": 2}
+    });
+    assert!(
+      Index::tap_perp_verify_certificate(&stored, &signed.action, None, None)
+        .unwrap()
+        .valid
+    );
+
+    let mut tampered = signed.action.clone();
+    tampered["cert"]["price"]["p"] = json!("121");
+    tampered["cert"]["net"] = json!("bitcoin:mainnet");
+    let report = Index::tap_perp_verify_certificate(
+      &policy,
+      &tampered,
+      Some(&json!({"id": "g0", "gh": "gh1"})),
+      Some(111),
+    )
+    .unwrap();
+    assert_eq!(
+      report.errors,
+      [
+        "`net` is `bitcoin:mainnet`, expected `bitcoin:signet`",
+        "`gh` is `gh0`, expected `gh1`",
+        "block 111 is outside the validity window 100 to 110",
+        "0 of 2 required signatures are valid",
+      ]
+    );
+    assert!(report.signatures.iter().all(|check| check.fatal));
+    assert_eq!(
+      report.signatures[0].error.as_deref(),
+      Some("`hash` is not the certificate message hash")
+    );
+
+    let mut forged = signed.action.clone();
+    forged["cert"]["sigs"][1]["sig"] = forged["cert"]["sigs"][0]["sig"].clone();
+    let report = Index::tap_perp_verify_certificate(&policy, &forged, None, None).unwrap();
+    assert_eq!(report.errors, ["1 of 2 required signatures are valid"]);
+    assert!(!report.signatures[1].fatal);
+    assert_eq!(
+      report.signatures[1].error.as_deref(),
+      Some("`sig` was not made by `signer`")
+    );
+
+    let stranger = SecretKey::from_slice(&[3; 32]).unwrap();
+    assert!(
+      Index::tap_perp_sign_certificate(&policy, &close(), &[stranger])
+        .unwrap_err()
+        .to_string()
+        .ends_with("is not a policy signer")
+    );
+  }
+}
@@ -0,0 +1,525 @@
+use super::*;
+
+/// Projected TAP balance of one address and ticker if a previewed transaction
+/// were mined in the next block.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TapPreviewBalance {
+  pub(crate) address: String,
+  pub(crate) tick: String,
+  pub(crate) balance_before: String,
+  pub(crate) balance_after: String,
+  pub(crate) transferable_before: String,
+  pub(crate) transferable_after: String,
+}
+
+/// A record the previewed transaction would append to a `blck/<kind>/<height>`
+/// list, as served by the `…ByBlock` routes. `fail` mirrors the record's own
+/// flag, so failed transfers, sends and trades are reported, not dropped.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TapPreviewEvent {
+  pub(crate) kind: String,
+  pub(crate) fail: bool,
+  pub(crate) record: serde_json::Value,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TapPreview {
+  pub(crate) txid: Txid,
+  pub(crate) height: u32,
+  pub(crate) balances: Vec<TapPreviewBalance>,
+  pub(crate) events: Vec<TapPreviewEvent>,
+}
+
+impl Index {
+  /// Index `tx` as if it were the only transaction in the next block. TAP
+  /// writes stay in the atomic overlay and inscription writes go to a scratch
+  /// database, so neither TAP state nor the inscription tables change.
+  ///
+  /// Previews read the last committed block and never wait for the indexer.
+  pub(crate) fn tap_preview(&self, tx: &Transaction) -> Result<TapPreview> {
+    ensure!(
+      self.index_inscriptions,
+      "TAP preview requires an index with inscriptions"
+    );
+    ensure!(!tx.is_coinbase(), "cannot preview a coinbase transaction");
+
+    let txid = tx.compute_txid();
+
+    self.tap_with_next_block_updater(|rtx, updater| {
+      let height = updater.height;
+
+      let outpoint_to_utxo_entry = rtx.0.open_table(OUTPOINT_TO_UTXO_ENTRY)?;
+      let mut input_utxo_entries = Vec::new();
+      for input in &tx.input {
+        let outpoint = input.previous_output;
+        if let Some(entry) = outpoint_to_utxo_entry.get(&outpoint.store())? {
+          input_utxo_entries.push(entry.value().to_buf());
+          continue;
+        }
+        ensure!(
+          !self.index_sats,
+          "input {outpoint} is not an indexed unspent output"
+        );
+        let txout = self
+          .get_transaction(outpoint.txid)?
+          .and_then(|transaction| {
+            transaction
+              .output
+              .into_iter()
+              .nth(usize::try_from(outpoint.vout).ok()?)
+          })
+          .ok_or_else(|| anyhow!("input {outpoint} not found"))?;
+        let mut entry = UtxoEntryBuf::new();
+        entry.push_value(txout.value.to_sat(), self);
+        if self.index_addresses {
+          entry.push_script_pubkey(txout.script_pubkey.as_bytes(), self);
+        }
+        input_utxo_entries.push(entry);
+      }
+      let input_utxo_entries = input_utxo_entries
+        .iter()
+        .map(|entry| entry.parse(self))
+        .collect::<Vec<ParsedUtxoEntry>>();
+      // Sat ranges only place inscriptions on sats, which TAP never reads, so
+      // outputs get empty ranges instead of splitting the inputs' ranges.
+      let mut output_utxo_entries = tx
+        .output
+        .iter()
+        .map(|txout| {
+          let mut entry = UtxoEntryBuf::new();
+          if self.index_sats {
+            entry.push_sat_ranges(&[], self);
+          } else {
+            entry.push_value(txout.value.to_sat(), self);
+          }
+          if self.index_addresses {
+            entry.push_script_pubkey(txout.script_pubkey.as_bytes(), self);
+          }
+          entry
+        })
+        .collect::<Vec<UtxoEntryBuf>>();
+
+      updater.index_inscriptions(
+        tx,
+        txid,
+        &input_utxo_entries,
+        &mut output_utxo_entries,
+        &mut HashMap::new(),
+        self,
+        None,
+      )?;
+      Self::tap_preview_collect(updater, txid, height)
+    })
+  }
+
+  /// Runs `f` with an inscription updater for the block after the tip. The
+  /// updater's tables live in a scratch in-memory database and fall back to
+  /// a read transaction on the index for committed state, so previews never
+  /// take the index write lock. TAP writes stay in the atomic overlay and the
+  /// scratch database is dropped afterwards.
+  pub(super) fn tap_with_next_block_updater<T>(
+    &self,
+    f: impl FnOnce(&rtx::Rtx, &mut InscriptionUpdater) -> Result<T>,
+  ) -> Result<T> {
+    let rtx = self.begin_read()?;
+
+    let scratch = Database::builder().create_with_backend(redb::backends::InMemoryBackend::new())?;
+    let wtx = scratch.begin_write()?;
+
+    let result = {
+      let height = rtx.block_count()?;
+
+      let statistic_to_count = rtx.0.open_table(STATISTIC_TO_COUNT)?;
+      let statistic = |statistic: Statistic| -> Result<u64> {
+        Ok(
+          statistic_to_count
+            .get(&statistic.key())?
+            .map(|count| count.value())
+            .unwrap_or(0),
+        )
+      };
+
+      let base = inscription_updater::InscriptionPreviewBase {
+        id_to_sequence_number: rtx.0.open_table(INSCRIPTION_ID_TO_SEQUENCE_NUMBER)?,
+        sequence_number_to_entry: rtx.0.open_table(SEQUENCE_NUMBER_TO_INSCRIPTION_ENTRY)?,
+      };
+
+      let next_sequence_number = base
+        .sequence_number_to_entry
+        .iter()?
+        .next_back()
+        .transpose()?
+        .map(|(number, _entry)| number.value() + 1)
+        .unwrap_or(0);
+      let home_inscription_count = rtx.0.open_table(HOME_INSCRIPTIONS)?.len()?;
+
+      let mut home_inscriptions = wtx.open_table(HOME_INSCRIPTIONS)?;
+      let mut id_to_sequence_number = wtx.open_table(INSCRIPTION_ID_TO_SEQUENCE_NUMBER)?;
+      let mut inscription_number_to_sequence_number =
+        wtx.open_table(INSCRIPTION_NUMBER_TO_SEQUENCE_NUMBER)?;
+      let mut sat_to_sequence_number = wtx.open_multimap_table(SAT_TO_SEQUENCE_NUMBER)?;
+      let mut sequence_number_to_children = wtx.open_multimap_table(SEQUENCE_NUMBER_TO_CHILDREN)?;
+      let mut sequence_number_to_entry = wtx.open_table(SEQUENCE_NUMBER_TO_INSCRIPTION_ENTRY)?;
+      let mut transaction_id_to_transaction = wtx.open_table(TRANSACTION_ID_TO_TRANSACTION)?;
+      let mut tap_kv = wtx.open_table(TAP_KV)?;
+      let mut tap_derived = wtx.open_table(TAP_DERIVED)?;
+      let mut tap_derived_db = inscription_updater::TapBatch::new(&mut tap_derived);
+      tap_derived_db.base = match rtx.0.open_table(TAP_DERIVED) {
+        Ok(table) => Some(table),
+        Err(redb::TableError::TableDoesNotExist(_)) => None,
+        Err(err) => return Err(err.into()),
+      };
+
+      let mut updater = InscriptionUpdater {
+        blessed_inscription_count: statistic(Statistic::BlessedInscriptions)?,
+        cursed_inscription_count: statistic(Statistic::CursedInscriptions)?,
+        flotsam: Vec::new(),
+        height,
+        home_inscription_count,
+        home_inscriptions: &mut home_inscriptions,
+        id_to_sequence_number: &mut id_to_sequence_number,
+        inscription_number_to_sequence_number: &mut inscription_number_to_sequence_number,
+        lost_sats: statistic(Statistic::LostSats)?,
+        next_sequence_number,
+        reward: Height(height).subsidy(),
+        sat_to_sequence_number: &mut sat_to_sequence_number,
+        sequence_number_to_children: &mut sequence_number_to_children,
+        sequence_number_to_entry: &mut sequence_number_to_entry,
+        timestamp: u32::try_from(Utc::now().timestamp()).unwrap_or(u32::MAX),
+        transaction_buffer: Vec::new(),
+        transaction_id_to_transaction: &mut transaction_id_to_transaction,
+        unbound_inscriptions: statistic(Statistic::UnboundInscriptions)?,
+        tap_db: inscription_updater::TapBatch::new(&mut tap_kv)
+          .with_base(rtx.0.open_table(TAP_KV)?),
+        tap_derived_db,
+        tap_delta_db: None,
+        tap_atomic_writes: None,
+        tap_atomic_overlay: None,
+        tap_atomic_list_len_cache: None,
+        tap_atomic_derived: None,
+        tap_route_index: None,
+        tap_route_index_verify: false,
+        list_len_cache: HashMap::new(),
+        block_availability_cache: HashMap::new(),
+        profile: false,
+        prof_bm_tr_us: 0,
+        prof_bm_tr_ct: 0,
+        prof_dmt_tr_us: 0,
+        prof_dmt_tr_ct: 0,
+        prof_prv_tr_us: 0,
+        prof_prv_tr_ct: 0,
+        prof_ttr_ex_us: 0,
+        prof_ttr_ex_ct: 0,
+        prof_tsend_ex_us: 0,
+        prof_tsend_ex_ct: 0,
+        prof_ttrade_ex_us: 0,
+        prof_ttrade_ex_ct: 0,
+        prof_tauth_ex_us: 0,
+        prof_tauth_ex_ct: 0,
+        prof_pra_ex_us: 0,
+        prof_pra_ex_ct: 0,
+        prof_blk_ex_us: 0,
+        prof_blk_ex_ct: 0,
+        prof_unblk_ex_us: 0,
+        prof_unblk_ex_ct: 0,
+        prof_created_total_us: 0,
+        prof_created_ct: 0,
+        prof_bm_cr_us: 0,
+        prof_bm_cr_ct: 0,
+        prof_dmt_el_cr_us: 0,
+        prof_dmt_el_cr_ct: 0,
+        prof_dpl_cr_us: 0,
+        prof_dpl_cr_ct: 0,
+        prof_dmtmint_cr_us: 0,
+        prof_dmtmint_cr_ct: 0,
+        prof_mint_cr_us: 0,
+        prof_mint_cr_ct: 0,
+        prof_ttr_cr_us: 0,
+        prof_ttr_cr_ct: 0,
+        prof_tsend_cr_us: 0,
+        prof_tsend_cr_ct: 0,
+        prof_ttrade_cr_us: 0,
+        prof_ttrade_cr_ct: 0,
+        prof_tauth_cr_us: 0,
+        prof_tauth_cr_ct: 0,
+        prof_dmtdep_cr_us: 0,
+        prof_dmtdep_cr_ct: 0,
+        prof_pra_cr_us: 0,
+        prof_pra_cr_ct: 0,
+        prof_prv_cr_us: 0,
+        prof_prv_cr_ct: 0,
+        prof_blk_cr_us: 0,
+        prof_blk_cr_ct: 0,
+        prof_unblk_cr_us: 0,
+        prof_unblk_cr_ct: 0,
+        // Core inscription-path profiling
+        prof_core_env_ms: 0,
+        prof_core_old_ms: 0,
+        prof_core_new_ms: 0,
+        prof_core_parent_ms: 0,
+        prof_core_txdb_ms: 0,
+        prof_core_addr_ms: 0,
+        prof_core_update_ms: 0,
+        prof_core_event_ms: 0,
+        prof_core_event_ct: 0,
+        prof_core_old_ct: 0,
+        prof_core_new_ct: 0,
+        prof_core_txdb_ct: 0,
+        prof_core_addr_ct: 0,
+        prof_core_update_ct: 0,
+        prof_core_up_old_ms: 0,
+        prof_core_up_old_ct: 0,
+        prof_core_up_new_ms: 0,
+        prof_core_up_new_ct: 0,
+        prof_core_up_new_parents_us: 0,
+        prof_core_up_new_entry_us: 0,
+        prof_core_up_new_serialize_us: 0,
+        prof_core_up_new_maps_us: 0,
+        prof_core_up_new_num_us: 0,
+        prof_core_up_new_sat_us: 0,
+        prof_core_up_new_delegate_us: 0,
+        prof_core_up_tap_us: 0,
+        prof_core_up_utxo_us: 0,
+        delegate_cache: HashMap::new(),
+        delegate_payload_cache: HashMap::new(),
+        btc_network: self.settings.chain().network(),
+        tap_snapshot_height: None,
+        emit_events: false,
+        tap_amm_candle_intervals: self.settings.tap_amm_candle_intervals(),
+        preview_base: Some(base),
+      };
+
+      updater.tap_atomic_begin();
+      let result = f(&rtx, &mut updater);
+      updater.tap_atomic_abort();
+      result
+    };
+
+    wtx.abort()?;
+
+    result
+  }
+
+  fn tap_preview_collect(
+    updater: &mut InscriptionUpdater,
+    txid: Txid,
+    height: u32,
+  ) -> Result<TapPreview> {
+    let writes = updater.tap_atomic_writes.clone().unwrap_or_default();
+    let overlay = updater.tap_atomic_overlay.clone().unwrap_or_default();
+
+    let mut pairs = BTreeSet::new();
+    let mut events = Vec::new();
+
+    for key in &writes {
+      if let Some((address, tick_key)) = key
+        .strip_prefix("b/")
+        .or_else(|| key.strip_prefix("t/"))
+        .and_then(|rest| rest.split_once('/'))
+      {
+        pairs.insert((address.to_string(), tick_key.to_string()));
+      } else if let Some(kind) = key
+        .strip_prefix("blcki/")
+        .and_then(|rest| rest.rsplitn(3, '/').nth(2))
+      {
+        let Some(Some((_, value))) = overlay.get(key) else {
+          continue;
+        };
+        let record = match value.as_str() {
+          Some(pointer) => match overlay.get(pointer) {
+            Some(Some((_, record))) => record.clone(),
+            _ => updater
+              .tap_db
+              .get(pointer.as_bytes())?
+              .and_then(|bytes| Index::tap_export_value_string(&bytes))
+              .and_then(|record| serde_json::from_str(&record).ok())
+              .unwrap_or_else(|| value.clone()),
+          },
+          None => value.clone(),
+        };
+        events.push(TapPreviewEvent {
+          kind: kind.to_string(),
+          fail: record
+            .get("fail")
+            .and_then(|fail| fail.as_bool())
+            .unwrap_or_default(),
+          record,
+        });
+      }
+    }
+
+    let mut amount = |key: String| -> Result<(String, String)> {
+      let before = updater
+        .tap_db
+        .get(key.as_bytes())?
+        .and_then(|bytes| Index::tap_decode_string_bytes(&bytes))
+        .unwrap_or_else(|| "0".into());
+      let after = match overlay.get(&key) {
+        Some(Some((bytes, _))) => {
+          Index::tap_decode_string_bytes(bytes).unwrap_or_else(|| "0".into())
+        }
+        Some(None) => "0".into(),
+        None => before.clone(),
+      };
+      Ok((before, after))
+    };
+
+    let mut balances = Vec::new();
+    for (address, tick_key) in pairs {
+      let (balance_before, balance_after) = amount(format!("b/{address}/{tick_key}"))?;
+      let (transferable_before, transferable_after) = amount(format!("t/{address}/{tick_key}"))?;
+      if balance_before == balance_after && transferable_before == transferable_after {
+        continue;
+      }
+      balances.push(TapPreviewBalance {
+        tick: serde_json::from_str::<String>(&tick_key).unwrap_or(tick_key),
+        address,
+        balance_before,
+        balance_after,
+        transferable_before,
+        transferable_after,
+      });
+    }
+
+    Ok(TapPreview {
+      txid,
+      height,
+      balances,
+      events,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::index::testing::Context};
+
+  fn inscribe(context: &Context, input: usize, body: &str) -> Txid {
+    context.core.broadcast_tx(TransactionTemplate {
+      inputs: &[(input, 0, 0, inscription("text/plain", body).to_witness())],
+      ..default()
+    })
+  }
+
+  #[test]
+  fn preview_projects_mint_without_persisting_it() {
+    let context = Context::builder().arg("--index-addresses").build();
+    context.mine_blocks(2);
+    inscribe(
+      &context,
+      1,
+      r#"{"p":"tap","op":"token-deploy","tick":"tap","max":"1000","lim":"100","dec":0}"#,
+    );
+    context.mine_blocks(1);
+
+    inscribe(
+      &context,
+      2,
+      r#"{"p":"tap","op":"token-mint","tick":"tap","amt":"100"}"#,
+    );
+    let tx = context.core.mempool().remove(0);
+
+    let digest = context
+      .index
+      .tap_export_state_digest()
+      .unwrap()
+      .state_digest;
+    let preview = context.index.tap_preview(&tx).unwrap();
+    assert_eq!(
+      context
+        .index
+        .tap_export_state_digest()
+        .unwrap()
+        .state_digest,
+      digest
+    );
+    assert_eq!(
+      context
+        .index
+        .get_inscriptions_for_output(OutPoint {
+          txid: tx.compute_txid(),
+          vout: 0,
+        })
+        .unwrap(),
+      Some(Vec::new())
+    );
+
+    assert_eq!(preview.txid, tx.compute_txid());
+    assert_eq!(preview.height, 4);
+    assert_eq!(preview.balances.len(), 1);
+    assert_eq!(preview.balances[0].tick, "tap");
+    assert_eq!(preview.balances[0].balance_before, "0");
+    assert_eq!(preview.balances[0].balance_after, "100");
+    assert!(preview
+      .events
+      .iter()
+      .any(|event| event.kind == "mnt" && !event.fail));
+  }
+
+  #[test]
+  fn preview_reports_failed_transfer() {
+    let context = Context::builder().build();
+    context.mine_blocks(3);
+    inscribe(
+      &context,
+      1,
+      r#"{"p":"tap","op":"token-deploy","tick":"tap","max":"1000","lim":"100","dec":0}"#,
+    );
+    context.mine_blocks(1);
+    inscribe(
+      &context,
+      2,
+      r#"{"p":"tap","op":"token-mint","tick":"tap","amt":"3"}"#,
+    );
+    context.mine_blocks(1);
+
+    inscribe(
+      &context,
+      3,
+      r#"{"p":"tap","op":"token-transfer","tick":"tap","amt":"5"}"#,
+    );
+    let preview = context
+      .index
+      .tap_preview(&context.core.mempool().remove(0))
+      .unwrap();
+
+    assert!(preview.balances.is_empty());
+    assert!(preview
+      .events
+      .iter()
+      .any(|event| event.kind == "trf" && event.fail));
+  }
+
+  #[test]
+  fn preview_does_not_take_the_write_lock() {
+    let context = Context::builder().build();
+    context.mine_blocks(2);
+    inscribe(
+      &context,
+      1,
+      r#"{"p":"tap","op":"token-deploy","tick":"tap","max":"1000","lim":"100","dec":0}"#,
+    );
+    context.mine_blocks(1);
+
+    inscribe(
+      &context,
+      2,
+      r#"{"p":"tap","op":"token-mint","tick":"tap","amt":"100"}"#,
+    );
+    let tx = context.core.mempool().remove(0);
+
+    let wtx = context.index.begin_write().unwrap();
+    let preview = context.index.tap_preview(&tx).unwrap();
+    wtx.abort().unwrap();
+
+    assert_eq!(preview.balances.len(), 1);
+    assert_eq!(preview.balances[0].balance_after, "100");
+  }
+
+  #[test]
+  fn preview_rejects_coinbase() {
+    let context = Context::builder().build();
+    context.mine_blocks(1);
+    assert!(context.index.tap_preview(&context.core.tx(1, 0)).is_err());
+  }
+}
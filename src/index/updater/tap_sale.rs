@@ -0,0 +1,468 @@
+use {super::*, inscription_updater::MAX_DEC_U64_STR};
+
+/// Proofs longer than this are rejected by sale contribution validation.
+const TAP_SALE_ALLOWLIST_MAX_DEPTH: usize = 32;
+
+/// One allowlisted contributor. `cap` is the most the address may contribute,
+/// in payment token units as written in its proof's `max`; without caps the
+/// tree uses `addr` leaves.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TapSaleAllowlistEntry {
+  pub(crate) claim: String,
+  pub(crate) cap: Option<String>,
+}
+
+/// A sale allowlist tree. `alw` goes into the `s` of a sale `auth-cfg`, and
+/// each proof's `alw` into the `contribute` actions of its claim address.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapSaleAllowlist {
+  pub(crate) alw: serde_json::Value,
+  pub(crate) proofs: Vec<TapSaleAllowlistProof>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapSaleAllowlistProof {
+  pub(crate) claim: String,
+  pub(crate) leaf: String,
+  pub(crate) alw: serde_json::Value,
+}
+
+/// Whether a sale action mined in the next block would pass, and the first
+/// block it can pass at. `from_block` is `None` when the sale's current status
+/// leaves no path to the action, for example refunds after finalization.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapSaleWindow {
+  pub(crate) allowed: bool,
+  pub(crate) from_block: Option<u32>,
+}
+
+/// One contributor's view of a sale. Amounts cover the contributor's
+/// contributions to this sale by claim address; `refundable` is in payment
+/// token units and `expected_allocation` in sale token units. `withdraw` and
+/// the reserve fields describe the authority's withdrawals, which the
+/// outstanding allocation is held back from.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapSaleProjection {
+  pub(crate) auth: String,
+  pub(crate) address: String,
+  pub(crate) block: u32,
+  pub(crate) phase: &'static str,
+  pub(crate) open_contributions: Vec<String>,
+  pub(crate) contributed: String,
+  pub(crate) expected_allocation: String,
+  pub(crate) claimed: String,
+  pub(crate) refunded: String,
+  pub(crate) refundable: String,
+  pub(crate) outstanding_allocation: String,
+  pub(crate) withdrawal_reserve: String,
+  pub(crate) claim: TapSaleWindow,
+  pub(crate) refund: TapSaleWindow,
+  pub(crate) withdraw: TapSaleWindow,
+}
+
+impl Index {
+  /// Build the `sha256-merkle` allowlist sale contributions are checked
+  /// against. Claims are normalized the way contribution validation does, and
+  /// caps are resolved with the payment token's `decimals`. Either every entry
+  /// has a cap or none does. Proofs are in `entries` order.
+  pub(crate) fn tap_sale_allowlist(
+    entries: &[TapSaleAllowlistEntry],
+    decimals: u32,
+  ) -> Result<TapSaleAllowlist> {
+    if entries.is_empty() {
+      bail!("allowlist is empty");
+    }
+    let capped = entries[0].cap.is_some();
+    let max_cap = InscriptionUpdater::resolve_number_string(MAX_DEC_U64_STR, decimals)
+      .and_then(|max| max.parse::<i128>().ok())
+      .ok_or_else(|| anyhow!("invalid payment token decimals {decimals}"))?;
+
+    let mut claims = HashSet::new();
+    let mut leaves = Vec::new();
+    for entry in entries {
+      let claim = InscriptionUpdater::normalize_address(&entry.claim);
+      if claim.is_empty() {
+        bail!("allowlist has an empty address");
+      }
+      if !claims.insert(claim.clone()) {
+        bail!("address {claim} is listed more than once");
+      }
+      if entry.cap.is_some() != capped {
+        bail!("either every address or none must have a cap");
+      }
+      let cap = entry
+        .cap
+        .as_deref()
+        .map(|cap| {
+          InscriptionUpdater::resolve_number_string(cap, decimals)
+            .and_then(|amount| amount.parse::<i128>().ok())
+            .filter(|amount| *amount > 0 && *amount <= max_cap)
+            .ok_or_else(|| anyhow!("invalid cap `{cap}` for {claim}"))
+        })
+        .transpose()?;
+      leaves.push(InscriptionUpdater::token_sale_allowlist_leaf(&claim, cap));
+    }
+
+    let mut levels = vec![leaves];
+    while levels[levels.len() - 1].len() > 1 {
+      let level = &levels[levels.len() - 1];
+      let parents = level
+        .chunks(2)
+        .map(|pair| match pair {
+          [left, right] => InscriptionUpdater::token_sale_merkle_parent(left, right)
+            .ok_or_else(|| anyhow!("invalid allowlist node")),
+          [node] => Ok(node.clone()),
+          _ => unreachable!(),
+        })
+        .collect::<Result<Vec<_>>>()?;
+      levels.push(parents);
+    }
+    if levels.len() - 1 > TAP_SALE_ALLOWLIST_MAX_DEPTH {
+      bail!("allowlist is too large for {TAP_SALE_ALLOWLIST_MAX_DEPTH} proof levels");
+    }
+
+    let proofs = entries
+      .iter()
+      .enumerate()
+      .map(|(index, entry)| {
+        let mut proof = Vec::new();
+        let mut position = index;
+        for level in &levels[..levels.len() - 1] {
+          if let Some(sibling) = level.get(position ^ 1) {
+            proof.push(sibling.clone());
+          }
+          position /= 2;
+        }
+        let mut alw = serde_json::json!({ "proof": proof });
+        if let Some(cap) = &entry.cap {
+          alw["max"] = cap.clone().into();
+        }
+        TapSaleAllowlistProof {
+          claim: InscriptionUpdater::normalize_address(&entry.claim),
+          leaf: levels[0][index].clone(),
+          alw,
+        }
+      })
+      .collect();
+
+    Ok(TapSaleAllowlist {
+      alw: serde_json::json!({
+        "ty": "sha256-merkle",
+        "lf": if capped { "addr-cap" } else { "addr" },
+        "root": levels[levels.len() - 1][0],
+      }),
+      proofs,
+    })
+  }
+
+  /// Project `address`'s contributions to sale `auth` as of `view`, for an
+  /// action mined in the next block. Windows follow the checks of
+  /// `claim-sale`, `refund-sale` and `withdraw-sale`. Claims and the
+  /// withdrawal of unsold tokens need the sale to be finalized first, so
+  /// their `from_block` is the first block `finalize-sale` or `resolve-sale`
+  /// can finalize it. Returns `None` when `auth` is not a sale.
+  pub(crate) fn tap_sale_projection(
+    view: &TapStateView,
+    auth: &str,
+    address: &str,
+  ) -> Result<Option<TapSaleProjection>> {
+    let Some(config) = view
+      .get_json_record(&format!("ah/{auth}"))?
+      .and_then(|record| serde_json::from_value::<AuthorityConfigRecord>(record).ok())
+      .filter(|config| config.k == "sale")
+    else {
+      return Ok(None);
+    };
+    let status = view
+      .get_json_record(&format!("sale/{auth}"))?
+      .unwrap_or_else(|| InscriptionUpdater::token_sale_status_default(auth, &config));
+    let address = InscriptionUpdater::normalize_address(address);
+    let block = view.height()?.map_or(0, |height| height + 1);
+
+    let mut open_contributions = Vec::new();
+    let (mut contributed, mut expected_allocation, mut claimed, mut refunded) = (0, 0, 0, 0);
+    for i in 0..view.get_length(&format!("sconcl/{address}"))? {
+      let Some(id) = view
+        .get_json_record(&format!("sconcli/{address}/{i}"))?
+        .and_then(|record| record["id"].as_str().map(str::to_string))
+      else {
+        continue;
+      };
+      let Some(contribution) = view
+        .get_json_record(&format!("scon/{id}"))?
+        .filter(|contribution| contribution["auth"].as_str() == Some(auth))
+      else {
+        continue;
+      };
+      let amount = |key: &str| {
+        contribution[key]
+          .as_str()
+          .and_then(|amount| amount.parse::<i128>().ok())
+          .unwrap_or(0)
+      };
+      match contribution["status"].as_str() {
+        Some("open") => {
+          contributed += amount("amt");
+          expected_allocation +=
+            InscriptionUpdater::token_sale_contribution_allocation(amount("amt"), &config)
+              .unwrap_or(0);
+          open_contributions.push(id);
+        }
+        Some("claimed") => claimed += amount("sa"),
+        Some("refunded") => refunded += amount("amt"),
+        _ => {}
+      }
+    }
+
+    let s = config.s.clone().unwrap_or_default();
+    let start_height = s.get("sh").and_then(InscriptionUpdater::js_parse_int);
+    let end_height = s
+      .get("eh")
+      .and_then(InscriptionUpdater::js_parse_int)
+      .unwrap_or(i128::MAX);
+    let cap = |key: &str| {
+      s.get(key)
+        .and_then(|cap| cap.as_str())
+        .and_then(|cap| cap.parse::<i128>().ok())
+    };
+    let soft_cap = cap("sc").unwrap_or(0);
+    let hard_cap = cap("hc").unwrap_or(i128::MAX);
+    let total = InscriptionUpdater::token_sale_status_i128(&status, "tc");
+    let finalized = InscriptionUpdater::token_sale_status_bool(&status, "fin");
+    let cancelled = InscriptionUpdater::token_sale_status_bool(&status, "can");
+
+    let next = i128::from(block);
+    let after_end = next.max(end_height.saturating_add(1));
+    let at = |height: i128| u32::try_from(height).ok();
+    let finalize_from = if finalized || cancelled {
+      None
+    } else if total >= hard_cap {
+      at(next)
+    } else if total >= soft_cap {
+      at(after_end)
+    } else {
+      None
+    };
+    let open = !open_contributions.is_empty();
+
+    let claim_from = if finalized { at(next) } else { finalize_from }.filter(|_| open);
+    let refund_from = if finalized {
+      None
+    } else if cancelled {
+      at(next)
+    } else if total < soft_cap {
+      at(after_end)
+    } else {
+      None
+    }
+    .filter(|_| open);
+    let withdraw_from = if finalized || cancelled {
+      at(next)
+    } else if total < soft_cap {
+      at(after_end)
+    } else {
+      finalize_from
+    };
+
+    let sale_tick = config.st.clone().unwrap_or_default();
+    Ok(Some(TapSaleProjection {
+      auth: auth.into(),
+      address,
+      block,
+      phase: if finalized {
+        "finalized"
+      } else if cancelled && InscriptionUpdater::token_sale_status_bool(&status, "fail") {
+        "failed"
+      } else if cancelled {
+        "cancelled"
+      } else if start_height.is_some_and(|start| next < start) {
+        "upcoming"
+      } else if next <= end_height {
+        "open"
+      } else {
+        "ended"
+      },
+      open_contributions,
+      contributed: contributed.to_string(),
+      expected_allocation: expected_allocation.to_string(),
+      claimed: claimed.to_string(),
+      refunded: refunded.to_string(),
+      refundable: if refund_from.is_some() {
+        contributed
+      } else {
+        0
+      }
+      .to_string(),
+      outstanding_allocation: InscriptionUpdater::sale_outstanding_allocation(&status).to_string(),
+      withdrawal_reserve: InscriptionUpdater::sale_withdrawal_reserve(&config, &status, &sale_tick)
+        .to_string(),
+      claim: TapSaleWindow {
+        allowed: finalized && open,
+        from_block: claim_from,
+      },
+      refund: TapSaleWindow {
+        allowed: refund_from == Some(block),
+        from_block: refund_from,
+      },
+      withdraw: TapSaleWindow {
+        allowed: InscriptionUpdater::sale_withdraw_allowed(&config, &status, block),
+        from_block: withdraw_from,
+      },
+    }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::index::testing::Context, serde_json::json};
+
+  fn project(end_height: &str, status: serde_json::Value) -> TapSaleProjection {
+    let context = Context::builder().build();
+    let mut status_record = json!({
+      "auth": "s0",
+      "st": "sale",
+      "pt": "pay",
+      "tc": "150",
+      "inv": "300",
+      "alc": "225",
+      "clm": "0",
+      "ref": "0",
+      "wdr": "0",
+      "fin": false,
+      "can": false,
+      "fail": false,
+      "pp": false
+    });
+    for (key, value) in status.as_object().unwrap() {
+      status_record[key] = value.clone();
+    }
+    let contribution = |id: &str, auth: &str, status: &str, amount: &str, allocation: &str| {
+      (
+        format!("scon/{id}"),
+        json!({"id": id, "auth": auth, "status": status, "amt": amount, "sa": allocation})
+          .to_string(),
+      )
+    };
+    let pointer = |id: &str| json!({ "id": id }).to_string();
+    context
+      .index
+      .tap_test_put_raw_rows(vec![
+        (
+          "ah/s0".into(),
+          json!({
+            "id": "s0",
+            "k": "sale",
+            "st": "sale",
+            "pt": "pay",
+            "ctl": {"ty": "ta", "auth": "authority"},
+            "seq": 0,
+            "s": {
+              "sh": "0",
+              "eh": end_height,
+              "hc": "1000",
+              "sc": "100",
+              "r": {"cm": "fix", "pa": "2", "sa": "3", "rnd": "flr"}
+            },
+            "blck": 0,
+            "tx": "tx",
+            "vo": 0,
+            "val": "0",
+            "ins": "s0",
+            "num": 0,
+            "ts": 0
+          })
+          .to_string(),
+        ),
+        ("sale/s0".into(), status_record.to_string()),
+        ("sconcl/buyer".into(), "3".into()),
+        ("sconcli/buyer/0".into(), pointer("c0")),
+        ("sconcli/buyer/1".into(), pointer("c1")),
+        ("sconcli/buyer/2".into(), pointer("c2")),
+        contribution("c0", "s0", "open", "101", "151"),
+        contribution("c1", "other", "open", "500", "750"),
+        contribution("c2", "s0", "claimed", "20", "30"),
+      ])
+      .unwrap();
+    let view = context.index.tap_state_view(None).unwrap();
+    assert_eq!(
+      Index::tap_sale_projection(&view, "missing", "buyer").unwrap(),
+      None
+    );
+    Index::tap_sale_projection(&view, "s0", "buyer")
+      .unwrap()
+      .unwrap()
+  }
+
+  #[test]
+  fn projection_before_end_waits_for_finalization() {
+    let open = project("10", json!({}));
+    assert_eq!(open.block, 1);
+    assert_eq!(open.phase, "open");
+    assert_eq!(open.open_contributions, ["c0"]);
+    assert_eq!(open.contributed, "101");
+    assert_eq!(open.expected_allocation, "151");
+    assert_eq!(open.claimed, "30");
+    assert_eq!(open.refundable, "0");
+    assert_eq!(
+      open.claim,
+      TapSaleWindow {
+        allowed: false,
+        from_block: Some(11)
+      }
+    );
+    assert_eq!(
+      open.refund,
+      TapSaleWindow {
+        allowed: false,
+        from_block: None
+      }
+    );
+    assert_eq!(
+      open.withdraw,
+      TapSaleWindow {
+        allowed: false,
+        from_block: Some(11)
+      }
+    );
+
+    let sold_out = project("10", json!({"tc": "1000"}));
+    assert_eq!(sold_out.claim.from_block, Some(1));
+    assert!(!sold_out.claim.allowed);
+  }
+
+  #[test]
+  fn projection_follows_refund_and_claim_rules() {
+    let missed = project("0", json!({"tc": "50"}));
+    assert_eq!(missed.phase, "ended");
+    assert_eq!(missed.refundable, "101");
+    assert_eq!(
+      missed.refund,
+      TapSaleWindow {
+        allowed: true,
+        from_block: Some(1)
+      }
+    );
+    assert_eq!(missed.claim.from_block, None);
+    assert!(missed.withdraw.allowed);
+
+    let finalized = project("0", json!({"fin": true, "pp": true, "clm": "75"}));
+    assert_eq!(finalized.phase, "finalized");
+    assert_eq!(
+      finalized.claim,
+      TapSaleWindow {
+        allowed: true,
+        from_block: Some(1)
+      }
+    );
+    assert_eq!(finalized.refund.from_block, None);
+    assert_eq!(finalized.refundable, "0");
+    assert_eq!(finalized.outstanding_allocation, "150");
+    assert_eq!(finalized.withdrawal_reserve, "150");
+    assert!(finalized.withdraw.allowed);
+
+    let failed = project("0", json!({"can": true, "fail": true}));
+    assert_eq!(failed.phase, "failed");
+    assert!(failed.refund.allowed);
+    assert_eq!(failed.claim.from_block, None);
+  }
+}
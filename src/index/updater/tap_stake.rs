@@ -0,0 +1,355 @@
+use {super::*, num_bigint::BigInt, std::collections::BTreeMap};
+
+/// Blocks the reward rate looks back over unless the caller picks a window.
+pub(crate) const TAP_STAKE_REWARD_RATE_BLOCKS: u32 = 144;
+
+/// Rewards of one reward tick. `recent` is what the current shares would
+/// have earned from accumulator growth over the rate window, and
+/// `rate_per_block` is that spread over the window. Both are `None` when the
+/// window's start is outside the retained TAP history.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapStakeReward {
+  pub(crate) tick: String,
+  pub(crate) pending: String,
+  pub(crate) recent: Option<String>,
+  pub(crate) rate_per_block: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapStakePositionRewards {
+  pub(crate) position_id: String,
+  pub(crate) auth: String,
+  pub(crate) tick: String,
+  pub(crate) shares: String,
+  pub(crate) rewards: Vec<TapStakeReward>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapStakeAuthorityRewards {
+  pub(crate) auth: String,
+  pub(crate) shares: String,
+  pub(crate) rewards: Vec<TapStakeReward>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapStakeAddressRewards {
+  pub(crate) address: String,
+  pub(crate) rate_blocks: u32,
+  pub(crate) rate_from_block: Option<u32>,
+  pub(crate) positions: Vec<TapStakePositionRewards>,
+  pub(crate) authorities: Vec<TapStakeAuthorityRewards>,
+  pub(crate) totals: Vec<TapStakeReward>,
+}
+
+/// Running reward sum. `recent` becomes `None` once any part of it is unknown.
+struct TapStakeRewardSum {
+  pending: BigInt,
+  recent: Option<BigInt>,
+}
+
+impl Default for TapStakeRewardSum {
+  fn default() -> Self {
+    Self {
+      pending: BigInt::default(),
+      recent: Some(BigInt::default()),
+    }
+  }
+}
+
+impl TapStakeRewardSum {
+  fn add(&mut self, pending: &BigInt, recent: Option<&BigInt>) {
+    self.pending += pending;
+    self.recent = self
+      .recent
+      .take()
+      .zip(recent)
+      .map(|(sum, recent)| sum + recent);
+  }
+
+  fn reward(&self, tick: &str, rate_blocks: u32) -> TapStakeReward {
+    TapStakeReward {
+      tick: tick.into(),
+      pending: self.pending.to_string(),
+      recent: self.recent.as_ref().map(BigInt::to_string),
+      rate_per_block: self
+        .recent
+        .as_ref()
+        .map(|recent| (recent / rate_blocks.max(1)).to_string()),
+    }
+  }
+}
+
+impl Index {
+  /// Pending rewards of every open stake position claimable by `address`,
+  /// computed like `claim-reward` does, with per-authority and per-tick
+  /// totals. Reward rates compare each authority's `ahrps/` accumulator with
+  /// its value in `past`, the view `rate_blocks` blocks back, when given.
+  pub(crate) fn tap_stake_rewards_by_address(
+    view: &TapStateView,
+    past: Option<&TapStateView>,
+    address: &str,
+    rate_blocks: u32,
+  ) -> Result<TapStakeAddressRewards> {
+    let mut positions = Vec::new();
+    let mut authorities = BTreeMap::<String, (BigInt, BTreeMap<String, TapStakeRewardSum>)>::new();
+    let mut totals = BTreeMap::<String, TapStakeRewardSum>::new();
+    let mut accumulators = HashMap::<(String, String), (BigInt, Option<BigInt>)>::new();
+
+    for i in 0..view.get_length(&format!("spa/{address}"))? {
+      let Some(position_id) = view
+        .get_json_record(&format!("spai/{address}/{i}"))?
+        .and_then(|record| record["id"].as_str().map(str::to_string))
+      else {
+        continue;
+      };
+      let Some(position) = view
+        .get_json_record(&format!("sp/{position_id}"))?
+        .filter(|position| position["status"].as_str() == Some("open"))
+      else {
+        continue;
+      };
+      let auth = position["auth"].as_str().unwrap_or_default().to_string();
+      let shares = position["shares"]
+        .as_str()
+        .and_then(|shares| shares.parse::<BigInt>().ok())
+        .unwrap_or_default();
+
+      let (authority_shares, authority_rewards) = authorities.entry(auth.clone()).or_default();
+      *authority_shares += &shares;
+
+      let mut rewards = Vec::new();
+      for tick in Self::tap_stake_reward_ticks(view, &auth)? {
+        let key = (auth.clone(), tick.clone());
+        if !accumulators.contains_key(&key) {
+          let reward_key = InscriptionUpdater::json_stringify_lower(&tick);
+          let now = Self::tap_stake_accumulator(view, &auth, &reward_key)?;
+          let then = match past {
+            Some(past) => match Self::tap_stake_accumulator(past, &auth, &reward_key) {
+              Ok(then) => Some(then),
+              Err(err) if err.downcast_ref::<TapHistoryUnavailable>().is_some() => None,
+              Err(err) => return Err(err),
+            },
+            None => None,
+          };
+          accumulators.insert(key.clone(), (now, then));
+        }
+        let (now, then) = &accumulators[&key];
+
+        let paid = position["debt"][&tick]
+          .as_str()
+          .and_then(|paid| paid.parse::<BigInt>().ok())
+          .unwrap_or_default();
+        let pending = BigInt::from(
+          InscriptionUpdater::authority_reward_pending_i128(&shares, now, &paid).unwrap_or(0),
+        );
+        let recent = then.as_ref().map(|then| {
+          (&shares * (now - then) / InscriptionUpdater::authority_reward_precision())
+            .max(BigInt::default())
+        });
+
+        let mut sum = TapStakeRewardSum::default();
+        sum.add(&pending, recent.as_ref());
+        rewards.push(sum.reward(&tick, rate_blocks));
+
+        authority_rewards
+          .entry(tick.clone())
+          .or_default()
+          .add(&pending, recent.as_ref());
+        totals
+          .entry(tick)
+          .or_default()
+          .add(&pending, recent.as_ref());
+      }
+
+      positions.push(TapStakePositionRewards {
+        position_id,
+        auth,
+        tick: position["tick"].as_str().unwrap_or_default().to_string(),
+        shares: shares.to_string(),
+        rewards,
+      });
+    }
+
+    Ok(TapStakeAddressRewards {
+      address: address.into(),
+      rate_blocks,
+      rate_from_block: past.map(TapStateView::height).transpose()?.flatten(),
+      positions,
+      authorities: authorities
+        .into_iter()
+        .map(|(auth, (shares, rewards))| TapStakeAuthorityRewards {
+          auth,
+          shares: shares.to_string(),
+          rewards: rewards
+            .iter()
+            .map(|(tick, sum)| sum.reward(tick, rate_blocks))
+            .collect(),
+        })
+        .collect(),
+      totals: totals
+        .iter()
+        .map(|(tick, sum)| sum.reward(tick, rate_blocks))
+        .collect(),
+    })
+  }
+
+  /// Reward ticks of a staking authority, lowercased as position debts are
+  /// keyed: its configured `rt`, or else every tick it has held.
+  fn tap_stake_reward_ticks(view: &TapStateView, auth: &str) -> Result<Vec<String>> {
+    let configured = view
+      .get_json_record(&format!("ah/{auth}"))?
+      .and_then(|config| config["rt"].as_array().cloned())
+      .unwrap_or_default();
+    if !configured.is_empty() {
+      return Ok(
+        configured
+          .iter()
+          .filter_map(|tick| tick.as_str().map(tap_js_to_lowercase))
+          .collect(),
+      );
+    }
+    Ok(
+      view
+        .list_strings(&format!("abl/{auth}"), &format!("abli/{auth}"), 0, u64::MAX)?
+        .iter()
+        .map(|tick| tap_js_to_lowercase(tick))
+        .collect(),
+    )
+  }
+
+  fn tap_stake_accumulator(view: &TapStateView, auth: &str, reward_key: &str) -> Result<BigInt> {
+    Ok(
+      view
+        .get_string(&format!("ahrps/{auth}/{reward_key}"))?
+        .and_then(|acc| acc.parse::<BigInt>().ok())
+        .unwrap_or_default(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::index::testing::Context, serde_json::json};
+
+  fn context(rows: Vec<(String, String)>) -> Context {
+    let context = Context::builder().build();
+    context.index.tap_test_put_raw_rows(rows).unwrap();
+    context
+  }
+
+  fn accumulator(auth: &str, tick: &str, acc: &str) -> (String, String) {
+    (
+      format!("ahrps/{auth}/\"{tick}\""),
+      serde_json::to_string(acc).unwrap(),
+    )
+  }
+
+  fn position(
+    id: &str,
+    auth: &str,
+    shares: &str,
+    debt: serde_json::Value,
+    status: &str,
+  ) -> (String, String) {
+    (
+      format!("sp/{id}"),
+      json!({
+        "id": id,
+        "auth": auth,
+        "tick": "tap",
+        "shares": shares,
+        "debt": debt,
+        "status": status
+      })
+      .to_string(),
+    )
+  }
+
+  #[test]
+  fn rewards_are_summed_per_authority_and_tick() {
+    let pointer = |id: &str| json!({ "id": id }).to_string();
+    let current = context(vec![
+      (
+        "ah/a1".into(),
+        json!({"id": "a1", "rt": ["RWD"]}).to_string(),
+      ),
+      ("ah/a2".into(), json!({"id": "a2", "rt": []}).to_string()),
+      ("abl/a2".into(), "1".into()),
+      ("abli/a2/0".into(), "\"other\"".into()),
+      accumulator("a1", "rwd", "3000000000000000000"),
+      accumulator("a2", "other", "1000000000000000000"),
+      ("spa/addr".into(), "4".into()),
+      ("spai/addr/0".into(), pointer("p1")),
+      ("spai/addr/1".into(), pointer("p2")),
+      ("spai/addr/2".into(), pointer("p3")),
+      ("spai/addr/3".into(), pointer("p4")),
+      position("p1", "a1", "10", json!({"rwd": "5"}), "open"),
+      position("p2", "a2", "4", json!({}), "open"),
+      position("p3", "a1", "100", json!({}), "closed"),
+      position("p4", "a1", "2", json!({}), "open"),
+    ]);
+    let past = context(vec![accumulator("a1", "rwd", "1000000000000000000")]);
+
+    let view = current.index.tap_state_view(None).unwrap();
+    let rewards = Index::tap_stake_rewards_by_address(
+      &view,
+      Some(&past.index.tap_state_view(None).unwrap()),
+      "addr",
+      4,
+    )
+    .unwrap();
+
+    let reward = |tick: &str, pending: &str, recent: &str, rate: &str| TapStakeReward {
+      tick: tick.into(),
+      pending: pending.into(),
+      recent: Some(recent.into()),
+      rate_per_block: Some(rate.into()),
+    };
+    assert_eq!(
+      rewards
+        .positions
+        .iter()
+        .map(|position| position.position_id.as_str())
+        .collect::<Vec<_>>(),
+      ["p1", "p2", "p4"]
+    );
+    assert_eq!(
+      rewards.positions[0].rewards,
+      [reward("rwd", "25", "20", "5")]
+    );
+    assert_eq!(
+      rewards.authorities,
+      [
+        TapStakeAuthorityRewards {
+          auth: "a1".into(),
+          shares: "12".into(),
+          rewards: vec![reward("rwd", "31", "24", "6")],
+        },
+        TapStakeAuthorityRewards {
+          auth: "a2".into(),
+          shares: "4".into(),
+          rewards: vec![reward("other", "4", "4", "1")],
+        },
+      ]
+    );
+    assert_eq!(
+      rewards.totals,
+      [
+        reward("other", "4", "4", "1"),
+        reward("rwd", "31", "24", "6")
+      ]
+    );
+
+    let without_history = Index::tap_stake_rewards_by_address(&view, None, "addr", 4).unwrap();
+    assert_eq!(without_history.rate_from_block, None);
+    assert_eq!(
+      without_history.totals[1],
+      TapStakeReward {
+        tick: "rwd".into(),
+        pending: "31".into(),
+        recent: None,
+        rate_per_block: None,
+      }
+    );
+  }
+}
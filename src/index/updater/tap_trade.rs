@@ -0,0 +1,373 @@
+use {super::*, num_bigint::BigInt};
+
+/// Decimal places of order-book prices unless the caller asks for coarser
+/// levels.
+pub(crate) const TAP_TRADE_BOOK_PRICE_PRECISION: u32 = 18;
+
+/// A live offer on one side of a ticker pair. `amount` is in base units of
+/// the pair's first ticker and `total` in base units of the second, and
+/// `price` is the second per the first in whole tokens.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapTradeBookOffer {
+  pub(crate) trade_id: String,
+  pub(crate) address: String,
+  pub(crate) price: String,
+  pub(crate) amount: String,
+  pub(crate) total: String,
+  pub(crate) valid: i64,
+  pub(crate) block: u32,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapTradeBookLevel {
+  pub(crate) price: String,
+  pub(crate) offers: u64,
+  pub(crate) amount: String,
+  pub(crate) total: String,
+  pub(crate) cumulative_amount: String,
+  pub(crate) cumulative_total: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct TapTradeBook {
+  pub(crate) tick: String,
+  pub(crate) accept_tick: String,
+  pub(crate) precision: u32,
+  pub(crate) best_ask: Option<String>,
+  pub(crate) best_bid: Option<String>,
+  pub(crate) asks: Vec<TapTradeBookLevel>,
+  pub(crate) bids: Vec<TapTradeBookLevel>,
+}
+
+/// An offer with its exact price, `num / den`, kept for ordering.
+struct TapTradeBookEntry {
+  offer: TapTradeBookOffer,
+  num: BigInt,
+  den: BigInt,
+  amount: BigInt,
+  total: BigInt,
+}
+
+impl Index {
+  /// Open-offer book of `tick` against `accept_tick`. Asks are offers selling
+  /// `tick` for `accept_tick` and bids the reverse, both priced in
+  /// `accept_tick` per `tick` and aggregated into at most `depth` levels per
+  /// side. Ask prices are rounded up and bid prices down to `precision`
+  /// decimals. `None` when either ticker is not deployed.
+  pub(crate) fn tap_trade_book(
+    view: &TapStateView,
+    tick: &str,
+    accept_tick: &str,
+    precision: u32,
+    depth: usize,
+  ) -> Result<Option<TapTradeBook>> {
+    let Some(decimals) = Self::tap_trade_book_decimals(view, tick, accept_tick)? else {
+      return Ok(None);
+    };
+    let asks = Self::tap_trade_book_entries(view, tick, accept_tick, decimals, false)?;
+    let bids = Self::tap_trade_book_entries(view, accept_tick, tick, decimals, true)?;
+
+    Ok(Some(TapTradeBook {
+      tick: tap_js_to_lowercase(tick),
+      accept_tick: tap_js_to_lowercase(accept_tick),
+      precision,
+      best_ask: asks.first().map(|entry| entry.offer.price.clone()),
+      best_bid: bids.first().map(|entry| entry.offer.price.clone()),
+      asks: tap_trade_book_levels(&asks, precision, true, depth),
+      bids: tap_trade_book_levels(&bids, precision, false, depth),
+    }))
+  }
+
+  /// Open offers selling `tick` for `accept_tick`, cheapest first.
+  pub(crate) fn tap_trade_open_offers(
+    view: &TapStateView,
+    tick: &str,
+    accept_tick: &str,
+    offset: usize,
+    max: usize,
+  ) -> Result<Option<Vec<TapTradeBookOffer>>> {
+    let Some(decimals) = Self::tap_trade_book_decimals(view, tick, accept_tick)? else {
+      return Ok(None);
+    };
+    Ok(Some(
+      Self::tap_trade_book_entries(view, tick, accept_tick, decimals, false)?
+        .into_iter()
+        .skip(offset)
+        .take(max)
+        .map(|entry| entry.offer)
+        .collect(),
+    ))
+  }
+
+  fn tap_trade_book_decimals(
+    view: &TapStateView,
+    tick: &str,
+    accept_tick: &str,
+  ) -> Result<Option<(u32, u32)>> {
+    let decimals = |tick: &str| -> Result<Option<u32>> {
+      Ok(
+        view
+          .get_json_record(&format!(
+            "d/{}",
+            InscriptionUpdater::json_stringify_lower(tick)
+          ))?
+          .map(|deploy| {
+            deploy["dec"]
+              .as_u64()
+              .and_then(|dec| u32::try_from(dec).ok())
+              .unwrap_or(18)
+          }),
+      )
+    };
+    Ok(decimals(tick)?.zip(decimals(accept_tick)?))
+  }
+
+  /// Offers listed under the derived `tob/{sell}/{buy}` book, best price
+  /// first. Prices are always `decimals.1` per `decimals.0` tokens; `bids`
+  /// marks the reversed pair, whose offers sell the second ticker.
+  fn tap_trade_book_entries(
+    view: &TapStateView,
+    sell: &str,
+    buy: &str,
+    decimals: (u32, u32),
+    bids: bool,
+  ) -> Result<Vec<TapTradeBookEntry>> {
+    let pair = format!(
+      "{}/{}",
+      InscriptionUpdater::json_stringify_lower(sell),
+      InscriptionUpdater::json_stringify_lower(buy)
+    );
+    let scale = |decimals: u32| BigInt::from(10).pow(decimals);
+    let parse = |record: &serde_json::Value, field: &str| {
+      record[field]
+        .as_str()
+        .and_then(|value| value.parse::<BigInt>().ok())
+        .unwrap_or_default()
+    };
+
+    let mut entries = Vec::new();
+    for i in 0..view.get_derived_length(&format!("tob/{pair}"))? {
+      let Some(entry) = view.get_derived_json_record(&format!("tobi/{pair}/{i}"))? else {
+        continue;
+      };
+      let Some(offer) = entry["ptr"]
+        .as_str()
+        .map(|ptr| view.get_json_record(ptr))
+        .transpose()?
+        .flatten()
+      else {
+        continue;
+      };
+      let (sold, wanted) = (parse(&offer, "amt"), parse(&offer, "aamt"));
+      let (amount, total) = if bids { (wanted, sold) } else { (sold, wanted) };
+      if amount <= BigInt::default() || total <= BigInt::default() {
+        continue;
+      }
+      let num = &total * scale(decimals.0);
+      let den = &amount * scale(decimals.1);
+      entries.push(TapTradeBookEntry {
+        offer: TapTradeBookOffer {
+          trade_id: entry["id"].as_str().unwrap_or_default().to_string(),
+          address: offer["addr"].as_str().unwrap_or_default().to_string(),
+          price: tap_trade_format_price(&num, &den, TAP_TRADE_BOOK_PRICE_PRECISION, !bids),
+          amount: amount.to_string(),
+          total: total.to_string(),
+          valid: offer["vld"].as_i64().unwrap_or(-1),
+          block: offer["blck"]
+            .as_u64()
+            .and_then(|block| u32::try_from(block).ok())
+            .unwrap_or_default(),
+        },
+        num,
+        den,
+        amount,
+        total,
+      });
+    }
+
+    entries.sort_by(|a, b| {
+      let price = (&a.num * &b.den).cmp(&(&b.num * &a.den));
+      let price = if bids { price.reverse() } else { price };
+      price
+        .then(a.offer.block.cmp(&b.offer.block))
+        .then_with(|| a.offer.trade_id.cmp(&b.offer.trade_id))
+    });
+    Ok(entries)
+  }
+}
+
+/// Aggregates sorted offers into price levels, stopping at `depth` levels.
+fn tap_trade_book_levels(
+  entries: &[TapTradeBookEntry],
+  precision: u32,
+  round_up: bool,
+  depth: usize,
+) -> Vec<TapTradeBookLevel> {
+  let mut levels = Vec::<(String, u64, BigInt, BigInt)>::new();
+  for entry in entries {
+    let price = tap_trade_format_price(&entry.num, &entry.den, precision, round_up);
+    if let Some(level) = levels.last_mut().filter(|level| level.0 == price) {
+      level.1 += 1;
+      level.2 += &entry.amount;
+      level.3 += &entry.total;
+    } else if levels.len() == depth {
+      break;
+    } else {
+      levels.push((price, 1, entry.amount.clone(), entry.total.clone()));
+    }
+  }
+
+  let (mut cumulative_amount, mut cumulative_total) = (BigInt::default(), BigInt::default());
+  levels
+    .into_iter()
+    .map(|(price, offers, amount, total)| {
+      cumulative_amount += &amount;
+      cumulative_total += &total;
+      TapTradeBookLevel {
+        price,
+        offers,
+        amount: amount.to_string(),
+        total: total.to_string(),
+        cumulative_amount: cumulative_amount.to_string(),
+        cumulative_total: cumulative_total.to_string(),
+      }
+    })
+    .collect()
+}
+
+fn tap_trade_format_price(num: &BigInt, den: &BigInt, precision: u32, round_up: bool) -> String {
+  let scaled = num * BigInt::from(10).pow(precision);
+  let (quotient, remainder) = (&scaled / den, &scaled % den);
+  let quotient = if round_up && remainder != BigInt::default() {
+    quotient + 1
+  } else {
+    quotient
+  };
+  if precision == 0 {
+    return quotient.to_string();
+  }
+  let scale = BigInt::from(10).pow(precision);
+  format!(
+    "{}.{:0>width$}",
+    &quotient / &scale,
+    (&quotient % &scale).to_string(),
+    width = precision as usize
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::index::testing::Context, serde_json::json};
+
+  #[test]
+  fn book_sorts_offers_and_aggregates_price_levels() {
+    let context = Context::builder().build();
+    let mut rows = vec![
+      (
+        "d/\"foo\"".to_string(),
+        json!({"tick": "foo", "dec": 2}).to_string(),
+      ),
+      (
+        "d/\"bar\"".to_string(),
+        json!({"tick": "bar", "dec": 0}).to_string(),
+      ),
+    ];
+    let mut book_rows = Vec::new();
+    for (sell, buy, offers) in [
+      (
+        "foo",
+        "bar",
+        vec![
+          ("t1", "100", "3", 9),
+          ("t2", "200", "4", 10),
+          ("t3", "100", "2", 11),
+          ("t5", "200", "5", 12),
+        ],
+      ),
+      ("bar", "foo", vec![("t4", "3", "200", 13)]),
+    ] {
+      book_rows.push((
+        format!("tob/\"{sell}\"/\"{buy}\""),
+        offers.len().to_string(),
+      ));
+      for (i, (id, amt, aamt, blck)) in offers.into_iter().enumerate() {
+        let ptr = format!("atrofi/seller/\"{sell}\"/{id}");
+        book_rows.push((
+          format!("tobi/\"{sell}\"/\"{buy}\"/{i}"),
+          json!({"id": id, "ptr": ptr}).to_string(),
+        ));
+        rows.push((
+          ptr,
+          json!({"addr": "seller", "blck": blck, "tick": sell, "amt": amt, "atick": buy, "aamt": aamt, "vld": 100})
+            .to_string(),
+        ));
+      }
+    }
+    context.index.tap_test_put_raw_rows(rows).unwrap();
+    context.index.tap_test_put_derived_rows(book_rows).unwrap();
+    let view = context.index.tap_state_view(None).unwrap();
+
+    let level = |price: &str, offers: u64, amount: &str, total: &str, cumulative: (&str, &str)| {
+      TapTradeBookLevel {
+        price: price.into(),
+        offers,
+        amount: amount.into(),
+        total: total.into(),
+        cumulative_amount: cumulative.0.into(),
+        cumulative_total: cumulative.1.into(),
+      }
+    };
+
+    let book = Index::tap_trade_book(&view, "FOO", "bar", 18, 50)
+      .unwrap()
+      .unwrap();
+    assert_eq!(book.best_ask.as_deref(), Some("2.000000000000000000"));
+    assert_eq!(book.best_bid.as_deref(), Some("1.500000000000000000"));
+    assert_eq!(
+      book.asks,
+      [
+        level("2.000000000000000000", 2, "300", "6", ("300", "6")),
+        level("2.500000000000000000", 1, "200", "5", ("500", "11")),
+        level("3.000000000000000000", 1, "100", "3", ("600", "14")),
+      ]
+    );
+    assert_eq!(
+      book.bids,
+      [level("1.500000000000000000", 1, "200", "3", ("200", "3"))]
+    );
+
+    let book = Index::tap_trade_book(&view, "foo", "bar", 0, 50)
+      .unwrap()
+      .unwrap();
+    assert_eq!(
+      book.asks,
+      [
+        level("2", 2, "300", "6", ("300", "6")),
+        level("3", 2, "300", "8", ("600", "14")),
+      ]
+    );
+    assert_eq!(book.bids, [level("1", 1, "200", "3", ("200", "3"))]);
+    assert_eq!(
+      Index::tap_trade_book(&view, "foo", "bar", 0, 1)
+        .unwrap()
+        .unwrap()
+        .asks
+        .len(),
+      1
+    );
+
+    assert_eq!(
+      Index::tap_trade_open_offers(&view, "foo", "bar", 1, 2)
+        .unwrap()
+        .unwrap()
+        .iter()
+        .map(|offer| offer.trade_id.as_str())
+        .collect::<Vec<_>>(),
+      ["t3", "t5"]
+    );
+    assert_eq!(
+      Index::tap_trade_book(&view, "foo", "baz", 18, 50).unwrap(),
+      None
+    );
+  }
+}
@@ -0,0 +1,404 @@
+use {
+  super::{inscription_updater::TapRejection, *},
+  serde::{
+    de::{MapAccess, SeqAccess, Visitor},
+    Deserializer,
+  },
+  std::fmt,
+};
+
+/// Whether a TAP deploy or mint body would be accepted if it were inscribed
+/// in the next block. `valid` is `None` for bodies that are neither, which
+/// are not validated.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TapValidation {
+  pub(crate) height: u32,
+  pub(crate) op: Option<String>,
+  pub(crate) valid: Option<bool>,
+  pub(crate) reason: Option<String>,
+  pub(crate) warnings: Vec<String>,
+}
+
+impl Index {
+  /// Runs the indexer's deploy or mint checks on `body` at the next height,
+  /// as if it were inscribed to `address`. Privileged mints default to their
+  /// `prv.address`. Like previews, validations run on a read transaction and
+  /// never take the index write lock.
+  pub(crate) fn tap_validate(&self, body: &str, address: Option<&str>) -> Result<TapValidation> {
+    ensure!(
+      self.index_inscriptions,
+      "TAP validation requires an index with inscriptions"
+    );
+
+    self.tap_with_next_block_updater(|_rtx, updater| {
+      Ok(Self::tap_validate_body(updater, body, address))
+    })
+  }
+
+  fn tap_validate_body(
+    updater: &mut InscriptionUpdater,
+    body: &str,
+    address: Option<&str>,
+  ) -> TapValidation {
+    let mut validation = TapValidation {
+      height: updater.height,
+      op: None,
+      valid: None,
+      reason: None,
+      warnings: Vec::new(),
+    };
+
+    let reject = |mut validation: TapValidation, reason: TapRejection| {
+      validation.valid = Some(false);
+      validation.reason = Some(reason.to_string());
+      validation
+    };
+
+    let json = match updater.tap_parse_json(body) {
+      Ok(json) => json,
+      Err(reason) => return reject(validation, reason),
+    };
+
+    if let Ok(DuplicateKeys(keys)) =
+      serde_json::from_str(&InscriptionUpdater::preprocess_js_json_for_serde(body))
+    {
+      validation.warnings.extend(
+        keys
+          .into_iter()
+          .map(|key| format!("duplicate key `{key}`, only its last value is used")),
+      );
+    }
+
+    let field = |name: &str| {
+      json
+        .get(name)
+        .and_then(|value| value.as_str())
+        .unwrap_or_default()
+        .to_lowercase()
+    };
+    let (p, op) = (field("p"), field("op"));
+    validation.op = (!op.is_empty()).then(|| op.clone());
+
+    match (p.as_str(), op.as_str()) {
+      ("tap", "token-deploy") | ("brc-20", "deploy") => match updater.tap_check_deploy(&json, 0) {
+        Ok(deploy) => {
+          validation.valid = Some(true);
+          if deploy.dec == 18
+            && json
+              .get("dec")
+              .is_some_and(|dec| InscriptionUpdater::js_parse_int(dec) != Some(18))
+          {
+            validation
+              .warnings
+              .push("`dec` is not an integer from 0 to 18, 18 decimals are used".into());
+          }
+          validation
+        }
+        Err(reason) => reject(validation, reason),
+      },
+      ("tap", "token-mint") => {
+        let owner = address
+          .or_else(|| {
+            json
+              .get("prv")
+              .and_then(|prv| prv.get("address"))
+              .and_then(|address| address.as_str())
+          })
+          .unwrap_or_default()
+          .to_string();
+
+        match updater.tap_check_mint(&json, 0, &owner) {
+          Ok(mint) => match mint.fail {
+            Some(reason) => reject(validation, reason),
+            None => {
+              validation.valid = Some(true);
+              if mint.clamped {
+                validation.warnings.push(format!(
+                  "only {} base units are left to mint, the mint is reduced to them",
+                  mint.amount
+                ));
+              }
+              validation
+            }
+          },
+          Err(reason) => reject(validation, reason),
+        }
+      }
+      _ => {
+        validation.reason = Some(TapRejection::UnsupportedOperation.to_string());
+        validation
+      }
+    }
+  }
+}
+
+/// Object keys that appear more than once anywhere in a JSON document.
+struct DuplicateKeys(Vec<String>);
+
+impl<'de> Deserialize<'de> for DuplicateKeys {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    deserializer.deserialize_any(DuplicateKeysVisitor)
+  }
+}
+
+struct DuplicateKeysVisitor;
+
+impl<'de> Visitor<'de> for DuplicateKeysVisitor {
+  type Value = DuplicateKeys;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "a JSON value")
+  }
+
+  fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> {
+    Ok(DuplicateKeys(Vec::new()))
+  }
+
+  fn visit_i64<E>(self, _: i64) -> Result<Self::Value, E> {
+    Ok(DuplicateKeys(Vec::new()))
+  }
+
+  fn visit_u64<E>(self, _: u64) -> Result<Self::Value, E> {
+    Ok(DuplicateKeys(Vec::new()))
+  }
+
+  fn visit_f64<E>(self, _: f64) -> Result<Self::Value, E> {
+    Ok(DuplicateKeys(Vec::new()))
+  }
+
+  fn visit_str<E>(self, _: &str) -> Result<Self::Value, E> {
+    Ok(DuplicateKeys(Vec::new()))
+  }
+
+  fn visit_unit<E>(self) -> Result<Self::Value, E> {
+    Ok(DuplicateKeys(Vec::new()))
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+    let mut keys = Vec::new();
+    while let Some(DuplicateKeys(nested)) = seq.next_element()? {
+      keys.extend(nested);
+    }
+    Ok(DuplicateKeys(keys))
+  }
+
+  fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+    let mut seen = HashSet::new();
+    let mut keys = Vec::new();
+    while let Some(key) = map.next_key::<String>()? {
+      let DuplicateKeys(nested) = map.next_value()?;
+      if !seen.insert(key.clone()) {
+        keys.push(key);
+      }
+      keys.extend(nested);
+    }
+    Ok(DuplicateKeys(keys))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::index::testing::Context};
+
+  fn deploy(context: &Context, body: &str) {
+    context.core.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0, inscription("text/plain", body).to_witness())],
+      ..default()
+    });
+    context.mine_blocks(1);
+  }
+
+  #[track_caller]
+  fn rejected(context: &Context, body: &str) -> String {
+    let validation = context.index.tap_validate(body, None).unwrap();
+    assert_eq!(validation.valid, Some(false), "{body}");
+    validation.reason.unwrap()
+  }
+
+  #[test]
+  fn deploys_are_validated_at_the_next_height() {
+    let context = Context::builder().build();
+    context.mine_blocks(2);
+
+    let validation = context
+      .index
+      .tap_validate(
+        r#"{"p":"tap","op":"token-deploy","tick":"tap","max":"1000","lim":"100","dec":"x"}"#,
+        None,
+      )
+      .unwrap();
+    assert_eq!(
+      validation,
+      TapValidation {
+        height: 3,
+        op: Some("token-deploy".into()),
+        valid: Some(true),
+        reason: None,
+        warnings: vec!["`dec` is not an integer from 0 to 18, 18 decimals are used".into()],
+      }
+    );
+
+    assert_eq!(
+      rejected(&context, r#"{"p":"tap","op":"token-deploy","tick":"tap"}"#),
+      "missing `max`"
+    );
+    assert_eq!(
+      rejected(
+        &context,
+        r#"{"p":"tap","op":"token-deploy","tick":"dmt-tap","max":"1"}"#
+      ),
+      "tickers starting with `-` or `dmt-` cannot be deployed or minted with this operation"
+    );
+    assert_eq!(
+      rejected(
+        &context,
+        r#"{"p":"tap","op":"token-deploy","tick":"tap","max":1000}"#
+      ),
+      "`max`, `lim` and `amt` must be strings, raw numbers make the inscription invalid"
+    );
+    assert_eq!(
+      rejected(
+        &context,
+        r#"{"p":"tap","op":"token-deploy","tick":"tap","max":"1..5","dec":"0"}"#
+      ),
+      "`max` is not a valid amount with 0 decimals"
+    );
+    assert_eq!(
+      rejected(
+        &context,
+        r#"{"p":"tap","op":"token-deploy","tick":"tap","max":"18446744073709551616","dec":"0"}"#
+      ),
+      "`max` exceeds the cap of 18446744073709551615 whole tokens"
+    );
+    assert_eq!(
+      rejected(
+        &context,
+        r#"{"p":"tap","op":"token-deploy","tick":"tap","max":"10","lim":"0"}"#
+      ),
+      "`lim` must be greater than zero"
+    );
+    assert_eq!(
+      rejected(&context, r#"{"p":"tap","op":"token-deploy","#),
+      "body is not valid JSON: EOF while parsing a value at line 1 column 31"
+    );
+
+    deploy(
+      &context,
+      r#"{"p":"tap","op":"token-deploy","tick":"tap","max":"1000","lim":"100","dec":"0"}"#,
+    );
+    assert_eq!(
+      rejected(
+        &context,
+        r#"{"p":"tap","op":"token-deploy","tick":"TAP","max":"1000"}"#
+      ),
+      "ticker is already deployed"
+    );
+  }
+
+  #[test]
+  fn mints_are_validated_against_the_deployment() {
+    let context = Context::builder().build();
+    context.mine_blocks(2);
+
+    assert_eq!(
+      rejected(
+        &context,
+        r#"{"p":"tap","op":"token-mint","tick":"tap","amt":"1"}"#
+      ),
+      "ticker is not deployed"
+    );
+
+    deploy(
+      &context,
+      r#"{"p":"tap","op":"token-deploy","tick":"tap","max":"150","lim":"100","dec":"0"}"#,
+    );
+
+    let validation = context
+      .index
+      .tap_validate(
+        r#"{"p":"tap","op":"token-mint","tick":"tap","amt":"1","amt":"100"}"#,
+        None,
+      )
+      .unwrap();
+    assert_eq!(validation.valid, Some(true));
+    assert_eq!(
+      validation.warnings,
+      ["duplicate key `amt`, only its last value is used"]
+    );
+
+    assert_eq!(
+      rejected(
+        &context,
+        r#"{"p":"tap","op":"token-mint","tick":"tap","amt":"101"}"#
+      ),
+      "`amt` exceeds the mint limit of 100 base units, the mint would fail"
+    );
+    assert_eq!(
+      rejected(
+        &context,
+        r#"{"p":"tap","op":"token-mint","tick":"tap","amt":"0"}"#
+      ),
+      "`amt` must be greater than zero"
+    );
+
+    let digest = context
+      .index
+      .tap_export_state_digest()
+      .unwrap()
+      .state_digest;
+    context
+      .index
+      .tap_validate(
+        r#"{"p":"tap","op":"token-mint","tick":"tap","amt":"100"}"#,
+        None,
+      )
+      .unwrap();
+    assert_eq!(
+      context
+        .index
+        .tap_export_state_digest()
+        .unwrap()
+        .state_digest,
+      digest
+    );
+  }
+
+  #[test]
+  fn validation_does_not_take_the_write_lock() {
+    let context = Context::builder().build();
+    context.mine_blocks(2);
+
+    let wtx = context.index.begin_write().unwrap();
+    let validation = context
+      .index
+      .tap_validate(
+        r#"{"p":"tap","op":"token-mint","tick":"tap","amt":"1"}"#,
+        None,
+      )
+      .unwrap();
+    wtx.abort().unwrap();
+
+    assert_eq!(validation.valid, Some(false));
+    assert_eq!(validation.reason.as_deref(), Some("ticker is not deployed"));
+  }
+
+  #[test]
+  fn other_operations_are_not_validated() {
+    let context = Context::builder().build();
+    context.mine_blocks(1);
+
+    let validation = context
+      .index
+      .tap_validate(
+        r#"{"p":"tap","op":"token-transfer","tick":"tap","amt":"1"}"#,
+        None,
+      )
+      .unwrap();
+    assert_eq!(validation.op.as_deref(), Some("token-transfer"));
+    assert_eq!(validation.valid, None);
+    assert_eq!(
+      validation.reason.as_deref(),
+      Some("not a TAP token-deploy or token-mint")
+    );
+  }
+}
@@ -36,6 +36,11 @@ pub struct Settings {
   tap_writer_export_consumer_id: Option<String>,
   tap_writer_export_endpoint: Option<String>,
   tap_writer_export_token: Option<String>,
+  tap_writer_export_retain_blocks: Option<u32>,
+  tap_writer_export_retain_bytes: Option<u64>,
+  tap_writer_export_retain_consumers: bool,
+  tap_amm_candle_intervals: Option<Vec<u32>>,
+  tap_stream: bool,
   // TAP profiling: per-block timing breakdown
   tap_profile: bool,
 }
@@ -167,6 +172,18 @@ impl Settings {
       tap_writer_export_token: self
         .tap_writer_export_token
         .or(source.tap_writer_export_token),
+      tap_writer_export_retain_blocks: self
+        .tap_writer_export_retain_blocks
+        .or(source.tap_writer_export_retain_blocks),
+      tap_writer_export_retain_bytes: self
+        .tap_writer_export_retain_bytes
+        .or(source.tap_writer_export_retain_bytes),
+      tap_writer_export_retain_consumers: self.tap_writer_export_retain_consumers
+        || source.tap_writer_export_retain_consumers,
+      tap_amm_candle_intervals: self
+        .tap_amm_candle_intervals
+        .or(source.tap_amm_candle_intervals),
+      tap_stream: self.tap_stream || source.tap_stream,
       tap_profile: self.tap_profile || source.tap_profile,
     }
   }
@@ -212,6 +229,11 @@ impl Settings {
       tap_writer_export_consumer_id: None,
       tap_writer_export_endpoint: None,
       tap_writer_export_token: None,
+      tap_writer_export_retain_blocks: None,
+      tap_writer_export_retain_bytes: None,
+      tap_writer_export_retain_consumers: false,
+      tap_amm_candle_intervals: None,
+      tap_stream: false,
       tap_profile: options.tap_profile,
     }
   }
@@ -267,6 +289,34 @@ impl Settings {
         .with_context(|| format!("failed to parse environment variable ORD_{key} as u32"))
     };
 
+    let get_u64 = |key| {
+      env
+        .get(key)
+        .map(|int| int.parse::<u64>())
+        .transpose()
+        .with_context(|| format!("failed to parse environment variable ORD_{key} as u64"))
+    };
+
+    let get_intervals = |key| {
+      env
+        .get(key)
+        .map(|intervals| {
+          intervals
+            .split(',')
+            .map(str::trim)
+            .filter(|interval| !interval.is_empty())
+            .map(|interval| match interval.parse::<u32>() {
+              Ok(0) => Err(anyhow!("interval must be greater than zero")),
+              result => result.map_err(Error::from),
+            })
+            .collect::<Result<Vec<u32>>>()
+        })
+        .transpose()
+        .with_context(|| {
+          format!("failed to parse environment variable ORD_{key} as comma-separated seconds")
+        })
+    };
+
     let get_usize = |key| {
       env
         .get(key)
@@ -309,6 +359,11 @@ impl Settings {
       tap_writer_export_consumer_id: get_string("TAP_WRITER_EXPORT_CONSUMER_ID"),
       tap_writer_export_endpoint: get_string("TAP_WRITER_EXPORT_ENDPOINT"),
       tap_writer_export_token: get_string("TAP_WRITER_EXPORT_TOKEN"),
+      tap_writer_export_retain_blocks: get_u32("TAP_WRITER_EXPORT_RETAIN_BLOCKS")?,
+      tap_writer_export_retain_bytes: get_u64("TAP_WRITER_EXPORT_RETAIN_BYTES")?,
+      tap_writer_export_retain_consumers: get_bool("TAP_WRITER_EXPORT_RETAIN_CONSUMERS"),
+      tap_amm_candle_intervals: get_intervals("TAP_AMM_CANDLE_INTERVALS")?,
+      tap_stream: get_bool("TAP_STREAM"),
       tap_profile: get_bool("TAP_PROFILE"),
     })
   }
@@ -348,6 +403,11 @@ impl Settings {
       tap_writer_export_consumer_id: None,
       tap_writer_export_endpoint: None,
       tap_writer_export_token: None,
+      tap_writer_export_retain_blocks: None,
+      tap_writer_export_retain_bytes: None,
+      tap_writer_export_retain_consumers: false,
+      tap_amm_candle_intervals: None,
+      tap_stream: false,
       tap_profile: false,
     }
   }
@@ -431,6 +491,11 @@ impl Settings {
       tap_writer_export_consumer_id: self.tap_writer_export_consumer_id,
       tap_writer_export_endpoint: self.tap_writer_export_endpoint,
       tap_writer_export_token: self.tap_writer_export_token,
+      tap_writer_export_retain_blocks: self.tap_writer_export_retain_blocks,
+      tap_writer_export_retain_bytes: self.tap_writer_export_retain_bytes,
+      tap_writer_export_retain_consumers: self.tap_writer_export_retain_consumers,
+      tap_amm_candle_intervals: self.tap_amm_candle_intervals,
+      tap_stream: self.tap_stream,
       tap_profile: self.tap_profile,
     })
   }
@@ -675,6 +740,40 @@ impl Settings {
     self.tap_writer_export_token.as_deref()
   }
 
+  pub fn tap_writer_export_retain_blocks(&self) -> Option<u32> {
+    self.tap_writer_export_retain_blocks
+  }
+
+  pub fn tap_writer_export_retain_bytes(&self) -> Option<u64> {
+    self.tap_writer_export_retain_bytes
+  }
+
+  pub fn tap_writer_export_retain_consumers(&self) -> bool {
+    self.tap_writer_export_retain_consumers
+  }
+
+  /// Whether writer export deltas are pruned by a retention policy instead of
+  /// kept forever.
+  pub fn tap_writer_export_retention(&self) -> bool {
+    self.tap_writer_export_retain_blocks.is_some()
+      || self.tap_writer_export_retain_bytes.is_some()
+      || self.tap_writer_export_retain_consumers
+  }
+
+  /// Wall-clock AMM candle intervals in seconds, bucketed by block timestamp.
+  /// Per-block candles are always kept.
+  pub fn tap_amm_candle_intervals(&self) -> Vec<u32> {
+    self
+      .tap_amm_candle_intervals
+      .clone()
+      .unwrap_or_else(|| vec![3600, 86400])
+  }
+
+  /// Whether TAP block events are collected for `/r/tap/stream`.
+  pub fn tap_stream(&self) -> bool {
+    self.tap_stream
+  }
+
   pub fn tap_profile(&self) -> bool {
     self.tap_profile
   }
@@ -733,6 +832,7 @@ mod tests {
     assert_eq!(settings.tap_writer_export_consumer_id(), None);
     assert_eq!(settings.tap_writer_export_endpoint(), None);
     assert_eq!(settings.tap_writer_export_token(), None);
+    assert!(!settings.tap_writer_export_retention());
   }
 
   #[test]
@@ -771,6 +871,54 @@ mod tests {
     assert_eq!(settings.tap_writer_export_token(), Some("test-token"));
   }
 
+  #[test]
+  fn tap_writer_export_retention_reads_env_configuration() {
+    let mut env = BTreeMap::new();
+    env.insert(
+      "TAP_WRITER_EXPORT_RETAIN_BLOCKS".to_string(),
+      "10000".to_string(),
+    );
+    env.insert(
+      "TAP_WRITER_EXPORT_RETAIN_BYTES".to_string(),
+      "50000000000".to_string(),
+    );
+    env.insert(
+      "TAP_WRITER_EXPORT_RETAIN_CONSUMERS".to_string(),
+      "1".to_string(),
+    );
+    let settings = Settings::merge(Options::default(), env).unwrap();
+    assert!(settings.tap_writer_export_retention());
+    assert_eq!(settings.tap_writer_export_retain_blocks(), Some(10_000));
+    assert_eq!(
+      settings.tap_writer_export_retain_bytes(),
+      Some(50_000_000_000)
+    );
+    assert!(settings.tap_writer_export_retain_consumers());
+  }
+
+  #[test]
+  fn tap_amm_candle_intervals_read_env_configuration() {
+    let settings = Settings::merge(Options::default(), BTreeMap::new()).unwrap();
+    assert_eq!(settings.tap_amm_candle_intervals(), [3600, 86400]);
+
+    let mut env = BTreeMap::new();
+    env.insert(
+      "TAP_AMM_CANDLE_INTERVALS".to_string(),
+      "300, 3600".to_string(),
+    );
+    let settings = Settings::merge(Options::default(), env).unwrap();
+    assert_eq!(settings.tap_amm_candle_intervals(), [300, 3600]);
+
+    let mut env = BTreeMap::new();
+    env.insert("TAP_AMM_CANDLE_INTERVALS".to_string(), "".to_string());
+    let settings = Settings::merge(Options::default(), env).unwrap();
+    assert!(settings.tap_amm_candle_intervals().is_empty());
+
+    let mut env = BTreeMap::new();
+    env.insert("TAP_AMM_CANDLE_INTERVALS".to_string(), "0".to_string());
+    assert!(Settings::merge(Options::default(), env).is_err());
+  }
+
   #[test]
   fn auth_missing_rpc_user_is_an_error() {
     assert_eq!(
@@ -1265,6 +1413,10 @@ mod tests {
         tap_writer_export_consumer_id: None,
         tap_writer_export_endpoint: None,
         tap_writer_export_token: None,
+        tap_writer_export_retain_blocks: None,
+        tap_writer_export_retain_bytes: None,
+        tap_writer_export_retain_consumers: false,
+        tap_amm_candle_intervals: None,
         tap_profile: false,
       }
     );
@@ -1337,6 +1489,10 @@ mod tests {
         tap_writer_export_consumer_id: None,
         tap_writer_export_endpoint: None,
         tap_writer_export_token: None,
+        tap_writer_export_retain_blocks: None,
+        tap_writer_export_retain_bytes: None,
+        tap_writer_export_retain_consumers: false,
+        tap_amm_candle_intervals: None,
         tap_profile: false,
       }
     );
@@ -13,6 +13,7 @@ pub mod server;
 mod settings;
 pub mod subsidy;
 pub mod supply;
+pub mod tap;
 pub mod teleburn;
 pub mod traits;
 pub mod verify;
@@ -47,6 +48,8 @@ pub(crate) enum Subcommand {
   Subsidy(subsidy::Subsidy),
   #[command(about = "Display Bitcoin supply information")]
   Supply,
+  #[command(subcommand, about = "TAP commands")]
+  Tap(tap::TapSubcommand),
   #[command(about = "Generate teleburn addresses")]
   Teleburn(teleburn::Teleburn),
   #[command(about = "Display satoshi traits")]
@@ -80,6 +83,7 @@ impl Subcommand {
       Self::Settings => settings::run(settings),
       Self::Subsidy(subsidy) => subsidy.run(),
       Self::Supply => supply::run(),
+      Self::Tap(tap) => tap.run(settings),
       Self::Teleburn(teleburn) => teleburn.run(),
       Self::Traits(traits) => traits.run(),
       Self::Verify(verify) => verify.run(),
@@ -1,6 +1,7 @@
 use super::*;
 
 mod export;
+pub mod import_tap_snapshot;
 pub mod info;
 mod update;
 
@@ -8,6 +9,8 @@ mod update;
 pub(crate) enum IndexSubcommand {
   #[command(about = "Write inscription numbers and ids to a tab-separated file")]
   Export(export::Export),
+  #[command(about = "Load TAP state from a verified writer export snapshot")]
+  ImportTapSnapshot(import_tap_snapshot::ImportTapSnapshot),
   #[command(about = "Print index statistics")]
   Info(info::Info),
   #[command(about = "Update the index", alias = "run")]
@@ -18,6 +21,7 @@ impl IndexSubcommand {
   pub(crate) fn run(self, settings: Settings) -> SubcommandResult {
     match self {
       Self::Export(export) => export.run(settings),
+      Self::ImportTapSnapshot(import) => import.run(settings),
       Self::Info(info) => info.run(settings),
       Self::Update => update::run(settings),
     }
@@ -0,0 +1,82 @@
+use {
+  super::*,
+  crate::index::{TapExportSnapshotRow, TapExportStateDigest},
+  std::io::BufRead,
+};
+
+#[derive(Debug, Parser)]
+pub(crate) struct ImportTapSnapshot {
+  #[arg(
+    long,
+    help = "Verify the snapshot against the published state digest in <DIGEST>, as returned by `/r/tap/export/state-digest`."
+  )]
+  digest: PathBuf,
+  #[arg(
+    help = "Read snapshot rows from <SNAPSHOT>, one JSON row or `/r/tap/export/snapshot-read` page per line, in key order."
+  )]
+  snapshot: PathBuf,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Output {
+  pub height: u32,
+  pub row_count: u64,
+  pub state_digest: String,
+  pub routes: usize,
+}
+
+/// A snapshot line is either a single row or a page of rows.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Line {
+  Page { rows: Vec<TapExportSnapshotRow> },
+  Row(TapExportSnapshotRow),
+}
+
+impl ImportTapSnapshot {
+  pub(crate) fn run(self, settings: Settings) -> SubcommandResult {
+    let digest: TapExportStateDigest = serde_json::from_slice(
+      &fs::read(&self.digest)
+        .with_context(|| format!("failed to read TAP digest `{}`", self.digest.display()))?,
+    )
+    .with_context(|| format!("failed to parse TAP digest `{}`", self.digest.display()))?;
+
+    let snapshot = BufReader::new(
+      File::open(&self.snapshot)
+        .with_context(|| format!("failed to open TAP snapshot `{}`", self.snapshot.display()))?,
+    );
+
+    let index = Index::open(&settings)?;
+
+    let (import, routes) = index.import_tap_snapshot(&digest, Self::rows(snapshot))?;
+
+    Ok(Some(Box::new(Output {
+      height: import.height,
+      row_count: import.row_count,
+      state_digest: import.state_digest,
+      routes,
+    })))
+  }
+
+  fn rows(snapshot: impl BufRead) -> impl Iterator<Item = Result<TapExportSnapshotRow>> {
+    snapshot
+      .lines()
+      .enumerate()
+      .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+      .flat_map(|(i, line)| {
+        let rows = line.map_err(Error::from).and_then(|line| {
+          match serde_json::from_str(&line)
+            .with_context(|| format!("invalid TAP snapshot row on line {}", i + 1))?
+          {
+            Line::Page { rows } => Ok(rows),
+            Line::Row(row) => Ok(vec![row]),
+          }
+        });
+
+        match rows {
+          Ok(rows) => rows.into_iter().map(Ok).collect::<Vec<_>>(),
+          Err(err) => vec![Err(err)],
+        }
+      })
+  }
+}
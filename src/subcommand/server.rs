@@ -5,6 +5,7 @@ use {
     error::{OptionExt, ServerError, ServerResult},
   },
   super::*,
+  crate::index::TapExportRetention,
   crate::templates::{
     AddressHtml, BlockHtml, BlocksHtml, ChildrenHtml, ClockSvg, CollectionsHtml, HomeHtml,
     InputHtml, InscriptionHtml, InscriptionsBlockHtml, InscriptionsHtml, OutputHtml, PageContent,
@@ -14,7 +15,7 @@ use {
     TransactionHtml,
   },
   axum::{
-    extract::{DefaultBodyLimit, Extension, Json, Path, Query},
+    extract::{DefaultBodyLimit, Extension, Json, MatchedPath, Path, Query},
     http::{self, header, HeaderMap, HeaderName, HeaderValue, StatusCode, Uri},
     response::{IntoResponse, Redirect, Response},
     routing::{get, post},
@@ -59,7 +60,7 @@ enum SpawnConfig {
   Redirect(String),
 }
 
-enum TapWriterExportEndpoint {
+pub(crate) enum TapWriterExportEndpoint {
   Tcp(SocketAddr),
   #[cfg(unix)]
   Unix(PathBuf),
@@ -198,6 +199,10 @@ impl Server {
 
       let index_clone = index.clone();
       let integration_test = settings.integration_test();
+      let tap_export_retention = settings
+        .tap_writer_export_enabled()
+        .then(|| TapExportRetention::from_settings(&settings))
+        .flatten();
 
       let index_thread = thread::spawn(move || loop {
         if SHUTTING_DOWN.load(atomic::Ordering::Relaxed) {
@@ -210,6 +215,18 @@ impl Server {
           }
         }
 
+        if let Some(retention) = tap_export_retention {
+          match index_clone.prune_tap_export(retention) {
+            Ok(Some(prune)) if prune.bytes_removed > 0 => log::info!(
+              "tap export: retaining deltas from block {}, removed {} bytes",
+              prune.coverage_start,
+              prune.bytes_removed
+            ),
+            Ok(_) => {}
+            Err(error) => log::warn!("Pruning TAP writer export: {error}"),
+          }
+        }
+
         thread::sleep(if integration_test {
           Duration::from_millis(100)
         } else {
@@ -277,6 +294,7 @@ impl Server {
         )
         .route("/inscriptions/{page}", get(Self::inscriptions_paginated))
         .route("/install.sh", get(Self::install_script))
+        .route("/metrics", get(Self::metrics))
         .route("/ordinal/{sat}", get(Self::ordinal))
         .route("/output/{output}", get(Self::output))
         .route("/outputs", post(Self::outputs))
@@ -520,6 +538,18 @@ impl Server {
           "/r/tap/getTradesFilledList",
           get(r::tap_get_trades_filled_list),
         )
+        .route(
+          "/r/tap/getTradeOrderBook/{ticker}/{accept_ticker}",
+          get(r::tap_get_trade_order_book),
+        )
+        .route(
+          "/r/tap/getOpenTradeOffersLength/{ticker}/{accept_ticker}",
+          get(r::tap_get_open_trade_offers_length),
+        )
+        .route(
+          "/r/tap/getOpenTradeOffers/{ticker}/{accept_ticker}",
+          get(r::tap_get_open_trade_offers),
+        )
         // TAP token-auth endpoints
         .route(
           "/r/tap/getAuthCancelled/{inscription_id}",
@@ -571,6 +601,18 @@ impl Server {
           "/r/tap/getLockConsume/{lock_id}",
           get(r::tap_get_lock_consume),
         )
+        .route(
+          "/r/tap/getClaimableLocks/{address}",
+          get(r::tap_get_claimable_locks),
+        )
+        .route(
+          "/r/tap/getRefundableLocks/{address}",
+          get(r::tap_get_refundable_locks),
+        )
+        .route(
+          "/r/tap/getLocksByRefundHeight/{height}",
+          get(r::tap_get_locks_by_refund_height),
+        )
         .route(
           "/r/tap/getLockedBalance/{address}/{ticker}",
           get(r::tap_get_locked_balance),
@@ -793,10 +835,28 @@ impl Server {
           "/r/tap/getAmmPoolsByAsset/{asset_key}",
           get(r::tap_get_amm_pools_by_asset),
         )
+        .route(
+          "/r/tap/getAmmCandlesLength/{pool_id}",
+          get(r::tap_get_amm_candles_length),
+        )
+        .route("/r/tap/getAmmCandles/{pool_id}", get(r::tap_get_amm_candles))
+        .route("/r/tap/getAmmQuote/{pool_id}", get(r::tap_get_amm_quote))
+        .route(
+          "/r/tap/getAmmRouteQuote/{from_ticker}/{to_ticker}",
+          get(r::tap_get_amm_route_quote),
+        )
         .route(
           "/r/tap/getAmmPosition/{pool_id}/{target_type}/{target}",
           get(r::tap_get_amm_position),
         )
+        .route(
+          "/r/tap/getAmmPositionValue/{pool_id}/{target_type}/{target}",
+          get(r::tap_get_amm_position_value),
+        )
+        .route(
+          "/r/tap/getAmmPositionValuesByTarget/{target_type}/{target}",
+          get(r::tap_get_amm_position_values_by_target),
+        )
         .route(
           "/r/tap/getAmmPositionsByTargetLength/{target_type}/{target}",
           get(r::tap_get_amm_positions_by_target_length),
@@ -941,6 +1001,14 @@ impl Server {
           "/r/tap/getPerpPosition/{position_id}",
           get(r::tap_get_perp_position),
         )
+        .route(
+          "/r/tap/getPerpPositionRisk/{position_id}",
+          get(r::tap_get_perp_position_risk),
+        )
+        .route(
+          "/r/tap/getPerpGroupRisk/{group_id}",
+          get(r::tap_get_perp_group_risk),
+        )
         .route(
           "/r/tap/getPerpPositionListLength",
           get(r::tap_get_perp_position_list_length),
@@ -1234,6 +1302,10 @@ impl Server {
           "/r/tap/getPendingRewardsByPosition/{position_id}",
           get(r::tap_get_pending_rewards_by_position),
         )
+        .route(
+          "/r/tap/getPendingRewardsByAddress/{address}",
+          get(r::tap_get_pending_rewards_by_address),
+        )
         .route(
           "/r/tap/getRewardClaimListLength",
           get(r::tap_get_reward_claim_list_length),
@@ -1262,6 +1334,10 @@ impl Server {
           "/r/tap/getSaleStatus/{authority_id}",
           get(r::tap_get_sale_status),
         )
+        .route(
+          "/r/tap/getSaleProjection/{authority_id}/{address}",
+          get(r::tap_get_sale_projection),
+        )
         .route(
           "/r/tap/getSaleContributionsLength",
           get(r::tap_get_sale_contributions_length),
@@ -1737,7 +1813,12 @@ impl Server {
         .route(
           "/r/tap/getReorgs",
           get(r::tap_get_reorgs),
-        );
+        )
+        .route("/r/tap/batch", post(r::tap_batch))
+        .route("/r/tap/preview", post(r::tap_preview))
+        .route("/r/tap/preview/{txid}", get(r::tap_preview_txid))
+        .route("/r/tap/validate", post(r::tap_validate))
+        .route("/r/tap/stream", get(r::tap_stream));
 
       let router = if settings.tap_writer_export_endpoint().is_some() {
         router
@@ -1745,6 +1826,8 @@ impl Server {
         router.merge(Self::tap_writer_export_routes())
       };
 
+      let router = router.layer(axum::middleware::from_fn(Self::tap_snapshot_layer));
+
       let proxiable_routes = Router::new()
         .route("/content/{inscription_id}", get(r::content))
         .route("/r/children/{inscription_id}", get(r::children))
@@ -1761,7 +1844,9 @@ impl Server {
         )
         .layer(axum::middleware::from_fn(Self::proxy_layer));
 
-      let router = router.merge(proxiable_routes);
+      let router = router
+        .merge(proxiable_routes)
+        .layer(axum::middleware::from_fn(Self::tap_metrics_layer));
 
       let router = router
         .fallback(Self::fallback)
@@ -1866,6 +1951,7 @@ impl Server {
   {
     Router::new()
       .route("/r/tap/export/hello", get(r::tap_export_hello))
+      .route("/r/tap/export/ack", get(r::tap_export_ack))
       .route("/r/tap/export/snapshot", get(r::tap_export_snapshot))
       .route(
         "/r/tap/export/snapshot-open",
@@ -1892,14 +1978,15 @@ impl Server {
       .route("/r/tap/export/deltas", get(r::tap_export_deltas))
   }
 
-  fn tap_writer_export_router(index: Arc<Index>, settings: Arc<Settings>) -> Router {
+  pub(crate) fn tap_writer_export_router(index: Arc<Index>, settings: Arc<Settings>) -> Router {
     Self::tap_writer_export_routes::<()>()
+      .layer(axum::middleware::from_fn(Self::tap_snapshot_layer))
       .layer(Extension(index))
       .layer(Extension(settings))
       .layer(DefaultBodyLimit::disable())
   }
 
-  fn parse_tap_writer_export_endpoint(
+  pub(crate) fn parse_tap_writer_export_endpoint(
     endpoint: &str,
     public_bind: bool,
   ) -> Result<TapWriterExportEndpoint> {
@@ -2253,6 +2340,69 @@ impl Server {
     Ok(response)
   }
 
+  /// Time REST requests by matched route for `/metrics`.
+  async fn tap_metrics_layer(
+    Extension(index): Extension<Arc<Index>>,
+    request: http::Request<axum::body::Body>,
+    next: axum::middleware::Next,
+  ) -> Response {
+    let route = request
+      .extensions()
+      .get::<MatchedPath>()
+      .map(|path| path.as_str().to_owned())
+      .filter(|path| path.starts_with("/r/"));
+    let method = request.method().clone();
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+
+    if let Some(route) = route {
+      index.tap_metrics().record_request(
+        method.as_str(),
+        &route,
+        response.status().as_u16(),
+        start.elapsed(),
+      );
+    }
+
+    response
+  }
+
+  /// Refuse `/r/tap` requests while TAP state comes from a snapshot import
+  /// that has not been verified, or that ord has not yet indexed up to.
+  async fn tap_snapshot_layer(
+    Extension(index): Extension<Arc<Index>>,
+    request: http::Request<axum::body::Body>,
+    next: axum::middleware::Next,
+  ) -> ServerResult {
+    if request.uri().path().starts_with("/r/tap/") {
+      task::block_in_place(|| {
+        let Some(import) = index.tap_snapshot_import()? else {
+          return Ok(());
+        };
+
+        if !import.verified {
+          return Err(ServerError::ServiceUnavailable(format!(
+            "TAP snapshot import at height {} has not been verified",
+            import.height
+          )));
+        }
+
+        let block_count = index.block_count()?;
+        if block_count <= import.height {
+          return Err(ServerError::ServiceUnavailable(format!(
+            "TAP state was imported at height {} but the index has only indexed {} blocks",
+            import.height, block_count
+          )));
+        }
+
+        Ok(())
+      })?;
+    }
+
+    Ok(next.run(request).await)
+  }
+
   fn index_height(index: &Index) -> ServerResult<Height> {
     index.block_height()?.ok_or_not_found(|| "genesis block")
   }
@@ -2967,6 +3117,18 @@ impl Server {
     })
   }
 
+  async fn metrics(Extension(index): Extension<Arc<Index>>) -> ServerResult {
+    task::block_in_place(|| {
+      Ok(
+        (
+          [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+          index.tap_metrics_text()?,
+        )
+          .into_response(),
+      )
+    })
+  }
+
   async fn search_by_query(
     Extension(index): Extension<Arc<Index>>,
     Query(search): Query<Search>,
@@ -3674,6 +3836,629 @@ mod tests {
     );
   }
 
+  #[test]
+  fn tap_routes_are_refused_until_imported_snapshot_height_is_indexed() {
+    let server = TestServer::new();
+    server.index.tap_test_clear().unwrap();
+
+    let digest = crate::index::TapExportStateDigest {
+      source_height: 3,
+      ..server.index.tap_export_state_digest().unwrap()
+    };
+    server
+      .index
+      .import_tap_snapshot(&digest, std::iter::empty())
+      .unwrap();
+
+    server.assert_response(
+      "/r/tap/getDeploymentsLength",
+      StatusCode::SERVICE_UNAVAILABLE,
+      "TAP state was imported at height 2 but the index has only indexed 1 blocks",
+    );
+
+    server.mine_blocks(2);
+
+    assert_eq!(
+      server.get("/r/tap/getDeploymentsLength").status(),
+      StatusCode::OK
+    );
+  }
+
+  #[test]
+  fn metrics_report_indexed_height_and_rest_latency_by_route() {
+    let server = TestServer::new();
+    server.mine_blocks(1);
+
+    assert_eq!(
+      server.get("/r/tap/getBalance/alice/tap").status(),
+      StatusCode::OK
+    );
+
+    let response = server.get("/metrics");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+      response.headers().get(header::CONTENT_TYPE).unwrap(),
+      "text/plain; version=0.0.4"
+    );
+
+    let text = response.text().unwrap();
+    assert!(text.contains("ord_tap_indexed_height 1\n"));
+    assert!(text.contains(
+      "ord_tap_http_request_duration_seconds_count{method=\"GET\",route=\"/r/tap/getBalance/{address}/{ticker}\",status=\"200\"} 1\n"
+    ));
+    assert!(text.contains("ord_tap_block_index_seconds_count"));
+  }
+
+  #[test]
+  fn tap_batch_answers_lookups_from_one_snapshot() {
+    let server = TestServer::new();
+    server
+      .index
+      .tap_test_put_raw_rows([
+        ("b/alice/\"tap\"", "300"),
+        ("ab/pool/\"tap\"", "150"),
+        ("amm/p0", r#"{"k":"amm","r":["150","75"]}"#),
+      ])
+      .unwrap();
+
+    let response = reqwest::blocking::Client::new()
+      .post(server.join_url("/r/tap/batch"))
+      .json(&serde_json::json!({
+        "lookups": [
+          {"type": "balance", "address": "alice", "ticker": "TAP"},
+          {"type": "transferable", "address": "alice", "ticker": "tap"},
+          {"type": "lockedBalance", "address": "alice", "ticker": "tap"},
+          {"type": "authorityBalance", "authority_id": "pool", "ticker": "tap"},
+          {"type": "ammPool", "pool_id": "p0"},
+          {"type": "ammPool", "pool_id": "missing"},
+        ]
+      }))
+      .send()
+      .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+      response.json::<serde_json::Value>().unwrap(),
+      serde_json::json!({
+        "height": 0,
+        "result": [
+          "300",
+          null,
+          "0",
+          "150",
+          {"k": "amm", "r": ["150", "75"]},
+          null,
+        ]
+      })
+    );
+  }
+
+  #[test]
+  fn tap_batch_reports_pending_inscriptions() {
+    let server = TestServer::new();
+    server
+      .index
+      .tap_test_put_raw_rows([
+        ("tl/transferi0", r#""atrli/alice/\"tap\"/0""#),
+        (
+          "atrli/alice/\"tap\"/0",
+          r#"{"addr":"alice","blck":1,"amt":"5","trf":"5","bal":"10","tx":"t","vo":0,"val":"546","ins":"transferi0","num":0,"ts":0,"fail":false,"int":false}"#,
+        ),
+        ("tl/senti0", r#""""#),
+        (
+          "a/sendi0",
+          r#"{"op":"token-send","json":{},"ins":"sendi0","blck":1,"tx":"t","vo":0,"num":1,"ts":0,"addr":"bob"}"#,
+        ),
+      ])
+      .unwrap();
+
+    let response = reqwest::blocking::Client::new()
+      .post(server.join_url("/r/tap/batch"))
+      .json(&serde_json::json!({
+        "lookups": [
+          {"type": "pendingInscription", "inscription_id": "transferi0"},
+          {"type": "pendingInscription", "inscription_id": "senti0"},
+          {"type": "pendingInscription", "inscription_id": "sendi0"},
+        ]
+      }))
+      .send()
+      .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+      response.json::<serde_json::Value>().unwrap()["result"],
+      serde_json::json!([
+        {"op": "token-transfer", "addr": "alice"},
+        null,
+        {"op": "token-send", "addr": "bob"},
+      ])
+    );
+  }
+
+  #[test]
+  fn tap_batch_reports_dmt_reward_addresses() {
+    let server = TestServer::new();
+    server
+      .index
+      .tap_test_put_raw_rows([("dmtrwd/miner", r#""""#)])
+      .unwrap();
+
+    let response = reqwest::blocking::Client::new()
+      .post(server.join_url("/r/tap/batch"))
+      .json(&serde_json::json!({
+        "lookups": [
+          {"type": "dmtRewardAddress", "address": "miner"},
+          {"type": "dmtRewardAddress", "address": "alice"},
+        ]
+      }))
+      .send()
+      .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+      response.json::<serde_json::Value>().unwrap()["result"],
+      serde_json::json!([true, false])
+    );
+  }
+
+  #[test]
+  fn tap_batch_reports_open_trade_offers() {
+    let server = TestServer::new();
+    server
+      .index
+      .tap_test_put_raw_rows([
+        (
+          "tol/t0",
+          r#"{"op":"token-trade","json":{},"ins":"t0","blck":1,"tx":"t","vo":0,"num":0,"ts":0,"addr":"seller"}"#,
+        ),
+        (r#"to/t0/"bar""#, r#""atrofi/seller/\"foo\"/0""#),
+        (
+          r#"atrofi/seller/"foo"/0"#,
+          r#"{"addr":"seller","blck":1,"tick":"foo","amt":"4","atick":"bar","aamt":"10","vld":100,"trf":"0","bal":"4","tx":"t","vo":0,"val":"546","ins":"t0","num":0,"ts":0,"fail":false}"#,
+        ),
+        (r#"to/t1/"bar""#, r#""atrofi/seller/\"foo\"/0""#),
+      ])
+      .unwrap();
+
+    let response = reqwest::blocking::Client::new()
+      .post(server.join_url("/r/tap/batch"))
+      .json(&serde_json::json!({
+        "lookups": [
+          {"type": "tradeOffer", "trade_id": "t0", "ticker": "BAR"},
+          {"type": "tradeOffer", "trade_id": "t0", "ticker": "baz"},
+          {"type": "tradeOffer", "trade_id": "t1", "ticker": "bar"},
+        ]
+      }))
+      .send()
+      .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let result = response.json::<serde_json::Value>().unwrap()["result"].take();
+    assert_eq!(result[0]["addr"], "seller");
+    assert_eq!(result[0]["amt"], "4");
+    assert_eq!(result[0]["aamt"], "10");
+    assert_eq!(result[1], serde_json::Value::Null);
+    assert_eq!(result[2], serde_json::Value::Null);
+  }
+
+  #[test]
+  fn tap_batch_rejects_oversized_batches() {
+    let server = TestServer::new();
+
+    let lookup = serde_json::json!({"type": "balance", "address": "alice", "ticker": "tap"});
+    let response = reqwest::blocking::Client::new()
+      .post(server.join_url("/r/tap/batch"))
+      .json(&serde_json::json!({ "lookups": vec![lookup; 501] }))
+      .send()
+      .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+      response.text().unwrap(),
+      "batch has 501 lookups, at most 500 are allowed"
+    );
+  }
+
+  #[test]
+  fn tap_validate_reports_rejection_reasons() {
+    let server = TestServer::new();
+
+    let validate = |body: &str| {
+      let response = reqwest::blocking::Client::new()
+        .post(server.join_url("/r/tap/validate?address=bc1qxyz"))
+        .body(body.to_string())
+        .send()
+        .unwrap();
+      assert_eq!(response.status(), StatusCode::OK);
+      response.json::<serde_json::Value>().unwrap()
+    };
+
+    let validation = validate(r#"{"p":"tap","op":"token-deploy","tick":"tap","max":"21000000"}"#);
+    assert_eq!(validation["op"], "token-deploy");
+    assert_eq!(validation["valid"], false);
+    assert_eq!(
+      validation["reason"],
+      "TAP token deployments start at block 801993"
+    );
+
+    let validation = validate(r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"21000000"}"#);
+    assert_eq!(validation["height"], 1);
+    assert_eq!(validation["valid"], true);
+    assert_eq!(validation["reason"], serde_json::Value::Null);
+
+    let validation = validate(r#"{"p":"tap","op":"token-mint","tick":"tap","amt":"1"}"#);
+    assert_eq!(validation["valid"], false);
+    assert_eq!(validation["reason"], "ticker is not deployed");
+  }
+
+  #[test]
+  fn tap_amm_candles_page_by_interval() {
+    let server = TestServer::new();
+    server
+      .index
+      .tap_test_put_derived_rows([
+        ("ammc/p0/blk", "2"),
+        ("ammci/p0/blk/0", r#"{"t":5,"c":"1.000000000000000000"}"#),
+        ("ammci/p0/blk/1", r#"{"t":6,"c":"0.500000000000000000"}"#),
+        ("ammc/p0/3600", "1"),
+        ("ammci/p0/3600/0", r#"{"t":0,"c":"0.500000000000000000"}"#),
+      ])
+      .unwrap();
+
+    server.assert_response(
+      "/r/tap/getAmmCandlesLength/p0",
+      StatusCode::OK,
+      r#"{"result":2}"#,
+    );
+    server.assert_response(
+      "/r/tap/getAmmCandles/p0?offset=1",
+      StatusCode::OK,
+      r#"{"result":[{"t":6,"c":"0.500000000000000000"}]}"#,
+    );
+    server.assert_response(
+      "/r/tap/getAmmCandles/p0?interval=3600",
+      StatusCode::OK,
+      r#"{"result":[{"t":0,"c":"0.500000000000000000"}]}"#,
+    );
+    server.assert_response(
+      "/r/tap/getAmmCandles/p0?interval=hour",
+      StatusCode::BAD_REQUEST,
+      "interval must be `blk` or a number of seconds",
+    );
+  }
+
+  #[test]
+  fn tap_amm_position_value_converts_shares_to_amounts() {
+    let server = TestServer::new();
+    server
+      .index
+      .tap_test_put_raw_rows([
+        (
+          "amm/p0",
+          r#"{"id":"p0","k":"amm","ctl":{},"seq":0,"r":["1000","4000"],"a":[{"ty":"tap","tick":"aaa"},{"ty":"tap","tick":"bbb"}],"sh":"1000","fee":"30","pf":"2000","blck":1,"tx":"tx","vo":0,"val":"546","ins":"ins","num":0,"ts":0}"#,
+        ),
+        ("ammp/p0/a/owner", "250"),
+        ("ammpr/p0/a/owner", r#"{"pid":"p0","tt":"a","to":"owner","sh":"250"}"#),
+        ("amma/a/owner", "1"),
+        ("ammai/a/owner/0", r#""ammpr/p0/a/owner""#),
+      ])
+      .unwrap();
+    server
+      .index
+      .tap_test_put_derived_rows([(
+        "ammpb/p0/a/owner",
+        r#"{"dep":["250","1000"],"liq":"500000000000000000000","r":["1000","4000"],"blck":3}"#,
+      )])
+      .unwrap();
+
+    let value = |path: &str| {
+      reqwest::blocking::get(server.join_url(path))
+        .unwrap()
+        .json::<serde_json::Value>()
+        .unwrap()
+    };
+
+    let position = value("/r/tap/getAmmPositionValue/p0/a/owner");
+    assert_eq!(
+      position["result"]["amounts"],
+      serde_json::json!(["250", "1000"])
+    );
+    assert_eq!(position["result"]["value"], "2000");
+    assert_eq!(
+      position["result"]["fees_earned"],
+      serde_json::json!(["0", "0"])
+    );
+    assert_eq!(position["result"]["impermanent_loss_bps"], "0");
+    assert_eq!(position["result"]["basis_block"], 3);
+
+    let by_target = value("/r/tap/getAmmPositionValuesByTarget/a/owner");
+    assert_eq!(by_target["result"][0], position["result"]);
+
+    assert_eq!(
+      value("/r/tap/getAmmPositionValue/p0/a/stranger")["result"],
+      serde_json::Value::Null
+    );
+  }
+
+  #[test]
+  fn tap_sale_projection_reports_refund_window() {
+    let server = TestServer::new();
+    server
+      .index
+      .tap_test_put_raw_rows([
+        (
+          "ah/s0",
+          r#"{"id":"s0","k":"sale","st":"sale","pt":"pay","ctl":{"ty":"ta","auth":"authority"},"seq":0,"s":{"sh":"0","eh":"0","hc":"1000","sc":"100","r":{"cm":"fix","pa":"1","sa":"2","rnd":"flr"}},"blck":0,"tx":"tx","vo":0,"val":"0","ins":"s0","num":0,"ts":0}"#,
+        ),
+        ("sale/s0", r#"{"auth":"s0","tc":"40","alc":"80","fin":false,"can":false}"#),
+        ("sconcl/buyer", "1"),
+        ("sconcli/buyer/0", r#"{"id":"c0"}"#),
+        (
+          "scon/c0",
+          r#"{"id":"c0","auth":"s0","status":"open","amt":"40","sa":"80"}"#,
+        ),
+      ])
+      .unwrap();
+
+    let get = |path: &str| {
+      reqwest::blocking::get(server.join_url(path))
+        .unwrap()
+        .json::<serde_json::Value>()
+        .unwrap()
+    };
+
+    let projection = get("/r/tap/getSaleProjection/s0/buyer");
+    assert_eq!(projection["result"]["phase"], "ended");
+    assert_eq!(projection["result"]["expected_allocation"], "80");
+    assert_eq!(projection["result"]["refundable"], "40");
+    assert_eq!(projection["result"]["refund"]["allowed"], true);
+    assert_eq!(
+      projection["result"]["claim"]["from_block"],
+      serde_json::Value::Null
+    );
+
+    assert_eq!(
+      get("/r/tap/getSaleProjection/missing/buyer")["result"],
+      serde_json::Value::Null
+    );
+  }
+
+  #[test]
+  fn tap_pending_rewards_by_address_sums_positions() {
+    let server = TestServer::new();
+    server
+      .index
+      .tap_test_put_raw_rows([
+        ("ah/a0", r#"{"id":"a0","rt":["RWD"]}"#),
+        (r#"ahrps/a0/"rwd""#, r#""2000000000000000000""#),
+        ("spa/staker", "2"),
+        ("spai/staker/0", r#"{"id":"p0"}"#),
+        ("spai/staker/1", r#"{"id":"p1"}"#),
+        (
+          "sp/p0",
+          r#"{"id":"p0","auth":"a0","tick":"tap","shares":"10","debt":{"rwd":"5"},"status":"open"}"#,
+        ),
+        (
+          "sp/p1",
+          r#"{"id":"p1","auth":"a0","tick":"tap","shares":"3","debt":{},"status":"open"}"#,
+        ),
+      ])
+      .unwrap();
+
+    let rewards =
+      reqwest::blocking::get(server.join_url("/r/tap/getPendingRewardsByAddress/staker"))
+        .unwrap()
+        .json::<serde_json::Value>()
+        .unwrap();
+    assert_eq!(
+      rewards["result"]["positions"][0]["rewards"][0]["pending"],
+      "15"
+    );
+    assert_eq!(rewards["result"]["authorities"][0]["shares"], "13");
+    assert_eq!(rewards["result"]["totals"][0]["tick"], "rwd");
+    assert_eq!(rewards["result"]["totals"][0]["pending"], "21");
+    assert_eq!(
+      rewards["result"]["totals"][0]["rate_per_block"],
+      serde_json::Value::Null
+    );
+
+    assert_eq!(
+      reqwest::blocking::get(
+        server.join_url("/r/tap/getPendingRewardsByAddress/staker?rate_blocks=0")
+      )
+      .unwrap()
+      .status(),
+      StatusCode::BAD_REQUEST
+    );
+  }
+
+  #[test]
+  fn tap_trade_order_book_lists_open_offers() {
+    let server = TestServer::new();
+    server
+      .index
+      .tap_test_put_raw_rows([
+        (r#"d/"foo""#, r#"{"tick":"foo","dec":0}"#),
+        (r#"d/"bar""#, r#"{"tick":"bar","dec":0}"#),
+        (
+          r#"atrofi/seller/"foo"/0"#,
+          r#"{"addr":"seller","blck":1,"tick":"foo","amt":"4","atick":"bar","aamt":"10","vld":100}"#,
+        ),
+      ])
+      .unwrap();
+    server
+      .index
+      .tap_test_put_derived_rows([
+        (r#"tob/"foo"/"bar""#, "1"),
+        (
+          r#"tobi/"foo"/"bar"/0"#,
+          r#"{"id":"t0","ptr":"atrofi/seller/\"foo\"/0"}"#,
+        ),
+      ])
+      .unwrap();
+
+    let get = |path: &str| reqwest::blocking::get(server.join_url(path)).unwrap();
+
+    let book = get("/r/tap/getTradeOrderBook/foo/bar?precision=1")
+      .json::<serde_json::Value>()
+      .unwrap();
+    assert_eq!(book["result"]["best_ask"], "2.500000000000000000");
+    assert_eq!(book["result"]["asks"][0]["price"], "2.5");
+    assert_eq!(book["result"]["bids"], serde_json::json!([]));
+
+    assert_eq!(
+      get("/r/tap/getOpenTradeOffersLength/foo/bar")
+        .json::<serde_json::Value>()
+        .unwrap()["result"],
+      1
+    );
+    assert_eq!(
+      get("/r/tap/getOpenTradeOffers/foo/bar")
+        .json::<serde_json::Value>()
+        .unwrap()["result"][0]["trade_id"],
+      "t0"
+    );
+    assert_eq!(
+      get("/r/tap/getTradeOrderBook/foo/bar?precision=19").status(),
+      StatusCode::BAD_REQUEST
+    );
+  }
+
+  #[test]
+  fn tap_claimable_and_refundable_locks_respect_expiry() {
+    let server = TestServer::new();
+    server
+      .index
+      .tap_test_put_raw_rows([
+        (r#"b/owner/"tap""#, r#""10""#),
+        (r#"ll/owner/"tap""#, r#""10""#),
+        (
+          "l/l0",
+          r#"{"id":"l0","owner":"owner","tick":"tap","remaining":"1","claim":"alice","refund":"bob","refund_after":1,"condition":{"type":"hashlock","hash":"00"}}"#,
+        ),
+      ])
+      .unwrap();
+    server
+      .index
+      .tap_test_put_derived_rows([
+        ("lcop/alice", "1"),
+        ("lcopi/alice/0", r#""l0""#),
+        ("lrop/bob", "1"),
+        ("lropi/bob/0", r#""l0""#),
+        ("lrh/1", "1"),
+        ("lrhi/1/0", r#""l0""#),
+      ])
+      .unwrap();
+
+    let get = |path: &str| {
+      reqwest::blocking::get(server.join_url(path))
+        .unwrap()
+        .json::<serde_json::Value>()
+        .unwrap()
+    };
+
+    assert_eq!(
+      get("/r/tap/getClaimableLocks/alice")["result"],
+      serde_json::json!([])
+    );
+    let refundable = get("/r/tap/getRefundableLocks/bob");
+    assert_eq!(refundable["result"][0]["lock"]["id"], "l0");
+    assert_eq!(refundable["result"][0]["action"], "refund");
+    assert_eq!(
+      get("/r/tap/getLocksByRefundHeight/1")["result"][0]["id"],
+      "l0"
+    );
+  }
+
+  #[test]
+  fn tap_perp_risk_reports_liquidation_state() {
+    let server = TestServer::new();
+    server
+      .index
+      .tap_test_put_raw_rows([
+        (
+          "perp/g/g0",
+          r#"{"id":"g0","state":"active","expiry":100,"leverage":{"value":"5"},"maintenance_bps":"500","entry_price":{"p":"100","q":"1"},"long_open_collateral":"1000"}"#,
+        ),
+        (
+          "perp/pos/p0",
+          r#"{"id":"p0","group":"g0","side":"long","open_collateral":"1000","open_liquidation_bounty_reserve":"10","leverage":"5","state":"active"}"#,
+        ),
+        ("perp/pgl/g0", "1"),
+        ("perp/pgli/g0/0", r#""p0""#),
+        ("perp/cg/g0", "1"),
+        ("perp/cgi/g0/0", r#""c1""#),
+        ("perp/c/c1", r#"{"id":"c1","price":{"p":"80","q":"1","seq":"1"}}"#),
+      ])
+      .unwrap();
+
+    let get = |path: &str| {
+      reqwest::blocking::get(server.join_url(path))
+        .unwrap()
+        .json::<serde_json::Value>()
+        .unwrap()
+    };
+
+    let position = get("/r/tap/getPerpPositionRisk/p0");
+    assert_eq!(position["result"]["equity"], "0");
+    assert_eq!(
+      position["result"]["liquidation_price"],
+      serde_json::json!({"p": "81", "q": "1"})
+    );
+    assert_eq!(position["result"]["liquidatable"], true);
+
+    let group = get("/r/tap/getPerpGroupRisk/g0");
+    assert_eq!(group["result"]["long"]["equity"], "0");
+    assert_eq!(group["result"]["positions"][0], position["result"]);
+
+    assert_eq!(
+      get("/r/tap/getPerpPositionRisk/missing")["result"],
+      serde_json::Value::Null
+    );
+  }
+
+  #[test]
+  fn tap_amm_quote_uses_indexer_swap_math() {
+    let server = TestServer::new();
+    server
+      .index
+      .tap_test_put_raw_rows([(
+        "amm/p0",
+        r#"{"id":"p0","k":"amm","ctl":{},"seq":0,"r":["1000000","2000000"],"a":[{"ty":"tap","tick":"aaa"},{"ty":"tap","tick":"bbb"}],"sh":"1000","fee":"30","pf":"2000","blck":1,"tx":"tx","vo":0,"val":"546","ins":"ins","num":0,"ts":0}"#,
+      )])
+      .unwrap();
+
+    let quote = reqwest::blocking::get(
+      server.join_url("/r/tap/getAmmQuote/p0?side=0&amount_in=10000&slippage_bps=50"),
+    )
+    .unwrap()
+    .json::<serde_json::Value>()
+    .unwrap();
+    assert_eq!(quote["result"]["amount_out"], "19743");
+    assert_eq!(quote["result"]["lp_fee"], "24");
+    assert_eq!(quote["result"]["min_out"], "19644");
+
+    let response = reqwest::blocking::get(
+      server.join_url("/r/tap/getAmmQuote/p0?side=0&amount_in=1&amount_out=1"),
+    )
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+      response.text().unwrap(),
+      "exactly one of amount_in and amount_out is required"
+    );
+
+    let response =
+      reqwest::blocking::get(server.join_url("/r/tap/getAmmQuote/p0?side=1&amount_out=2000000"))
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+      response.text().unwrap(),
+      "amount cannot be swapped against current reserves"
+    );
+  }
+
   #[derive(Default)]
   struct Builder {
     core: Option<mockcore::Handle>,
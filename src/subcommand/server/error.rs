@@ -3,12 +3,14 @@ use {super::*, std::fmt::Write};
 #[derive(Debug)]
 pub(super) enum ServerError {
   BadRequest(String),
+  Gone(String),
   Internal(Error),
   NotAcceptable {
     accept_encoding: AcceptEncoding,
     content_encoding: HeaderValue,
   },
   NotFound(String),
+  ServiceUnavailable(String),
   Unauthorized(String),
 }
 
@@ -18,6 +20,7 @@ impl IntoResponse for ServerError {
   fn into_response(self) -> Response {
     match self {
       Self::BadRequest(message) => (StatusCode::BAD_REQUEST, message).into_response(),
+      Self::Gone(message) => (StatusCode::GONE, message).into_response(),
       Self::Internal(error) => {
         eprintln!("error serving request: {error}");
         (
@@ -51,6 +54,9 @@ impl IntoResponse for ServerError {
         message,
       )
         .into_response(),
+      Self::ServiceUnavailable(message) => {
+        (StatusCode::SERVICE_UNAVAILABLE, message).into_response()
+      }
       Self::Unauthorized(message) => (StatusCode::UNAUTHORIZED, message).into_response(),
     }
   }
@@ -1,8 +1,12 @@
 use super::*;
 use crate::index::{
   tap_js_json_stringify_str, tap_js_json_stringify_value, tap_js_preprocess_json_for_serde,
-  tap_js_to_lowercase,
+  tap_js_to_lowercase, TapAmmQuoteAmount, TapAmmUnquotable, TapExportResnapshotRequired,
+  TapHistoryUnavailable, TapStateView, TapStreamCursor, TapStreamEvent, TapStreamFilter,
+  TAP_AMM_ROUTE_MAX_HOPS, TAP_DMT_REWARD_ADDRESS_PREFIX, TAP_STAKE_REWARD_RATE_BLOCKS,
+  TAP_TRADE_BOOK_PRICE_PRECISION,
 };
+use axum::response::sse::{self, KeepAlive, Sse};
 use ciborium::de::from_reader as cbor_from_reader;
 use std::io::{BufRead, BufReader};
 
@@ -876,6 +880,59 @@ mod tests {
     });
   }
 
+  #[test]
+  fn tap_balance_routes_refuse_uncovered_at_block_instead_of_returning_tip() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+      .enable_all()
+      .build()
+      .unwrap();
+    let context = Context::builder().build();
+    context
+      .index
+      .tap_test_put_raw_rows(vec![(
+        format!("b/addr1/{}", json_stringify_lower("tap")),
+        "100".to_string(),
+      )])
+      .unwrap();
+    let index = Arc::new(context.index);
+
+    runtime.block_on(async {
+      assert_eq!(
+        route_json(tap_get_balance(
+          Extension(index.clone()),
+          Path(("addr1".to_string(), "tap".to_string())),
+          Query(TapAtBlockQuery { at_block: None })
+        ))
+        .await,
+        json!({"result": "100"})
+      );
+
+      let error = tap_get_balance(
+        Extension(index.clone()),
+        Path(("addr1".to_string(), "tap".to_string())),
+        Query(TapAtBlockQuery { at_block: Some(0) }),
+      )
+      .await
+      .unwrap_err();
+      assert!(
+        matches!(error, ServerError::BadRequest(ref message) if message.contains("ORD_TAP_WRITER_EXPORT"))
+      );
+
+      let error = tap_get_holders(
+        Extension(index.clone()),
+        Path("tap".to_string()),
+        Query(TapAtBlockListQuery {
+          offset: None,
+          max: None,
+          at_block: Some(0),
+        }),
+      )
+      .await
+      .unwrap_err();
+      assert!(matches!(error, ServerError::BadRequest(_)));
+    });
+  }
+
   fn list_query() -> TapListQuery {
     TapListQuery {
       offset: Some(0),
@@ -2126,32 +2183,33 @@ struct AccountTokenBalanceItem {
 pub(super) async fn tap_get_account_tokens_balance(
   Extension(index): Extension<Arc<Index>>,
   Path(address): Path<String>,
-  Query(q): Query<TapListQuery>,
-) -> ServerResult<Json<serde_json::Value>> {
-  task::block_in_place(|| {
-    let offset = q.offset.unwrap_or(0);
-    let max = q.max.unwrap_or(500).min(500);
-    let total = index.tap_get_length(&format!("atl/{}", &address))?;
-    let tokens = index.tap_list_strings(
-      &format!("atl/{}", &address),
-      &format!("atli/{}", &address),
-      offset,
-      max,
-    )?;
-    let mut list: Vec<AccountTokenBalanceItem> = Vec::new();
-    for t in tokens {
-      let tkey = json_stringify_lower(&t);
-      let overall = index.tap_get_string(&format!("b/{}/{}", &address, &tkey))?;
-      let tr = index.tap_get_string(&format!("t/{}/{}", &address, &tkey))?;
-      list.push(AccountTokenBalanceItem {
-        ticker: t.to_lowercase(),
-        overallBalance: overall,
-        transferableBalance: tr.unwrap_or_default(),
-      });
-    }
-    Ok(Json(
-      serde_json::json!({"data": {"total": total, "list": list} }),
-    ))
+  Query(q): Query<TapAtBlockListQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    tap_at_block_response((|| {
+      let view = index.tap_state_view(q.at_block)?;
+      let offset = q.offset.unwrap_or(0);
+      let max = q.max.unwrap_or(500).min(500);
+      let total = view.get_length(&format!("atl/{}", &address))?;
+      let tokens = view.list_strings(
+        &format!("atl/{}", &address),
+        &format!("atli/{}", &address),
+        offset,
+        max,
+      )?;
+      let mut list: Vec<AccountTokenBalanceItem> = Vec::new();
+      for t in tokens {
+        let tkey = json_stringify_lower(&t);
+        let overall = view.get_string(&format!("b/{}/{}", &address, &tkey))?;
+        let tr = view.get_string(&format!("t/{}/{}", &address, &tkey))?;
+        list.push(AccountTokenBalanceItem {
+          ticker: t.to_lowercase(),
+          overallBalance: overall,
+          transferableBalance: tr.unwrap_or_default(),
+        });
+      }
+      Ok(serde_json::json!({"data": {"total": total, "list": list} }))
+    })())
   })
 }
 
@@ -2254,6 +2312,254 @@ pub(super) async fn tap_get_current_block(
   })
 }
 
+const TAP_BATCH_MAX_LOOKUPS: usize = 500;
+
+#[derive(Deserialize)]
+pub(super) struct TapBatchRequest {
+  lookups: Vec<TapBatchLookup>,
+}
+
+// One single-record lookup per variant, answered like the matching GET route
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum TapBatchLookup {
+  Balance {
+    address: String,
+    ticker: String,
+  },
+  Transferable {
+    address: String,
+    ticker: String,
+  },
+  LockedBalance {
+    address: String,
+    ticker: String,
+  },
+  Deployment {
+    ticker: String,
+  },
+  MintTokensLeft {
+    ticker: String,
+  },
+  Lock {
+    lock_id: String,
+  },
+  Authority {
+    authority_id: String,
+  },
+  AuthorityBalance {
+    authority_id: String,
+    ticker: String,
+  },
+  AmmPool {
+    pool_id: String,
+  },
+  PerpPosition {
+    position_id: String,
+  },
+  StakePosition {
+    position_id: String,
+  },
+  PendingInscription {
+    inscription_id: String,
+  },
+  DmtRewardAddress {
+    address: String,
+  },
+  TradeOffer {
+    trade_id: String,
+    ticker: String,
+  },
+}
+
+// Answer many lookups from one read transaction, so no two values straddle a
+// block commit
+pub(super) async fn tap_batch(
+  Extension(index): Extension<Arc<Index>>,
+  Query(q): Query<TapAtBlockQuery>,
+  Json(request): Json<TapBatchRequest>,
+) -> ServerResult<Json<serde_json::Value>> {
+  if request.lookups.len() > TAP_BATCH_MAX_LOOKUPS {
+    return Err(ServerError::BadRequest(format!(
+      "batch has {} lookups, at most {TAP_BATCH_MAX_LOOKUPS} are allowed",
+      request.lookups.len()
+    )));
+  }
+
+  task::block_in_place(|| {
+    tap_at_block_response((|| {
+      let view = index.tap_state_view(q.at_block)?;
+      let result = request
+        .lookups
+        .iter()
+        .map(|lookup| tap_batch_lookup(&view, lookup))
+        .collect::<Result<Vec<serde_json::Value>>>()?;
+      Ok(serde_json::json!({"height": view.height()?, "result": result}))
+    })())
+  })
+}
+
+fn tap_batch_lookup(view: &TapStateView, lookup: &TapBatchLookup) -> Result<serde_json::Value> {
+  let string = |key: String| view.get_string(&key);
+  let amount = |key: String| -> Result<String> {
+    Ok(view.get_string(&key)?.unwrap_or_else(|| "0".to_string()))
+  };
+
+  Ok(match lookup {
+    TapBatchLookup::Balance { address, ticker } => serde_json::json!(string(format!(
+      "b/{}/{}",
+      address,
+      json_stringify_lower(ticker)
+    ))?),
+    TapBatchLookup::Transferable { address, ticker } => serde_json::json!(string(format!(
+      "t/{}/{}",
+      address,
+      json_stringify_lower(ticker)
+    ))?),
+    TapBatchLookup::LockedBalance { address, ticker } => serde_json::json!(amount(format!(
+      "ll/{}/{}",
+      address,
+      json_stringify_lower(ticker)
+    ))?),
+    TapBatchLookup::Deployment { ticker } => serde_json::json!(view
+      .get_raw(&format!("d/{}", json_stringify_lower(ticker)))?
+      .and_then(|b| tap_decode_deploy_record(&b))),
+    TapBatchLookup::MintTokensLeft { ticker } => {
+      serde_json::json!(string(format!("dc/{}", json_stringify_lower(ticker)))?)
+    }
+    TapBatchLookup::Lock { lock_id } => serde_json::json!(view
+      .get_raw(&format!("l/{}", lock_id))?
+      .and_then(|b| tap_decode_token_lock_record(&b))),
+    TapBatchLookup::Authority { authority_id } => serde_json::json!(view
+      .get_raw(&format!("ah/{}", authority_id))?
+      .and_then(|b| tap_decode_authority_config_record(&b))
+      .map(tap_authority_config_record_to_value)),
+    TapBatchLookup::AuthorityBalance {
+      authority_id,
+      ticker,
+    } => serde_json::json!(amount(format!(
+      "ab/{}/{}",
+      authority_id,
+      json_stringify_lower(ticker)
+    ))?),
+    TapBatchLookup::AmmPool { pool_id } => serde_json::json!(view
+      .get_raw(&format!("amm/{}", pool_id))?
+      .and_then(|b| tap_decode_json_value(&b))),
+    TapBatchLookup::PerpPosition { position_id } => serde_json::json!(view
+      .get_raw(&format!("perp/pos/{}", position_id))?
+      .and_then(|b| tap_decode_json_value(&b))),
+    TapBatchLookup::StakePosition { position_id } => serde_json::json!(view
+      .get_raw(&format!("sp/{}", position_id))?
+      .and_then(|b| tap_decode_stake_position_record(&b))),
+    TapBatchLookup::PendingInscription { inscription_id } => {
+      tap_pending_inscription(view, inscription_id)?
+    }
+    TapBatchLookup::DmtRewardAddress { address } => serde_json::json!(view
+      .get_raw(&format!("{TAP_DMT_REWARD_ADDRESS_PREFIX}/{address}"))?
+      .is_some()),
+    TapBatchLookup::TradeOffer { trade_id, ticker } => tap_trade_offer(view, trade_id, ticker)?,
+  })
+}
+
+// The offer of open trade `trade_id` accepting `ticker`, as token-trade side 1
+// inscriptions would fill it
+fn tap_trade_offer(view: &TapStateView, trade_id: &str, ticker: &str) -> Result<serde_json::Value> {
+  if view.get_raw(&format!("tol/{}", trade_id))?.is_none() {
+    return Ok(serde_json::Value::Null);
+  }
+
+  let Some(ptr) = view.get_string(&format!("to/{}/{}", trade_id, json_stringify_lower(ticker)))?
+  else {
+    return Ok(serde_json::Value::Null);
+  };
+
+  Ok(serde_json::json!(view
+    .get_raw(&ptr)?
+    .and_then(|b| tap_decode_trade_offer_record(&b))))
+}
+
+// TAP state that moving the inscription would execute: a token-transfer that
+// has not been sent yet, or an accumulator (token-send, token-auth,
+// block-transferables, ...) that has not been tapped
+fn tap_pending_inscription(view: &TapStateView, inscription_id: &str) -> Result<serde_json::Value> {
+  if let Some(ptr) = view
+    .get_string(&format!("tl/{}", inscription_id))?
+    .filter(|ptr| !ptr.is_empty())
+  {
+    if let Some(transfer) = view
+      .get_raw(&ptr)?
+      .and_then(|b| tap_decode_transfer_init_record(&b))
+    {
+      return Ok(serde_json::json!({"op": "token-transfer", "addr": transfer.addr}));
+    }
+  }
+
+  Ok(
+    match view
+      .get_raw(&format!("a/{}", inscription_id))?
+      .and_then(|b| tap_decode_accumulator_entry(&b))
+    {
+      Some(accumulator) => serde_json::json!({"op": accumulator.op, "addr": accumulator.addr}),
+      None => serde_json::Value::Null,
+    },
+  )
+}
+
+// Preview the TAP effects of an unconfirmed transaction as if it were mined next
+pub(super) async fn tap_preview(
+  Extension(index): Extension<Arc<Index>>,
+  body: String,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    let transaction = consensus::encode::deserialize_hex::<Transaction>(body.trim())
+      .map_err(|err| ServerError::BadRequest(format!("invalid transaction hex: {err}")))?;
+    tap_preview_response(&index, &transaction)
+  })
+}
+
+// Same as tap_preview, for a mempool transaction fetched from bitcoind
+pub(super) async fn tap_preview_txid(
+  Extension(index): Extension<Arc<Index>>,
+  Path(txid): Path<Txid>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    let transaction = index
+      .get_transaction(txid)?
+      .ok_or_not_found(|| format!("transaction {txid}"))?;
+    tap_preview_response(&index, &transaction)
+  })
+}
+
+fn tap_preview_response(
+  index: &Index,
+  transaction: &Transaction,
+) -> ServerResult<Json<serde_json::Value>> {
+  let preview = index
+    .tap_preview(transaction)
+    .map_err(|err| ServerError::BadRequest(err.to_string()))?;
+  Ok(Json(serde_json::to_value(preview).map_err(Error::from)?))
+}
+
+#[derive(Deserialize)]
+pub(super) struct TapValidateQuery {
+  #[serde(default)]
+  pub(super) address: Option<String>,
+}
+
+// Check a deploy or mint inscription body against the indexer's rules at the next height
+pub(super) async fn tap_validate(
+  Extension(index): Extension<Arc<Index>>,
+  Query(q): Query<TapValidateQuery>,
+  body: String,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    let validation = index
+      .tap_validate(&body, q.address.as_deref())
+      .map_err(|err| ServerError::BadRequest(err.to_string()))?;
+    Ok(Json(serde_json::to_value(validation).map_err(Error::from)?))
+  })
+}
+
 // Report which backend is used for DMT regex validation (RE2 vs stub)
 pub(super) async fn tap_get_regex_backend() -> ServerResult<Json<serde_json::Value>> {
   Ok(Json(
@@ -2305,6 +2611,104 @@ pub(super) async fn tap_get_reorgs(
   })
 }
 
+#[derive(Deserialize)]
+pub(super) struct TapStreamQuery {
+  #[serde(default)]
+  cursor: Option<String>,
+  #[serde(default)]
+  tick: Option<String>,
+  #[serde(default)]
+  address: Option<String>,
+  #[serde(default)]
+  authority: Option<String>,
+  #[serde(default)]
+  family: Option<String>,
+}
+
+// Push TAP block events as they commit. Resumes after `cursor` (or the SSE
+// `Last-Event-ID` header) from the in-memory retained window.
+pub(super) async fn tap_stream(
+  Extension(index): Extension<Arc<Index>>,
+  headers: HeaderMap,
+  Query(q): Query<TapStreamQuery>,
+) -> ServerResult<Sse<impl futures::Stream<Item = Result<sse::Event, std::convert::Infallible>>>> {
+  if !index.tap_stream().enabled() {
+    return Err(ServerError::NotFound("tap stream disabled".to_string()));
+  }
+
+  let cursor = q.cursor.or_else(|| {
+    headers
+      .get("last-event-id")
+      .and_then(|value| value.to_str().ok())
+      .map(str::to_string)
+  });
+  let cursor = cursor
+    .map(|cursor| cursor.parse::<TapStreamCursor>())
+    .transpose()
+    .map_err(|err| ServerError::BadRequest(err.to_string()))?;
+
+  let filter = TapStreamFilter {
+    ticker: q.tick,
+    address: q.address,
+    authority: q.authority,
+    families: q
+      .family
+      .map(|families| {
+        families
+          .split(',')
+          .map(str::trim)
+          .filter(|family| !family.is_empty())
+          .map(str::to_string)
+          .collect()
+      })
+      .unwrap_or_default(),
+  };
+
+  let (replay, receiver) = index
+    .tap_stream()
+    .subscribe(cursor)
+    .map_err(|err| ServerError::BadRequest(err.to_string()))?;
+
+  let stream = futures::stream::unfold(
+    (replay.into_iter(), receiver, filter, false),
+    |(mut replay, mut receiver, filter, done)| async move {
+      if done {
+        return None;
+      }
+      loop {
+        let event = match replay.next() {
+          Some(event) => event,
+          None => match receiver.recv().await {
+            Ok(event) => event,
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+              let event = sse::Event::default().event("lagged").data(format!(
+                "subscriber fell {skipped} events behind; reconnect with the last cursor"
+              ));
+              return Some((Ok(event), (replay, receiver, filter, true)));
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+          },
+        };
+        if !filter.matches(&event) {
+          continue;
+        }
+        let kind = match event {
+          TapStreamEvent::Record { .. } => "record",
+          TapStreamEvent::Rollback { .. } => "rollback",
+        };
+        let sse_event = sse::Event::default()
+          .event(kind)
+          .id(event.cursor().to_string())
+          .json_data(&event)
+          .unwrap_or_else(|_| sse::Event::default().event(kind));
+        return Some((Ok(sse_event), (replay, receiver, filter, false)));
+      }
+    },
+  );
+
+  Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
 fn require_tap_export(headers: &HeaderMap, settings: &Settings) -> ServerResult<()> {
   if !settings.tap_writer_export_enabled() {
     return Err(ServerError::NotFound(
@@ -2362,6 +2766,11 @@ pub(super) struct TapExportSnapshotReadQuery {
   limit_bytes: Option<usize>,
 }
 
+#[derive(Deserialize)]
+pub(super) struct TapExportAckQuery {
+  next_block: u32,
+}
+
 #[derive(Deserialize)]
 pub(super) struct TapExportDeltaQuery {
   from_block: Option<u32>,
@@ -2396,6 +2805,39 @@ pub(super) async fn tap_export_hello(
   })
 }
 
+// Consumers that fell behind the pruned range get a 410 telling them to
+// resnapshot instead of a generic server error.
+fn tap_export_response<T: Serialize>(result: Result<T>) -> ServerResult<Json<serde_json::Value>> {
+  result
+    .map(|value| Json(serde_json::json!(value)))
+    .map_err(
+      |error| match error.downcast_ref::<TapExportResnapshotRequired>() {
+        Some(pruned) => ServerError::Gone(pruned.to_string()),
+        None => ServerError::Internal(error),
+      },
+    )
+}
+
+pub(super) async fn tap_export_ack(
+  headers: HeaderMap,
+  Extension(index): Extension<Arc<Index>>,
+  Extension(settings): Extension<Arc<Settings>>,
+  Query(query): Query<TapExportAckQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    require_tap_export(&headers, &settings)?;
+    let consumer = headers
+      .get("x-tap-export-consumer")
+      .and_then(|value| value.to_str().ok())
+      .unwrap_or_default();
+    index.tap_export_ack(consumer, query.next_block);
+    Ok(Json(serde_json::json!({
+      "consumer": consumer,
+      "next_block": query.next_block,
+    })))
+  })
+}
+
 pub(super) async fn tap_export_snapshot(
   headers: HeaderMap,
   Extension(index): Extension<Arc<Index>>,
@@ -2516,8 +2958,7 @@ pub(super) async fn tap_export_block_digest(
 ) -> ServerResult<Json<serde_json::Value>> {
   task::block_in_place(|| {
     require_tap_export(&headers, &settings)?;
-    let digest = index.tap_export_block_digest(height)?;
-    Ok(Json(serde_json::json!(digest)))
+    tap_export_response(index.tap_export_block_digest(height))
   })
 }
 
@@ -2529,13 +2970,12 @@ pub(super) async fn tap_export_deltas(
 ) -> ServerResult<Json<serde_json::Value>> {
   task::block_in_place(|| {
     require_tap_export(&headers, &settings)?;
-    let page = index.tap_export_deltas(
+    tap_export_response(index.tap_export_deltas(
       query.from_block.unwrap_or(0),
       query.from_sequence.unwrap_or(0),
       query.limit.unwrap_or(10_000),
       query.limit_bytes,
-    )?;
-    Ok(Json(serde_json::json!(page)))
+    ))
   })
 }
 
@@ -2575,6 +3015,35 @@ pub(super) struct TapListQuery {
   max: Option<u64>,
 }
 
+#[derive(Deserialize)]
+pub(super) struct TapAtBlockQuery {
+  #[serde(default)]
+  at_block: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct TapAtBlockListQuery {
+  #[serde(default)]
+  offset: Option<u64>,
+  #[serde(default)]
+  max: Option<u64>,
+  #[serde(default)]
+  at_block: Option<u32>,
+}
+
+// Point-in-time reads refuse heights outside the retained export range with a
+// 400 rather than silently answering from the tip.
+fn tap_at_block_response(
+  result: Result<serde_json::Value>,
+) -> ServerResult<Json<serde_json::Value>> {
+  result.map(Json).map_err(
+    |error| match error.downcast_ref::<TapHistoryUnavailable>() {
+      Some(unavailable) => ServerError::BadRequest(unavailable.to_string()),
+      None => ServerError::Internal(error),
+    },
+  )
+}
+
 #[derive(Deserialize)]
 pub(super) struct TapPerpPairQuery {
   #[serde(default)]
@@ -3183,22 +3652,28 @@ pub(super) async fn tap_get_ticker_minted_list_by_block(
 pub(super) async fn tap_get_balance(
   Extension(index): Extension<Arc<Index>>,
   Path((address, ticker)): Path<(String, String)>,
+  Query(q): Query<TapAtBlockQuery>,
 ) -> ServerResult<Json<serde_json::Value>> {
   task::block_in_place(|| {
-    let key = format!("b/{}/{}", address, json_stringify_lower(&ticker));
-    let result = index.tap_get_string(&key)?;
-    Ok(Json(serde_json::json!({"result": result})))
+    tap_at_block_response((|| {
+      let key = format!("b/{}/{}", address, json_stringify_lower(&ticker));
+      let result = index.tap_state_view(q.at_block)?.get_string(&key)?;
+      Ok(serde_json::json!({"result": result}))
+    })())
   })
 }
 
 pub(super) async fn tap_get_transferable(
   Extension(index): Extension<Arc<Index>>,
   Path((address, ticker)): Path<(String, String)>,
+  Query(q): Query<TapAtBlockQuery>,
 ) -> ServerResult<Json<serde_json::Value>> {
   task::block_in_place(|| {
-    let key = format!("t/{}/{}", address, json_stringify_lower(&ticker));
-    let result = index.tap_get_string(&key)?;
-    Ok(Json(serde_json::json!({"result": result})))
+    tap_at_block_response((|| {
+      let key = format!("t/{}/{}", address, json_stringify_lower(&ticker));
+      let result = index.tap_state_view(q.at_block)?.get_string(&key)?;
+      Ok(serde_json::json!({"result": result}))
+    })())
   })
 }
 
@@ -3233,28 +3708,31 @@ pub(super) async fn tap_get_historic_holders_length(
 pub(super) async fn tap_get_holders(
   Extension(index): Extension<Arc<Index>>,
   Path(ticker): Path<String>,
-  Query(q): Query<TapListQuery>,
-) -> ServerResult<Json<serde_json::Value>> {
-  task::block_in_place(|| {
-    let offset = q.offset.unwrap_or(0);
-    let max = q.max.unwrap_or(100).min(500);
-    let tkey = json_stringify_lower(&ticker);
-    let addrs =
-      index.tap_list_strings(&format!("h/{}", tkey), &format!("hi/{}", tkey), offset, max)?;
-    let mut out = Vec::new();
-    for a in addrs {
-      let bal = index.tap_get_string(&format!("b/{}/{}", a, tkey))?;
-      let tr = index.tap_get_string(&format!("t/{}/{}", a, tkey))?;
-      out.push(serde_json::json!({"address": a, "balance": bal, "transferable": tr}));
-    }
-    Ok(Json(serde_json::json!({"result": out})))
+  Query(q): Query<TapAtBlockListQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    tap_at_block_response((|| {
+      let view = index.tap_state_view(q.at_block)?;
+      let offset = q.offset.unwrap_or(0);
+      let max = q.max.unwrap_or(100).min(500);
+      let tkey = json_stringify_lower(&ticker);
+      let addrs =
+        view.list_strings(&format!("h/{}", tkey), &format!("hi/{}", tkey), offset, max)?;
+      let mut out = Vec::new();
+      for a in addrs {
+        let bal = view.get_string(&format!("b/{}/{}", a, tkey))?;
+        let tr = view.get_string(&format!("t/{}/{}", a, tkey))?;
+        out.push(serde_json::json!({"address": a, "balance": bal, "transferable": tr}));
+      }
+      Ok(serde_json::json!({"result": out}))
+    })())
   })
 }
 
 pub(super) async fn tap_get_historic_holders(
   Extension(index): Extension<Arc<Index>>,
   Path(ticker): Path<String>,
-  Query(q): Query<TapListQuery>,
+  Query(q): Query<TapAtBlockListQuery>,
 ) -> ServerResult<Json<serde_json::Value>> {
   tap_get_holders(Extension(index), Path(ticker), Query(q)).await
 }
@@ -4070,6 +4548,79 @@ pub(super) async fn tap_get_trades_filled_list(
   })
 }
 
+#[derive(Deserialize)]
+pub(super) struct TapTradeBookQuery {
+  #[serde(default)]
+  precision: Option<u32>,
+  #[serde(default)]
+  depth: Option<usize>,
+}
+
+pub(super) async fn tap_get_trade_order_book(
+  Extension(index): Extension<Arc<Index>>,
+  Path((ticker, accept_ticker)): Path<(String, String)>,
+  Query(q): Query<TapTradeBookQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  let precision = q.precision.unwrap_or(TAP_TRADE_BOOK_PRICE_PRECISION);
+  if precision > TAP_TRADE_BOOK_PRICE_PRECISION {
+    return Err(ServerError::BadRequest(format!(
+      "precision must be at most {TAP_TRADE_BOOK_PRICE_PRECISION}"
+    )));
+  }
+
+  task::block_in_place(|| {
+    let view = index.tap_state_view(None)?;
+    let book = Index::tap_trade_book(
+      &view,
+      &ticker,
+      &accept_ticker,
+      precision,
+      q.depth.unwrap_or(50).min(500),
+    )?;
+    Ok(Json(
+      serde_json::json!({"height": view.height()?, "result": book}),
+    ))
+  })
+}
+
+pub(super) async fn tap_get_open_trade_offers_length(
+  Extension(index): Extension<Arc<Index>>,
+  Path((ticker, accept_ticker)): Path<(String, String)>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    let pair = format!(
+      "{}/{}",
+      json_stringify_lower(&ticker),
+      json_stringify_lower(&accept_ticker)
+    );
+    Ok(Json(serde_json::json!({
+      "result": index
+        .tap_state_view(None)?
+        .get_derived_length(&format!("tob/{}", pair))?
+    })))
+  })
+}
+
+pub(super) async fn tap_get_open_trade_offers(
+  Extension(index): Extension<Arc<Index>>,
+  Path((ticker, accept_ticker)): Path<(String, String)>,
+  Query(q): Query<TapListQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    let view = index.tap_state_view(None)?;
+    let offers = Index::tap_trade_open_offers(
+      &view,
+      &ticker,
+      &accept_ticker,
+      usize::try_from(q.offset.unwrap_or(0)).unwrap_or(usize::MAX),
+      usize::try_from(q.max.unwrap_or(500).min(500)).unwrap_or(500),
+    )?;
+    Ok(Json(
+      serde_json::json!({"height": view.height()?, "result": offers}),
+    ))
+  })
+}
+
 // --- Token-auth endpoints ---
 
 // Check if a token-auth inscription has been cancelled
@@ -4287,6 +4838,51 @@ pub(super) async fn tap_get_lock_consume(
   })
 }
 
+pub(super) async fn tap_get_claimable_locks(
+  Extension(index): Extension<Arc<Index>>,
+  Path(address): Path<String>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    let view = index.tap_state_view(None)?;
+    let locks = Index::tap_claimable_locks(&view, &address)?;
+    Ok(Json(
+      serde_json::json!({"height": view.height()?, "result": locks}),
+    ))
+  })
+}
+
+pub(super) async fn tap_get_refundable_locks(
+  Extension(index): Extension<Arc<Index>>,
+  Path(address): Path<String>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    let view = index.tap_state_view(None)?;
+    let locks = Index::tap_refundable_locks(&view, &address)?;
+    Ok(Json(
+      serde_json::json!({"height": view.height()?, "result": locks}),
+    ))
+  })
+}
+
+pub(super) async fn tap_get_locks_by_refund_height(
+  Extension(index): Extension<Arc<Index>>,
+  Path(height): Path<u32>,
+  Query(q): Query<TapListQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    let view = index.tap_state_view(None)?;
+    let locks = Index::tap_locks_by_refund_height(
+      &view,
+      height,
+      q.offset.unwrap_or(0),
+      q.max.unwrap_or(500).min(500),
+    )?;
+    Ok(Json(
+      serde_json::json!({"height": view.height()?, "result": locks}),
+    ))
+  })
+}
+
 pub(super) async fn tap_get_lock_list_length(
   Extension(index): Extension<Arc<Index>>,
 ) -> ServerResult<Json<serde_json::Value>> {
@@ -5111,29 +5707,32 @@ pub(super) async fn tap_get_authority_balances_length(
 pub(super) async fn tap_get_authority_balances(
   Extension(index): Extension<Arc<Index>>,
   Path(authority_id): Path<String>,
-  Query(q): Query<TapListQuery>,
-) -> ServerResult<Json<serde_json::Value>> {
-  task::block_in_place(|| {
-    let offset = q.offset.unwrap_or(0);
-    let max = q.max.unwrap_or(500).min(500);
-    let ticks = index.tap_list_strings(
-      &format!("abl/{}", authority_id),
-      &format!("abli/{}", authority_id),
-      offset,
-      max,
-    )?;
-    let mut out = Vec::new();
-    for tick in ticks {
-      let balance = index
-        .tap_get_string(&format!(
-          "ab/{}/{}",
-          authority_id,
-          json_stringify_lower(&tick)
-        ))?
-        .unwrap_or_else(|| "0".to_string());
-      out.push(serde_json::json!({"tick": tick, "bal": balance}));
-    }
-    Ok(Json(serde_json::json!({"result": out})))
+  Query(q): Query<TapAtBlockListQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    tap_at_block_response((|| {
+      let view = index.tap_state_view(q.at_block)?;
+      let offset = q.offset.unwrap_or(0);
+      let max = q.max.unwrap_or(500).min(500);
+      let ticks = view.list_strings(
+        &format!("abl/{}", authority_id),
+        &format!("abli/{}", authority_id),
+        offset,
+        max,
+      )?;
+      let mut out = Vec::new();
+      for tick in ticks {
+        let balance = view
+          .get_string(&format!(
+            "ab/{}/{}",
+            authority_id,
+            json_stringify_lower(&tick)
+          ))?
+          .unwrap_or_else(|| "0".to_string());
+        out.push(serde_json::json!({"tick": tick, "bal": balance}));
+      }
+      Ok(serde_json::json!({"result": out}))
+    })())
   })
 }
 
@@ -5202,6 +5801,183 @@ pub(super) async fn tap_get_amm_pools_by_asset(
   })
 }
 
+const TAP_AMM_DEFAULT_SLIPPAGE_BPS: u32 = 50;
+
+#[derive(Deserialize)]
+pub(super) struct TapAmmQuoteQuery {
+  side: usize,
+  #[serde(default)]
+  amount_in: Option<String>,
+  #[serde(default)]
+  amount_out: Option<String>,
+  #[serde(default)]
+  slippage_bps: Option<u32>,
+  #[serde(default)]
+  at_block: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct TapAmmRouteQuoteQuery {
+  amount_in: String,
+  #[serde(default)]
+  max_hops: Option<usize>,
+  #[serde(default)]
+  slippage_bps: Option<u32>,
+  #[serde(default)]
+  at_block: Option<u32>,
+}
+
+fn tap_amm_parse_amount(name: &str, value: &str) -> ServerResult<num_bigint::BigInt> {
+  if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+    return Err(ServerError::BadRequest(format!(
+      "{name} must be a base-unit integer"
+    )));
+  }
+  value
+    .parse()
+    .map_err(|_| ServerError::BadRequest(format!("{name} must be a base-unit integer")))
+}
+
+// Quotes run the indexer's own swap math, so anything swap validation would
+// reject comes back as a 400 with the reason instead of a misleading number.
+fn tap_amm_quote_response(
+  result: Result<serde_json::Value>,
+) -> ServerResult<Json<serde_json::Value>> {
+  result.map(Json).map_err(|error| {
+    if let Some(unquotable) = error.downcast_ref::<TapAmmUnquotable>() {
+      return ServerError::BadRequest(unquotable.to_string());
+    }
+    if let Some(unavailable) = error.downcast_ref::<TapHistoryUnavailable>() {
+      return ServerError::BadRequest(unavailable.to_string());
+    }
+    ServerError::Internal(error)
+  })
+}
+
+pub(super) async fn tap_get_amm_quote(
+  Extension(index): Extension<Arc<Index>>,
+  Path(pool_id): Path<String>,
+  Query(q): Query<TapAmmQuoteQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  let amount = match (&q.amount_in, &q.amount_out) {
+    (Some(amount_in), None) => {
+      TapAmmQuoteAmount::ExactIn(tap_amm_parse_amount("amount_in", amount_in)?)
+    }
+    (None, Some(amount_out)) => {
+      TapAmmQuoteAmount::ExactOut(tap_amm_parse_amount("amount_out", amount_out)?)
+    }
+    _ => {
+      return Err(ServerError::BadRequest(
+        "exactly one of amount_in and amount_out is required".into(),
+      ))
+    }
+  };
+
+  task::block_in_place(|| {
+    tap_amm_quote_response((|| {
+      let view = index.tap_state_view(q.at_block)?;
+      let quote = Index::tap_amm_quote(
+        &view,
+        &pool_id,
+        q.side,
+        &amount,
+        q.slippage_bps.unwrap_or(TAP_AMM_DEFAULT_SLIPPAGE_BPS),
+      )?;
+      Ok(serde_json::json!({"height": view.height()?, "result": quote}))
+    })())
+  })
+}
+
+pub(super) async fn tap_get_amm_route_quote(
+  Extension(index): Extension<Arc<Index>>,
+  Path((from_ticker, to_ticker)): Path<(String, String)>,
+  Query(q): Query<TapAmmRouteQuoteQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  let amount_in = tap_amm_parse_amount("amount_in", &q.amount_in)?;
+  let max_hops = q.max_hops.unwrap_or(TAP_AMM_ROUTE_MAX_HOPS);
+  if max_hops == 0 || max_hops > TAP_AMM_ROUTE_MAX_HOPS {
+    return Err(ServerError::BadRequest(format!(
+      "max_hops must be between 1 and {TAP_AMM_ROUTE_MAX_HOPS}"
+    )));
+  }
+
+  task::block_in_place(|| {
+    tap_amm_quote_response((|| {
+      let view = index.tap_state_view(q.at_block)?;
+      let route = Index::tap_amm_route_quote(
+        &view,
+        &from_ticker,
+        &to_ticker,
+        &amount_in,
+        max_hops,
+        q.slippage_bps.unwrap_or(TAP_AMM_DEFAULT_SLIPPAGE_BPS),
+      )?;
+      Ok(serde_json::json!({"height": view.height()?, "result": route}))
+    })())
+  })
+}
+
+#[derive(Deserialize)]
+pub(super) struct TapAmmCandleQuery {
+  #[serde(default)]
+  interval: Option<String>,
+  #[serde(default)]
+  offset: Option<u64>,
+  #[serde(default)]
+  max: Option<u64>,
+}
+
+// `blk` is the per-block series; any other interval is a number of seconds
+// from the indexer's `tap_amm_candle_intervals` setting.
+fn tap_amm_candle_interval(interval: &Option<String>) -> ServerResult<String> {
+  let interval = interval.as_deref().unwrap_or("blk");
+  if interval == "blk" || interval.parse::<u32>().map(|i| i > 0).unwrap_or(false) {
+    Ok(interval.to_string())
+  } else {
+    Err(ServerError::BadRequest(
+      "interval must be `blk` or a number of seconds".into(),
+    ))
+  }
+}
+
+pub(super) async fn tap_get_amm_candles_length(
+  Extension(index): Extension<Arc<Index>>,
+  Path(pool_id): Path<String>,
+  Query(q): Query<TapAmmCandleQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  let interval = tap_amm_candle_interval(&q.interval)?;
+  task::block_in_place(|| {
+    Ok(Json(serde_json::json!({
+      "result": index
+        .tap_state_view(None)?
+        .get_derived_length(&format!("ammc/{}/{}", pool_id, interval))?
+    })))
+  })
+}
+
+pub(super) async fn tap_get_amm_candles(
+  Extension(index): Extension<Arc<Index>>,
+  Path(pool_id): Path<String>,
+  Query(q): Query<TapAmmCandleQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  let interval = tap_amm_candle_interval(&q.interval)?;
+  task::block_in_place(|| {
+    let view = index.tap_state_view(None)?;
+    let length = view.get_derived_length(&format!("ammc/{}/{}", pool_id, interval))?;
+    let offset = q.offset.unwrap_or(0);
+    let end = length.min(offset.saturating_add(q.max.unwrap_or(500).min(500)));
+    let mut out = Vec::new();
+    for i in offset..end {
+      if let Some(candle) =
+        view.get_derived_json_record(&format!("ammci/{}/{}/{}", pool_id, interval, i))?
+      {
+        out.push(candle);
+      }
+    }
+    Ok(Json(serde_json::json!({"result": out})))
+  })
+}
+
 pub(super) async fn tap_get_amm_position(
   Extension(index): Extension<Arc<Index>>,
   Path((pool_id, target_type, target)): Path<(String, String, String)>,
@@ -5244,6 +6020,40 @@ pub(super) async fn tap_get_amm_positions_by_target(
   })
 }
 
+pub(super) async fn tap_get_amm_position_value(
+  Extension(index): Extension<Arc<Index>>,
+  Path((pool_id, target_type, target)): Path<(String, String, String)>,
+  Query(q): Query<TapAtBlockQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    tap_at_block_response((|| {
+      let view = index.tap_state_view(q.at_block)?;
+      let value = Index::tap_amm_position_value(&view, &pool_id, &target_type, &target)?;
+      Ok(serde_json::json!({"height": view.height()?, "result": value}))
+    })())
+  })
+}
+
+pub(super) async fn tap_get_amm_position_values_by_target(
+  Extension(index): Extension<Arc<Index>>,
+  Path((target_type, target)): Path<(String, String)>,
+  Query(q): Query<TapAtBlockListQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    tap_at_block_response((|| {
+      let view = index.tap_state_view(q.at_block)?;
+      let values = Index::tap_amm_position_values_by_target(
+        &view,
+        &target_type,
+        &target,
+        q.offset.unwrap_or(0),
+        q.max.unwrap_or(25).min(25),
+      )?;
+      Ok(serde_json::json!({"height": view.height()?, "result": values}))
+    })())
+  })
+}
+
 pub(super) async fn tap_get_amm_events_by_pool_length(
   Extension(index): Extension<Arc<Index>>,
   Path(pool_id): Path<String>,
@@ -5714,6 +6524,39 @@ pub(super) async fn tap_get_perp_position(
   })
 }
 
+pub(super) async fn tap_get_perp_position_risk(
+  Extension(index): Extension<Arc<Index>>,
+  Path(position_id): Path<String>,
+  Query(q): Query<TapAtBlockQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    tap_at_block_response((|| {
+      let view = index.tap_state_view(q.at_block)?;
+      let risk = Index::tap_perp_position_risk(&view, &position_id)?;
+      Ok(serde_json::json!({"height": view.height()?, "result": risk}))
+    })())
+  })
+}
+
+pub(super) async fn tap_get_perp_group_risk(
+  Extension(index): Extension<Arc<Index>>,
+  Path(group_id): Path<String>,
+  Query(q): Query<TapAtBlockListQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    tap_at_block_response((|| {
+      let view = index.tap_state_view(q.at_block)?;
+      let risk = Index::tap_perp_group_risk(
+        &view,
+        &group_id,
+        q.offset.unwrap_or(0),
+        q.max.unwrap_or(25).min(25),
+      )?;
+      Ok(serde_json::json!({"height": view.height()?, "result": risk}))
+    })())
+  })
+}
+
 pub(super) async fn tap_get_perp_position_list_length(
   Extension(index): Extension<Arc<Index>>,
 ) -> ServerResult<Json<serde_json::Value>> {
@@ -6766,6 +7609,47 @@ pub(super) async fn tap_get_pending_rewards_by_position(
   })
 }
 
+#[derive(Deserialize)]
+pub(super) struct TapStakeRewardsQuery {
+  #[serde(default)]
+  rate_blocks: Option<u32>,
+  #[serde(default)]
+  at_block: Option<u32>,
+}
+
+pub(super) async fn tap_get_pending_rewards_by_address(
+  Extension(index): Extension<Arc<Index>>,
+  Path(address): Path<String>,
+  Query(q): Query<TapStakeRewardsQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  let rate_blocks = q.rate_blocks.unwrap_or(TAP_STAKE_REWARD_RATE_BLOCKS);
+  if rate_blocks == 0 {
+    return Err(ServerError::BadRequest(
+      "rate_blocks must be positive".into(),
+    ));
+  }
+
+  task::block_in_place(|| {
+    tap_at_block_response((|| {
+      let view = index.tap_state_view(q.at_block)?;
+      let height = view.height()?;
+      // Without history for the window's start, rewards are still reported
+      // and only the rate is left out.
+      let past = match height.and_then(|height| height.checked_sub(rate_blocks)) {
+        Some(from) => match index.tap_state_view(Some(from)) {
+          Ok(past) => Some(past),
+          Err(error) if error.downcast_ref::<TapHistoryUnavailable>().is_some() => None,
+          Err(error) => return Err(error),
+        },
+        None => None,
+      };
+      let rewards =
+        Index::tap_stake_rewards_by_address(&view, past.as_ref(), &address, rate_blocks)?;
+      Ok(serde_json::json!({"height": height, "result": rewards}))
+    })())
+  })
+}
+
 pub(super) async fn tap_get_sale_status(
   Extension(index): Extension<Arc<Index>>,
   Path(authority_id): Path<String>,
@@ -6778,6 +7662,20 @@ pub(super) async fn tap_get_sale_status(
   })
 }
 
+pub(super) async fn tap_get_sale_projection(
+  Extension(index): Extension<Arc<Index>>,
+  Path((authority_id, address)): Path<(String, String)>,
+  Query(q): Query<TapAtBlockQuery>,
+) -> ServerResult<Json<serde_json::Value>> {
+  task::block_in_place(|| {
+    tap_at_block_response((|| {
+      let view = index.tap_state_view(q.at_block)?;
+      let projection = Index::tap_sale_projection(&view, &authority_id, &address)?;
+      Ok(serde_json::json!({"height": view.height()?, "result": projection}))
+    })())
+  })
+}
+
 pub(super) async fn tap_get_sale_contributions_length(
   Extension(index): Extension<Arc<Index>>,
 ) -> ServerResult<Json<serde_json::Value>> {
@@ -0,0 +1,32 @@
+use super::*;
+
+mod audit;
+pub mod mirror;
+mod perp;
+mod sale;
+
+#[derive(Debug, Parser)]
+pub(crate) enum TapSubcommand {
+  #[command(about = "Check TAP balances, locks and lists for conservation violations")]
+  Audit,
+  #[command(about = "Mirror TAP state from a writer export service into a local database")]
+  Mirror(mirror::Mirror),
+  #[command(
+    subcommand,
+    about = "Build, sign and verify perp policies and price certificates"
+  )]
+  Perp(perp::Perp),
+  #[command(subcommand, about = "Build token sale allowlists")]
+  Sale(sale::Sale),
+}
+
+impl TapSubcommand {
+  pub(crate) fn run(self, settings: Settings) -> SubcommandResult {
+    match self {
+      Self::Audit => audit::run(settings),
+      Self::Mirror(mirror) => mirror.run(settings),
+      Self::Perp(perp) => perp.run(),
+      Self::Sale(sale) => sale.run(settings),
+    }
+  }
+}
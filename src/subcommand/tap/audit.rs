@@ -0,0 +1,19 @@
+use super::*;
+
+pub(crate) fn run(settings: Settings) -> SubcommandResult {
+  let index = Index::open(&settings)?;
+
+  index.update()?;
+
+  let audit = index.tap_audit()?;
+
+  if audit.violations.is_empty() {
+    return Ok(Some(Box::new(audit)));
+  }
+
+  let violations = audit.violations.len();
+
+  audit.print(OutputFormat::Json);
+
+  bail!("TAP audit found {violations} violation(s)");
+}
@@ -0,0 +1,745 @@
+use {
+  self::{
+    client::{ExportClient, Hello, ResnapshotRequired},
+    store::MirrorStore,
+  },
+  super::*,
+  crate::index::{
+    TapExportBlockDigest, TapExportDeltaPage, TapExportDeltaRecord, TapExportRetentionStatus,
+    TapExportRollingState, TapExportSnapshotOpen, TapExportSnapshotRead, TapExportSnapshotRow,
+  },
+  serde::de::DeserializeOwned,
+  sha2::{Digest, Sha256},
+  std::collections::VecDeque,
+};
+
+mod client;
+mod store;
+
+const EXPORT_PROTOCOL: &str = "tap-writer-ordtap-v1";
+const DELTA_PAGE_ROWS: usize = 10_000;
+const REORG_DEPTH: u32 = 100;
+const SNAPSHOT_ATTEMPTS: usize = 3;
+const SNAPSHOT_PAGE_ROWS: usize = 10_000;
+
+#[derive(Debug, Parser)]
+pub(crate) struct Mirror {
+  #[arg(
+    long,
+    help = "Connect to the writer export service at <ENDPOINT>. Defaults to ORD_TAP_WRITER_EXPORT_ENDPOINT."
+  )]
+  endpoint: Option<String>,
+  #[arg(
+    long,
+    help = "Identify as export consumer <CONSUMER>. Defaults to ORD_TAP_WRITER_EXPORT_CONSUMER_ID."
+  )]
+  consumer: Option<String>,
+  #[arg(
+    long,
+    help = "Authenticate with export <TOKEN>. Defaults to ORD_TAP_WRITER_EXPORT_TOKEN."
+  )]
+  token: Option<String>,
+  #[arg(
+    long,
+    help = "Store mirrored TAP state in <DATABASE>. Defaults to <DATA_DIR>/tap-mirror.redb."
+  )]
+  database: Option<PathBuf>,
+  #[arg(long, help = "Exit once the mirror has caught up with the writer.")]
+  once: bool,
+  #[arg(
+    long,
+    default_value = "5s",
+    help = "Poll the writer every <POLLING_INTERVAL> once caught up."
+  )]
+  polling_interval: humantime::Duration,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Output {
+  pub height: Option<u32>,
+  pub block_hash: Option<String>,
+  pub row_count: u64,
+  pub state_digest: String,
+  pub snapshots: u64,
+  pub blocks: u64,
+  pub rollbacks: u64,
+}
+
+impl Mirror {
+  pub(crate) fn run(self, settings: Settings) -> SubcommandResult {
+    let endpoint = self
+      .endpoint
+      .or_else(|| settings.tap_writer_export_endpoint().map(str::to_string))
+      .ok_or_else(|| {
+        anyhow!("no writer export endpoint: pass --endpoint or set ORD_TAP_WRITER_EXPORT_ENDPOINT")
+      })?;
+
+    let consumer = self
+      .consumer
+      .or_else(|| settings.tap_writer_export_consumer_id().map(str::to_string))
+      .ok_or_else(|| {
+        anyhow!(
+          "no writer export consumer: pass --consumer or set ORD_TAP_WRITER_EXPORT_CONSUMER_ID"
+        )
+      })?;
+
+    let token = self
+      .token
+      .or_else(|| settings.tap_writer_export_token().map(str::to_string))
+      .ok_or_else(|| {
+        anyhow!("no writer export token: pass --token or set ORD_TAP_WRITER_EXPORT_TOKEN")
+      })?;
+
+    let mut follower = Follower::new(
+      ExportClient::new(
+        &endpoint,
+        settings.tap_writer_export_public_bind(),
+        consumer,
+        token,
+      )?,
+      MirrorStore::open(
+        &self
+          .database
+          .unwrap_or_else(|| settings.data_dir().join("tap-mirror.redb")),
+      )?,
+    );
+
+    loop {
+      if SHUTTING_DOWN.load(atomic::Ordering::Relaxed) {
+        break;
+      }
+
+      if follower.sync()? {
+        if self.once {
+          break;
+        }
+
+        thread::sleep(self.polling_interval.into());
+      }
+    }
+
+    Ok(Some(Box::new(follower.output()?)))
+  }
+}
+
+/// Pages through `/r/tap/export/deltas` once per sync pass and hands out the
+/// rows of one block at a time.
+struct DeltaReader {
+  exhausted: bool,
+  from_block: u32,
+  from_sequence: u64,
+  rows: VecDeque<TapExportDeltaRecord>,
+}
+
+impl DeltaReader {
+  fn new(from_block: u32) -> Self {
+    Self {
+      exhausted: false,
+      from_block,
+      from_sequence: 0,
+      rows: VecDeque::new(),
+    }
+  }
+
+  fn rows_for_block(
+    &mut self,
+    client: &ExportClient,
+    height: u32,
+  ) -> Result<Vec<TapExportDeltaRecord>> {
+    while !self.exhausted && self.rows.back().map_or(true, |row| row.height <= height) {
+      let page = client.deltas(self.from_block, self.from_sequence, DELTA_PAGE_ROWS)?;
+
+      match page.next {
+        Some((block, sequence)) => {
+          self.from_block = block;
+          self.from_sequence = sequence;
+        }
+        None => self.exhausted = true,
+      }
+
+      self.rows.extend(page.rows);
+    }
+
+    let mut rows = Vec::new();
+    while self.rows.front().is_some_and(|row| row.height <= height) {
+      rows.extend(self.rows.pop_front());
+    }
+
+    Ok(rows)
+  }
+}
+
+struct Follower {
+  blocks: u64,
+  client: ExportClient,
+  rollbacks: u64,
+  snapshots: u64,
+  store: MirrorStore,
+}
+
+impl Follower {
+  fn new(client: ExportClient, store: MirrorStore) -> Self {
+    Self {
+      blocks: 0,
+      client,
+      rollbacks: 0,
+      snapshots: 0,
+      store,
+    }
+  }
+
+  /// Run one pass against the writer. Returns true once the mirror has
+  /// applied every block the writer currently covers.
+  fn sync(&mut self) -> Result<bool> {
+    match self.follow() {
+      Err(err) if err.downcast_ref::<ResnapshotRequired>().is_some() => {
+        log::warn!("{err}; resnapshotting TAP mirror");
+        let hello = self.client.hello()?;
+        self.snapshot(&hello)?;
+        Ok(false)
+      }
+      result => result,
+    }
+  }
+
+  fn follow(&mut self) -> Result<bool> {
+    let hello = self.client.hello()?;
+
+    ensure!(
+      hello.export_protocol == EXPORT_PROTOCOL,
+      "unsupported writer export protocol `{}`, expected `{EXPORT_PROTOCOL}`",
+      hello.export_protocol
+    );
+
+    let Some(state) = self.store.state()? else {
+      self.snapshot(&hello)?;
+      return Ok(false);
+    };
+
+    ensure!(
+      state.network == hello.network,
+      "TAP mirror database holds {} state but the writer exports {}",
+      state.network,
+      hello.network
+    );
+
+    // Tells a writer pruning by consumer cursor which blocks this mirror no
+    // longer needs. Writers without retention accept and ignore it.
+    if let Err(err) = self.client.ack(state.next_height) {
+      log::warn!("failed to acknowledge TAP mirror cursor: {err}");
+    }
+
+    if let Some(tip) = state.next_height.checked_sub(1) {
+      if !self.on_writer_chain(tip)? {
+        self.rollback(&hello)?;
+        return Ok(false);
+      }
+    }
+
+    let retention = self.client.retention()?;
+
+    if state.next_height > hello.watermark {
+      return Ok(true);
+    }
+
+    let (Some(coverage_start), Some(coverage_tip)) = (
+      retention.export_enabled_from_height,
+      retention.export_coverage_tip,
+    ) else {
+      log::warn!("writer export has no delta coverage; resnapshotting TAP mirror");
+      self.snapshot(&hello)?;
+      return Ok(false);
+    };
+
+    if state.next_height < coverage_start {
+      log::warn!(
+        "TAP mirror needs block {} but writer export deltas start at block {coverage_start}; resnapshotting",
+        state.next_height
+      );
+      self.snapshot(&hello)?;
+      return Ok(false);
+    }
+
+    let target = coverage_tip.min(hello.watermark);
+
+    if state.next_height > target {
+      return Ok(true);
+    }
+
+    let mut reader = DeltaReader::new(state.next_height);
+
+    for height in state.next_height..=target {
+      let digest = self.client.block_digest(height)?;
+
+      let Some(block_hash) = digest.block_hash.as_deref() else {
+        return Ok(false);
+      };
+
+      if let Some(parent) = height.checked_sub(1) {
+        if let Some(local) = self.store.block_hash(parent)? {
+          if digest.parent_block_hash.as_deref() != Some(local.as_str()) {
+            return Ok(false);
+          }
+        }
+      }
+
+      let rows = reader.rows_for_block(&self.client, height)?;
+
+      // The writer reorged between the digest and delta requests. The next
+      // pass rolls back to the fork point before following again.
+      if rows
+        .iter()
+        .any(|row| row.block_hash.as_deref() != Some(block_hash))
+      {
+        return Ok(false);
+      }
+
+      Self::verify_block(&digest, &rows)?;
+
+      self.store.apply_block(
+        height,
+        block_hash,
+        &rows,
+        Self::rolling_state(&digest).as_ref(),
+        REORG_DEPTH,
+      )?;
+
+      self.blocks += 1;
+    }
+
+    Ok(false)
+  }
+
+  fn verify_block(digest: &TapExportBlockDigest, rows: &[TapExportDeltaRecord]) -> Result {
+    ensure!(
+      rows.len() as u64 == digest.delta_rows,
+      "writer export returned {} delta rows for block {} but its block digest reports {}",
+      rows.len(),
+      digest.height,
+      digest.delta_rows
+    );
+
+    for row in rows {
+      ensure!(
+        row.height == digest.height,
+        "writer export returned a delta row for block {} while reading block {}",
+        row.height,
+        digest.height
+      );
+
+      let row_hash = Index::tap_export_row_hash(
+        row.height,
+        row.sequence,
+        &row.op,
+        &row.key,
+        row.value.as_deref(),
+      )?;
+
+      ensure!(
+        row_hash == row.row_hash,
+        "delta row {}:{} hash mismatch: writer sent {}, computed {row_hash}",
+        row.height,
+        row.sequence,
+        row.row_hash
+      );
+    }
+
+    let delta_digest = Index::tap_export_delta_digest(rows.iter().map(|row| row.row_hash.as_str()));
+
+    ensure!(
+      delta_digest == digest.delta_digest,
+      "block {} delta digest mismatch: writer reports {}, computed {delta_digest}",
+      digest.height,
+      digest.delta_digest
+    );
+
+    Ok(())
+  }
+
+  fn rolling_state(digest: &TapExportBlockDigest) -> Option<TapExportRollingState> {
+    Some(TapExportRollingState {
+      row_count: digest.rolling_state_row_count?,
+      state_digest: digest.rolling_state_digest.clone()?,
+    })
+  }
+
+  fn on_writer_chain(&self, height: u32) -> Result<bool> {
+    let Some(local) = self.store.block_hash(height)? else {
+      return Ok(true);
+    };
+
+    Ok(self.client.block_digest(height)?.block_hash.as_deref() == Some(local.as_str()))
+  }
+
+  /// Undo blocks the writer no longer has until the mirror tip is back on the
+  /// writer's chain, falling back to a snapshot when the fork predates the
+  /// retained undo records.
+  fn rollback(&mut self, hello: &Hello) -> Result {
+    loop {
+      let Some(tip) = self
+        .store
+        .state()?
+        .and_then(|state| state.next_height.checked_sub(1))
+      else {
+        return Ok(());
+      };
+
+      if self.on_writer_chain(tip)? {
+        let digest = self.client.block_digest(tip)?;
+        if let (Some(expected), Some(state)) = (Self::rolling_state(&digest), self.store.state()?) {
+          ensure!(
+            state.rolling == expected,
+            "TAP mirror diverged from the writer at block {tip} after rolling back"
+          );
+        }
+        return Ok(());
+      }
+
+      if !self.store.rollback_block()? {
+        log::warn!("TAP mirror fork at block {tip} predates retained undo records; resnapshotting");
+        return self.snapshot(hello);
+      }
+
+      log::info!("TAP mirror rolled back block {tip}");
+
+      self.rollbacks += 1;
+    }
+  }
+
+  fn snapshot(&mut self, hello: &Hello) -> Result {
+    for attempt in 1..=SNAPSHOT_ATTEMPTS {
+      let open = self.client.snapshot_open()?;
+
+      let expected = match open.source_height.checked_sub(1) {
+        Some(height) => Self::rolling_state(&self.client.block_digest(height)?),
+        None => None,
+      };
+
+      let client = &self.client;
+
+      let result =
+        self
+          .store
+          .restore_snapshot(&hello.network, &open, expected.as_ref(), |after_key| {
+            Ok(
+              client
+                .snapshot_read(&open.snapshot_id, after_key, SNAPSHOT_PAGE_ROWS)?
+                .rows,
+            )
+          });
+
+      self.client.snapshot_close()?;
+
+      match result {
+        Ok(_) => {
+          log::info!(
+            "TAP mirror restored snapshot {} with {} rows",
+            open.snapshot_id,
+            open.row_count
+          );
+          self.snapshots += 1;
+          return Ok(());
+        }
+        Err(err) if attempt < SNAPSHOT_ATTEMPTS => {
+          log::warn!("retrying TAP mirror snapshot after error: {err}");
+        }
+        Err(err) => return Err(err),
+      }
+    }
+
+    unreachable!()
+  }
+
+  fn output(&self) -> Result<Output> {
+    let state = self.store.state()?;
+    let height = state
+      .as_ref()
+      .and_then(|state| state.next_height.checked_sub(1));
+
+    Ok(Output {
+      height,
+      block_hash: height
+        .map(|height| self.store.block_hash(height))
+        .transpose()?
+        .flatten(),
+      row_count: state.as_ref().map_or(0, |state| state.rolling.row_count),
+      state_digest: state.map_or_else(Index::tap_export_rolling_zero_digest, |state| {
+        state.rolling.state_digest
+      }),
+      snapshots: self.snapshots,
+      blocks: self.blocks,
+      rollbacks: self.rollbacks,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::{index::testing::Context, options::Options, subcommand::server::Server},
+    tempfile::TempDir,
+  };
+
+  type Blocks = BTreeMap<u32, Vec<(&'static str, Option<&'static str>)>>;
+
+  struct Writer {
+    core: mockcore::Handle,
+    endpoint: String,
+    index: Arc<Index>,
+    #[allow(unused)]
+    runtime: tokio::runtime::Runtime,
+    #[allow(unused)]
+    tempdir: TempDir,
+  }
+
+  impl Writer {
+    fn new() -> Self {
+      let Context {
+        mut index,
+        core,
+        tempdir,
+      } = Context::builder().build();
+      index.set_durability(redb::Durability::Immediate);
+      let index = Arc::new(index);
+
+      let mut env = BTreeMap::new();
+      env.insert("TAP_WRITER_EXPORT".to_string(), "1".to_string());
+      env.insert(
+        "TAP_WRITER_EXPORT_CONSUMER_ID".to_string(),
+        "mirror-test".to_string(),
+      );
+      env.insert(
+        "TAP_WRITER_EXPORT_TOKEN".to_string(),
+        "test-token".to_string(),
+      );
+      let settings = Arc::new(Settings::merge(Options::default(), env).unwrap());
+
+      let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+      let listener = runtime
+        .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+        .unwrap();
+      let endpoint = format!("tcp://{}", listener.local_addr().unwrap());
+      let router = Server::tap_writer_export_router(index.clone(), settings);
+      runtime.spawn(async move { axum::serve(listener, router.into_make_service()).await });
+
+      Self {
+        core,
+        endpoint,
+        index,
+        runtime,
+        tempdir,
+      }
+    }
+
+    fn mine(&self, subsidy: u64, blocks: &Blocks, coverage_start: u32) {
+      self.core.mine_blocks_with_subsidy(1, subsidy);
+      self.index.update().unwrap();
+      self
+        .index
+        .tap_test_write_export(blocks, coverage_start)
+        .unwrap();
+    }
+
+    fn rows(&self) -> BTreeMap<String, String> {
+      self
+        .index
+        .tap_export_snapshot(None, 50_000, None)
+        .unwrap()
+        .rows
+        .into_iter()
+        .map(|row| (row.key, row.value))
+        .collect()
+    }
+
+    fn follower(&self, tempdir: &TempDir) -> Follower {
+      Follower::new(
+        ExportClient::new(
+          &self.endpoint,
+          false,
+          "mirror-test".into(),
+          "test-token".into(),
+        )
+        .unwrap(),
+        MirrorStore::open(&tempdir.path().join("tap-mirror.redb")).unwrap(),
+      )
+    }
+  }
+
+  fn sync(follower: &mut Follower) -> Output {
+    for _ in 0..10 {
+      if follower.sync().unwrap() {
+        return follower.output().unwrap();
+      }
+    }
+    panic!("TAP mirror did not catch up");
+  }
+
+  #[test]
+  fn mirror_follows_snapshot_deltas_reorgs_and_coverage_loss() {
+    let writer = Writer::new();
+    let mut blocks = Blocks::new();
+
+    blocks.insert(1, vec![("a", Some("1")), ("b", Some("2"))]);
+    writer.mine(50 * COIN_VALUE, &blocks, 0);
+    blocks.insert(2, vec![("a", Some("3")), ("b", None), ("c", Some("4"))]);
+    writer.mine(50 * COIN_VALUE, &blocks, 0);
+
+    let tempdir = TempDir::new().unwrap();
+    let mut follower = writer.follower(&tempdir);
+
+    let output = sync(&mut follower);
+    assert_eq!(output.height, Some(2));
+    assert_eq!(output.snapshots, 1);
+    assert_eq!(output.blocks, 0);
+    assert_eq!(follower.store.rows().unwrap(), writer.rows());
+
+    blocks.insert(3, vec![("d", Some("5")), ("a", Some("6"))]);
+    writer.mine(50 * COIN_VALUE, &blocks, 0);
+
+    let output = sync(&mut follower);
+    assert_eq!(output.height, Some(3));
+    assert_eq!(output.snapshots, 1);
+    assert_eq!(output.blocks, 1);
+    assert_eq!(follower.store.rows().unwrap(), writer.rows());
+
+    writer.core.invalidate_tip();
+    blocks.insert(3, vec![("e", Some("7"))]);
+    writer.mine(25 * COIN_VALUE, &blocks, 0);
+    blocks.insert(4, vec![("a", None)]);
+    writer.mine(25 * COIN_VALUE, &blocks, 0);
+
+    let output = sync(&mut follower);
+    assert_eq!(output.height, Some(4));
+    assert_eq!(output.rollbacks, 1);
+    assert_eq!(output.snapshots, 1);
+    assert_eq!(output.blocks, 3);
+    assert_eq!(
+      output.block_hash,
+      writer
+        .index
+        .block_hash(Some(4))
+        .unwrap()
+        .map(|hash| hash.to_string())
+    );
+    assert_eq!(
+      follower.store.rows().unwrap(),
+      [("c", "4"), ("e", "7")]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<BTreeMap<String, String>>()
+    );
+
+    blocks.insert(5, vec![("f", Some("8"))]);
+    writer.mine(50 * COIN_VALUE, &blocks, 6);
+
+    let output = sync(&mut follower);
+    assert_eq!(output.height, Some(5));
+    assert_eq!(output.snapshots, 2);
+    assert_eq!(output.blocks, 3);
+    assert_eq!(follower.store.rows().unwrap(), writer.rows());
+  }
+
+  #[test]
+  fn mirror_acknowledges_its_cursor_and_resnapshots_behind_pruned_deltas() {
+    let writer = Writer::new();
+    let mut blocks = Blocks::new();
+
+    blocks.insert(1, vec![("a", Some("1"))]);
+    writer.mine(50 * COIN_VALUE, &blocks, 0);
+
+    let tempdir = TempDir::new().unwrap();
+    let mut follower = writer.follower(&tempdir);
+
+    let output = sync(&mut follower);
+    assert_eq!(output.height, Some(1));
+    assert_eq!(output.snapshots, 1);
+    assert_eq!(
+      writer
+        .index
+        .tap_export_retention_status()
+        .unwrap()
+        .consumer_cursors
+        .get("mirror-test"),
+      Some(&2)
+    );
+
+    writer.core.mine_blocks(29);
+    blocks.insert(31, vec![("b", Some("2"))]);
+    writer.mine(50 * COIN_VALUE, &blocks, 0);
+
+    let prune = writer
+      .index
+      .prune_tap_export(crate::index::TapExportRetention {
+        blocks: Some(1),
+        bytes: None,
+        consumers: false,
+      })
+      .unwrap()
+      .unwrap();
+    assert_eq!(prune.coverage_start, 12);
+
+    assert!(follower
+      .client
+      .deltas(2, 0, 10)
+      .unwrap_err()
+      .downcast_ref::<ResnapshotRequired>()
+      .unwrap()
+      .to_string()
+      .contains("resnapshot required: block 2 was pruned"));
+
+    let output = sync(&mut follower);
+    assert_eq!(output.height, Some(31));
+    assert_eq!(output.snapshots, 2);
+    assert_eq!(follower.store.rows().unwrap(), writer.rows());
+  }
+
+  #[test]
+  fn mirror_refuses_block_whose_rows_do_not_match_the_block_digest() {
+    let digest = TapExportBlockDigest {
+      height: 7,
+      block_hash: None,
+      parent_block_hash: None,
+      delta_rows: 1,
+      delta_digest: Index::tap_export_delta_digest(["00"]),
+      rolling_state_row_count: None,
+      rolling_state_digest: None,
+    };
+
+    let row = TapExportDeltaRecord {
+      height: 7,
+      sequence: 0,
+      op: "put".into(),
+      key: "a".into(),
+      value: Some("1".into()),
+      row_hash: "00".into(),
+      block_hash: None,
+      parent_block_hash: None,
+    };
+
+    assert!(Follower::verify_block(&digest, &[row.clone()])
+      .unwrap_err()
+      .to_string()
+      .contains("delta row 7:0 hash mismatch"));
+
+    let row = TapExportDeltaRecord {
+      row_hash: Index::tap_export_row_hash(7, 0, "put", "a", Some("1")).unwrap(),
+      ..row
+    };
+
+    assert!(Follower::verify_block(&digest, &[row.clone()])
+      .unwrap_err()
+      .to_string()
+      .contains("block 7 delta digest mismatch"));
+
+    let digest = TapExportBlockDigest {
+      delta_digest: Index::tap_export_delta_digest([row.row_hash.as_str()]),
+      ..digest
+    };
+
+    Follower::verify_block(&digest, &[row]).unwrap();
+  }
+}
@@ -0,0 +1,169 @@
+use {
+  super::*,
+  crate::subcommand::server::{Server, TapWriterExportEndpoint},
+  http_body_util::{BodyExt, Empty},
+  hyper::body::Bytes,
+  hyper_util::rt::TokioIo,
+  tokio::io::{AsyncRead, AsyncWrite},
+};
+
+/// Response from `/r/tap/export/hello`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct Hello {
+  pub(crate) network: String,
+  pub(crate) export_protocol: String,
+  pub(crate) watermark: u32,
+}
+
+/// The writer pruned deltas the mirror still needs, so the mirror must
+/// resnapshot before following again.
+#[derive(Debug)]
+pub(crate) struct ResnapshotRequired(pub(crate) String);
+
+impl Display for ResnapshotRequired {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl std::error::Error for ResnapshotRequired {}
+
+/// Blocking client for the TAP writer export service.
+///
+/// Every request opens a fresh connection, so the same code path serves Unix
+/// sockets, named pipes, and loopback TCP without connection pooling.
+pub(crate) struct ExportClient {
+  consumer: String,
+  endpoint: TapWriterExportEndpoint,
+  runtime: Runtime,
+  token: String,
+}
+
+impl ExportClient {
+  pub(crate) fn new(endpoint: &str, public: bool, consumer: String, token: String) -> Result<Self> {
+    Ok(Self {
+      consumer,
+      endpoint: Server::parse_tap_writer_export_endpoint(endpoint, public)?,
+      runtime: tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?,
+      token,
+    })
+  }
+
+  pub(crate) fn hello(&self) -> Result<Hello> {
+    self.get("/r/tap/export/hello")
+  }
+
+  pub(crate) fn ack(&self, next_block: u32) -> Result {
+    self.get::<serde_json::Value>(&format!("/r/tap/export/ack?next_block={next_block}"))?;
+    Ok(())
+  }
+
+  pub(crate) fn retention(&self) -> Result<TapExportRetentionStatus> {
+    self.get("/r/tap/export/retention")
+  }
+
+  pub(crate) fn block_digest(&self, height: u32) -> Result<TapExportBlockDigest> {
+    self.get(&format!("/r/tap/export/block-digest/{height}"))
+  }
+
+  pub(crate) fn snapshot_open(&self) -> Result<TapExportSnapshotOpen> {
+    self.get("/r/tap/export/snapshot-open")
+  }
+
+  pub(crate) fn snapshot_read(
+    &self,
+    snapshot_id: &str,
+    after_key: Option<&str>,
+    limit_rows: usize,
+  ) -> Result<TapExportSnapshotRead> {
+    let mut path = format!(
+      "/r/tap/export/snapshot-read?snapshot_id={}&limit_rows={limit_rows}",
+      urlencoding::encode(snapshot_id)
+    );
+    if let Some(after_key) = after_key {
+      path.push_str(&format!("&after_key={}", urlencoding::encode(after_key)));
+    }
+    self.get(&path)
+  }
+
+  pub(crate) fn snapshot_close(&self) -> Result {
+    self.get::<serde_json::Value>("/r/tap/export/snapshot-close")?;
+    Ok(())
+  }
+
+  pub(crate) fn deltas(
+    &self,
+    from_block: u32,
+    from_sequence: u64,
+    limit: usize,
+  ) -> Result<TapExportDeltaPage> {
+    self.get(&format!(
+      "/r/tap/export/deltas?from_block={from_block}&from_sequence={from_sequence}&limit={limit}"
+    ))
+  }
+
+  fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+    let request = hyper::Request::get(path)
+      .header(hyper::header::HOST, "localhost")
+      .header("x-tap-export-consumer", &self.consumer)
+      .header("x-tap-export-token", &self.token)
+      .body(Empty::<Bytes>::new())?;
+
+    let (status, body) = self.runtime.block_on(async {
+      match &self.endpoint {
+        TapWriterExportEndpoint::Tcp(addr) => {
+          Self::send(tokio::net::TcpStream::connect(addr).await?, request).await
+        }
+        #[cfg(unix)]
+        TapWriterExportEndpoint::Unix(path) => {
+          Self::send(tokio::net::UnixStream::connect(path).await?, request).await
+        }
+        #[cfg(windows)]
+        TapWriterExportEndpoint::NamedPipe(path) => {
+          Self::send(
+            tokio::net::windows::named_pipe::ClientOptions::new().open(path)?,
+            request,
+          )
+          .await
+        }
+      }
+    })?;
+
+    if status == StatusCode::GONE {
+      bail!(ResnapshotRequired(
+        String::from_utf8_lossy(&body).trim().to_string()
+      ));
+    }
+
+    ensure!(
+      status.is_success(),
+      "tap writer export request `{path}` failed with {status}: {}",
+      String::from_utf8_lossy(&body).trim()
+    );
+
+    serde_json::from_slice(&body)
+      .with_context(|| format!("failed to decode tap writer export response from `{path}`"))
+  }
+
+  async fn send<S>(stream: S, request: hyper::Request<Empty<Bytes>>) -> Result<(StatusCode, Bytes)>
+  where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+  {
+    let (mut sender, connection) =
+      hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+
+    tokio::spawn(async move {
+      if let Err(error) = connection.await {
+        log::debug!("tap writer export connection closed: {error}");
+      }
+    });
+
+    let response = sender.send_request(request).await?;
+    let status = response.status();
+    let body = response.into_body().collect().await?.to_bytes();
+
+    Ok((status, body))
+  }
+}
@@ -0,0 +1,336 @@
+use {
+  super::*,
+  redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition},
+};
+
+define_table! { MIRROR_BLOCK_HASHES, u32, &str }
+define_table! { MIRROR_KV, &str, &str }
+define_table! { MIRROR_METADATA, &str, &str }
+define_table! { MIRROR_UNDO, u32, &[u8] }
+
+const NETWORK: &str = "network";
+const NEXT_HEIGHT: &str = "next_height";
+const ROLLING_ROW_COUNT: &str = "rolling_state_row_count";
+const ROLLING_DIGEST: &str = "rolling_state_digest";
+
+/// Keys touched by one applied block together with their values before the
+/// block, in application order, so the block can be rolled back on reorg.
+type Undo = Vec<(String, Option<String>)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MirrorState {
+  pub(crate) network: String,
+  pub(crate) next_height: u32,
+  pub(crate) rolling: TapExportRollingState,
+}
+
+/// Local redb copy of the writer's TAP_KV, stored as export value strings.
+pub(crate) struct MirrorStore {
+  database: Database,
+}
+
+impl MirrorStore {
+  pub(crate) fn open(path: &Path) -> Result<Self> {
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)
+        .with_context(|| format!("failed to create mirror dir `{}`", parent.display()))?;
+    }
+
+    let database = Database::create(path)
+      .with_context(|| format!("failed to open TAP mirror database `{}`", path.display()))?;
+
+    let wtx = database.begin_write()?;
+    wtx.open_table(MIRROR_BLOCK_HASHES)?;
+    wtx.open_table(MIRROR_KV)?;
+    wtx.open_table(MIRROR_METADATA)?;
+    wtx.open_table(MIRROR_UNDO)?;
+    wtx.commit()?;
+
+    Ok(Self { database })
+  }
+
+  pub(crate) fn state(&self) -> Result<Option<MirrorState>> {
+    let rtx = self.database.begin_read()?;
+    let metadata = rtx.open_table(MIRROR_METADATA)?;
+
+    let get = |key: &str| -> Result<Option<String>> {
+      Ok(metadata.get(key)?.map(|value| value.value().to_string()))
+    };
+
+    let (Some(network), Some(next_height), Some(row_count), Some(state_digest)) = (
+      get(NETWORK)?,
+      get(NEXT_HEIGHT)?,
+      get(ROLLING_ROW_COUNT)?,
+      get(ROLLING_DIGEST)?,
+    ) else {
+      return Ok(None);
+    };
+
+    Ok(Some(MirrorState {
+      network,
+      next_height: next_height.parse()?,
+      rolling: TapExportRollingState {
+        row_count: row_count.parse()?,
+        state_digest,
+      },
+    }))
+  }
+
+  pub(crate) fn block_hash(&self, height: u32) -> Result<Option<String>> {
+    Ok(
+      self
+        .database
+        .begin_read()?
+        .open_table(MIRROR_BLOCK_HASHES)?
+        .get(height)?
+        .map(|hash| hash.value().to_string()),
+    )
+  }
+
+  #[cfg(test)]
+  pub(crate) fn rows(&self) -> Result<BTreeMap<String, String>> {
+    let rtx = self.database.begin_read()?;
+    let table = rtx.open_table(MIRROR_KV)?;
+    let mut rows = BTreeMap::new();
+    for result in table.iter()? {
+      let (key, value) = result?;
+      rows.insert(key.value().to_string(), value.value().to_string());
+    }
+    Ok(rows)
+  }
+
+  /// Replace the mirror with a full snapshot. Pages are requested with the
+  /// last key received until an empty page is returned, and the result is
+  /// only committed if it matches the row count and digest advertised when the
+  /// snapshot was opened and, when the writer records one, its rolling state.
+  pub(crate) fn restore_snapshot(
+    &self,
+    network: &str,
+    open: &TapExportSnapshotOpen,
+    expected: Option<&TapExportRollingState>,
+    mut read: impl FnMut(Option<&str>) -> Result<Vec<TapExportSnapshotRow>>,
+  ) -> Result<TapExportRollingState> {
+    let wtx = self.database.begin_write()?;
+
+    let mut rolling = TapExportRollingState {
+      row_count: 0,
+      state_digest: Index::tap_export_rolling_zero_digest(),
+    };
+
+    {
+      wtx.delete_table(MIRROR_BLOCK_HASHES)?;
+      wtx.delete_table(MIRROR_KV)?;
+      wtx.delete_table(MIRROR_UNDO)?;
+
+      let mut kv = wtx.open_table(MIRROR_KV)?;
+      let mut hasher = Sha256::new();
+      let mut after_key = None::<String>;
+
+      loop {
+        let rows = read(after_key.as_deref())?;
+
+        let Some(last) = rows.last() else {
+          break;
+        };
+
+        after_key = Some(last.key.clone());
+
+        for row in rows {
+          Index::tap_export_digest_pair(&mut hasher, row.key.as_bytes(), row.value.as_bytes());
+          Index::tap_export_rolling_state_apply(&mut rolling, &row.key, None, Some(&row.value))?;
+          kv.insert(row.key.as_str(), row.value.as_str())?;
+        }
+      }
+
+      ensure!(
+        rolling.row_count == open.row_count && kv.len()? == open.row_count,
+        "snapshot `{}` returned {} rows but advertised {}",
+        open.snapshot_id,
+        rolling.row_count,
+        open.row_count,
+      );
+
+      let digest = Index::tap_export_digest_hex(hasher);
+
+      ensure!(
+        digest == open.state_digest,
+        "snapshot `{}` state digest mismatch: expected {}, computed {digest}",
+        open.snapshot_id,
+        open.state_digest,
+      );
+
+      if let Some(expected) = expected {
+        ensure!(
+          &rolling == expected,
+          "snapshot `{}` does not match the writer's rolling state: writer has digest {}, snapshot has {}",
+          open.snapshot_id,
+          expected.state_digest,
+          rolling.state_digest,
+        );
+      }
+
+      if let (Some(height), Some(hash)) = (
+        open.source_height.checked_sub(1),
+        open.source_block_hash.as_deref(),
+      ) {
+        wtx.open_table(MIRROR_BLOCK_HASHES)?.insert(height, hash)?;
+      }
+
+      let mut metadata = wtx.open_table(MIRROR_METADATA)?;
+      metadata.insert(NETWORK, network)?;
+      Self::put_position(&mut metadata, open.source_height, &rolling)?;
+    }
+
+    wtx.commit()?;
+
+    Ok(rolling)
+  }
+
+  /// Apply one block of verified delta rows, keeping an undo record for
+  /// `reorg_depth` blocks. The block is not committed if the resulting
+  /// rolling state differs from `expected`.
+  pub(crate) fn apply_block(
+    &self,
+    height: u32,
+    block_hash: &str,
+    rows: &[TapExportDeltaRecord],
+    expected: Option<&TapExportRollingState>,
+    reorg_depth: u32,
+  ) -> Result<TapExportRollingState> {
+    let state = self
+      .state()?
+      .ok_or_else(|| anyhow!("TAP mirror is not initialized"))?;
+
+    ensure!(
+      state.next_height == height,
+      "TAP mirror expected block {} but was given block {height}",
+      state.next_height
+    );
+
+    let mut rolling = state.rolling;
+    let mut undo = Undo::new();
+
+    let wtx = self.database.begin_write()?;
+
+    {
+      let mut kv = wtx.open_table(MIRROR_KV)?;
+
+      for row in rows {
+        let old = kv
+          .get(row.key.as_str())?
+          .map(|value| value.value().to_string());
+
+        let new = match row.op.as_str() {
+          "put" => Some(
+            row
+              .value
+              .as_deref()
+              .ok_or_else(|| anyhow!("delta put for `{}` has no value", row.key))?,
+          ),
+          "del" => None,
+          op => bail!("unknown delta op `{op}` for `{}`", row.key),
+        };
+
+        Index::tap_export_rolling_state_apply(&mut rolling, &row.key, old.as_deref(), new)?;
+
+        match new {
+          Some(value) => kv.insert(row.key.as_str(), value)?,
+          None => kv.remove(row.key.as_str())?,
+        };
+
+        undo.push((row.key.clone(), old));
+      }
+
+      if let Some(expected) = expected {
+        ensure!(
+          &rolling == expected,
+          "TAP mirror diverged from the writer at block {height}: writer has {} rows with digest {}, mirror has {} rows with digest {}",
+          expected.row_count,
+          expected.state_digest,
+          rolling.row_count,
+          rolling.state_digest,
+        );
+      }
+
+      let mut hashes = wtx.open_table(MIRROR_BLOCK_HASHES)?;
+      let mut undos = wtx.open_table(MIRROR_UNDO)?;
+
+      hashes.insert(height, block_hash)?;
+      undos.insert(height, serde_json::to_vec(&undo)?.as_slice())?;
+
+      if let Some(oldest) = height.checked_sub(reorg_depth) {
+        hashes.retain(|retained, _| retained >= oldest)?;
+        undos.retain(|retained, _| retained > oldest)?;
+      }
+
+      Self::put_position(&mut wtx.open_table(MIRROR_METADATA)?, height + 1, &rolling)?;
+    }
+
+    wtx.commit()?;
+
+    Ok(rolling)
+  }
+
+  /// Roll back the most recently applied block. Returns false when no undo
+  /// record is retained for it, in which case the caller must resnapshot.
+  pub(crate) fn rollback_block(&self) -> Result<bool> {
+    let Some(state) = self.state()? else {
+      return Ok(false);
+    };
+
+    let Some(height) = state.next_height.checked_sub(1) else {
+      return Ok(false);
+    };
+
+    let mut rolling = state.rolling;
+
+    let wtx = self.database.begin_write()?;
+
+    {
+      let mut undos = wtx.open_table(MIRROR_UNDO)?;
+
+      let Some(undo) = undos.remove(height)? else {
+        return Ok(false);
+      };
+
+      let undo: Undo = serde_json::from_slice(undo.value())?;
+
+      let mut kv = wtx.open_table(MIRROR_KV)?;
+
+      for (key, old) in undo.into_iter().rev() {
+        let current = kv.get(key.as_str())?.map(|value| value.value().to_string());
+
+        Index::tap_export_rolling_state_apply(
+          &mut rolling,
+          &key,
+          current.as_deref(),
+          old.as_deref(),
+        )?;
+
+        match old {
+          Some(value) => kv.insert(key.as_str(), value.as_str())?,
+          None => kv.remove(key.as_str())?,
+        };
+      }
+
+      wtx.open_table(MIRROR_BLOCK_HASHES)?.remove(height)?;
+
+      Self::put_position(&mut wtx.open_table(MIRROR_METADATA)?, height, &rolling)?;
+    }
+
+    wtx.commit()?;
+
+    Ok(true)
+  }
+
+  fn put_position(
+    metadata: &mut redb::Table<&str, &str>,
+    next_height: u32,
+    rolling: &TapExportRollingState,
+  ) -> Result {
+    metadata.insert(NEXT_HEIGHT, next_height.to_string().as_str())?;
+    metadata.insert(ROLLING_ROW_COUNT, rolling.row_count.to_string().as_str())?;
+    metadata.insert(ROLLING_DIGEST, rolling.state_digest.as_str())?;
+    Ok(())
+  }
+}
@@ -0,0 +1,139 @@
+use {super::*, secp256k1::SecretKey};
+
+#[derive(Debug, Parser)]
+pub(crate) enum Perp {
+  #[command(about = "Fill in the hash of a perp-policy action and sign it")]
+  SignPolicy(SignPolicy),
+  #[command(about = "Complete the price certificate of a perp action and sign it")]
+  Certify(Certify),
+  #[command(about = "Check a perp action's price certificate against a policy offline")]
+  Verify(Verify),
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct SignPolicy {
+  #[arg(long, help = "Read the perp-policy action from <ACTION>.")]
+  action: PathBuf,
+  #[arg(
+    long = "key-file",
+    help = "Sign with the secp256k1 key in <KEY_FILE>, as 64 hex characters or WIF. May be repeated."
+  )]
+  key_files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct Certify {
+  #[arg(
+    long,
+    help = "Read the policy from <POLICY>, either a perp-policy action or a getPerpPolicy record."
+  )]
+  policy: PathBuf,
+  #[arg(
+    long,
+    help = "Read the perp action carrying the certificate from <ACTION>."
+  )]
+  action: PathBuf,
+  #[arg(
+    long = "key-file",
+    help = "Sign with the secp256k1 key in <KEY_FILE>, as 64 hex characters or WIF. May be repeated."
+  )]
+  key_files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct Verify {
+  #[arg(
+    long,
+    help = "Read the policy from <POLICY>, either a perp-policy action or a getPerpPolicy record."
+  )]
+  policy: PathBuf,
+  #[arg(
+    long,
+    help = "Read the perp action carrying the certificate from <ACTION>."
+  )]
+  action: PathBuf,
+  #[arg(
+    long,
+    help = "Check the certificate names the getPerpGroup record in <GROUP>."
+  )]
+  group: Option<PathBuf>,
+  #[arg(long, help = "Check the certificate is valid at block <HEIGHT>.")]
+  height: Option<u32>,
+}
+
+impl Perp {
+  pub(crate) fn run(self) -> SubcommandResult {
+    match self {
+      Self::SignPolicy(sign) => {
+        let action = read_json(&sign.action)?;
+        let keys = read_keys(&sign.key_files)?;
+        Ok(Some(Box::new(Index::tap_perp_sign_policy(&action, &keys)?)))
+      }
+      Self::Certify(certify) => {
+        let policy = read_json(&certify.policy)?;
+        let action = read_json(&certify.action)?;
+        let keys = read_keys(&certify.key_files)?;
+        Ok(Some(Box::new(Index::tap_perp_sign_certificate(
+          &policy, &action, &keys,
+        )?)))
+      }
+      Self::Verify(verify) => {
+        let policy = read_json(&verify.policy)?;
+        let action = read_json(&verify.action)?;
+        let group = verify.group.as_deref().map(read_json).transpose()?;
+
+        let report =
+          Index::tap_perp_verify_certificate(&policy, &action, group.as_ref(), verify.height)?;
+
+        if report.valid {
+          return Ok(Some(Box::new(report)));
+        }
+
+        let errors = report.errors.len();
+
+        report.print(OutputFormat::Json);
+
+        bail!("perp certificate failed {errors} check(s)");
+      }
+    }
+  }
+}
+
+/// Read a JSON document, unwrapping the `result` of a saved REST response.
+fn read_json(path: &Path) -> Result<serde_json::Value> {
+  let value: serde_json::Value = serde_json::from_str(
+    &fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?,
+  )
+  .with_context(|| format!("failed to parse {}", path.display()))?;
+
+  match value {
+    serde_json::Value::Object(mut object) if object.contains_key("result") => {
+      Ok(object.remove("result").unwrap_or_default())
+    }
+    value => Ok(value),
+  }
+}
+
+fn read_keys(paths: &[PathBuf]) -> Result<Vec<SecretKey>> {
+  if paths.is_empty() {
+    bail!("no signing keys: pass at least one --key-file");
+  }
+
+  paths
+    .iter()
+    .map(|path| {
+      let key =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+      let key = key.trim();
+      if let Ok(bytes) = hex::decode(key) {
+        return SecretKey::from_slice(&bytes)
+          .with_context(|| format!("invalid secret key in {}", path.display()));
+      }
+      Ok(
+        bitcoin::PrivateKey::from_wif(key)
+          .with_context(|| format!("invalid secret key in {}", path.display()))?
+          .inner,
+      )
+    })
+    .collect()
+}
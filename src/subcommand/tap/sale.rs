@@ -0,0 +1,83 @@
+use {super::*, crate::index::TapSaleAllowlistEntry};
+
+#[derive(Debug, Parser)]
+pub(crate) enum Sale {
+  #[command(about = "Build a sale allowlist root and per-address contribution proofs")]
+  Allowlist(Allowlist),
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct Allowlist {
+  #[arg(
+    long,
+    help = "Read allowlisted addresses from <CSV>, one `address` or `address,cap` per line."
+  )]
+  csv: PathBuf,
+  #[arg(
+    long,
+    help = "Resolve caps with the payment token's <DECIMALS>. Required when caps are given."
+  )]
+  decimals: Option<u32>,
+}
+
+impl Sale {
+  pub(crate) fn run(self, settings: Settings) -> SubcommandResult {
+    match self {
+      Self::Allowlist(allowlist) => allowlist.run(settings),
+    }
+  }
+}
+
+impl Allowlist {
+  fn run(self, settings: Settings) -> SubcommandResult {
+    let csv = fs::read_to_string(&self.csv)
+      .with_context(|| format!("failed to read {}", self.csv.display()))?;
+
+    let mut entries = Vec::new();
+    for (i, line) in csv.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let mut fields = line.split(',').map(str::trim);
+      let claim = fields.next().unwrap_or_default();
+      let cap = fields.next().filter(|cap| !cap.is_empty());
+      if fields.next().is_some() {
+        bail!("line {}: expected `address` or `address,cap`", i + 1);
+      }
+      if entries.is_empty() && claim.eq_ignore_ascii_case("address") {
+        continue;
+      }
+
+      claim
+        .parse::<Address<NetworkUnchecked>>()
+        .ok()
+        .and_then(|address| address.require_network(settings.chain().network()).ok())
+        .ok_or_else(|| {
+          anyhow!(
+            "line {}: `{claim}` is not a {} address",
+            i + 1,
+            settings.chain()
+          )
+        })?;
+
+      entries.push(TapSaleAllowlistEntry {
+        claim: claim.into(),
+        cap: cap.map(str::to_string),
+      });
+    }
+
+    let decimals = match self.decimals {
+      Some(decimals) => decimals,
+      None if entries.iter().any(|entry| entry.cap.is_some()) => {
+        bail!("caps need the payment token's --decimals")
+      }
+      None => 0,
+    };
+
+    Ok(Some(Box::new(Index::tap_sale_allowlist(
+      &entries, decimals,
+    )?)))
+  }
+}
@@ -151,6 +151,8 @@ impl Burn {
       "runic outpoints may not be burned"
     );
 
+    wallet.check_tap_spend(satpoint.outpoint, Some(satpoint))?;
+
     let change = [wallet.get_change_address()?, wallet.get_change_address()?];
 
     Ok(
@@ -73,28 +73,36 @@ impl Inscribe {
       }
     }
 
+    let destination = match self.destination.clone() {
+      Some(destination) => destination.require_network(chain.network())?,
+      None => wallet.get_change_address()?,
+    };
+
+    let inscription = Inscription::new(
+      chain,
+      self.shared.compress,
+      self.delegate,
+      WalletCommand::parse_metadata(self.cbor_metadata, self.json_metadata)?,
+      self.metaprotocol,
+      self.parent.into_iter().collect(),
+      self.file,
+      None,
+      Properties {
+        gallery: self.gallery,
+      },
+      None,
+    )?;
+
+    if let Some(body) = inscription.body() {
+      Self::check_tap(&wallet, body, &destination)?;
+    }
+
     batch::Plan {
       commit_fee_rate: self.shared.commit_fee_rate.unwrap_or(self.shared.fee_rate),
-      destinations: vec![match self.destination.clone() {
-        Some(destination) => destination.require_network(chain.network())?,
-        None => wallet.get_change_address()?,
-      }],
+      destinations: vec![destination],
       dry_run: self.shared.dry_run,
       etching: None,
-      inscriptions: vec![Inscription::new(
-        chain,
-        self.shared.compress,
-        self.delegate,
-        WalletCommand::parse_metadata(self.cbor_metadata, self.json_metadata)?,
-        self.metaprotocol,
-        self.parent.into_iter().collect(),
-        self.file,
-        None,
-        Properties {
-          gallery: self.gallery,
-        },
-        None,
-      )?],
+      inscriptions: vec![inscription],
       mode: batch::Mode::SeparateOutputs,
       no_backup: self.shared.no_backup,
       no_limit: self.shared.no_limit,
@@ -116,6 +124,39 @@ impl Inscribe {
       &wallet,
     )
   }
+
+  /// Refuses TAP deploys and mints that the indexer would reject in the next
+  /// block, so the mistake is found before paying for the inscription.
+  fn check_tap(wallet: &Wallet, body: &[u8], destination: &Address) -> Result {
+    let body = String::from_utf8_lossy(body);
+
+    if !Self::is_tap(&body) {
+      return Ok(());
+    }
+
+    let validation = wallet.tap_validate(&body, destination)?;
+
+    for warning in validation["warnings"].as_array().into_iter().flatten() {
+      eprintln!("warning: {}", warning.as_str().unwrap_or_default());
+    }
+
+    ensure!(
+      validation["valid"] != false,
+      "TAP {} would be rejected at block {}: {}",
+      validation["op"].as_str().unwrap_or("inscription"),
+      validation["height"],
+      validation["reason"].as_str().unwrap_or_default(),
+    );
+
+    Ok(())
+  }
+
+  fn is_tap(body: &str) -> bool {
+    body
+      .split_whitespace()
+      .collect::<String>()
+      .contains(r#""p":"tap""#)
+  }
 }
 
 #[cfg(test)]
@@ -207,4 +248,16 @@ mod tests {
     ])
     .is_ok());
   }
+
+  #[test]
+  fn tap_bodies_are_detected() {
+    assert!(Inscribe::is_tap(
+      r#"{"p":"tap","op":"token-mint","tick":"tap","amt":"1"}"#
+    ));
+    assert!(Inscribe::is_tap(
+      "{\n  \"p\": \"tap\",\n  \"op\": \"token-deploy\"\n}"
+    ));
+    assert!(!Inscribe::is_tap(r#"{"p":"brc-20","op":"mint"}"#));
+    assert!(!Inscribe::is_tap("hello"));
+  }
 }
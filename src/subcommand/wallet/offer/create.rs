@@ -74,6 +74,10 @@ impl Create {
 
     let tx = Transaction::consensus_decode(&mut tx.as_slice())?;
 
+    for input in &tx.input {
+      wallet.check_tap_spend(input.previous_output, None)?;
+    }
+
     let psbt = Psbt::from_unsigned_tx(tx)?;
 
     let result = wallet
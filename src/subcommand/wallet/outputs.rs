@@ -18,6 +18,8 @@ pub struct Output {
   pub runes: Option<BTreeMap<SpacedRune, Decimal>>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub sat_ranges: Option<Vec<String>>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub tap: Option<BTreeMap<InscriptionId, String>>,
 }
 
 impl Outputs {
@@ -61,6 +63,12 @@ impl Outputs {
         None
       };
 
+      let tap = inscriptions
+        .iter()
+        .flatten()
+        .filter_map(|id| Some((*id, wallet.tap_inscriptions().get(id)?.clone())))
+        .collect::<BTreeMap<InscriptionId, String>>();
+
       outputs.push(Output {
         address,
         amount: txout.value.to_sat(),
@@ -68,6 +76,7 @@ impl Outputs {
         output: *output,
         runes,
         sat_ranges,
+        tap: (!tap.is_empty()).then_some(tap),
       });
     }
 
@@ -144,7 +144,12 @@ impl Split {
       &unfunded_transaction,
     )?;
 
-    let unsigned_transaction = consensus::encode::deserialize(&unsigned_transaction)?;
+    let unsigned_transaction =
+      consensus::encode::deserialize::<Transaction>(&unsigned_transaction)?;
+
+    for input in &unsigned_transaction.input {
+      wallet.check_tap_spend(input.previous_output, None)?;
+    }
 
     let (txid, psbt, fee) =
       wallet.sign_and_broadcast_transaction(unsigned_transaction, self.dry_run, None)?;
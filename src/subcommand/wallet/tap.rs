@@ -0,0 +1,219 @@
+use {
+  super::*,
+  indicatif::ProgressBar,
+  shared_args::{SharedArgs, Tapped},
+};
+
+pub mod send;
+mod shared_args;
+pub mod trade;
+pub mod transfer;
+
+// How long to wait for an inscription to be mined and indexed before giving
+// up on tapping it.
+const TAP_INDEX_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Parser)]
+pub(crate) enum Tap {
+  #[command(about = "Inscribe token-sends from a CSV file and tap them")]
+  Send(send::Send),
+  #[command(subcommand, about = "Offer, fill and cancel token trades")]
+  Trade(trade::Trade),
+  #[command(about = "Inscribe a token-transfer and send it to the recipient")]
+  Transfer(transfer::Transfer),
+}
+
+impl Tap {
+  pub(crate) fn run(self, wallet: Wallet) -> SubcommandResult {
+    match self {
+      Self::Send(send) => send.run(wallet),
+      Self::Trade(trade) => trade.run(wallet),
+      Self::Transfer(transfer) => transfer.run(wallet),
+    }
+  }
+
+  /// Amount of `ticker` at `address` that new token-transfer inscriptions
+  /// can use: the balance less transferable, locked and obligation-locked
+  /// amounts, as transfer inscription validation computes it. All amounts
+  /// are in base units.
+  pub(crate) fn available_balance(wallet: &Wallet, address: &str, ticker: &str) -> Result<u128> {
+    let amount = |segments: &[&str]| -> Result<u128> {
+      match wallet.tap_get(segments)? {
+        serde_json::Value::Null => Ok(0),
+        serde_json::Value::String(amount) => amount
+          .parse()
+          .with_context(|| format!("invalid amount `{amount}` from {}", segments[0])),
+        value => bail!("invalid amount `{value}` from {}", segments[0]),
+      }
+    };
+
+    let balance = amount(&["getBalance", address, ticker])?;
+    let reserved = amount(&["getTransferable", address, ticker])?
+      + amount(&["getLockedBalance", address, ticker])?
+      + amount(&["getObligationLockedBalance", "a", address, ticker])?;
+
+    Ok(balance.saturating_sub(reserved))
+  }
+
+  /// Decimals of `ticker`, or an error when it is not deployed.
+  pub(crate) fn decimals(wallet: &Wallet, ticker: &str) -> Result<u8> {
+    let deployment = wallet.tap_get(&["getDeployment", ticker])?;
+
+    ensure!(!deployment.is_null(), "ticker `{ticker}` is not deployed");
+
+    deployment["dec"]
+      .as_u64()
+      .and_then(|dec| u8::try_from(dec).ok())
+      .ok_or_else(|| anyhow!("ticker `{ticker}` has invalid decimals"))
+  }
+
+  /// `amount` whole tokens of `ticker` in base units, checked to be positive
+  /// and at most the TAP maximum of `u64::MAX` whole tokens.
+  pub(crate) fn base_units(wallet: &Wallet, ticker: &str, amount: Decimal) -> Result<u128> {
+    let decimals = Self::decimals(wallet, ticker)?;
+
+    let amount = amount.to_integer(decimals)?;
+
+    ensure!(amount > 0, "{ticker} amount must be positive");
+
+    ensure!(
+      amount <= u128::from(u64::MAX) * 10u128.pow(decimals.into()),
+      "{ticker} amount exceeds the TAP maximum of {}",
+      u64::MAX,
+    );
+
+    Ok(amount)
+  }
+
+  /// Whether the miner-reward shield has marked `address` as a DMT reward
+  /// address.
+  pub(crate) fn is_dmt_reward_address(wallet: &Wallet, address: &str) -> Result<bool> {
+    Ok(
+      wallet.tap_batch(&[serde_json::json!({
+        "type": "dmtRewardAddress",
+        "address": address,
+      })])?[0]
+        .as_bool()
+        .unwrap_or_default(),
+    )
+  }
+
+  /// Spends TAP inscription reveal outputs holding `postage` in total. The
+  /// last output receives whatever is left after the other outputs and the
+  /// fee.
+  pub(crate) fn execution_transaction(
+    fee_rate: FeeRate,
+    reveals: &[OutPoint],
+    postage: Amount,
+    mut output: Vec<TxOut>,
+  ) -> Result<(Transaction, Amount)> {
+    let fee = fee_rate.fee(TransactionBuilder::estimate_vbytes_with(
+      reveals.len(),
+      &output,
+    ));
+
+    let reserved = output[..output.len() - 1]
+      .iter()
+      .fold(Amount::ZERO, |sum, txout| sum + txout.value);
+
+    let last = output.last_mut().unwrap();
+
+    last.value = postage
+      .checked_sub(reserved + fee)
+      .filter(|value| *value >= last.script_pubkey.minimal_non_dust())
+      .ok_or_else(|| {
+        anyhow!("postage of {postage} is too small to pay the execution fee of {fee}")
+      })?;
+
+    Ok((
+      Transaction {
+        version: Version(2),
+        lock_time: LockTime::ZERO,
+        input: reveals
+          .iter()
+          .map(|reveal| TxIn {
+            previous_output: *reveal,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+          })
+          .collect(),
+        output,
+      },
+      fee,
+    ))
+  }
+
+  /// Signs and broadcasts an execution transaction. Its inputs are reveal
+  /// outputs that may not be in the wallet's UTXO set yet, so signing goes
+  /// straight to Bitcoin Core.
+  pub(crate) fn broadcast_execution(wallet: &Wallet, execution: Transaction) -> Result<Txid> {
+    let psbt = wallet
+      .bitcoin_client()
+      .wallet_process_psbt(
+        &base64_encode(&Psbt::from_unsigned_tx(execution)?.serialize()),
+        Some(true),
+        None,
+        None,
+      )?
+      .psbt;
+
+    let signed_execution = wallet
+      .bitcoin_client()
+      .finalize_psbt(&psbt, None)?
+      .hex
+      .ok_or_else(|| anyhow!("unable to sign execution transaction"))?;
+
+    wallet.send_raw_transaction(&signed_execution, None)
+  }
+
+  /// Waits until the TAP endpoint at `segments` reports state for the `op`
+  /// inscription `inscription`, and fails if it is indexed without any or is
+  /// still not indexed after `TAP_INDEX_TIMEOUT`.
+  pub(crate) fn wait_until_indexed(
+    wallet: &Wallet,
+    inscription: InscriptionId,
+    op: &str,
+    segments: &[&str],
+  ) -> Result {
+    let progress = ProgressBar::new_spinner().with_message(format!(
+      "Waiting for {op} inscription {inscription} to be indexed…"
+    ));
+
+    let indexed = || -> Result<bool> { Ok(!wallet.tap_get(segments)?.is_null()) };
+
+    let start = Instant::now();
+
+    loop {
+      if SHUTTING_DOWN.load(atomic::Ordering::Relaxed) {
+        progress.finish_and_clear();
+        bail!("stopped waiting for {op} inscription {inscription} to be indexed");
+      }
+
+      if indexed()? {
+        progress.finish_and_clear();
+        return Ok(());
+      }
+
+      if wallet.inscription_exists(inscription)? && !indexed()? {
+        progress.finish_and_clear();
+        bail!("inscription {inscription} was indexed but is not a valid {op}");
+      }
+
+      if start.elapsed() >= TAP_INDEX_TIMEOUT {
+        progress.finish_and_clear();
+        bail!(
+          "{op} inscription {inscription} was not indexed within {} minutes; it stays pending \
+          until moved, so send it by ID once it is indexed",
+          TAP_INDEX_TIMEOUT.as_secs() / 60,
+        );
+      }
+
+      progress.tick();
+
+      if !wallet.integration_test() {
+        thread::sleep(Duration::from_secs(5));
+      }
+    }
+  }
+}
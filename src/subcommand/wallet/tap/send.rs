@@ -0,0 +1,310 @@
+use {
+  super::*,
+  crate::index::{tap_js_to_lowercase, tap_token_send_item_check},
+  bitcoin::policy::MAX_STANDARD_TX_WEIGHT,
+};
+
+#[derive(Debug, Parser)]
+pub(crate) struct Send {
+  #[command(flatten)]
+  shared: SharedArgs,
+  #[arg(
+    long,
+    help = "Put at most <MAX_ITEMS> items in each token-send inscription. By default all \
+    items go in one inscription. Every inscription is revealed in the same transaction, so \
+    splitting does not raise the MAX_STANDARD_TX_WEIGHT limit of 400,000 weight units on the \
+    total."
+  )]
+  max_items: Option<usize>,
+  #[arg(help = "Read token-send items from <CSV>, one `address,ticker,amount[,dta]` per line.")]
+  csv: PathBuf,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenSend {
+  pub inscription: InscriptionId,
+  pub items: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Output {
+  pub sends: Vec<TokenSend>,
+  pub totals: BTreeMap<String, String>,
+  #[serde(flatten)]
+  pub tapped: Tapped,
+}
+
+#[derive(Debug, PartialEq)]
+struct Item {
+  line: usize,
+  address: String,
+  ticker: String,
+  amount: Decimal,
+  dta: Option<String>,
+}
+
+impl Send {
+  pub(crate) fn run(self, wallet: Wallet) -> SubcommandResult {
+    ensure!(self.max_items != Some(0), "--max-items must be positive");
+
+    let from = self.shared.sender(&wallet)?;
+
+    let csv = fs::read_to_string(&self.csv)
+      .with_context(|| format!("failed to read {}", self.csv.display()))?;
+
+    let height = u32::try_from(wallet.bitcoin_client().get_block_count()? + 1)?;
+
+    let items = Self::items(&csv, wallet.chain(), height, &from)?;
+
+    let mut totals = BTreeMap::<String, u128>::new();
+    for item in &items {
+      let amount = Tap::base_units(&wallet, &item.ticker, item.amount)
+        .with_context(|| format!("line {}", item.line))?;
+
+      *totals.entry(item.ticker.clone()).or_default() += amount;
+    }
+
+    for (ticker, total) in &totals {
+      let available = Tap::available_balance(&wallet, &from.to_string(), ticker)?;
+
+      ensure!(
+        *total <= available,
+        "{from} has {available} {ticker} available for sends, less than the {total} in {} \
+        (amounts in base units)",
+        self.csv.display(),
+      );
+    }
+
+    let sends = Self::bodies(&items, self.max_items.unwrap_or(items.len()));
+
+    let bytes = sends.iter().map(|(body, _)| body.len()).sum::<usize>();
+    ensure!(
+      bytes < MAX_STANDARD_TX_WEIGHT as usize,
+      "{} makes {bytes} bytes of token-send inscriptions, more than fit in a standard reveal \
+      transaction of {MAX_STANDARD_TX_WEIGHT} weight units, split it into several sends",
+      self.csv.display(),
+    );
+
+    let tapped = self.shared.inscribe_and_tap(
+      &wallet,
+      &from,
+      "token-send",
+      sends.iter().map(|(body, _)| body.clone()).collect(),
+    )?;
+
+    Ok(Some(Box::new(Output {
+      sends: tapped
+        .inscribe
+        .inscriptions
+        .iter()
+        .zip(&sends)
+        .map(|(inscription, (_, items))| TokenSend {
+          inscription: inscription.id,
+          items: *items,
+        })
+        .collect(),
+      totals: totals
+        .iter()
+        .map(|(ticker, total)| (ticker.clone(), total.to_string()))
+        .collect(),
+      tapped,
+    })))
+  }
+
+  /// Parses `address,ticker,amount[,dta]` lines, skipping blank lines, `#`
+  /// comments and a header. Each item must pass the checks the indexer applies
+  /// to token-send items at `height`, since one failing item makes it ignore
+  /// the whole inscription. Tickers are lowercased the way the indexer keys
+  /// balances, so differently cased rows of one ticker are checked against the
+  /// same balance.
+  fn items(csv: &str, chain: Chain, height: u32, from: &Address) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+
+    for (i, line) in csv.lines().enumerate() {
+      let line_number = i + 1;
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let fields = line.splitn(4, ',').map(str::trim).collect::<Vec<&str>>();
+      let [address, ticker, amount, ..] = fields.as_slice() else {
+        bail!("line {line_number}: expected `address,ticker,amount[,dta]`");
+      };
+      let dta = fields.get(3).copied().filter(|dta| !dta.is_empty());
+
+      if items.is_empty() && address.eq_ignore_ascii_case("address") {
+        continue;
+      }
+
+      let address = address
+        .parse::<Address<NetworkUnchecked>>()
+        .ok()
+        .and_then(|address| address.require_network(chain.network()).ok())
+        .ok_or_else(|| anyhow!("line {line_number}: `{address}` is not a {chain} address"))?;
+
+      ensure!(
+        address != *from,
+        "line {line_number}: sending to {from} itself does not move any tokens"
+      );
+
+      ensure!(!ticker.is_empty(), "line {line_number}: missing ticker");
+
+      let address =
+        tap_token_send_item_check(chain.network(), height, ticker, &address.to_string(), dta)
+          .map_err(|err| anyhow!("line {line_number}: {err}"))?;
+
+      items.push(Item {
+        line: line_number,
+        address,
+        ticker: tap_js_to_lowercase(ticker),
+        amount: amount
+          .parse()
+          .with_context(|| format!("line {line_number}: invalid amount `{amount}`"))?,
+        dta: dta.map(str::to_string),
+      });
+    }
+
+    ensure!(!items.is_empty(), "no token-send items");
+
+    Ok(items)
+  }
+
+  /// Splits `items` into token-send inscription bodies of at most
+  /// `max_items` items each, returning each body with its item count.
+  fn bodies(items: &[Item], max_items: usize) -> Vec<(Vec<u8>, usize)> {
+    items
+      .chunks(max_items)
+      .map(|chunk| {
+        (
+          serde_json::json!({
+            "p": "tap",
+            "op": "token-send",
+            "items": chunk
+              .iter()
+              .map(|item| {
+                let mut value = serde_json::json!({
+                  "tick": item.ticker,
+                  "amt": item.amount.to_string(),
+                  "address": item.address,
+                });
+                if let Some(dta) = &item.dta {
+                  value["dta"] = dta.as_str().into();
+                }
+                value
+              })
+              .collect::<Vec<serde_json::Value>>(),
+          })
+          .to_string()
+          .into_bytes(),
+          chunk.len(),
+        )
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HEIGHT: u32 = 950_000;
+
+  #[test]
+  fn items_are_parsed_and_normalized() {
+    let csv = format!(
+      "address,ticker,amount,dta\n\n# comment\n{}, TAP ,1.5\n{},-Tap,2,hi, there\n",
+      address(0),
+      address(1),
+    );
+
+    assert_eq!(
+      Send::items(&csv, Chain::Mainnet, HEIGHT, &address(9)).unwrap(),
+      [
+        Item {
+          line: 4,
+          address: address(0).to_string(),
+          ticker: "tap".into(),
+          amount: "1.5".parse().unwrap(),
+          dta: None,
+        },
+        Item {
+          line: 5,
+          address: address(1).to_string(),
+          ticker: "-tap".into(),
+          amount: "2".parse().unwrap(),
+          dta: Some("hi, there".into()),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn invalid_items_are_rejected() {
+    #[track_caller]
+    fn case(csv: &str, expected: &str) {
+      assert_eq!(
+        Send::items(csv, Chain::Mainnet, HEIGHT, &address(9))
+          .unwrap_err()
+          .to_string(),
+        expected,
+      );
+    }
+
+    case("", "no token-send items");
+    case("foo,tap", "line 1: expected `address,ticker,amount[,dta]`");
+    case("foo,tap,1", "line 1: `foo` is not a mainnet address");
+    case(
+      &format!("{},tap,1", address(9)),
+      &format!(
+        "line 1: sending to {} itself does not move any tokens",
+        address(9)
+      ),
+    );
+    case(&format!("{},,1", address(0)), "line 1: missing ticker");
+    case(
+      &format!("{},tap,1\n{},tap,x", address(0), address(0)),
+      "line 2: invalid amount `x`",
+    );
+    case(
+      &format!("{},{},1", address(0), "t".repeat(33)),
+      &format!("line 1: ticker `{}` has an invalid length", "t".repeat(33)),
+    );
+    case(
+      &format!("{},tap,1,{}", address(0), "d".repeat(513)),
+      "line 1: dta is longer than 512 bytes",
+    );
+  }
+
+  #[test]
+  fn items_are_split_into_token_sends() {
+    let items = (0..5)
+      .map(|i| Item {
+        line: i + 1,
+        address: address(0).to_string(),
+        ticker: "tap".into(),
+        amount: "1".parse().unwrap(),
+        dta: None,
+      })
+      .collect::<Vec<Item>>();
+
+    let bodies = Send::bodies(&items, 2);
+
+    assert_eq!(
+      bodies
+        .iter()
+        .map(|(_, items)| *items)
+        .collect::<Vec<usize>>(),
+      [2, 2, 1]
+    );
+
+    assert_eq!(
+      serde_json::from_slice::<serde_json::Value>(&bodies[2].0).unwrap(),
+      serde_json::json!({
+        "p": "tap",
+        "op": "token-send",
+        "items": [{"tick": "tap", "amt": "1", "address": address(0).to_string()}],
+      })
+    );
+  }
+}
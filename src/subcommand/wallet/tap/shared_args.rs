@@ -0,0 +1,174 @@
+use super::*;
+
+#[derive(Debug, Parser)]
+pub(super) struct SharedArgs {
+  #[arg(
+    long,
+    help = "Send the execution transaction right after the reveal, without waiting for the \
+    inscriptions to be confirmed and indexed."
+  )]
+  pub(crate) chain: bool,
+  #[arg(
+    long,
+    help = "Use <COMMIT_FEE_RATE> sats/vbyte for commit transaction.\nDefaults to <FEE_RATE> if unset."
+  )]
+  pub(crate) commit_fee_rate: Option<FeeRate>,
+  #[arg(long, help = "Don't sign or broadcast transactions.")]
+  pub(crate) dry_run: bool,
+  #[arg(long, help = "Use fee rate of <FEE_RATE> sats/vB.")]
+  pub(crate) fee_rate: FeeRate,
+  #[arg(long, help = "Use tokens held by wallet address <FROM>.")]
+  pub(crate) from: Address<NetworkUnchecked>,
+  #[arg(long, alias = "nobackup", help = "Do not back up recovery key.")]
+  pub(crate) no_backup: bool,
+  #[arg(
+    long,
+    help = "Include <AMOUNT> postage with each inscription. [default: 10000sat]",
+    value_name = "AMOUNT"
+  )]
+  pub(crate) postage: Option<Amount>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Tapped {
+  pub inscribe: batch::Output,
+  pub execution: Txid,
+  pub execution_broadcast: bool,
+  pub execution_psbt: Option<String>,
+  pub execution_fee: u64,
+}
+
+impl SharedArgs {
+  /// The `--from` address, checked to belong to the wallet.
+  pub(crate) fn from_address(&self, wallet: &Wallet) -> Result<Address> {
+    let from = self
+      .from
+      .clone()
+      .require_network(wallet.chain().network())?;
+
+    ensure!(
+      wallet.is_mine(&from)?,
+      "{from} is not an address of this wallet"
+    );
+
+    Ok(from)
+  }
+
+  /// The `--from` address, also checked not to be marked by the miner-reward
+  /// shield, which ignores accumulator inscriptions from DMT reward addresses.
+  pub(crate) fn sender(&self, wallet: &Wallet) -> Result<Address> {
+    let from = self.from_address(wallet)?;
+
+    ensure!(
+      !Tap::is_dmt_reward_address(wallet, &from.to_string())?,
+      "{from} is a DMT reward address, the miner-reward shield ignores its inscriptions"
+    );
+
+    Ok(from)
+  }
+
+  /// Inscribes one text inscription per body to `from`, each in its own
+  /// output with `--postage`.
+  pub(crate) fn inscribe(
+    &self,
+    wallet: &Wallet,
+    from: &Address,
+    bodies: Vec<Vec<u8>>,
+  ) -> Result<batch::Output> {
+    let postage = self.postage.unwrap_or(TARGET_POSTAGE);
+
+    batch::Plan {
+      commit_fee_rate: self.commit_fee_rate.unwrap_or(self.fee_rate),
+      destinations: vec![from.clone(); bodies.len()],
+      dry_run: self.dry_run,
+      etching: None,
+      postages: vec![postage; bodies.len()],
+      inscriptions: bodies
+        .into_iter()
+        .map(|body| Inscription {
+          body: Some(body),
+          content_type: Some("text/plain;charset=utf-8".into()),
+          ..default()
+        })
+        .collect(),
+      mode: batch::Mode::SeparateOutputs,
+      no_backup: self.no_backup,
+      no_limit: false,
+      parent_info: Vec::new(),
+      reinscribe: false,
+      reveal_fee_rate: self.fee_rate,
+      reveal_satpoints: Vec::new(),
+      satpoint: None,
+    }
+    .execute(
+      &wallet.locked_utxos().clone().into_keys().collect(),
+      wallet.get_runic_outputs()?.unwrap_or_default(),
+      wallet.utxos(),
+      wallet,
+    )
+  }
+
+  /// Inscribes one `op` accumulator per body to `from`, then taps them all
+  /// with a transaction moving each back to `from`, which executes them.
+  pub(crate) fn inscribe_and_tap(
+    &self,
+    wallet: &Wallet,
+    from: &Address,
+    op: &str,
+    bodies: Vec<Vec<u8>>,
+  ) -> Result<Tapped> {
+    let postage = self.postage.unwrap_or(TARGET_POSTAGE);
+
+    let inscribe = self.inscribe(wallet, from, bodies)?;
+
+    let count = inscribe.inscriptions.len();
+
+    let (execution, execution_fee) = Tap::execution_transaction(
+      self.fee_rate,
+      &inscribe
+        .inscriptions
+        .iter()
+        .map(|inscription| inscription.location.outpoint)
+        .collect::<Vec<OutPoint>>(),
+      postage * u64::try_from(count).unwrap(),
+      vec![
+        TxOut {
+          script_pubkey: from.script_pubkey(),
+          value: postage,
+        };
+        count
+      ],
+    )?;
+
+    if self.dry_run {
+      return Ok(Tapped {
+        inscribe,
+        execution: execution.compute_txid(),
+        execution_broadcast: false,
+        execution_psbt: Some(base64_encode(
+          &Psbt::from_unsigned_tx(execution)?.serialize(),
+        )),
+        execution_fee: execution_fee.to_sat(),
+      });
+    }
+
+    if !self.chain {
+      for inscription in &inscribe.inscriptions {
+        Tap::wait_until_indexed(
+          wallet,
+          inscription.id,
+          op,
+          &["getAccumulator", &inscription.id.to_string()],
+        )?;
+      }
+    }
+
+    Ok(Tapped {
+      inscribe,
+      execution: Tap::broadcast_execution(wallet, execution)?,
+      execution_broadcast: true,
+      execution_psbt: None,
+      execution_fee: execution_fee.to_sat(),
+    })
+  }
+}
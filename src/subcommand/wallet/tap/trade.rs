@@ -0,0 +1,540 @@
+use {super::*, crate::index::TAP_TOKEN_TRADE_EMERGENCY_ACTIVATION_HEIGHT};
+
+/// Blocks an offer stays open for unless `--valid` is given, about a week.
+const DEFAULT_VALID_BLOCKS: u32 = 1008;
+
+#[derive(Debug, Parser)]
+pub(crate) enum Trade {
+  #[command(about = "Cancel a token-trade offer")]
+  Cancel(Cancel),
+  #[command(about = "Fill an open token-trade offer")]
+  Fill(Fill),
+  #[command(about = "Offer tokens in exchange for any of several tokens")]
+  Offer(Offer),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Accept {
+  pub amount: Decimal,
+  pub ticker: String,
+}
+
+impl FromStr for Accept {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (amount, ticker) = s
+      .split_once(':')
+      .ok_or_else(|| anyhow!("expected `AMOUNT:TICKER`"))?;
+
+    ensure!(!ticker.is_empty(), "missing ticker");
+
+    Ok(Self {
+      amount: amount.parse()?,
+      ticker: ticker.into(),
+    })
+  }
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct Offer {
+  #[command(flatten)]
+  shared: SharedArgs,
+  #[arg(
+    long,
+    required = true,
+    value_name = "AMOUNT:TICKER",
+    help = "Accept <AMOUNT> whole tokens of <TICKER> for the whole offer. May be given more than \
+    once."
+  )]
+  accept: Vec<Accept>,
+  #[arg(
+    long,
+    help = "Keep the offer open up to block <VALID>. [default: 1008 blocks from now]"
+  )]
+  valid: Option<u64>,
+  #[arg(help = "Offer <AMOUNT> whole tokens.")]
+  amount: Decimal,
+  #[arg(help = "Offer tokens of <TICKER>.")]
+  ticker: String,
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct Fill {
+  #[command(flatten)]
+  shared: SharedArgs,
+  #[arg(
+    long,
+    value_name = "ADDRESS",
+    help = "Pay the 0.3% trade fee, in the accepted token, to <FEE_RECEIVER>."
+  )]
+  fee_receiver: Option<Address<NetworkUnchecked>>,
+  #[arg(help = "Fill the offer inscribed by <TRADE>.")]
+  trade: InscriptionId,
+  #[arg(help = "Pay with <TICKER>, one of the tokens the offer accepts.")]
+  ticker: String,
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct Cancel {
+  #[command(flatten)]
+  shared: SharedArgs,
+  #[arg(help = "Cancel the offer inscribed by <TRADE>.")]
+  trade: InscriptionId,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct OfferOutput {
+  pub trade: InscriptionId,
+  pub ticker: String,
+  pub amount: String,
+  pub accept: Vec<Accept>,
+  pub valid: u64,
+  #[serde(flatten)]
+  pub tapped: Tapped,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct BalanceChange {
+  pub address: String,
+  pub ticker: String,
+  pub amount: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct FillOutput {
+  pub trade: InscriptionId,
+  pub seller: String,
+  pub fee: String,
+  pub changes: Vec<BalanceChange>,
+  #[serde(flatten)]
+  pub tapped: Tapped,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CancelOutput {
+  pub trade: InscriptionId,
+  #[serde(flatten)]
+  pub tapped: Tapped,
+}
+
+/// An open offer as `/r/tap/batch` reports it, amounts in base units.
+#[derive(Debug, Deserialize)]
+struct OpenOffer {
+  addr: String,
+  tick: String,
+  amt: String,
+  atick: String,
+  aamt: String,
+  vld: i64,
+}
+
+impl Trade {
+  pub(crate) fn run(self, wallet: Wallet) -> SubcommandResult {
+    let height = wallet.bitcoin_client().get_block_count()? + 1;
+
+    ensure!(
+      !Self::shut_down(wallet.chain(), height),
+      "token trades are shut down on {} since block {TAP_TOKEN_TRADE_EMERGENCY_ACTIVATION_HEIGHT}, \
+      token-trade inscriptions are ignored",
+      wallet.chain(),
+    );
+
+    match self {
+      Self::Cancel(cancel) => cancel.run(wallet),
+      Self::Fill(fill) => fill.run(wallet, height),
+      Self::Offer(offer) => offer.run(wallet, height),
+    }
+  }
+
+  /// Whether the token-trade emergency shutdown, under which the indexer
+  /// ignores all token-trade inscriptions, covers a block at `height`.
+  fn shut_down(chain: Chain, height: u64) -> bool {
+    chain == Chain::Mainnet && height >= TAP_TOKEN_TRADE_EMERGENCY_ACTIVATION_HEIGHT.into()
+  }
+
+  /// Trade fee the indexer charges when a fill names a fee receiver, 0.3% of
+  /// `amount` rounded down.
+  fn fee(amount: u128) -> u128 {
+    amount / 10000 * 30 + amount % 10000 * 30 / 10000
+  }
+}
+
+impl Offer {
+  fn run(self, wallet: Wallet, height: u64) -> SubcommandResult {
+    let from = self.shared.sender(&wallet)?;
+
+    let amount = Tap::base_units(&wallet, &self.ticker, self.amount)?;
+
+    let available = Tap::available_balance(&wallet, &from.to_string(), &self.ticker)?;
+
+    ensure!(
+      amount <= available,
+      "{from} has {available} {} available for trades, less than {amount} (amounts in base units)",
+      self.ticker,
+    );
+
+    let mut tickers = HashSet::new();
+    for accept in &self.accept {
+      ensure!(
+        tickers.insert(accept.ticker.to_lowercase()),
+        "{} is accepted more than once",
+        accept.ticker,
+      );
+
+      Tap::base_units(&wallet, &accept.ticker, accept.amount)?;
+    }
+
+    let valid = self
+      .valid
+      .unwrap_or(height + u64::from(DEFAULT_VALID_BLOCKS));
+
+    ensure!(
+      valid > height,
+      "offer would expire at block {valid}, before it is tapped"
+    );
+
+    let tapped = self.shared.inscribe_and_tap(
+      &wallet,
+      &from,
+      "token-trade",
+      vec![Self::body(&self.ticker, self.amount, &self.accept, valid)],
+    )?;
+
+    Ok(Some(Box::new(OfferOutput {
+      trade: tapped.inscribe.inscriptions[0].id,
+      ticker: self.ticker,
+      amount: amount.to_string(),
+      accept: self.accept,
+      valid,
+      tapped,
+    })))
+  }
+
+  fn body(ticker: &str, amount: Decimal, accept: &[Accept], valid: u64) -> Vec<u8> {
+    serde_json::json!({
+      "p": "tap",
+      "op": "token-trade",
+      "side": "0",
+      "tick": ticker,
+      "amt": amount.to_string(),
+      "accept": accept
+        .iter()
+        .map(|accept| serde_json::json!({
+          "tick": accept.ticker,
+          "amt": accept.amount.to_string(),
+        }))
+        .collect::<Vec<serde_json::Value>>(),
+      "valid": valid.to_string(),
+    })
+    .to_string()
+    .into_bytes()
+  }
+}
+
+impl Fill {
+  fn run(self, wallet: Wallet, height: u64) -> SubcommandResult {
+    let from = self.shared.sender(&wallet)?;
+
+    let fee_receiver = self
+      .fee_receiver
+      .clone()
+      .map(|address| address.require_network(wallet.chain().network()))
+      .transpose()?;
+
+    let offer = wallet.tap_batch(&[serde_json::json!({
+      "type": "tradeOffer",
+      "trade_id": self.trade,
+      "ticker": self.ticker,
+    })])?[0]
+      .take();
+
+    ensure!(
+      !offer.is_null(),
+      "trade {} has no open offer accepting {}",
+      self.trade,
+      self.ticker,
+    );
+
+    let offer = serde_json::from_value::<OpenOffer>(offer)?;
+
+    ensure!(
+      offer.addr != from.to_string(),
+      "trade {} is an offer from {from} itself",
+      self.trade,
+    );
+
+    ensure!(
+      offer.vld < 0 || i128::from(height) <= i128::from(offer.vld),
+      "trade {} expired at block {}",
+      self.trade,
+      offer.vld,
+    );
+
+    ensure!(
+      !Tap::is_dmt_reward_address(&wallet, &offer.addr)?,
+      "trade {} is from DMT reward address {}, the miner-reward shield ignores its fills",
+      self.trade,
+      offer.addr,
+    );
+
+    let amount = offer.amt.parse::<u128>()?;
+    let accept_amount = offer.aamt.parse::<u128>()?;
+    let fee = if fee_receiver.is_some() {
+      Trade::fee(accept_amount)
+    } else {
+      0
+    };
+
+    let seller_available = Tap::available_balance(&wallet, &offer.addr, &offer.tick)?;
+
+    ensure!(
+      amount <= seller_available,
+      "seller {} has {seller_available} {} available, less than the {amount} offered (amounts in \
+      base units)",
+      offer.addr,
+      offer.tick,
+    );
+
+    let available = Tap::available_balance(&wallet, &from.to_string(), &offer.atick)?;
+
+    ensure!(
+      accept_amount + fee <= available,
+      "{from} has {available} {} available for trades, less than the {} needed (amounts in base \
+      units)",
+      offer.atick,
+      accept_amount + fee,
+    );
+
+    let body = serde_json::json!({
+      "p": "tap",
+      "op": "token-trade",
+      "side": "1",
+      "trade": self.trade,
+      "tick": offer.atick,
+      "amt": Decimal {
+        value: accept_amount,
+        scale: Tap::decimals(&wallet, &offer.atick)?,
+      }
+      .to_string(),
+    });
+
+    let tapped = self.shared.inscribe_and_tap(
+      &wallet,
+      &from,
+      "token-trade",
+      vec![Self::with_fee_receiver(body, fee_receiver.as_ref())],
+    )?;
+
+    Ok(Some(Box::new(FillOutput {
+      trade: self.trade,
+      fee: fee.to_string(),
+      changes: Self::changes(
+        &offer,
+        &from,
+        fee_receiver.as_ref(),
+        amount,
+        accept_amount,
+        fee,
+      ),
+      seller: offer.addr,
+      tapped,
+    })))
+  }
+
+  fn with_fee_receiver(mut body: serde_json::Value, fee_receiver: Option<&Address>) -> Vec<u8> {
+    if let Some(fee_receiver) = fee_receiver {
+      body["fee_rcv"] = fee_receiver.to_string().into();
+    }
+
+    body.to_string().into_bytes()
+  }
+
+  /// Balance changes a successful fill makes, in base units.
+  fn changes(
+    offer: &OpenOffer,
+    buyer: &Address,
+    fee_receiver: Option<&Address>,
+    amount: u128,
+    accept_amount: u128,
+    fee: u128,
+  ) -> Vec<BalanceChange> {
+    let change = |address: &str, ticker: &str, amount: String| BalanceChange {
+      address: address.into(),
+      ticker: ticker.into(),
+      amount,
+    };
+
+    let mut changes = vec![
+      change(&buyer.to_string(), &offer.tick, amount.to_string()),
+      change(&offer.addr, &offer.tick, format!("-{amount}")),
+      change(
+        &buyer.to_string(),
+        &offer.atick,
+        format!("-{}", accept_amount + fee),
+      ),
+      change(&offer.addr, &offer.atick, accept_amount.to_string()),
+    ];
+
+    if let Some(fee_receiver) = fee_receiver {
+      changes.push(change(
+        &fee_receiver.to_string(),
+        &offer.atick,
+        fee.to_string(),
+      ));
+    }
+
+    changes
+  }
+}
+
+impl Cancel {
+  fn run(self, wallet: Wallet) -> SubcommandResult {
+    let from = self.shared.sender(&wallet)?;
+
+    let lock = wallet.tap_get(&["getTrade", &self.trade.to_string()])?;
+
+    ensure!(!lock.is_null(), "trade {} has no open offer", self.trade);
+
+    ensure!(
+      lock["addr"].as_str() == Some(&from.to_string()),
+      "trade {} was offered by {}, not {from}",
+      self.trade,
+      lock["addr"].as_str().unwrap_or_default(),
+    );
+
+    let tapped = self.shared.inscribe_and_tap(
+      &wallet,
+      &from,
+      "token-trade",
+      vec![serde_json::json!({
+        "p": "tap",
+        "op": "token-trade",
+        "side": "0",
+        "trade": self.trade,
+      })
+      .to_string()
+      .into_bytes()],
+    )?;
+
+    Ok(Some(Box::new(CancelOutput {
+      trade: self.trade,
+      tapped,
+    })))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn accept_from_str() {
+    assert_eq!(
+      "1.5:Tap".parse::<Accept>().unwrap(),
+      Accept {
+        amount: "1.5".parse().unwrap(),
+        ticker: "Tap".into(),
+      }
+    );
+
+    assert_eq!(
+      "10:dmt:x".parse::<Accept>().unwrap().ticker,
+      "dmt:x".to_string()
+    );
+
+    assert_eq!(
+      "10".parse::<Accept>().unwrap_err().to_string(),
+      "expected `AMOUNT:TICKER`"
+    );
+
+    assert_eq!(
+      "10:".parse::<Accept>().unwrap_err().to_string(),
+      "missing ticker"
+    );
+  }
+
+  #[test]
+  fn offer_body() {
+    assert_eq!(
+      serde_json::from_slice::<serde_json::Value>(&Offer::body(
+        "foo",
+        "1.5".parse().unwrap(),
+        &["2:bar".parse().unwrap(), "3.25:-baz".parse().unwrap()],
+        900,
+      ))
+      .unwrap(),
+      serde_json::json!({
+        "p": "tap",
+        "op": "token-trade",
+        "side": "0",
+        "tick": "foo",
+        "amt": "1.5",
+        "accept": [{"tick": "bar", "amt": "2"}, {"tick": "-baz", "amt": "3.25"}],
+        "valid": "900",
+      })
+    );
+  }
+
+  #[test]
+  fn fee_matches_indexer_rounding() {
+    assert_eq!(Trade::fee(0), 0);
+    assert_eq!(Trade::fee(333), 0);
+    assert_eq!(Trade::fee(334), 1);
+    assert_eq!(Trade::fee(10000), 30);
+    assert_eq!(Trade::fee(12345), 37);
+  }
+
+  #[test]
+  fn fill_balance_changes() {
+    let offer = OpenOffer {
+      addr: address(0).to_string(),
+      tick: "foo".into(),
+      amt: "100".into(),
+      atick: "bar".into(),
+      aamt: "10000".into(),
+      vld: 900,
+    };
+
+    assert_eq!(
+      Fill::changes(&offer, &address(1), Some(&address(2)), 100, 10000, 30),
+      [
+        BalanceChange {
+          address: address(1).to_string(),
+          ticker: "foo".into(),
+          amount: "100".into(),
+        },
+        BalanceChange {
+          address: address(0).to_string(),
+          ticker: "foo".into(),
+          amount: "-100".into(),
+        },
+        BalanceChange {
+          address: address(1).to_string(),
+          ticker: "bar".into(),
+          amount: "-10030".into(),
+        },
+        BalanceChange {
+          address: address(0).to_string(),
+          ticker: "bar".into(),
+          amount: "10000".into(),
+        },
+        BalanceChange {
+          address: address(2).to_string(),
+          ticker: "bar".into(),
+          amount: "30".into(),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn emergency_shutdown_covers_mainnet_from_activation() {
+    let activation = u64::from(TAP_TOKEN_TRADE_EMERGENCY_ACTIVATION_HEIGHT);
+
+    assert!(!Trade::shut_down(Chain::Mainnet, activation - 1));
+    assert!(Trade::shut_down(Chain::Mainnet, activation));
+    assert!(!Trade::shut_down(Chain::Signet, activation));
+    assert!(!Trade::shut_down(Chain::Regtest, activation));
+  }
+}
@@ -0,0 +1,243 @@
+use super::*;
+
+#[derive(Debug, Parser)]
+pub(crate) struct Transfer {
+  #[command(flatten)]
+  shared: SharedArgs,
+  #[arg(
+    long,
+    help = "Send the transfer inscription with dust postage and return the rest of its postage \
+    to <ADDRESS>.",
+    value_name = "ADDRESS"
+  )]
+  return_postage: Option<Address<NetworkUnchecked>>,
+  #[arg(help = "Transfer tokens to <ADDRESS>.")]
+  address: Address<NetworkUnchecked>,
+  #[arg(help = "Transfer <AMOUNT> whole tokens.")]
+  amount: Decimal,
+  #[arg(help = "Transfer tokens of <TICKER>.")]
+  ticker: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Output {
+  pub ticker: String,
+  pub amount: String,
+  pub available: String,
+  pub inscription: InscriptionId,
+  pub inscribe: batch::Output,
+  pub execution: Txid,
+  pub execution_broadcast: bool,
+  pub execution_psbt: Option<String>,
+  pub execution_fee: u64,
+  pub transfer_amount: Option<String>,
+}
+
+impl Transfer {
+  pub(crate) fn run(self, wallet: Wallet) -> SubcommandResult {
+    let network = wallet.chain().network();
+    let from = self.shared.from_address(&wallet)?;
+    let recipient = self.address.clone().require_network(network)?;
+    let return_postage = self
+      .return_postage
+      .clone()
+      .map(|address| address.require_network(network))
+      .transpose()?;
+
+    let amount = Tap::base_units(&wallet, &self.ticker, self.amount)?;
+
+    let available = Tap::available_balance(&wallet, &from.to_string(), &self.ticker)?;
+
+    ensure!(
+      amount <= available,
+      "{from} has {available} {} available for transfers, less than {amount} (amounts in base units)",
+      self.ticker,
+    );
+
+    let inscribe = self.shared.inscribe(
+      &wallet,
+      &from,
+      vec![serde_json::json!({
+        "p": "tap",
+        "op": "token-transfer",
+        "tick": self.ticker,
+        "amt": self.amount.to_string(),
+      })
+      .to_string()
+      .into_bytes()],
+    )?;
+
+    let inscription = inscribe.inscriptions[0].id;
+
+    let (execution, execution_fee) = self.execution_transaction(
+      inscribe.inscriptions[0].location.outpoint,
+      self.shared.postage.unwrap_or(TARGET_POSTAGE),
+      &recipient,
+      return_postage.as_ref(),
+    )?;
+
+    if self.shared.dry_run {
+      return Ok(Some(Box::new(Output {
+        ticker: self.ticker,
+        amount: amount.to_string(),
+        available: available.to_string(),
+        inscription,
+        inscribe,
+        execution: execution.compute_txid(),
+        execution_broadcast: false,
+        execution_psbt: Some(base64_encode(
+          &Psbt::from_unsigned_tx(execution)?.serialize(),
+        )),
+        execution_fee: execution_fee.to_sat(),
+        transfer_amount: None,
+      })));
+    }
+
+    if !self.shared.chain {
+      Tap::wait_until_indexed(
+        &wallet,
+        inscription,
+        "token-transfer",
+        &["getTransferAmountByInscription", &inscription.to_string()],
+      )?;
+    }
+
+    let execution = Tap::broadcast_execution(&wallet, execution)?;
+
+    Ok(Some(Box::new(Output {
+      ticker: self.ticker,
+      amount: amount.to_string(),
+      available: available.to_string(),
+      inscription,
+      inscribe,
+      execution,
+      execution_broadcast: true,
+      execution_psbt: None,
+      execution_fee: execution_fee.to_sat(),
+      transfer_amount: wallet
+        .tap_get(&["getTransferAmountByInscription", &inscription.to_string()])?
+        .as_str()
+        .map(str::to_string),
+    })))
+  }
+
+  /// Spends the reveal output, moving the inscription on its first sat to the
+  /// recipient. The execution fee comes out of the postage.
+  fn execution_transaction(
+    &self,
+    reveal: OutPoint,
+    postage: Amount,
+    recipient: &Address,
+    return_postage: Option<&Address>,
+  ) -> Result<(Transaction, Amount)> {
+    let mut output = vec![TxOut {
+      script_pubkey: recipient.script_pubkey(),
+      value: postage,
+    }];
+
+    if let Some(address) = return_postage {
+      output[0].value = output[0].script_pubkey.minimal_non_dust();
+      output.push(TxOut {
+        script_pubkey: address.script_pubkey(),
+        value: postage,
+      });
+    }
+
+    Tap::execution_transaction(self.shared.fee_rate, &[reveal], postage, output)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn transfer(fee_rate: &str, args: &[&str]) -> Transfer {
+    match Arguments::try_parse_from(
+      [
+        "ord",
+        "wallet",
+        "tap",
+        "transfer",
+        "--fee-rate",
+        fee_rate,
+        "--from",
+      ]
+      .iter()
+      .chain(args),
+    )
+    .unwrap()
+    .subcommand
+    {
+      crate::subcommand::Subcommand::Wallet(WalletCommand {
+        subcommand: Subcommand::Tap(Tap::Transfer(transfer)),
+        ..
+      }) => transfer,
+      subcommand => panic!("unexpected subcommand: {subcommand:?}"),
+    }
+  }
+
+  #[test]
+  fn execution_transaction_returns_postage() {
+    let from = change(0).to_string();
+    let recipient = recipient_address().to_string();
+
+    let (tx, fee) = transfer("1", &[&from, &recipient, "1.5", "tap"])
+      .execution_transaction(
+        outpoint(1),
+        TARGET_POSTAGE,
+        &recipient_address(),
+        Some(&change(1)),
+      )
+      .unwrap();
+
+    assert_eq!(tx.input.len(), 1);
+    assert_eq!(tx.input[0].previous_output, outpoint(1));
+    assert_eq!(tx.output.len(), 2);
+    assert_eq!(
+      tx.output[0].script_pubkey,
+      recipient_address().script_pubkey()
+    );
+    assert_eq!(
+      tx.output[0].value,
+      recipient_address().script_pubkey().minimal_non_dust()
+    );
+    assert_eq!(tx.output[1].script_pubkey, change(1).script_pubkey());
+    assert_eq!(
+      tx.output[0].value + tx.output[1].value + fee,
+      TARGET_POSTAGE
+    );
+    assert!(fee > Amount::ZERO);
+  }
+
+  #[test]
+  fn execution_transaction_sends_postage_to_recipient() {
+    let from = change(0).to_string();
+    let recipient = recipient_address().to_string();
+
+    let (tx, fee) = transfer("1", &[&from, &recipient, "1", "tap"])
+      .execution_transaction(outpoint(1), TARGET_POSTAGE, &recipient_address(), None)
+      .unwrap();
+
+    assert_eq!(tx.output.len(), 1);
+    assert_eq!(tx.output[0].value + fee, TARGET_POSTAGE);
+  }
+
+  #[test]
+  fn execution_transaction_requires_postage_for_fee() {
+    let from = change(0).to_string();
+    let recipient = recipient_address().to_string();
+
+    assert_eq!(
+      transfer("100", &[&from, &recipient, "1", "tap"])
+        .execution_transaction(
+          outpoint(1),
+          Amount::from_sat(1000),
+          &recipient_address(),
+          Some(&change(1)),
+        )
+        .unwrap_err()
+        .to_string(),
+      "postage of 0.00001000 BTC is too small to pay the execution fee of 0.00013000 BTC",
+    );
+  }
+}
@@ -83,6 +83,7 @@ pub(crate) struct Wallet {
   inscriptions: BTreeMap<SatPoint, Vec<InscriptionId>>,
   locked_utxos: BTreeMap<OutPoint, TxOut>,
   settings: Settings,
+  tap_inscriptions: BTreeMap<InscriptionId, String>,
 }
 
 impl Wallet {
@@ -232,6 +233,84 @@ impl Wallet {
     )
   }
 
+  /// The `result` of the `/r/tap` route at `segments`, which are percent
+  /// encoded, so tickers may contain any character.
+  pub(crate) fn tap_get(&self, segments: &[&str]) -> Result<serde_json::Value> {
+    let mut url = self.rpc_url.join("/r/tap")?;
+
+    url
+      .path_segments_mut()
+      .map_err(|()| anyhow!("invalid server URL"))?
+      .extend(segments);
+
+    let response = self.ord_client.get(url.clone()).send()?;
+
+    if !response.status().is_success() {
+      bail!("request to {url} failed: {}", response.text()?);
+    }
+
+    Ok(response.json::<serde_json::Value>()?["result"].take())
+  }
+
+  pub(crate) fn tap_batch(&self, lookups: &[serde_json::Value]) -> Result<Vec<serde_json::Value>> {
+    let url = self.rpc_url.join("/r/tap/batch")?;
+
+    let response = self
+      .ord_client
+      .post(url.clone())
+      .json(&serde_json::json!({ "lookups": lookups }))
+      .send()?;
+
+    if !response.status().is_success() {
+      bail!("request to {url} failed: {}", response.text()?);
+    }
+
+    let result = serde_json::from_value::<Vec<serde_json::Value>>(
+      response.json::<serde_json::Value>()?["result"].take(),
+    )?;
+
+    ensure!(
+      result.len() == lookups.len(),
+      "unexpected server `/r/tap/batch` response length",
+    );
+
+    Ok(result)
+  }
+
+  pub(crate) fn tap_validate(&self, body: &str, address: &Address) -> Result<serde_json::Value> {
+    let mut url = self.rpc_url.join("/r/tap/validate")?;
+
+    url
+      .query_pairs_mut()
+      .append_pair("address", &address.to_string());
+
+    let response = self
+      .ord_client
+      .post(url.clone())
+      .body(body.to_string())
+      .send()?;
+
+    if !response.status().is_success() {
+      bail!("request to {url} failed: {}", response.text()?);
+    }
+
+    Ok(response.json::<serde_json::Value>()?)
+  }
+
+  pub(crate) fn is_mine(&self, address: &Address) -> Result<bool> {
+    #[derive(Deserialize)]
+    struct AddressInfo {
+      ismine: bool,
+    }
+
+    Ok(
+      self
+        .bitcoin_client()
+        .call::<AddressInfo>("getaddressinfo", &[address.to_string().into()])?
+        .ismine,
+    )
+  }
+
   pub(crate) fn get_inscriptions_in_output(
     &self,
     output: &OutPoint,
@@ -276,6 +355,31 @@ impl Wallet {
     Ok(parent_info)
   }
 
+  /// Wallet inscriptions with pending TAP state, mapped to the TAP operation
+  /// that spending their output would execute.
+  pub(crate) fn tap_inscriptions(&self) -> &BTreeMap<InscriptionId, String> {
+    &self.tap_inscriptions
+  }
+
+  /// Refuses to spend an output that carries pending TAP state, unless the
+  /// inscription at `targeted` is the one being moved.
+  pub(crate) fn check_tap_spend(&self, outpoint: OutPoint, targeted: Option<SatPoint>) -> Result {
+    for (id, op) in &self.tap_inscriptions {
+      let Some(info) = self.inscription_info.get(id) else {
+        continue;
+      };
+
+      if info.satpoint.outpoint == outpoint && Some(info.satpoint) != targeted {
+        bail!(
+          "output {outpoint} carries inscription {id} with a pending TAP `{op}`, which spending \
+          the output would execute; send that inscription by ID to move it on purpose"
+        );
+      }
+    }
+
+    Ok(())
+  }
+
   pub(crate) fn get_runic_outputs(&self) -> Result<Option<BTreeSet<OutPoint>>> {
     let mut runic_outputs = BTreeSet::new();
     for (output, info) in &self.output_info {
@@ -909,6 +1013,8 @@ impl Wallet {
       "runic outpoints may not be sent by satpoint"
     );
 
+    self.check_tap_spend(satpoint.outpoint, sending_inscription.then_some(satpoint))?;
+
     let change = [self.get_change_address()?, self.get_change_address()?];
 
     let postage = if let Some(postage) = postage {
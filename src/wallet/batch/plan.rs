@@ -46,6 +46,21 @@ impl Plan {
     utxos: &BTreeMap<OutPoint, TxOut>,
     wallet: &Wallet,
   ) -> SubcommandResult {
+    Ok(Some(Box::new(self.execute(
+      locked_utxos,
+      runic_utxos,
+      utxos,
+      wallet,
+    )?)))
+  }
+
+  pub(crate) fn execute(
+    &self,
+    locked_utxos: &BTreeSet<OutPoint>,
+    runic_utxos: BTreeSet<OutPoint>,
+    utxos: &BTreeMap<OutPoint, TxOut>,
+    wallet: &Wallet,
+  ) -> Result<Output> {
     let Transactions {
       commit_tx,
       commit_vout,
@@ -78,7 +93,7 @@ impl Plan {
 
       let reveal_psbt = Psbt::from_unsigned_tx(Self::remove_witnesses(reveal_tx.clone()))?;
 
-      return Ok(Some(Box::new(self.output(
+      return Ok(self.output(
         commit_tx.compute_txid(),
         Some(commit_psbt),
         reveal_tx.compute_txid(),
@@ -87,7 +102,7 @@ impl Plan {
         total_fees,
         self.inscriptions.clone(),
         rune,
-      ))));
+      ));
     }
 
     let signed_commit_tx = wallet
@@ -154,9 +169,7 @@ impl Plan {
         ),
       )?;
 
-      Ok(Some(Box::new(
-        wallet.wait_for_maturation(rune_info.rune.rune)?,
-      )))
+      wallet.wait_for_maturation(rune_info.rune.rune)
     } else {
       let reveal = match wallet
         .bitcoin_client()
@@ -170,7 +183,7 @@ impl Plan {
         }
       };
 
-      Ok(Some(Box::new(self.output(
+      Ok(self.output(
         commit_txid,
         None,
         reveal,
@@ -179,7 +192,7 @@ impl Plan {
         total_fees,
         self.inscriptions.clone(),
         rune,
-      ))))
+      ))
     }
   }
 
@@ -444,7 +444,7 @@ impl TransactionBuilder {
     Self::estimate_vbytes_with(self.inputs.len(), &self.outputs)
   }
 
-  fn estimate_vbytes_with(inputs: usize, outputs: &[TxOut]) -> usize {
+  pub(crate) fn estimate_vbytes_with(inputs: usize, outputs: &[TxOut]) -> usize {
     Transaction {
       version: Version(2),
       lock_time: LockTime::ZERO,
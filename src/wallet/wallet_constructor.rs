@@ -120,6 +120,8 @@ impl WalletConstructor {
       .flat_map(|(_output, info)| info.inscriptions.clone().unwrap_or_default())
       .collect::<Vec<InscriptionId>>();
 
+    let tap_inscriptions = self.get_tap_inscriptions(&inscriptions)?;
+
     let (inscriptions, inscription_info) = self.get_inscriptions(&inscriptions)?;
 
     let status = self.get_server_status()?;
@@ -136,6 +138,7 @@ impl WalletConstructor {
       output_info,
       rpc_url: self.rpc_url,
       settings: self.settings,
+      tap_inscriptions,
       utxos,
     })
   }
@@ -193,6 +196,65 @@ impl WalletConstructor {
     Ok((inscriptions, inscription_infos))
   }
 
+  /// Pending TAP operations of `inscriptions`, as reported by `ord server`.
+  /// Servers that do not answer `/r/tap/batch` report none, so the wallet still
+  /// works against them without TAP spend protection.
+  fn get_tap_inscriptions(
+    &self,
+    inscriptions: &[InscriptionId],
+  ) -> Result<BTreeMap<InscriptionId, String>> {
+    #[derive(Deserialize)]
+    struct BatchResponse {
+      result: Vec<Option<PendingInscription>>,
+    }
+
+    #[derive(Deserialize)]
+    struct PendingInscription {
+      op: String,
+    }
+
+    let mut tap_inscriptions = BTreeMap::new();
+
+    for chunk in inscriptions.chunks(500) {
+      let lookups = chunk
+        .iter()
+        .map(|id| serde_json::json!({"type": "pendingInscription", "inscription_id": id}))
+        .collect::<Vec<serde_json::Value>>();
+
+      let response = self.post("/r/tap/batch", &serde_json::json!({ "lookups": lookups }))?;
+
+      if !response.status().is_success() {
+        log::warn!(
+          "`ord server` answered `/r/tap/batch` with {}, not checking wallet outputs for pending \
+          TAP state",
+          response.status(),
+        );
+        return Ok(BTreeMap::new());
+      }
+
+      let Ok(response) = serde_json::from_str::<BatchResponse>(&response.text()?) else {
+        log::warn!(
+          "`ord server` sent a malformed `/r/tap/batch` response, not checking wallet outputs for \
+          pending TAP state",
+        );
+        return Ok(BTreeMap::new());
+      };
+
+      ensure! {
+        response.result.len() == chunk.len(),
+        "unexpected server `/r/tap/batch` response length",
+      }
+
+      for (id, pending) in chunk.iter().zip(response.result) {
+        if let Some(pending) = pending {
+          tap_inscriptions.insert(*id, pending.op);
+        }
+      }
+    }
+
+    Ok(tap_inscriptions)
+  }
+
   fn get_utxos(bitcoin_client: &Client) -> Result<BTreeMap<OutPoint, TxOut>> {
     Ok(
       bitcoin_client
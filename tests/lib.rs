@@ -140,6 +140,39 @@ fn inscribe(core: &mockcore::Handle, ord: &TestServer) -> (InscriptionId, Txid)
   inscribe_with_options(core, ord, None, 1)
 }
 
+// Deploys and mints `foo` to one wallet address and inscribes a token-transfer
+// there, which is left pending until the inscription is moved.
+fn tap_transfer(core: &mockcore::Handle, ord: &TestServer) -> (InscriptionId, Txid) {
+  let destination = CommandBuilder::new(format!("--chain {} wallet receive", core.network()))
+    .core(core)
+    .ord(ord)
+    .run_and_deserialize_output::<ord::subcommand::wallet::receive::Output>()
+    .addresses
+    .remove(0)
+    .assume_checked();
+
+  let inscribe = |body: &str| {
+    core.mine_blocks(1);
+
+    let output = CommandBuilder::new(format!(
+      "--chain {} wallet inscribe --fee-rate 1 --destination {destination} --file tap.json",
+      core.network(),
+    ))
+    .write("tap.json", body)
+    .core(core)
+    .ord(ord)
+    .run_and_deserialize_output::<Batch>();
+
+    core.mine_blocks(1);
+
+    (output.inscriptions[0].id, output.reveal)
+  };
+
+  inscribe(r#"{"p":"tap","op":"token-deploy","tick":"foo","max":"1000","lim":"1000","dec":0}"#);
+  inscribe(r#"{"p":"tap","op":"token-mint","tick":"foo","amt":"1000"}"#);
+  inscribe(r#"{"p":"tap","op":"token-transfer","tick":"foo","amt":"1"}"#)
+}
+
 fn drain(core: &mockcore::Handle, ord: &TestServer) {
   let balance = CommandBuilder::new("--regtest --index-runes wallet balance")
     .core(core)
@@ -39,6 +39,11 @@ fn default() {
   "tap_writer_export_consumer_id": null,
   "tap_writer_export_endpoint": null,
   "tap_writer_export_token": null,
+  "tap_writer_export_retain_blocks": null,
+  "tap_writer_export_retain_bytes": null,
+  "tap_writer_export_retain_consumers": false,
+  "tap_amm_candle_intervals": null,
+  "tap_stream": false,
   "tap_profile": false
 \}
 "#,
@@ -116,6 +116,48 @@ fn created_offer_is_correct() {
   }
 }
 
+#[test]
+fn created_offer_does_not_spend_outputs_with_pending_tap_state() {
+  let core = mockcore::builder().network(Network::Regtest).build();
+
+  let ord = TestServer::spawn_with_server_args(&core, &["--regtest"], &[]);
+
+  create_wallet(&core, &ord);
+
+  let (inscription, _) = inscribe(&core, &ord);
+
+  CommandBuilder::new(format!(
+    "--regtest wallet send --fee-rate 0 bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw {inscription}"
+  ))
+  .core(&core)
+  .ord(&ord)
+  .run_and_deserialize_output::<Send>();
+
+  core.mine_blocks(1);
+
+  let (_, reveal) = tap_transfer(&core, &ord);
+
+  let pending = OutPoint {
+    txid: reveal,
+    vout: 0,
+  };
+
+  let create = CommandBuilder::new(format!(
+    "--regtest wallet offer create --inscription {inscription} --amount 1btc --fee-rate 1"
+  ))
+  .core(&core)
+  .ord(&ord)
+  .run_and_deserialize_output::<Create>();
+
+  let psbt = Psbt::deserialize(&base64_decode(&create.psbt).unwrap()).unwrap();
+
+  assert!(psbt
+    .unsigned_tx
+    .input
+    .iter()
+    .all(|input| input.previous_output != pending));
+}
+
 #[test]
 fn inscription_must_exist() {
   let core = mockcore::spawn();
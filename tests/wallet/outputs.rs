@@ -152,6 +152,7 @@ fn outputs_includes_runes_and_inscriptions() {
       .collect()
     ),
     sat_ranges: None,
+    tap: None,
   }));
 
   assert!(output.contains(&Output {
@@ -161,5 +162,6 @@ fn outputs_includes_runes_and_inscriptions() {
     inscriptions: Some(vec![etched.output.inscriptions[0].id]),
     runes: Some(BTreeMap::new()),
     sat_ranges: None,
+    tap: None,
   }));
 }
@@ -441,6 +441,38 @@ fn inscriptions_cannot_be_sent_by_satpoint() {
   .run_and_extract_stdout();
 }
 
+#[test]
+fn outputs_with_pending_tap_state_cannot_be_sent_by_satpoint() {
+  let core = mockcore::builder().network(Network::Regtest).build();
+
+  let ord = TestServer::spawn_with_server_args(&core, &["--regtest"], &[]);
+
+  create_wallet(&core, &ord);
+
+  let (inscription, reveal) = tap_transfer(&core, &ord);
+
+  CommandBuilder::new(format!(
+    "--regtest wallet send --fee-rate 1 bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw {reveal}:0:1"
+  ))
+  .core(&core)
+  .ord(&ord)
+  .expected_stderr(format!(
+    "error: output {reveal}:0 carries inscription {inscription} with a pending TAP \
+    `token-transfer`, which spending the output would execute; send that inscription by ID to \
+    move it on purpose\n"
+  ))
+  .expected_exit_code(1)
+  .run_and_extract_stdout();
+
+  CommandBuilder::new(format!(
+    "--regtest wallet send --fee-rate 1 bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw {inscription}"
+  ))
+  .core(&core)
+  .ord(&ord)
+  .stdout_regex(".*")
+  .run_and_deserialize_output::<Send>();
+}
+
 #[test]
 fn send_btc_with_fee_rate() {
   let core = mockcore::spawn();
@@ -205,6 +205,87 @@ outputs:
   );
 }
 
+#[test]
+fn split_does_not_spend_outputs_with_pending_tap_state() {
+  let core = mockcore::builder().network(Network::Regtest).build();
+
+  let ord = TestServer::spawn_with_server_args(&core, &["--regtest", "--index-runes"], &[]);
+
+  create_wallet(&core, &ord);
+
+  let rune = Rune(RUNE);
+  let spaced_rune = SpacedRune { rune, spacers: 1 };
+
+  batch(
+    &core,
+    &ord,
+    batch::File {
+      etching: Some(batch::Etching {
+        supply: "100.0".parse().unwrap(),
+        divisibility: 1,
+        terms: None,
+        premine: "100.0".parse().unwrap(),
+        rune: spaced_rune,
+        symbol: '¢',
+        turbo: false,
+      }),
+      inscriptions: vec![batch::Entry {
+        file: Some("inscription.jpeg".into()),
+        ..default()
+      }],
+      ..default()
+    },
+  );
+
+  let (inscription, reveal) = tap_transfer(&core, &ord);
+
+  let pending = OutPoint {
+    txid: reveal,
+    vout: 0,
+  };
+
+  let output = CommandBuilder::new("--regtest wallet split --fee-rate 10 --splits splits.yaml")
+    .core(&core)
+    .ord(&ord)
+    .write(
+      "splits.yaml",
+      format!(
+        "
+outputs:
+- address: bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw
+  runes:
+    {spaced_rune}: 50.1
+"
+      ),
+    )
+    .run_and_deserialize_output::<Split>();
+
+  let split = core.mempool().remove(0);
+
+  assert_eq!(split.compute_txid(), output.txid);
+
+  assert!(split
+    .input
+    .iter()
+    .all(|input| input.previous_output != pending));
+
+  core.mine_blocks(1);
+
+  let outputs = CommandBuilder::new("--regtest wallet outputs")
+    .core(&core)
+    .ord(&ord)
+    .run_and_deserialize_output::<Vec<ord::subcommand::wallet::outputs::Output>>();
+
+  assert_eq!(
+    outputs
+      .into_iter()
+      .find(|output| output.output == pending)
+      .unwrap()
+      .tap,
+    Some([(inscription, "token-transfer".to_string())].into()),
+  );
+}
+
 #[test]
 fn oversize_op_returns_are_allowed_with_flag() {
   let core = mockcore::builder().network(Network::Regtest).build();